@@ -12,7 +12,7 @@ done
 
 # run some benchmarks with naive algorithm for comparison
 for B in DNA_arbitrary_distance DNA_growing_distance; do
-  cargo run --release -- --naive-quadratic --benchmark-file $B.json > results/$B-naive.json
+  cargo run --release -- --algorithm naive-quadratic --benchmark-file $B.json > results/$B-naive.json
 done
 
 
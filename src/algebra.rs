@@ -0,0 +1,491 @@
+//     _    _            _
+//    / \  | | __ _  ___| |__  _ __ __ _
+//   / _ \ | |/ _` |/ _ \ '_ \| '__/ _` |
+//  / ___ \| | (_| |  __/ |_) | | | (_| |
+// /_/   \_\_|\__, |\___|_.__/|_|  \__,_|
+//            |___/
+
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use std::ops::Range;
+
+use super::gazetteer::Gazetteer;
+use super::mapping::Mapping;
+use super::spanner::CompiledSpanner;
+
+/// Composition of several patterns with the operators of the spanner algebra,
+/// whose patterns are compiled once and which is then evaluated over texts.
+///
+/// Plans are parsed from expressions such as `join(p1, p2) | project(x, y)`,
+/// where:
+///  - a pattern is either the name of a definition, which is a regex or a
+///    dictionary, or a regex between single or double quotes, which can't
+///    contain the quote itself;
+///  - `union(e1, e2, ...)` gives the mappings of any of its operands;
+///  - `join(e1, e2, ...)` merges the mappings of its operands which agree on
+///    the spans of their common groups;
+///  - `concat(e1, e2, ...)` merges the mappings of its operands whose spans
+///    follow each other;
+///  - `contains(e1, e2)` merges the mappings of `e1` with the mappings of `e2`
+///    whose span is within theirs;
+///  - `near(e1, e2, n)` merges the mappings of its operands whose spans are
+///    separated by at most `n` characters, in any order;
+///  - `project(e, x, y, ...)` only keeps the given groups of the mappings;
+///  - `e | f(...)` is a shorthand for `f(e, ...)`.
+///
+/// The span of a mapping goes from the start of its first group to the end of
+/// its last group, and the operators comparing spans also require the merged
+/// mappings to agree on their common groups, as a join does. Both regexes and
+/// dictionaries are evaluated with an `IndexedDag`.
+///
+/// The mappings of a plan are sets: each distinct mapping is given once.
+#[derive(Clone)]
+pub enum Plan {
+    Pattern {
+        spanner: Box<CompiledSpanner>,
+        schema: Vec<String>,
+    },
+    Union(Vec<Plan>),
+    Join(Vec<Plan>),
+    Position(Box<Plan>, Box<Plan>, Relation),
+    Project(Box<Plan>, Vec<String>),
+}
+
+/// Relation between the spans of two mappings merged by a plan.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Relation {
+    /// The first span is immediately followed by the second one.
+    Concat,
+    /// The second span is within the first one.
+    Contains,
+    /// The spans are separated by at most this number of characters.
+    Near(usize),
+}
+
+impl Relation {
+    fn holds(self, text: &str, span1: &Range<usize>, span2: &Range<usize>) -> bool {
+        match self {
+            Relation::Concat => span1.end == span2.start,
+            Relation::Contains => span1.start <= span2.start && span2.end <= span1.end,
+            Relation::Near(distance) => {
+                let gap = if span1.end <= span2.start {
+                    &text[span1.end..span2.start]
+                } else if span2.end <= span1.start {
+                    &text[span2.end..span1.start]
+                } else {
+                    ""
+                };
+
+                gap.chars().nth(distance).is_none()
+            }
+        }
+    }
+
+    /// Bounds on the start of the second span given the first one, as
+    /// characters are at most 4 bytes long.
+    fn start_bounds(self, span1: &Range<usize>) -> (usize, usize) {
+        match self {
+            Relation::Concat => (span1.end, span1.end),
+            Relation::Contains => (span1.start, span1.end),
+            Relation::Near(distance) => (0, span1.end.saturating_add(distance.saturating_mul(4))),
+        }
+    }
+}
+
+impl Plan {
+    /// Parse an expression whose named patterns are given by a map from their
+    /// names to their regex.
+    pub fn parse(expr: &str, definitions: &HashMap<String, String>) -> Result<Plan, String> {
+        Plan::parse_with(expr, definitions, &HashMap::new())
+    }
+
+    /// Parse an expression which may also refer to dictionaries by their
+    /// names, each occurrence of an entry is assigned to the group of the
+    /// dictionary.
+    pub fn parse_with(
+        expr: &str,
+        definitions: &HashMap<String, String>,
+        dictionaries: &HashMap<String, Gazetteer>,
+    ) -> Result<Plan, String> {
+        let mut parser = Parser {
+            input: expr,
+            pos: 0,
+            definitions,
+            dictionaries,
+        };
+
+        let plan = parser.expr()?;
+        parser.skip_whitespaces();
+
+        if parser.pos < expr.len() {
+            return Err(parser.error("Unexpected input"));
+        }
+
+        Ok(plan)
+    }
+
+    fn pattern(regex: &str) -> Plan {
+        let spanner = Box::new(CompiledSpanner::new(regex));
+        let schema = (spanner.get_automaton().get_variables().iter())
+            .map(|var| var.get_name().to_string())
+            .collect();
+
+        Plan::Pattern { spanner, schema }
+    }
+
+    fn dictionary(gazetteer: &Gazetteer) -> Plan {
+        let spanner = Box::new(CompiledSpanner::from_automaton(gazetteer.get_automaton()));
+        let schema = vec![gazetteer.get_group().to_string()];
+        Plan::Pattern { spanner, schema }
+    }
+
+    /// Names of the groups which may be bound by the mappings of the plan.
+    pub fn get_schema(&self) -> Vec<String> {
+        match self {
+            Plan::Pattern { schema, .. } => schema.clone(),
+            Plan::Union(plans) | Plan::Join(plans) => {
+                let mut schema: Vec<String> = plans.iter().flat_map(Plan::get_schema).collect();
+                schema.sort();
+                schema.dedup();
+                schema
+            }
+            Plan::Position(plan1, plan2, _) => {
+                let mut schema = plan1.get_schema();
+                schema.extend(plan2.get_schema());
+                schema.sort();
+                schema.dedup();
+                schema
+            }
+            Plan::Project(_, groups) => groups.clone(),
+        }
+    }
+
+    /// Evaluate the plan over a text. The mappings bind variables numbered
+    /// after their position in the schema of the plan.
+    pub fn eval<'t>(&self, text: &'t str) -> Vec<Mapping<'t>> {
+        let schema = self.get_schema();
+
+        match self {
+            Plan::Pattern { spanner, .. } => (spanner.find_all(text).into_iter())
+                .map(|mapping| Mapping::from_groups(text, &schema, mapping.iter_groups()))
+                .collect(),
+            Plan::Union(plans) => dedup(plans.iter().flat_map(|plan| {
+                (plan.eval(text).into_iter())
+                    .map(|mapping| Mapping::from_groups(text, &schema, mapping.iter_groups()))
+            })),
+            Plan::Join(plans) => {
+                let mut plans = plans.iter();
+                let first = plans.next().map(|plan| (plan.get_schema(), plan.eval(text)));
+                let (_, mappings) = plans.fold(first.unwrap(), |(schema1, mappings1), plan| {
+                    let schema2 = plan.get_schema();
+                    let mappings2 = plan.eval(text);
+                    join(text, (&schema1, mappings1), (&schema2, mappings2))
+                });
+
+                (mappings.into_iter())
+                    .map(|mapping| Mapping::from_groups(text, &schema, mapping.iter_groups()))
+                    .collect()
+            }
+            Plan::Position(plan1, plan2, relation) => {
+                position(text, &schema, plan1.eval(text), plan2.eval(text), *relation)
+            }
+            Plan::Project(plan, _) => dedup(plan.eval(text).into_iter().map(|mapping| {
+                let groups = (mapping.iter_groups())
+                    .filter(|(name, _)| schema.iter().any(|group| group == name));
+                Mapping::from_groups(text, &schema, groups)
+            })),
+        }
+    }
+}
+
+/// Remove duplicated mappings, keeping the first occurrence of each mapping.
+fn dedup<'t>(mappings: impl Iterator<Item = Mapping<'t>>) -> Vec<Mapping<'t>> {
+    let mut seen = HashSet::new();
+    mappings.filter(|mapping| seen.insert(mapping.clone())).collect()
+}
+
+/// Natural join of two sets of mappings with their schemas. The mappings of
+/// the second set are grouped by their spans over the common groups, so that
+/// each mapping of the first set is only compared to compatible groups.
+fn join<'t>(
+    text: &'t str,
+    (schema1, mappings1): (&[String], Vec<Mapping<'t>>),
+    (schema2, mappings2): (&[String], Vec<Mapping<'t>>),
+) -> (Vec<String>, Vec<Mapping<'t>>) {
+    let common: Vec<&String> = schema1.iter().filter(|x| schema2.contains(x)).collect();
+    let key = |mapping: &Mapping| -> Vec<Option<Range<usize>>> {
+        common.iter().map(|group| mapping.get(group)).collect()
+    };
+
+    let mut groups: HashMap<_, Vec<Mapping<'t>>> = HashMap::new();
+
+    for mapping in mappings2 {
+        groups.entry(key(&mapping)).or_default().push(mapping);
+    }
+
+    let mut schema: Vec<String> = schema1.iter().chain(schema2).cloned().collect();
+    schema.sort();
+    schema.dedup();
+
+    // An unbound group is compatible with any span.
+    let compatible = |key1: &[Option<Range<usize>>], key2: &[Option<Range<usize>>]| {
+        iter::zip(key1, key2).all(|pair| match pair {
+            (Some(span1), Some(span2)) => span1 == span2,
+            _ => true,
+        })
+    };
+
+    let mut joined = Vec::new();
+
+    for mapping1 in &mappings1 {
+        let key1 = key(mapping1);
+
+        for (_, mappings2) in groups.iter().filter(|(key2, _)| compatible(&key1, key2)) {
+            for mapping2 in mappings2 {
+                let groups = mapping1.iter_groups().chain(mapping2.iter_groups());
+                joined.push(Mapping::from_groups(text, &schema, groups));
+            }
+        }
+    }
+
+    let joined = dedup(joined.into_iter());
+    (schema, joined)
+}
+
+/// Merge the mappings of two sets whose spans are in the given relation and
+/// which agree on their common groups. The mappings of the second set are
+/// sorted by the start of their span, so that each mapping of the first set is
+/// only compared to those which may be in relation with it.
+fn position<'t>(
+    text: &'t str,
+    schema: &[String],
+    mappings1: Vec<Mapping<'t>>,
+    mappings2: Vec<Mapping<'t>>,
+    relation: Relation,
+) -> Vec<Mapping<'t>> {
+    let mut mappings2: Vec<_> = (mappings2.into_iter())
+        .filter_map(|mapping| Some((mapping.main_span()?, mapping)))
+        .collect();
+    mappings2.sort_by_key(|(span, _)| span.start);
+
+    let agree = |mapping1: &Mapping, mapping2: &Mapping| {
+        (mapping1.iter_groups())
+            .all(|(name, span)| mapping2.get(name).is_none_or(|other| other == span))
+    };
+
+    let mut merged = Vec::new();
+
+    for mapping1 in &mappings1 {
+        let span1 = match mapping1.main_span() {
+            Some(span) => span,
+            None => continue,
+        };
+
+        let (min_start, max_start) = relation.start_bounds(&span1);
+        let first = mappings2.partition_point(|(span2, _)| span2.start < min_start);
+        let candidates = (mappings2[first..].iter())
+            .take_while(|(span2, _)| span2.start <= max_start)
+            .filter(|(span2, mapping2)| {
+                relation.holds(text, &span1, span2) && agree(mapping1, mapping2)
+            });
+
+        for (_, mapping2) in candidates {
+            let groups = mapping1.iter_groups().chain(mapping2.iter_groups());
+            merged.push(Mapping::from_groups(text, schema, groups));
+        }
+    }
+
+    dedup(merged.into_iter())
+}
+
+//  ____
+// |  _ \ __ _ _ __ ___  ___ _ __
+// | |_) / _` | '__/ __|/ _ \ '__|
+// |  __/ (_| | |  \__ \  __/ |
+// |_|   \__,_|_|  |___/\___|_|
+//
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    definitions: &'a HashMap<String, String>,
+    dictionaries: &'a HashMap<String, Gazetteer>,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {} of expression: {}", message, self.pos, self.input)
+    }
+
+    fn skip_whitespaces(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespaces();
+        self.input[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(x) if x == c => {
+                self.pos += c.len_utf8();
+                Ok(())
+            }
+            _ => Err(self.error(&format!("Expected `{}`", c))),
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str, String> {
+        self.skip_whitespaces();
+        let rest = &self.input[self.pos..];
+        let len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+
+        if len == 0 {
+            return Err(self.error("Expected a name"));
+        }
+
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Expression with any number of piped operators.
+    fn expr(&mut self) -> Result<Plan, String> {
+        let mut plan = self.term()?;
+
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            let name = self.ident()?;
+            self.expect('(')?;
+            plan = self.operator(name, Some(plan))?;
+        }
+
+        Ok(plan)
+    }
+
+    fn term(&mut self) -> Result<Plan, String> {
+        match self.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                let start = self.pos + 1;
+
+                match self.input[start..].find(quote) {
+                    Some(len) => {
+                        self.pos = start + len + 1;
+                        Ok(Plan::pattern(&self.input[start..start + len]))
+                    }
+                    None => Err(self.error("Unclosed pattern")),
+                }
+            }
+            _ => {
+                let name = self.ident()?;
+
+                if self.peek() == Some('(') {
+                    self.pos += 1;
+                    return self.operator(name, None);
+                }
+
+                match (self.definitions.get(name), self.dictionaries.get(name)) {
+                    (Some(regex), _) => Ok(Plan::pattern(regex)),
+                    (None, Some(gazetteer)) => Ok(Plan::dictionary(gazetteer)),
+                    (None, None) => Err(self.error(&format!("Unknown pattern `{}`", name))),
+                }
+            }
+        }
+    }
+
+    /// Arguments of an operator up to the closing parenthesis, its first
+    /// operand is already known if the operator is piped.
+    fn operator(&mut self, name: &str, piped: Option<Plan>) -> Result<Plan, String> {
+        let plan = match name {
+            "union" | "join" => {
+                let mut plans: Vec<Plan> = piped.into_iter().collect();
+
+                if plans.is_empty() || self.peek() != Some(')') {
+                    plans.push(self.expr()?);
+                }
+
+                while self.peek() == Some(',') {
+                    self.pos += 1;
+                    plans.push(self.expr()?);
+                }
+
+                match name {
+                    "union" => Plan::Union(plans),
+                    _ => Plan::Join(plans),
+                }
+            }
+            "concat" => {
+                let mut plan = match piped {
+                    Some(plan) => plan,
+                    None => self.expr()?,
+                };
+
+                while self.peek() == Some(',') {
+                    self.pos += 1;
+                    let next = self.expr()?;
+                    plan = Plan::Position(Box::new(plan), Box::new(next), Relation::Concat);
+                }
+
+                plan
+            }
+            "contains" | "near" => {
+                let plan1 = match piped {
+                    Some(plan) => plan,
+                    None => {
+                        let plan = self.expr()?;
+                        self.expect(',')?;
+                        plan
+                    }
+                };
+
+                let plan2 = self.expr()?;
+
+                let relation = match name {
+                    "contains" => Relation::Contains,
+                    _ => {
+                        self.expect(',')?;
+                        let distance = self.ident()?;
+
+                        match distance.parse() {
+                            Ok(distance) => Relation::Near(distance),
+                            Err(_) => return Err(self.error("Expected a number of characters")),
+                        }
+                    }
+                };
+
+                Plan::Position(Box::new(plan1), Box::new(plan2), relation)
+            }
+            "project" => {
+                let plan = match piped {
+                    Some(plan) => plan,
+                    None => {
+                        let plan = self.expr()?;
+                        self.expect(',')?;
+                        plan
+                    }
+                };
+
+                let schema = plan.get_schema();
+                let mut groups = vec![self.ident()?.to_string()];
+
+                while self.peek() == Some(',') {
+                    self.pos += 1;
+                    groups.push(self.ident()?.to_string());
+                }
+
+                if let Some(group) = groups.iter().find(|group| !schema.contains(group)) {
+                    return Err(self.error(&format!("Unknown group `{}`", group)));
+                }
+
+                Plan::Project(Box::new(plan), groups)
+            }
+            _ => return Err(self.error(&format!("Unknown operator `{}`", name))),
+        };
+
+        self.expect(')')?;
+        Ok(plan)
+    }
+}
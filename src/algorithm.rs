@@ -0,0 +1,67 @@
+//     _    _                  _ _   _
+//    / \  | | __ _  ___  _ __(_) |_| |__  _ __ ___
+//   / _ \ | |/ _` |/ _ \| '__| | __| '_ \| '_ ` _ \
+//  / ___ \| | (_| | (_) | |  | | |_| | | | | | | | |
+// /_/   \_\_|\__, |\___/|_|  |_|\__|_| |_|_| |_| |_|
+//            |___/
+
+use std::fmt;
+
+/// Algorithm used to enumerate the matches of a pattern.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    /// Constant-delay enumeration over an indexed DAG, see `mapping::IndexedDag`.
+    ICDT19,
+    /// Enumeration over an indexed DAG whose levels only store, for each
+    /// vertex, the vertices of the previous level it can reach. This takes
+    /// less memory than the matrices of ICDT19 at the cost of a delay that
+    /// grows with the length of the jumps.
+    JumpPointers,
+    /// Constant-delay enumeration over the runs of a deterministic extended
+    /// automaton, see `mapping::DeterministicEva`.
+    PODS18,
+    /// Run the automaton from each position of the text, see `naive::naive`.
+    Naive,
+    /// Check each subword of the text in time O(|regex||text|²), see
+    /// `naive::naive_quadratic`.
+    NaiveQuadratic,
+    /// Check each subword of the text in time O(|text|³ + exp(|regex|)), see
+    /// `naive::naive_cubic`.
+    NaiveCubic,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 6] = [
+        Algorithm::ICDT19,
+        Algorithm::JumpPointers,
+        Algorithm::PODS18,
+        Algorithm::Naive,
+        Algorithm::NaiveQuadratic,
+        Algorithm::NaiveCubic,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Algorithm::ICDT19 => "icdt19",
+            Algorithm::JumpPointers => "jump-pointers",
+            Algorithm::PODS18 => "pods18",
+            Algorithm::Naive => "naive",
+            Algorithm::NaiveQuadratic => "naive-quadratic",
+            Algorithm::NaiveCubic => "naive-cubic",
+        }
+    }
+
+    /// Find an algorithm from the name given by `get_name`.
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| algorithm.get_name() == name)
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
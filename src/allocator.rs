@@ -0,0 +1,91 @@
+//     _    _ _                 _
+//    / \  | | | ___   ___ __ _| |_ ___  _ __
+//   / _ \ | | |/ _ \ / __/ _` | __/ _ \| '__|
+//  / ___ \| | | (_) | (_| (_| | || (_) | |
+// /_/   \_\_|_|\___/ \___\__,_|\__\___/|_|
+//
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "alloc-stats")]
+use std::alloc::{GlobalAlloc, Layout, System};
+
+/// Bytes currently allocated on the heap.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+/// Maximal value of `CURRENT` since the start of the current phase.
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Wrapper around the system allocator that keeps track of the heap usage, it
+/// is only installed if the crate is built with the `alloc-stats` feature.
+#[cfg(feature = "alloc-stats")]
+pub struct CountingAllocator;
+
+#[cfg(feature = "alloc-stats")]
+impl CountingAllocator {
+    fn grow(size: usize) {
+        let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn shrink(size: usize) {
+        CURRENT.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+
+        if !ptr.is_null() {
+            CountingAllocator::grow(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+
+        if !ptr.is_null() {
+            CountingAllocator::grow(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CountingAllocator::shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+
+        if !new_ptr.is_null() {
+            CountingAllocator::grow(new_size);
+            CountingAllocator::shrink(layout.size());
+        }
+
+        new_ptr
+    }
+}
+
+/// Whether the heap usage is tracked in this build.
+pub fn is_enabled() -> bool {
+    cfg!(feature = "alloc-stats")
+}
+
+/// Start a new phase of the measurements by resetting the peak usage, returns
+/// the current heap usage.
+pub fn start_phase() -> usize {
+    let current = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(current, Ordering::Relaxed);
+    current
+}
+
+/// Peak heap usage since the start of the phase, relative to the usage when
+/// it started.
+pub fn phase_peak(start: usize) -> usize {
+    PEAK.load(Ordering::Relaxed).saturating_sub(start)
+}
@@ -0,0 +1,576 @@
+//     _
+//    / \   _ __ _ __ _____      __
+//   / _ \ | '__| '__/ _ \ \ /\ / /
+//  / ___ \| |  | | | (_) \ V  V /
+// /_/   \_\_|  |_|  \___/ \_/\_/
+//
+
+use std::io::{self, Write};
+
+use super::mapping::Mapping;
+
+/// Number of rows of a record batch, the last one being shorter.
+const BATCH_SIZE: usize = 1 << 16;
+
+/// Version of the metadata of Arrow messages, V5.
+const METADATA_VERSION: i16 = 4;
+
+/// Write matches in the Arrow IPC streaming format, in record batches with
+/// columns `file`, `span_start` and `span_end` for the main span, and a
+/// column holding the text of each group, which is null for the matches not
+/// binding it.
+///
+/// Only the subset of the format needed by these columns is implemented: the
+/// texts are `LargeUtf8` arrays and the offsets are signed 64 bits integers.
+pub struct ArrowWriter<W: Write> {
+    output: W,
+    file: String,
+    groups: Vec<String>,
+    starts: Vec<i64>,
+    ends: Vec<i64>,
+    columns: Vec<StringColumn>,
+}
+
+impl<W: Write> ArrowWriter<W> {
+    /// Create a writer and write the schema of the stream, matches are read
+    /// from the given file and may bind the given groups.
+    pub fn new(mut output: W, file: &str, groups: Vec<String>) -> io::Result<ArrowWriter<W>> {
+        let mut fields = vec![
+            field("file", false, Type::LargeUtf8),
+            field("span_start", false, Type::Int64),
+            field("span_end", false, Type::Int64),
+        ];
+        fields.extend(groups.iter().map(|group| field(group, true, Type::LargeUtf8)));
+
+        let schema = Fb::Table(vec![Some(Fb::I16(0)), Some(Fb::Tables(fields))]);
+        write_message(&mut output, MessageHeader::Schema, schema, &[])?;
+
+        Ok(ArrowWriter {
+            output,
+            file: file.to_string(),
+            columns: (0..=groups.len()).map(|_| StringColumn::new()).collect(),
+            groups,
+            starts: Vec::new(),
+            ends: Vec::new(),
+        })
+    }
+
+    pub fn write(&mut self, mapping: &Mapping) -> io::Result<()> {
+        // Mappings assigning no group, which are given by optional groups,
+        // have no span to write.
+        let span = match mapping.main_span() {
+            Some(span) => span,
+            None => return Ok(()),
+        };
+
+        self.columns[0].push(Some(&self.file));
+        self.starts.push(span.start as i64);
+        self.ends.push(span.end as i64);
+
+        for (group, column) in self.groups.iter().zip(&mut self.columns[1..]) {
+            column.push(mapping.get_text(group));
+        }
+
+        if self.starts.len() == BATCH_SIZE {
+            self.write_batch()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the remaining matches and the end of the stream.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.starts.is_empty() {
+            self.write_batch()?;
+        }
+
+        self.output.write_all(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0])?;
+        self.output.flush()
+    }
+
+    fn write_batch(&mut self) -> io::Result<()> {
+        let num_rows = self.starts.len();
+        let mut body = Vec::new();
+        let mut nodes = Vec::new();
+        let mut buffers = Vec::new();
+
+        let (file, groups) = self.columns.split_first().unwrap();
+        file.write_buffers(&mut body, &mut nodes, &mut buffers);
+
+        for integers in &[&self.starts, &self.ends] {
+            let data: Vec<u8> = integers.iter().flat_map(|x| x.to_le_bytes()).collect();
+            nodes.push(vec![num_rows as i64, 0]);
+            push_buffer(&mut body, &mut buffers, &[]);
+            push_buffer(&mut body, &mut buffers, &data);
+        }
+
+        for column in groups {
+            column.write_buffers(&mut body, &mut nodes, &mut buffers);
+        }
+
+        let batch = Fb::Table(vec![
+            Some(Fb::I64(num_rows as i64)),
+            Some(Fb::Structs(nodes)),
+            Some(Fb::Structs(buffers)),
+        ]);
+        write_message(&mut self.output, MessageHeader::RecordBatch, batch, &body)?;
+
+        self.starts.clear();
+        self.ends.clear();
+
+        for column in &mut self.columns {
+            *column = StringColumn::new();
+        }
+
+        Ok(())
+    }
+}
+
+/// Column of nullable strings, in the layout of Arrow's `LargeUtf8` arrays.
+struct StringColumn {
+    offsets: Vec<i64>,
+    data: Vec<u8>,
+    validity: Vec<u8>,
+    num_nulls: usize,
+}
+
+impl StringColumn {
+    fn new() -> StringColumn {
+        StringColumn {
+            offsets: vec![0],
+            data: Vec::new(),
+            validity: Vec::new(),
+            num_nulls: 0,
+        }
+    }
+
+    fn push(&mut self, value: Option<&str>) {
+        let row = self.offsets.len() - 1;
+
+        if row.is_multiple_of(8) {
+            self.validity.push(0);
+        }
+
+        match value {
+            None => self.num_nulls += 1,
+            Some(value) => {
+                self.validity[row / 8] |= 1 << (row % 8);
+                self.data.extend_from_slice(value.as_bytes());
+            }
+        }
+
+        self.offsets.push(self.data.len() as i64);
+    }
+
+    fn write_buffers(
+        &self,
+        body: &mut Vec<u8>,
+        nodes: &mut Vec<Vec<i64>>,
+        buffers: &mut Vec<Vec<i64>>,
+    ) {
+        let offsets: Vec<u8> = self.offsets.iter().flat_map(|x| x.to_le_bytes()).collect();
+        nodes.push(vec![self.offsets.len() as i64 - 1, self.num_nulls as i64]);
+        push_buffer(body, buffers, &self.validity);
+        push_buffer(body, buffers, &offsets);
+        push_buffer(body, buffers, &self.data);
+    }
+}
+
+/// Append a buffer to the body of a message, buffers are aligned on 8 bytes.
+fn push_buffer(body: &mut Vec<u8>, buffers: &mut Vec<Vec<i64>>, data: &[u8]) {
+    buffers.push(vec![body.len() as i64, data.len() as i64]);
+    body.extend_from_slice(data);
+    pad(body, 8);
+}
+
+//  __  __
+// |  \/  | ___  ___ ___  __ _  __ _  ___  ___
+// | |\/| |/ _ \/ __/ __|/ _` |/ _` |/ _ \/ __|
+// | |  | |  __/\__ \__ \ (_| | (_| |  __/\__ \
+// |_|  |_|\___||___/___/\__,_|\__, |\___||___/
+//                             |___/
+
+#[derive(Clone, Copy)]
+enum MessageHeader {
+    Schema = 1,
+    RecordBatch = 3,
+}
+
+#[derive(Clone, Copy)]
+enum Type {
+    Int64,
+    LargeUtf8,
+}
+
+/// Description of a column in a schema, its type is given as a member of the
+/// union `Type` of Arrow's schema.
+fn field(name: &str, nullable: bool, ty: Type) -> Fb {
+    let (type_id, type_table) = match ty {
+        Type::Int64 => (2, Fb::Table(vec![Some(Fb::I32(64)), Some(Fb::Bool(true))])),
+        Type::LargeUtf8 => (20, Fb::Table(Vec::new())),
+    };
+
+    Fb::Table(vec![
+        Some(Fb::Str(name.to_string())),
+        Some(Fb::Bool(nullable)),
+        Some(Fb::U8(type_id)),
+        Some(type_table),
+        None,
+        Some(Fb::Tables(Vec::new())),
+    ])
+}
+
+/// Write an encapsulated message: a continuation marker, the size of the
+/// metadata, the metadata and the body of the message.
+fn write_message<W: Write>(
+    output: &mut W,
+    header_type: MessageHeader,
+    header: Fb,
+    body: &[u8],
+) -> io::Result<()> {
+    let message = Fb::Table(vec![
+        Some(Fb::I16(METADATA_VERSION)),
+        Some(Fb::U8(header_type as u8)),
+        Some(header),
+        Some(Fb::I64(body.len() as i64)),
+    ]);
+    let metadata = message.finish();
+
+    output.write_all(&[0xff, 0xff, 0xff, 0xff])?;
+    output.write_all(&(metadata.len() as i32).to_le_bytes())?;
+    output.write_all(&metadata)?;
+    output.write_all(body)
+}
+
+//  _____ _       _   _            __  __
+// |  ___| | __ _| |_| |__  _   _ / _|/ _| ___ _ __ ___
+// | |_  | |/ _` | __| '_ \| | | | |_| |_ / _ \ '__/ __|
+// |  _| | | (_| | |_| |_) | |_| |  _|  _|  __/ |  \__ \
+// |_|   |_|\__,_|\__|_.__/ \__,_|_| |_|  \___|_|  |___/
+//
+
+/// Value encoded in a flatbuffer, only covering what the metadata of Arrow
+/// messages needs.
+enum Fb {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Str(String),
+    /// Fields of a table, in the order of their ids, absent fields are
+    /// left to their default value.
+    Table(Vec<Option<Fb>>),
+    Tables(Vec<Fb>),
+    /// Vector of structs made of 64 bits integers.
+    Structs(Vec<Vec<i64>>),
+}
+
+impl Fb {
+    /// Encode a root table. Objects are written after the offsets referencing
+    /// them, which must be positive.
+    fn finish(&self) -> Vec<u8> {
+        let mut buf = vec![0; 4];
+        let root = self.write_object(&mut buf);
+        patch_offset(&mut buf, 0, root);
+        pad(&mut buf, 8);
+        buf
+    }
+
+    /// Size of the value when it is stored in a table, objects are stored as
+    /// an offset.
+    fn inline_size(&self) -> usize {
+        match self {
+            Fb::Bool(_) | Fb::U8(_) => 1,
+            Fb::I16(_) => 2,
+            Fb::I32(_) => 4,
+            Fb::I64(_) => 8,
+            Fb::Str(_) | Fb::Table(_) | Fb::Tables(_) | Fb::Structs(_) => 4,
+        }
+    }
+
+    /// Write a scalar at a given position of the buffer, return false for
+    /// objects.
+    fn write_scalar(&self, buf: &mut [u8], pos: usize) -> bool {
+        let bytes = match self {
+            Fb::Bool(x) => vec![*x as u8],
+            Fb::U8(x) => vec![*x],
+            Fb::I16(x) => x.to_le_bytes().to_vec(),
+            Fb::I32(x) => x.to_le_bytes().to_vec(),
+            Fb::I64(x) => x.to_le_bytes().to_vec(),
+            _ => return false,
+        };
+
+        buf[pos..pos + bytes.len()].copy_from_slice(&bytes);
+        true
+    }
+
+    /// Append an object to the buffer and return its position.
+    fn write_object(&self, buf: &mut Vec<u8>) -> usize {
+        match self {
+            Fb::Str(string) => {
+                pad(buf, 4);
+                let pos = buf.len();
+                buf.extend_from_slice(&(string.len() as u32).to_le_bytes());
+                buf.extend_from_slice(string.as_bytes());
+                buf.push(0);
+                pos
+            }
+            Fb::Structs(structs) => {
+                // The elements following the length are aligned on 8 bytes.
+                while !(buf.len() + 4).is_multiple_of(8) {
+                    buf.push(0);
+                }
+
+                let pos = buf.len();
+                buf.extend_from_slice(&(structs.len() as u32).to_le_bytes());
+
+                for x in structs.iter().flatten() {
+                    buf.extend_from_slice(&x.to_le_bytes());
+                }
+
+                pos
+            }
+            Fb::Tables(tables) => {
+                pad(buf, 4);
+                let pos = buf.len();
+                buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+                buf.resize(pos + 4 + 4 * tables.len(), 0);
+
+                for (i, table) in tables.iter().enumerate() {
+                    let table_pos = table.write_object(buf);
+                    patch_offset(buf, pos + 4 + 4 * i, table_pos);
+                }
+
+                pos
+            }
+            Fb::Table(fields) => {
+                // Inline fields follow the offset to the vtable, each aligned
+                // on its size, the table itself being aligned on 8 bytes.
+                let mut layout = vec![0; fields.len()];
+                let mut size: usize = 4;
+
+                for (field, offset) in fields.iter().zip(&mut layout) {
+                    if let Some(field) = field {
+                        let field_size = field.inline_size();
+                        size = size.div_ceil(field_size) * field_size;
+                        *offset = size;
+                        size += field_size;
+                    }
+                }
+
+                pad(buf, 2);
+                let vtable = buf.len();
+                buf.extend_from_slice(&(4 + 2 * fields.len() as u16).to_le_bytes());
+                buf.extend_from_slice(&(size as u16).to_le_bytes());
+
+                for offset in &layout {
+                    buf.extend_from_slice(&(*offset as u16).to_le_bytes());
+                }
+
+                pad(buf, 8);
+                let table = buf.len();
+                buf.extend_from_slice(&((table - vtable) as i32).to_le_bytes());
+                buf.resize(table + size, 0);
+
+                let objects: Vec<_> = fields
+                    .iter()
+                    .zip(&layout)
+                    .filter_map(|(field, offset)| Some((field.as_ref()?, table + offset)))
+                    .filter(|(field, pos)| !field.write_scalar(buf, *pos))
+                    .collect();
+
+                for (object, pos) in objects {
+                    let object_pos = object.write_object(buf);
+                    patch_offset(buf, pos, object_pos);
+                }
+
+                table
+            }
+            _ => unreachable!("Scalars are stored inline"),
+        }
+    }
+}
+
+/// Store at position `pos` the offset from there to `target`.
+fn patch_offset(buf: &mut [u8], pos: usize, target: usize) {
+    buf[pos..pos + 4].copy_from_slice(&((target - pos) as u32).to_le_bytes());
+}
+
+fn pad(buf: &mut Vec<u8>, align: usize) {
+    while !buf.len().is_multiple_of(align) {
+        buf.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use enum_spanner_rs::mapping::indexed_dag::IndexedDag;
+    use enum_spanner_rs::mapping::SpannerEnumerator;
+    use enum_spanner_rs::regex;
+
+    use super::ArrowWriter;
+
+    /// Row of the stream: file, span and text of each group.
+    type Row = (String, i64, i64, Vec<Option<String>>);
+
+    fn read_u32(buf: &[u8], pos: usize) -> usize {
+        u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap()) as usize
+    }
+
+    fn read_i64(buf: &[u8], pos: usize) -> i64 {
+        i64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+    }
+
+    /// Position of a field of a flatbuffer table, if it is present.
+    fn field(buf: &[u8], table: usize, id: usize) -> Option<usize> {
+        let vtable = table - read_u32(buf, table);
+        let vtable_len = u16::from_le_bytes([buf[vtable], buf[vtable + 1]]) as usize;
+
+        if 4 + 2 * id >= vtable_len {
+            return None;
+        }
+
+        match u16::from_le_bytes([buf[vtable + 4 + 2 * id], buf[vtable + 5 + 2 * id]]) {
+            0 => None,
+            offset => Some(table + offset as usize),
+        }
+    }
+
+    fn object(buf: &[u8], table: usize, id: usize) -> usize {
+        let pos = field(buf, table, id).unwrap();
+        pos + read_u32(buf, pos)
+    }
+
+    /// Structs of two 64 bits integers of a vector.
+    fn pairs(buf: &[u8], table: usize, id: usize) -> Vec<(i64, i64)> {
+        let vector = object(buf, table, id);
+        (0..read_u32(buf, vector))
+            .map(|i| vector + 4 + 16 * i)
+            .map(|pos| (read_i64(buf, pos), read_i64(buf, pos + 8)))
+            .collect()
+    }
+
+    /// Read a stream of the schema written by `ArrowWriter`, which gives the
+    /// names of the columns and their rows.
+    fn read_stream(stream: &[u8]) -> (Vec<String>, Vec<Row>) {
+        let mut names = Vec::new();
+        let mut rows = Vec::new();
+        let mut pos = 0;
+
+        loop {
+            assert_eq!(read_u32(stream, pos), 0xffff_ffff);
+            let len = read_u32(stream, pos + 4);
+            pos += 8;
+
+            if len == 0 {
+                break;
+            }
+
+            assert_eq!((pos + len) % 8, 0);
+            let meta = &stream[pos..pos + len];
+            let message = read_u32(meta, 0);
+            let header = object(meta, message, 2);
+            let body_len = read_i64(meta, field(meta, message, 3).unwrap()) as usize;
+            let body = &stream[pos + len..pos + len + body_len];
+            pos += len + body_len;
+
+            if meta[field(meta, message, 1).unwrap()] == 1 {
+                let fields = object(meta, header, 1);
+
+                for i in 0..read_u32(meta, fields) {
+                    let pos = fields + 4 + 4 * i;
+                    let name = object(meta, pos + read_u32(meta, pos), 0);
+                    let len = read_u32(meta, name);
+                    names.push(String::from_utf8(meta[name + 4..name + 4 + len].to_vec()).unwrap());
+                }
+
+                continue;
+            }
+
+            let num_rows = read_i64(meta, field(meta, header, 0).unwrap()) as usize;
+            let nodes = pairs(meta, header, 1);
+            let buffers = pairs(meta, header, 2);
+            let buffer = |i: usize| {
+                let (offset, len) = buffers[i];
+                &body[offset as usize..(offset + len) as usize]
+            };
+
+            // Each string column has three buffers, integers have two.
+            let strings = |column: usize, first_buffer: usize| -> Vec<Option<String>> {
+                assert_eq!(nodes[column].0 as usize, num_rows);
+                let (validity, offsets, data) =
+                    (buffer(first_buffer), buffer(first_buffer + 1), buffer(first_buffer + 2));
+
+                (0..num_rows)
+                    .map(|row| {
+                        if validity[row / 8] & (1 << (row % 8)) == 0 {
+                            return None;
+                        }
+
+                        let start = read_i64(offsets, 8 * row) as usize;
+                        let end = read_i64(offsets, 8 * row + 8) as usize;
+                        Some(String::from_utf8(data[start..end].to_vec()).unwrap())
+                    })
+                    .collect()
+            };
+
+            let files = strings(0, 0);
+            let (starts, ends) = (buffer(4), buffer(6));
+            let groups: Vec<_> = (3..nodes.len()).map(|i| strings(i, 7 + 3 * (i - 3))).collect();
+
+            for row in 0..num_rows {
+                rows.push((
+                    files[row].clone().unwrap(),
+                    read_i64(starts, 8 * row),
+                    read_i64(ends, 8 * row),
+                    groups.iter().map(|group| group[row].clone()).collect(),
+                ));
+            }
+        }
+
+        assert_eq!(pos, stream.len());
+        (names, rows)
+    }
+
+    #[test]
+    fn round_trip() {
+        let automaton = regex::compile(r"(?P<x>a)?(?P<y>b)é?");
+        let text = "ab bé ba";
+        let groups = vec!["x".to_string(), "y".to_string()];
+
+        let mut dag = IndexedDag::builder(automaton, text).build();
+        dag.preprocess();
+        let mappings: Vec<_> = dag.iter().collect();
+
+        let mut stream = Vec::new();
+        let mut writer = ArrowWriter::new(&mut stream, "file.txt", groups.clone()).unwrap();
+
+        for mapping in &mappings {
+            writer.write(mapping).unwrap();
+        }
+
+        writer.finish().unwrap();
+
+        let expected: Vec<Row> = (mappings.iter())
+            .map(|mapping| {
+                let span = mapping.main_span().unwrap();
+                let texts = groups.iter().map(|group| mapping.get_text(group));
+                let texts = texts.map(|text| text.map(str::to_string)).collect();
+                ("file.txt".to_string(), span.start as i64, span.end as i64, texts)
+            })
+            .collect();
+
+        let (names, rows) = read_stream(&stream);
+        assert_eq!(names, ["file", "span_start", "span_end", "x", "y"]);
+        assert_eq!(rows, expected);
+        assert!(rows.iter().any(|row| row.3[0].is_none()));
+
+        // A stream without matches only holds its schema.
+        let mut stream = Vec::new();
+        ArrowWriter::new(&mut stream, "file.txt", groups).unwrap().finish().unwrap();
+        assert_eq!(read_stream(&stream).1, []);
+    }
+}
@@ -2,7 +2,7 @@ use regex_syntax::hir;
 use std::fmt;
 
 /// Represent a set of characters as an union of ranges.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Atom {
     Literal(hir::Literal),
     Class(hir::Class),
@@ -19,6 +19,43 @@ impl Atom {
             _ => panic!("Byte regex are not supported"),
         }
     }
+
+    /// Set of the characters matched by an atom.
+    pub fn to_class(&self) -> hir::ClassUnicode {
+        match self {
+            Atom::Literal(hir::Literal::Unicode(x)) => {
+                hir::ClassUnicode::new(Some(hir::ClassUnicodeRange::new(*x, *x)))
+            }
+            Atom::Class(hir::Class::Unicode(class)) => class.clone(),
+            _ => panic!("Byte regex are not supported"),
+        }
+    }
+
+    /// Split an atom into the newline it matches and the other characters it
+    /// matches, if any.
+    pub fn split_newline(&self) -> (Option<Atom>, Option<Atom>) {
+        let newline = Atom::Literal(hir::Literal::Unicode('\n'));
+
+        match self {
+            Atom::Literal(_) if self.is_match(&'\n') => (Some(newline), None),
+            Atom::Literal(_) => (None, Some(self.clone())),
+            Atom::Class(hir::Class::Unicode(class)) => {
+                let mut others = class.clone();
+                others.difference(&hir::ClassUnicode::new(Some(hir::ClassUnicodeRange::new(
+                    '\n', '\n',
+                ))));
+
+                let newline = Some(newline).filter(|_| self.is_match(&'\n'));
+                let others = match others.ranges() {
+                    [] => None,
+                    _ => Some(Atom::Class(hir::Class::Unicode(others))),
+                };
+
+                (newline, others)
+            }
+            _ => panic!("Byte regex are not supported"),
+        }
+    }
 }
 
 impl fmt::Display for Atom {
@@ -1,13 +1,16 @@
 pub mod atom;
 
 use bit_set::BitSet;
+use regex_syntax::hir;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::rc::Rc;
+use std::path::Path;
+use std::sync::Arc;
 
-use super::mapping::Marker;
+use super::mapping::{Marker, Variable};
 
 //     _         _                        _
 //    / \  _   _| |_ ___  _ __ ___   __ _| |_ ___  _ __
@@ -18,16 +21,16 @@ use super::mapping::Marker;
 #[derive(Clone, Debug)]
 pub struct Automaton {
     pub nb_states: usize,
-    pub transitions: Vec<(usize, Rc<Label>, usize)>,
+    pub transitions: Vec<(usize, Arc<Label>, usize)>,
     pub finals: BitSet,
 
     // Redundant caching structures
-    adj: Vec<Vec<(Rc<Label>, usize)>>,
+    adj: Vec<Vec<(Arc<Label>, usize)>>,
     adj_for_char: HashMap<char, Vec<Vec<usize>>>,
     adj_for_char_with_closure: HashMap<char, Vec<Vec<usize>>>,
-    rev_adj_for_char_with_closure: HashMap<char, Vec<Vec<usize>>>,
-    assignations: Vec<Vec<(Rc<Label>, usize)>>,
-    rev_assignations: Vec<Vec<(Rc<Label>, usize)>>,
+    rev_rows_for_char_with_closure: HashMap<char, Vec<BitSet>>,
+    assignations: Vec<Vec<(Arc<Label>, usize)>>,
+    rev_assignations: Vec<Vec<(Arc<Label>, usize)>>,
     closure_for_assignations: Vec<Vec<usize>>,
     closure_for_rev_assignations: Vec<Vec<usize>>,
     jump_states: BitSet,
@@ -36,7 +39,7 @@ pub struct Automaton {
 impl Automaton {
     pub fn new<T, U>(nb_states: usize, transitions: T, finals: U) -> Automaton
     where
-        T: Iterator<Item = (usize, Rc<Label>, usize)>,
+        T: Iterator<Item = (usize, Arc<Label>, usize)>,
         U: Iterator<Item = usize>,
     {
         let mut automaton = Automaton {
@@ -47,7 +50,7 @@ impl Automaton {
             adj: Vec::new(),
             adj_for_char: HashMap::new(),
             adj_for_char_with_closure: HashMap::new(),
-            rev_adj_for_char_with_closure: HashMap::new(),
+            rev_rows_for_char_with_closure: HashMap::new(),
             assignations: Vec::new(),
             rev_assignations: Vec::new(),
             closure_for_assignations: Vec::new(),
@@ -71,6 +74,79 @@ impl Automaton {
         }) + 1
     }
 
+    /// Variables that can be assigned by the automaton, sorted by id. A group
+    /// which is in this list but not in a mapping was left unbound.
+    pub fn get_variables(&self) -> Vec<Variable> {
+        let mut variables: Vec<Variable> = self
+            .transitions
+            .iter()
+            .filter_map(|(_, label, _)| label.get_marker().ok())
+            .map(|marker| marker.variable().clone())
+            .collect();
+
+        variables.sort_by_key(|var| var.get_id());
+        variables.dedup();
+        variables
+    }
+
+    /// Automaton which only assigns the variables of a list. The transitions
+    /// assigning the markers of other variables are removed and the states
+    /// they lead to are reached directly, such that runs which only differ by
+    /// these markers produce a single mapping.
+    pub fn project(&self, variables: &[String]) -> Automaton {
+        let is_dropped = |label: &Label| match label {
+            Label::Assignation(marker) => {
+                !variables.iter().any(|name| name == marker.variable().get_name())
+            }
+            _ => false,
+        };
+
+        // States reached from each state by only assigning dropped markers.
+        let closure: Vec<BitSet> = (0..self.nb_states)
+            .map(|state| {
+                let mut reached = BitSet::new();
+                let mut stack = vec![state];
+
+                while let Some(source) = stack.pop() {
+                    if reached.insert(source) {
+                        stack.extend(
+                            (self.adj[source].iter())
+                                .filter(|(label, _)| is_dropped(label))
+                                .map(|(_, target)| *target),
+                        );
+                    }
+                }
+
+                reached
+            })
+            .collect();
+
+        let mut transitions: Vec<(usize, Arc<Label>, usize)> = Vec::new();
+        let mut finals = Vec::new();
+
+        for (state, reached) in closure.iter().enumerate() {
+            let start = transitions.len();
+
+            for middle in reached.iter() {
+                for (label, target) in &self.adj[middle] {
+                    let is_new = !transitions[start..]
+                        .iter()
+                        .any(|(_, other, dest)| dest == target && other == label);
+
+                    if !is_dropped(label) && is_new {
+                        transitions.push((state, label.clone(), *target));
+                    }
+                }
+            }
+
+            if !reached.is_disjoint(&self.finals) {
+                finals.push(state);
+            }
+        }
+
+        Automaton::new(self.nb_states, transitions.into_iter(), finals.into_iter())
+    }
+
     pub fn get_initial(&self) -> usize {
         0
     }
@@ -79,7 +155,11 @@ impl Automaton {
         self.nb_states
     }
 
-    pub fn get_adj(&self) -> &Vec<Vec<(Rc<Label>, usize)>> {
+    pub fn get_nb_transitions(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn get_adj(&self) -> &Vec<Vec<(Arc<Label>, usize)>> {
         &self.adj
     }
 
@@ -105,8 +185,11 @@ impl Automaton {
         })
     }
 
-    pub fn get_rev_adj_for_char_with_closure(&self, x: char) -> &Vec<Vec<usize>> {
-        self.rev_adj_for_char_with_closure.get(&x).unwrap()
+    /// Get the reverse of the adjacency lists given by
+    /// `Automaton::get_adj_for_char_with_closure` as a set of sources for each
+    /// target, which must have been computed first.
+    pub fn get_rev_rows_for_char_with_closure(&self, x: char) -> &Vec<BitSet> {
+        self.rev_rows_for_char_with_closure.get(&x).unwrap()
     }
 
     pub fn get_adj_for_char_with_closure(&mut self, x: char) -> &Vec<Vec<usize>> {
@@ -116,26 +199,26 @@ impl Automaton {
         let closure_for_assignations = &self.closure_for_assignations;
         let closure_for_rev_assignations = &self.closure_for_rev_assignations;
         let adj_for_char_with_closure = &mut self.adj_for_char_with_closure;
-        let rev_adj_for_char_with_closure = &mut self.rev_adj_for_char_with_closure;
+        let rev_rows_for_char_with_closure = &mut self.rev_rows_for_char_with_closure;
 
         adj_for_char_with_closure.entry(x).or_insert_with(|| {
             let mut res = vec![Vec::new(); nb_states];
             let mut res_closure = vec![Vec::new(); nb_states];
-            let mut res_rev_closure = vec![Vec::new(); nb_states];
+            let mut res_rev_closure = vec![BitSet::with_capacity(nb_states); nb_states];
 
             for (source, label, target) in transitions {
                 if let Label::Atom(atom) = &**label {
                     if atom.is_match(&x) {
                         res[*source].push(*target);
                         res_closure[*source].push(*target);
-                        res_rev_closure[*target].push(*source);
+                        res_rev_closure[*target].insert(*source);
 
                         for &ttarget in &closure_for_assignations[*target] {
                             res_closure[*source].push(ttarget);
                         }
 
                         for &ssource in &closure_for_rev_assignations[*source] {
-                            res_rev_closure[*target].push(ssource);
+                            res_rev_closure[*target].insert(ssource);
                         }
                     }
                 }
@@ -146,12 +229,7 @@ impl Automaton {
                 targets.dedup();
             }
 
-            for sources in &mut res_rev_closure {
-                sources.sort();
-                sources.dedup();
-            }
-
-            rev_adj_for_char_with_closure.insert(x, res_rev_closure);
+            rev_rows_for_char_with_closure.insert(x, res_rev_closure);
 
             adj_for_char.insert(x, res);
 
@@ -161,13 +239,13 @@ impl Automaton {
 
     /// Get adjacency lists labeled with the corresponding marker for
     /// transitions labeled with an assignation.
-    pub fn get_assignations(&self) -> &Vec<Vec<(Rc<Label>, usize)>> {
+    pub fn get_assignations(&self) -> &Vec<Vec<(Arc<Label>, usize)>> {
         &self.assignations
     }
 
     /// Get the reverse of assignations as defined in
     /// `Automata::get_assignations`.
-    pub fn get_rev_assignations(&self) -> &Vec<Vec<(Rc<Label>, usize)>> {
+    pub fn get_rev_assignations(&self) -> &Vec<Vec<(Arc<Label>, usize)>> {
         &self.rev_assignations
     }
 
@@ -178,20 +256,24 @@ impl Automaton {
     }
 
     /// Render the automaton as a dotfile for later rendering with graphviz.
-    pub fn render(&self, filename: &str) -> std::io::Result<()> {
-        let mut buf = File::create(filename)?;
-        buf.write(b"digraph automaton {\n")?;
+    pub fn render<P: AsRef<Path>>(&self, filename: P) -> std::io::Result<()> {
+        self.write_dot(&mut File::create(filename)?)
+    }
+
+    /// Write the automaton in the dot format into any output.
+    pub fn write_dot<W: Write>(&self, buf: &mut W) -> std::io::Result<()> {
+        buf.write_all(b"digraph automaton {\n")?;
 
         // Use doublecircles for final states
-        buf.write(b"\tnode [shape=doublecircle]\n")?;
+        buf.write_all(b"\tnode [shape=doublecircle]\n")?;
 
         for state in &self.finals {
             let node = format!("\tq{}\n", state);
-            buf.write(node.as_bytes())?;
+            buf.write_all(node.as_bytes())?;
         }
 
         // Draw edges
-        buf.write(b"\n\tnode [shape=circle]\n")?;
+        buf.write_all(b"\n\tnode [shape=circle]\n")?;
 
         for (source, label, target) in &self.transitions {
             let mut label_str = format!("{}", label).escape_debug().to_string();
@@ -201,18 +283,18 @@ impl Automaton {
             }
 
             let edge = format!("\tq{} -> q{} [label=\" {} \"]\n", source, target, label_str);
-            buf.write(edge.as_bytes())?;
+            buf.write_all(edge.as_bytes())?;
         }
 
         // Add an arrow towards initial state
-        buf.write(b"\n\tnode [shape=point]\n")?;
-        buf.write(b"\tbefore_q0 -> q0\n")?;
+        buf.write_all(b"\n\tnode [shape=point]\n")?;
+        buf.write_all(b"\tbefore_q0 -> q0\n")?;
 
-        buf.write(b"}\n")?;
+        buf.write_all(b"}\n")?;
         Ok(())
     }
 
-    fn init_adj(&self) -> Vec<Vec<(Rc<Label>, usize)>> {
+    fn init_adj(&self) -> Vec<Vec<(Arc<Label>, usize)>> {
         let mut ret = vec![Vec::new(); self.nb_states];
 
         for (source, label, target) in &self.transitions {
@@ -222,7 +304,7 @@ impl Automaton {
         ret
     }
 
-    fn init_assignations(&self) -> Vec<Vec<(Rc<Label>, usize)>> {
+    fn init_assignations(&self) -> Vec<Vec<(Arc<Label>, usize)>> {
         // Compute adjacency list
         let mut adj = vec![Vec::new(); self.get_nb_states()];
 
@@ -241,7 +323,7 @@ impl Automaton {
         adj
     }
 
-    fn init_rev_assignations(&self) -> Vec<Vec<(Rc<Label>, usize)>> {
+    fn init_rev_assignations(&self) -> Vec<Vec<(Arc<Label>, usize)>> {
         // Compute adjacency list
         let mut adj = vec![Vec::new(); self.get_nb_states()];
 
@@ -300,18 +382,175 @@ impl Automaton {
         &self.jump_states
     }
 
+    /// Metrics on the shape of the automaton.
+    pub fn get_stats(&self) -> AutomatonStats {
+        let num_assignations = self
+            .transitions
+            .iter()
+            .filter(|(_, label, _)| matches!(**label, Label::Assignation(_)))
+            .count();
+        let closure_sizes = self.closure_for_assignations.iter().map(Vec::len);
+
+        AutomatonStats {
+            num_states: self.nb_states,
+            num_finals: self.finals.len(),
+            num_transitions: self.transitions.len(),
+            num_atom_transitions: self.transitions.len() - num_assignations,
+            num_assignation_transitions: num_assignations,
+            num_variables: self.get_variables().len(),
+            num_jump_states: self.jump_states.len(),
+            closure_size_avg: closure_sizes.clone().sum::<usize>() as f64
+                / self.nb_states.max(1) as f64,
+            closure_size_max: closure_sizes.max().unwrap_or(0),
+            functional: self.is_functional(),
+        }
+    }
+
+    /// Check if the spanner is functional: every run reaching a final state
+    /// opens and then closes each variable exactly once, thus all mappings
+    /// bind all the groups. Only the states from which a final state can be
+    /// reached are considered.
+    pub fn is_functional(&self) -> bool {
+        let variables = self.get_variables();
+        let nb_vars = variables.last().map_or(0, |var| var.get_id() + 1);
+
+        // States from which a final state can be reached.
+        let mut rev_adj = vec![Vec::new(); self.nb_states];
+
+        for (source, _, target) in &self.transitions {
+            rev_adj[*target].push(*source);
+        }
+
+        let mut useful = self.finals.clone();
+        let mut heap: Vec<_> = self.finals.iter().collect();
+
+        while let Some(target) = heap.pop() {
+            for &source in &rev_adj[target] {
+                if useful.insert(source) {
+                    heap.push(source);
+                }
+            }
+        }
+
+        // Each useful state must be reached with the same status of the
+        // variables by all runs: 0 if not opened yet, 1 if open and 2 if
+        // closed.
+        let mut status: Vec<Option<Vec<u8>>> = vec![None; self.nb_states];
+        let initial = self.get_initial();
+
+        if !useful.contains(initial) {
+            return true;
+        }
+
+        status[initial] = Some(vec![0; nb_vars]);
+        heap.push(initial);
+
+        while let Some(source) = heap.pop() {
+            for (label, target) in &self.adj[source] {
+                if !useful.contains(*target) {
+                    continue;
+                }
+
+                let mut next = status[source].clone().unwrap();
+
+                if let Ok(marker) = label.get_marker() {
+                    let var = marker.variable().get_id();
+
+                    match (marker, next[var]) {
+                        (Marker::Open(_), 0) => next[var] = 1,
+                        (Marker::Close(_), 1) => next[var] = 2,
+                        _ => return false,
+                    }
+                }
+
+                match &status[*target] {
+                    Some(known) if *known != next => return false,
+                    Some(_) => {}
+                    None => {
+                        status[*target] = Some(next);
+                        heap.push(*target);
+                    }
+                }
+            }
+        }
+
+        self.finals.iter().all(|state| match &status[state] {
+            Some(status) => variables.iter().all(|var| status[var.get_id()] == 2),
+            None => true,
+        })
+    }
+
+    /// Final states from which the automaton accepts any suffix of the text,
+    /// such as the states of the trailing `.*` added around patterns. A run
+    /// reaching one of these states can always be completed into a mapping.
+    pub fn get_universal_finals(&self) -> BitSet {
+        let any_char = hir::ClassUnicode::new(Some(hir::ClassUnicodeRange::new('\0', char::MAX)));
+        let mut universal = self.finals.clone();
+
+        // Remove the states that can't read some character while staying
+        // universal until a fixpoint is reached.
+        loop {
+            let removed: Vec<_> = universal
+                .iter()
+                .filter(|&state| {
+                    let mut class = hir::ClassUnicode::empty();
+
+                    for (label, target) in &self.adj[state] {
+                        if let Label::Atom(atom) = &**label {
+                            if universal.contains(*target) {
+                                class.union(&atom.to_class());
+                            }
+                        }
+                    }
+
+                    class != any_char
+                })
+                .collect();
+
+            if removed.is_empty() {
+                return universal;
+            }
+
+            for state in removed {
+                universal.remove(state);
+            }
+        }
+    }
+
     fn init_jump_states(&self) -> BitSet {
         self.transitions
             .clone()
             .into_iter()
             .filter_map(|(_, l, q)| match *l {
                 Label::Assignation(_) => Some(q),
-                Label::Atom(_) => None,
+                Label::Atom(_) | Label::Assertion(_) => None,
             })
             .collect::<BitSet>()
     }
 }
 
+/// Metrics on the shape of an automaton, to be correlated with the
+/// performances of the index built from it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AutomatonStats {
+    pub num_states: usize,
+    pub num_finals: usize,
+    pub num_transitions: usize,
+    /// Transitions reading a character.
+    pub num_atom_transitions: usize,
+    /// Transitions assigning a marker.
+    pub num_assignation_transitions: usize,
+    pub num_variables: usize,
+    /// States that are the target of an assignation, between which the reach
+    /// matrices of the index are computed.
+    pub num_jump_states: usize,
+    /// Number of states reachable from each state by assigning markers.
+    pub closure_size_avg: f64,
+    pub closure_size_max: usize,
+    /// Whether every accepting run assigns each variable exactly once.
+    pub functional: bool,
+}
+
 //  _          _          _
 // | |    __ _| |__   ___| |
 // | |   / _` | '_ \ / _ \ |
@@ -319,10 +558,13 @@ impl Automaton {
 // |_____\__,_|_.__/ \___|_|
 //
 
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq)]
 pub enum Label {
     Atom(atom::Atom),
     Assignation(Marker),
+    /// Condition on the characters around the current position, which only
+    /// appears while the automaton is built from a regex.
+    Assertion(Assertion),
 }
 
 impl Label {
@@ -330,6 +572,7 @@ impl Label {
         match self {
             Label::Assignation(marker) => Ok(marker),
             Label::Atom(_) => Err("Can't get a marker out of an atom label."),
+            Label::Assertion(_) => Err("Can't get a marker out of an assertion label."),
         }
     }
 }
@@ -339,6 +582,38 @@ impl fmt::Display for Label {
         match self {
             Label::Assignation(marker) => write!(f, "{}", marker),
             Label::Atom(atom) => write!(f, "{}", atom),
+            Label::Assertion(assertion) => write!(f, "{}", assertion),
+        }
+    }
+}
+
+//     _                        _   _
+//    / \   ___ ___  ___ _ __| |_(_) ___  _ __
+//   / _ \ / __/ __|/ _ \ '__| __| |/ _ \| '_ \
+//  / ___ \\__ \__ \  __/ |  | |_| | (_) | | | |
+// /_/   \_\___/___/\___|_|   \__|_|\___/|_| |_|
+//
+
+/// Zero-width condition on the text around a position.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Assertion {
+    /// The position is the start of the text.
+    StartText,
+    /// The position is the end of the text.
+    EndText,
+    /// The position follows a newline or is the start of the text.
+    StartLine,
+    /// The position is followed by a newline or is the end of the text.
+    EndLine,
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Assertion::StartText => write!(f, "^"),
+            Assertion::EndText => write!(f, "$"),
+            Assertion::StartLine => write!(f, "(?m:^)"),
+            Assertion::EndLine => write!(f, "(?m:$)"),
         }
     }
 }
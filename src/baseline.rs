@@ -0,0 +1,193 @@
+//  ____                 _ _
+// | __ )  __ _ ___  ___| (_)_ __   ___
+// |  _ \ / _` / __|/ _ \ | | '_ \ / _ \
+// | |_) | (_| \__ \  __/ | | | | |  __/
+// |____/ \__,_|___/\___|_|_|_| |_|\___|
+//
+
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use super::benchmark::{Measure, Sampling};
+
+/// Placeholder replaced by the pattern in the arguments of external commands.
+static REGEX_PLACEHOLDER: &str = "{regex}";
+
+/// Regex engine run over the pattern and text of a benchmark, to compare the
+/// enumeration of all the spans with the search of classic matches.
+///
+/// An engine is given by one of these specifications:
+///  - `regex`: the `regex` crate, run in this process, which finds the first
+///    match and all the leftmost non-overlapping matches;
+///  - `pcre2`: PCRE2 through its `pcre2grep` command;
+///  - `NAME=COMMAND ARGS...`: any command reading the text on its standard
+///    input and printing one line per match, where `{regex}` in the arguments
+///    is replaced by the pattern. RE2 has no standard command-line tool and is
+///    run this way through a small wrapper.
+///
+/// External commands are timed as a whole, including the start of the process
+/// and the compilation of the pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Engine {
+    Regex,
+    External { name: String, command: Vec<String> },
+}
+
+impl Engine {
+    pub fn from_spec(spec: &str) -> Engine {
+        match spec {
+            "regex" => Engine::Regex,
+            "pcre2" => Engine::External {
+                name: String::from("pcre2"),
+                command: vec![
+                    String::from("pcre2grep"),
+                    String::from("-o"),
+                    String::from("--"),
+                    String::from(REGEX_PLACEHOLDER),
+                ],
+            },
+            _ => match spec.find('=') {
+                Some(pos) => Engine::External {
+                    name: spec[..pos].to_string(),
+                    command: spec[pos + 1..].split_whitespace().map(String::from).collect(),
+                },
+                None => panic!("Invalid baseline engine: {}", spec),
+            },
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Engine::Regex => "regex",
+            Engine::External { name, .. } => name,
+        }
+    }
+
+    /// Run the engine over a text once for each sample, with the semantics it
+    /// supports.
+    pub fn run(&self, regex: &str, text: &str, sampling: Sampling) -> Vec<BaselineResult> {
+        match self {
+            Engine::Regex => {
+                let timer = Instant::now();
+                let compiled = match ::regex::Regex::new(regex) {
+                    Ok(compiled) => compiled,
+                    Err(err) => return vec![self.failure("all", err.to_string())],
+                };
+                let compile = timer.elapsed().as_secs_f64();
+
+                let first = self.measure("first", compile, sampling, || {
+                    Ok(compiled.find(text).iter().count())
+                });
+                let all = self.measure("all", compile, sampling, || {
+                    Ok(compiled.find_iter(text).count())
+                });
+
+                vec![first, all]
+            }
+            Engine::External { command, .. } => {
+                vec![self.measure("all", 0.0, sampling, || run_command(command, regex, text))]
+            }
+        }
+    }
+
+    fn measure<F>(
+        &self,
+        semantics: &str,
+        compile: f64,
+        sampling: Sampling,
+        mut f: F,
+    ) -> BaselineResult
+    where
+        F: FnMut() -> io::Result<usize>,
+    {
+        let mut samples = Vec::with_capacity(sampling.samples);
+        let mut num_results = 0;
+
+        for run in 0..sampling.warmup + sampling.samples.max(1) {
+            let timer = Instant::now();
+
+            num_results = match f() {
+                Ok(num_results) => num_results,
+                Err(err) => return self.failure(semantics, err.to_string()),
+            };
+
+            if run >= sampling.warmup {
+                samples.push(timer.elapsed().as_secs_f64());
+            }
+        }
+
+        BaselineResult {
+            engine: self.get_name().to_string(),
+            semantics: semantics.to_string(),
+            num_results,
+            compile: Some(compile).filter(|_| *self == Engine::Regex),
+            search: Some(Measure::from_samples(&samples)),
+            error: None,
+        }
+    }
+
+    fn failure(&self, semantics: &str, error: String) -> BaselineResult {
+        BaselineResult {
+            engine: self.get_name().to_string(),
+            semantics: semantics.to_string(),
+            num_results: 0,
+            compile: None,
+            search: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Run an external command over the text and count the lines it prints.
+fn run_command(command: &[String], regex: &str, text: &str) -> io::Result<usize> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::other("empty command"))?;
+
+    let mut child = Command::new(program)
+        .args(args.iter().map(|arg| arg.replace(REGEX_PLACEHOLDER, regex)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // The text is written from another thread as the command may print its
+    // matches before reading the whole input.
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+
+    let num_lines = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(text.as_bytes()));
+
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output)?;
+        Ok::<_, io::Error>(output.iter().filter(|&&byte| byte == b'\n').count())
+    })?;
+
+    let status = child.wait()?;
+
+    // Grep-like commands exit with 1 when nothing matched.
+    match status.code() {
+        Some(0) | Some(1) => Ok(num_lines),
+        _ => Err(io::Error::other(format!("{} exited with {}", program, status))),
+    }
+}
+
+/// Timings of a baseline engine for some semantics of matching: `first` only
+/// looks for the leftmost match and `all` for all the leftmost non-overlapping
+/// matches.
+#[derive(Serialize, Deserialize)]
+pub struct BaselineResult {
+    engine: String,
+    semantics: String,
+    num_results: usize,
+    /// Time to compile the pattern, only known for engines run in-process.
+    compile: Option<f64>,
+    search: Option<Measure>,
+    /// Reason why the engine could not be run.
+    error: Option<String>,
+}
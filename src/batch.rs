@@ -0,0 +1,197 @@
+//  ____        _       _
+// | __ )  __ _| |_ ___| |__
+// |  _ \ / _` | __/ __| '_ \
+// | |_) | (_| | || (__| | | |
+// |____/ \__,_|\__\___|_| |_|
+//
+
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::spanner::CompiledSpanner;
+
+/// Number of documents per worker which can be handed out before the results
+/// of the previous ones are output. It bounds the results that are kept while
+/// a long document is being processed.
+const MAX_PENDING_PER_JOB: usize = 4;
+
+/// Evaluate a pattern over a collection of documents with a pool of workers.
+///
+/// The pattern is compiled once and the spanner is shared by all the workers,
+/// which then process any number of documents with it.
+pub struct Batch {
+    spanner: CompiledSpanner,
+    jobs: usize,
+}
+
+impl Batch {
+    pub fn new(pattern: &str, jobs: usize) -> Batch {
+        Batch::from_spanner(CompiledSpanner::new(pattern), jobs)
+    }
+
+    pub fn from_spanner(spanner: CompiledSpanner, jobs: usize) -> Batch {
+        assert!(jobs > 0, "At least one worker is needed");
+        Batch { spanner, jobs }
+    }
+
+    /// Process each document with `process`, which is called by the workers
+    /// with their compiled spanner. The results are given to `output` on the
+    /// current thread along with their document, in the order of the
+    /// documents, as soon as all the previous documents are done.
+    pub fn run<D, T, P, R, O>(&self, documents: D, process: P, mut output: O)
+    where
+        D: IntoIterator<Item = T>,
+        T: Send,
+        P: Fn(&CompiledSpanner, &T) -> R + Sync,
+        R: Send,
+        O: FnMut(T, R),
+    {
+        let spanner = &self.spanner;
+
+        if self.jobs == 1 || cfg!(not(feature = "parallel")) {
+            for document in documents {
+                let result = process(spanner, &document);
+                output(document, result);
+            }
+
+            return;
+        }
+
+        thread::scope(|scope| {
+            // Documents are read lazily, a few at a time.
+            let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, T)>(self.jobs);
+            let (result_sender, result_receiver) = mpsc::channel();
+            let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+            for _ in 0..self.jobs {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+                let process = &process;
+
+                scope.spawn(move || {
+                    loop {
+                        let job = job_receiver.lock().unwrap().recv();
+
+                        let (index, document) = match job {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+
+                        // A panic is given back to the current thread, which
+                        // would otherwise wait for the result of the document.
+                        let result =
+                            panic::catch_unwind(AssertUnwindSafe(|| process(spanner, &document)));
+
+                        if result_sender.send((index, document, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            drop(result_sender);
+
+            // Results that are done before some previous document.
+            let mut pending = BTreeMap::new();
+            let mut next_index = 0;
+
+            let mut flush = |pending: &mut BTreeMap<usize, (T, R)>, next_index: &mut usize| {
+                while let Some((document, result)) = pending.remove(next_index) {
+                    output(document, result);
+                    *next_index += 1;
+                }
+            };
+
+            let receive = |pending: &mut BTreeMap<usize, (T, R)>, (index, document, result)| {
+                match result {
+                    Ok(result) => pending.insert(index, (document, result)),
+                    Err(err) => panic::resume_unwind(err),
+                };
+            };
+
+            for (index, document) in documents.into_iter().enumerate() {
+                while index - next_index >= MAX_PENDING_PER_JOB * self.jobs {
+                    let received = result_receiver
+                        .recv()
+                        .expect("Workers stopped before the end of the batch");
+                    receive(&mut pending, received);
+                    flush(&mut pending, &mut next_index);
+                }
+
+                job_sender
+                    .send((index, document))
+                    .expect("Workers stopped before the end of the batch");
+
+                for received in result_receiver.try_iter() {
+                    receive(&mut pending, received);
+                }
+
+                flush(&mut pending, &mut next_index);
+            }
+
+            drop(job_sender);
+
+            for received in result_receiver {
+                receive(&mut pending, received);
+                flush(&mut pending, &mut next_index);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Batch, MAX_PENDING_PER_JOB};
+
+    #[test]
+    fn batch_order() {
+        let documents: Vec<String> = (0..20).map(|i| "a@b ".repeat(i)).collect();
+        let mut counts = Vec::new();
+
+        Batch::new(r"(?P<x>a)@(?P<y>b)", 3).run(
+            documents.iter(),
+            |spanner, text| spanner.find_all(text).len(),
+            |text, count| counts.push((text.len() / 4, count)),
+        );
+
+        assert_eq!(counts, (0..20).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn batch_pending() {
+        // The first document is slow, the next ones are not processed too far
+        // ahead of it.
+        let processed = AtomicUsize::new(0);
+        let jobs = 2;
+
+        Batch::new(r"(?P<x>a)", jobs).run(
+            0..100,
+            |_, &i| {
+                if i == 0 {
+                    thread::sleep(Duration::from_millis(50));
+                }
+
+                processed.fetch_add(1, Ordering::SeqCst);
+            },
+            |i, _| assert!(processed.load(Ordering::SeqCst) <= i + 1 + MAX_PENDING_PER_JOB * jobs),
+        );
+    }
+
+    #[test]
+    fn batch_panic() {
+        // A panic of a worker stops the batch instead of leaving it waiting.
+        let result = panic::catch_unwind(|| {
+            Batch::new(r"(?P<x>a)", 2).run(0..100, |_, &i| assert!(i != 3), |_, _| ())
+        });
+
+        assert!(result.is_err());
+    }
+}
@@ -1,17 +1,22 @@
 use super::mapping::{
-    indexed_dag::{IndexedDag, TrimmingStrategy},
-    SpannerEnumerator,
+    indexed_dag::{IndexedDag, JumpSchedule, TrimmingStrategy},
+    DeterministicEva, SpannerEnumerator,
 };
-use super::Algorithm;
+use enum_spanner_rs::algorithm::Algorithm;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use serde::{Deserialize, Serialize};
 
+use super::allocator;
+use super::baseline::{BaselineResult, Engine};
 use super::naive;
 use super::regex;
+use super::regex::Construction;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct BenchmarkCase {
@@ -20,8 +25,16 @@ pub struct BenchmarkCase {
     filename: String,
     regex: String,
     jump: Option<usize>,
+    jump_schedule: Option<JumpSchedule>,
+    single_pass: Option<bool>,
+    mapped_levels: Option<bool>,
+    compressed_levels: Option<bool>,
     trimming: Option<TrimmingStrategy>,
+    construction: Option<Construction>,
     length: Option<u64>,
+    /// Engines run over the same pattern and text to compare with, as given to
+    /// `Engine::from_spec`.
+    baselines: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -30,7 +43,43 @@ pub struct Delay {
     delay_max: f64,
     delay_avg: f64,
     delay_stddev: f64,
+    /// Percentiles of the delays, by the nearest-rank method.
+    delay_p50: f64,
+    delay_p90: f64,
+    delay_p99: f64,
+    delay_p999: f64,
     delay_hist: Vec<u32>,
+    /// Delays in nanoseconds before each match but the first, for each
+    /// repetition.
+    #[serde(skip)]
+    samples: Vec<Vec<u32>>,
+}
+
+/// Statistics over the measured runs of a phase, in seconds.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Measure {
+    mean: f64,
+    stddev: f64,
+    /// Bounds of the 95% confidence interval of the mean.
+    ci95_low: f64,
+    ci95_high: f64,
+    samples: usize,
+}
+
+/// Peak heap usage in bytes of each phase, as measured by the allocator.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PeakMemory {
+    preprocess: usize,
+    enumerate: usize,
+}
+
+/// Number of runs of a benchmark case.
+#[derive(Clone, Copy)]
+pub struct Sampling {
+    /// Runs done before measuring, which are ignored.
+    pub warmup: usize,
+    /// Runs that are measured, at least one.
+    pub samples: usize,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,40 +90,246 @@ pub struct BenchmarkResult {
     width_avg: f64,
     width_max: usize,
     preprocess: f64,
+    preprocess_stats: Measure,
     create_dag: Option<f64>,
     trim_dag: Option<f64>,
     index_dag: Option<f64>,
     enumerate: f64,
+    enumerate_stats: Measure,
+    /// Time from the start of the preprocessing until the first match is
+    /// produced.
+    time_first: f64,
+    time_first_stats: Measure,
     delays: Option<Delay>,
     memory_usage: usize,
     memory_dag: usize,
     memory_matrices: usize,
     memory_jump_level: usize,
     memory_dag_max: usize,
+    /// Only available if the crate is built with the `alloc-stats` feature.
+    peak_memory: Option<PeakMemory>,
     num_matrices: usize,
     num_used_matrices: usize,
     matrix_avg_size: f64,
     matrix_max_size: usize,
+    /// Proportion of entries set over all the matrices.
+    matrix_avg_density: f64,
+    /// Percentiles of the density of each matrix, by the nearest-rank method.
+    matrix_density_p50: f64,
+    matrix_density_p90: f64,
+    matrix_density_p99: f64,
     num_levels: usize,
+    num_trimmed_levels: usize,
+    num_untrimmed_levels: usize,
+    baselines: Vec<BaselineResult>,
+    /// Number of jumps taken by the measured enumeration for each distance in
+    /// levels of the index.
+    jump_hist: Vec<usize>,
+    /// Matrices applied by the jumps of the measured enumeration per match.
+    matrix_applications_avg: f64,
+}
+
+/// Result of a benchmark case run with one of the algorithms compared.
+#[derive(Serialize, Deserialize)]
+pub struct ComparedResult {
+    algorithm: String,
+    /// Total time of this algorithm divided by the total time of ICDT19.
+    icdt19_speedup: f64,
+    /// Whether the algorithm found as many results as ICDT19.
+    consistent: bool,
+    num_results: usize,
+    preprocess: f64,
+    enumerate: f64,
+    time_first: f64,
+    delays: Option<Delay>,
+}
+
+/// Results of a benchmark case run with all algorithms.
+#[derive(Serialize, Deserialize)]
+pub struct Comparison {
+    benchmark: BenchmarkCase,
+    /// Whether all the algorithms found the same number of results.
+    consistent: bool,
+    results: Vec<ComparedResult>,
+    baselines: Vec<BaselineResult>,
+}
+
+/// Two-sided 95% quantiles of Student's t-distribution for 1 to 30 degrees of
+/// freedom, the normal distribution is used beyond.
+static STUDENT_T95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+impl Measure {
+    pub fn from_samples(samples: &[f64]) -> Measure {
+        let n = samples.len();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+
+        if n < 2 {
+            return Measure {
+                mean,
+                stddev: 0.0,
+                ci95_low: mean,
+                ci95_high: mean,
+                samples: n,
+            };
+        }
+
+        // Sample standard deviation
+        let variance =
+            samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1) as f64;
+        let stddev = variance.sqrt();
+        let t = STUDENT_T95.get(n - 2).cloned().unwrap_or(1.960);
+        let margin = t * stddev / (n as f64).sqrt();
+
+        Measure {
+            mean,
+            stddev,
+            ci95_low: mean - margin,
+            ci95_high: mean + margin,
+            samples: n,
+        }
+    }
+}
+
+/// Header of the CSV files holding raw delay samples.
+pub static DELAY_SAMPLES_HEADER: &str = "benchmark,algorithm,repetition,match,delay_ns";
+
+/// Smallest value of a sorted list such that at least the given percentage of
+/// the values are not greater, or 0 if the list is empty.
+fn percentile<T: Copy + Default>(sorted: &[T], percentage: f64) -> T {
+    let rank = (percentage / 100.0 * sorted.len() as f64).ceil() as usize;
+
+    match sorted.len() {
+        0 => T::default(),
+        len => sorted[rank.clamp(1, len) - 1],
+    }
+}
+
+impl Delay {
+
+    /// Write one CSV row per delay sample, matches are numbered from 1 and a
+    /// sample is the delay between a match and the previous one.
+    fn write_samples<W: Write>(
+        &self,
+        benchmark: &str,
+        algorithm: &str,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let benchmark = format!("\"{}\"", benchmark.replace('"', "\"\""));
+
+        for (repetition, delays) in self.samples.iter().enumerate() {
+            for (index, delay) in delays.iter().enumerate() {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    benchmark,
+                    algorithm,
+                    repetition + 1,
+                    index + 2,
+                    delay
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl BenchmarkResult {
+    /// Write the raw delay samples as CSV rows, if the delays were measured.
+    pub fn write_delay_samples<W: Write>(
+        &self,
+        algorithm: Algorithm,
+        out: &mut W,
+    ) -> io::Result<()> {
+        match &self.delays {
+            Some(delays) => delays.write_samples(&self.benchmark.name, algorithm.get_name(), out),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Comparison {
+    /// Write the raw delay samples of all algorithms as CSV rows, if the
+    /// delays were measured.
+    pub fn write_delay_samples<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for result in &self.results {
+            if let Some(delays) = &result.delays {
+                delays.write_samples(&self.benchmark.name, &result.algorithm, out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reason why the input of a benchmark case could not be used.
+#[derive(Debug)]
+pub enum InputError {
+    /// The file could not be opened or read.
+    Unreadable(std::io::Error),
+    /// The file is not valid UTF-8, the first invalid byte is at given offset.
+    InvalidUtf8(usize),
+    /// The file is larger than the allowed size, both given in bytes.
+    TooLarge { size: u64, limit: u64 },
+    /// The pattern can't be compiled, for the given reason.
+    InvalidPattern(String),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Unreadable(err) => write!(f, "unreadable: {}", err),
+            InputError::InvalidUtf8(offset) => write!(f, "invalid UTF-8 at byte {}", offset),
+            InputError::TooLarge { size, limit } => {
+                write!(f, "too large: {} bytes (limit is {})", size, limit)
+            }
+            InputError::InvalidPattern(reason) => write!(f, "invalid pattern: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+impl From<std::io::Error> for InputError {
+    fn from(err: std::io::Error) -> InputError {
+        InputError::Unreadable(err)
+    }
 }
 
 impl BenchmarkCase {
+    /// Read the cases of a benchmark file, which is a JSON list. Each case
+    /// that can't be read is given with its error, such that the other ones
+    /// can still be run.
     pub fn read_from_file(
         filename: &Path,
-    ) -> Result<Vec<BenchmarkCase>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<Result<BenchmarkCase, serde_json::Error>>, Box<dyn std::error::Error>> {
         let mut input = String::new();
 
         File::open(&filename)?.read_to_string(&mut input)?;
         let path = filename.parent();
 
-        let mut benchmarks: Vec<BenchmarkCase> = serde_json::from_str(&input)?;
+        let cases: Vec<serde_json::Value> = serde_json::from_str(&input)?;
+        let mut benchmarks: Vec<Result<BenchmarkCase, serde_json::Error>> =
+            cases.into_iter().map(serde_json::from_value).collect();
 
         if let Some(path) = path {
-            for mut benchmark in &mut benchmarks {
-                benchmark.filename = path
-                    .join(benchmark.filename.clone())
+            for benchmark in benchmarks.iter_mut().flatten() {
+                // Filenames are written with forward slashes in benchmark files,
+                // they are split to use the separator of the platform.
+                let mut filename = if benchmark.filename.starts_with('/') {
+                    PathBuf::from("/")
+                } else {
+                    path.to_path_buf()
+                };
+                filename.extend(benchmark.filename.split('/').filter(|part| !part.is_empty()));
+
+                benchmark.filename = filename
                     .to_str()
-                    .unwrap()
+                    .expect("Benchmark paths must be valid unicode")
                     .to_string();
             }
         }
@@ -89,6 +344,7 @@ impl BenchmarkCase {
         regex: String,
         jump: usize,
         trimming: TrimmingStrategy,
+        construction: Construction,
     ) -> BenchmarkCase {
         BenchmarkCase {
             name,
@@ -96,9 +352,89 @@ impl BenchmarkCase {
             filename,
             regex,
             length: None,
+            baselines: None,
             jump: Some(jump),
+            jump_schedule: None,
+            single_pass: None,
+            mapped_levels: None,
+            compressed_levels: None,
             trimming: Some(trimming),
+            construction: Some(construction),
+        }
+    }
+
+    pub fn with_jump_schedule(mut self, jump_schedule: JumpSchedule) -> BenchmarkCase {
+        self.jump_schedule = Some(jump_schedule);
+        self
+    }
+
+    pub fn with_single_pass(mut self, single_pass: bool) -> BenchmarkCase {
+        self.single_pass = Some(single_pass);
+        self
+    }
+
+    pub fn with_mapped_levels(mut self, mapped_levels: bool) -> BenchmarkCase {
+        self.mapped_levels = Some(mapped_levels);
+        self
+    }
+
+    pub fn with_compressed_levels(mut self, compressed_levels: bool) -> BenchmarkCase {
+        self.compressed_levels = Some(compressed_levels);
+        self
+    }
+
+    /// Add engines to compare with, see `Engine::from_spec`.
+    pub fn with_baselines(mut self, baselines: Vec<String>) -> BenchmarkCase {
+        self.baselines.get_or_insert_with(Vec::new).extend(baselines);
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Read the text of the benchmark, which is truncated to the length of the
+    /// case if specified.
+    fn read_input(&self, max_size: Option<u64>) -> Result<String, InputError> {
+        let file = File::open(&self.filename)?;
+        let length = self.length.unwrap_or(u64::MAX);
+
+        if let Some(limit) = max_size {
+            let size = file.metadata()?.len().min(length);
+
+            if size > limit {
+                return Err(InputError::TooLarge { size, limit });
+            }
         }
+
+        let mut input = Vec::new();
+        file.take(length).read_to_end(&mut input)?;
+
+        String::from_utf8(input)
+            .map_err(|err| InputError::InvalidUtf8(err.utf8_error().valid_up_to()))
+    }
+
+    /// Run the baseline engines of the benchmark over its input.
+    fn run_baselines(
+        &self,
+        sampling: Sampling,
+        max_size: Option<u64>,
+    ) -> Result<Vec<BaselineResult>, InputError> {
+        let baselines = match &self.baselines {
+            Some(baselines) if !baselines.is_empty() => baselines,
+            _ => return Ok(Vec::new()),
+        };
+
+        let input = self.read_input(max_size)?;
+
+        Ok(baselines
+            .iter()
+            .flat_map(|spec| Engine::from_spec(spec).run(&self.regex, &input, sampling))
+            .collect())
     }
 
     fn measure_delays<'a>(
@@ -170,63 +506,126 @@ impl BenchmarkCase {
             hist[i as usize / 1000] += 1;
         }
 
+        let mut sorted_delays = mean_delays.clone();
+        sorted_delays.sort_unstable();
+        let percentile = |p| percentile(&sorted_delays, p) as f64 / 1000000000.0;
+
         Some(Delay {
             delay_min: min as f64 / 1000000000.0,
             delay_max: max as f64 / 1000000000.0,
             delay_avg: mean as f64 / 1000000000.0,
             delay_stddev: stddev as f64 / 1000000000.0,
+            delay_p50: percentile(50.0),
+            delay_p90: percentile(90.0),
+            delay_p99: percentile(99.0),
+            delay_p999: percentile(99.9),
             delay_hist: hist,
+            samples: delays,
         })
     }
 
-    fn measure<'a>(&'a self, enumerator: &mut impl SpannerEnumerator<'a>) -> (usize, f64, f64) {
-        // Prepare the enumeration.
-        let timer = Instant::now();
-        enumerator.preprocess();
-        let preprocess = timer.elapsed();
-
-        // Count matches.
-        let timer = Instant::now();
-        let count_matches = enumerator.iter().count();
-        let enumerate = timer.elapsed();
+    /// Preprocess, find the first match and enumerate the matches once for
+    /// each run, the index of the last run is kept in the enumerator.
+    fn measure<'a>(
+        &'a self,
+        enumerator: &mut impl SpannerEnumerator<'a>,
+        sampling: Sampling,
+    ) -> (usize, Measure, Measure, Measure, Option<PeakMemory>) {
+        let mut count_matches = 0;
+        let mut preprocess = Vec::with_capacity(sampling.samples);
+        let mut enumerate = Vec::with_capacity(sampling.samples);
+        let mut time_first = Vec::with_capacity(sampling.samples);
+        let mut peak_memory = PeakMemory::default();
+
+        for run in 0..sampling.warmup + sampling.samples.max(1) {
+            // Prepare the enumeration.
+            let memory = allocator::start_phase();
+            let timer = Instant::now();
+            enumerator.preprocess();
+            let preprocess_time = timer.elapsed();
+            let preprocess_memory = allocator::phase_peak(memory);
+
+            // Find the first match.
+            let timer = Instant::now();
+            enumerator.first();
+            let first_time = preprocess_time + timer.elapsed();
+
+            // Count matches.
+            let memory = allocator::start_phase();
+            let timer = Instant::now();
+            count_matches = enumerator.count() as usize;
+            let enumerate_time = timer.elapsed();
+            let enumerate_memory = allocator::phase_peak(memory);
+
+            if run >= sampling.warmup {
+                preprocess.push(preprocess_time.as_nanos() as f64 / 1000000000.0);
+                enumerate.push(enumerate_time.as_nanos() as f64 / 1000000000.0);
+                time_first.push(first_time.as_nanos() as f64 / 1000000000.0);
+                peak_memory.preprocess = peak_memory.preprocess.max(preprocess_memory);
+                peak_memory.enumerate = peak_memory.enumerate.max(enumerate_memory);
+            }
+        }
 
         (
             count_matches,
-            preprocess.as_nanos() as f64 / 1000000000.0,
-            enumerate.as_nanos() as f64 / 1000000000.0,
+            Measure::from_samples(&preprocess),
+            Measure::from_samples(&enumerate),
+            Measure::from_samples(&time_first),
+            Some(peak_memory).filter(|_| allocator::is_enabled()),
         )
     }
 
-    pub fn run(&self, algorithm: Algorithm, k: usize) -> Result<BenchmarkResult, std::io::Error> {
-        let mut input = String::new();
-        let trimming_strategy = match self.trimming {
-            None => TrimmingStrategy::FullTrimming,
-            Some(s) => s,
-        };
-
-        let jump_distance = match self.jump {
-            None => 1,
-            Some(d) => d,
-        };
+    /// Run the benchmark with an algorithm, followed by the baseline engines.
+    pub fn run(
+        &self,
+        algorithm: Algorithm,
+        k: usize,
+        sampling: Sampling,
+        max_size: Option<u64>,
+    ) -> Result<BenchmarkResult, InputError> {
+        let mut result = self.run_algorithm(algorithm, k, sampling, max_size)?;
+        result.baselines = self.run_baselines(sampling, max_size)?;
+        Ok(result)
+    }
 
+    fn run_algorithm(
+        &self,
+        algorithm: Algorithm,
+        k: usize,
+        sampling: Sampling,
+        max_size: Option<u64>,
+    ) -> Result<BenchmarkResult, InputError> {
         // Read input file content.
-        File::open(&self.filename)?
-            .take(match self.length {
-                Some(l) => l,
-                None => std::u64::MAX,
-            })
-            .read_to_string(&mut input)?;
+        let input = self.read_input(max_size)?;
 
         // Compile the regex.
-        let automaton = regex::compile(&self.regex);
+        let construction = self.construction.unwrap_or(Construction::Glushkov);
+        let automaton =
+            regex::try_compile_with(&self.regex, construction).map_err(InputError::InvalidPattern)?;
 
         let num_states = automaton.get_nb_states();
 
         match algorithm {
-            Algorithm::ICDT19 => {
-                let mut enumerator =
-                    IndexedDag::new(automaton, &input, jump_distance, trimming_strategy, false);
-                let (count_matches, preprocess, enumerate) = self.measure(&mut enumerator);
+            Algorithm::ICDT19 | Algorithm::JumpPointers => {
+                let mut enumerator = IndexedDag::builder(automaton, &input)
+                    .with_jump_distance(self.jump.unwrap_or(1))
+                    .with_jump_schedule(self.jump_schedule.unwrap_or(JumpSchedule::Uniform))
+                    .with_trimming(self.trimming.unwrap_or(TrimmingStrategy::FullTrimming))
+                    .with_single_pass(self.single_pass.unwrap_or(false))
+                    .with_mapped_levels(self.mapped_levels.unwrap_or(false))
+                    .with_compressed_levels(self.compressed_levels.unwrap_or(false))
+                    .with_jump_pointers(algorithm == Algorithm::JumpPointers)
+                    .build();
+                let (
+                    count_matches,
+                    preprocess_stats,
+                    enumerate_stats,
+                    time_first_stats,
+                    peak_memory,
+                ) = self.measure(&mut enumerator, sampling);
+                // Jumps are counted before the enumerations measuring delays.
+                let (jump_hist, matrix_applications) =
+                    enumerator.get_jump_stats().unwrap_or((Vec::new(), 0));
                 let delays = self.measure_delays(count_matches, &enumerator, k);
                 let (
                     num_matrices,
@@ -241,7 +640,11 @@ impl BenchmarkCase {
                 let (create_dag, trim_dag, index_dag) = enumerator.get_times();
                 let (dag_mem_max, dag_mem, matrices_mem, jump_level_mem) =
                     enumerator.get_memory_usage().unwrap_or((0, 0, 0, 0));
+                let matrix_avg_density = enumerator.get_matrix_density().unwrap_or(0.0);
+                let densities = enumerator.get_matrix_densities().unwrap_or_default();
                 let num_levels = enumerator.num_levels().unwrap_or(0);
+                let (num_trimmed_levels, num_untrimmed_levels) =
+                    enumerator.get_trim_counts().unwrap_or((0, 0));
 
                 Ok(BenchmarkResult {
                     num_states,
@@ -251,16 +654,31 @@ impl BenchmarkCase {
                     num_used_matrices,
                     matrix_avg_size,
                     matrix_max_size,
+                    matrix_avg_density,
+                    matrix_density_p50: percentile(&densities, 50.0),
+                    matrix_density_p90: percentile(&densities, 90.0),
+                    matrix_density_p99: percentile(&densities, 99.0),
                     width_avg,
                     width_max,
-                    preprocess,
-                    enumerate,
+                    preprocess: preprocess_stats.mean,
+                    preprocess_stats,
+                    enumerate: enumerate_stats.mean,
+                    enumerate_stats,
+                    time_first: time_first_stats.mean,
+                    time_first_stats,
                     memory_usage: dag_mem + matrices_mem + jump_level_mem,
                     memory_dag_max: dag_mem_max,
+                    peak_memory,
                     memory_dag: dag_mem,
                     memory_matrices: matrices_mem,
+                    baselines: Vec::new(),
                     memory_jump_level: jump_level_mem,
                     num_levels,
+                    num_trimmed_levels,
+                    num_untrimmed_levels,
+                    jump_hist,
+                    matrix_applications_avg: matrix_applications as f64
+                        / count_matches.max(1) as f64,
                     create_dag: create_dag.map(|t| t.as_nanos() as f64 / 1000000000.0),
                     trim_dag: trim_dag.map(|t| t.as_nanos() as f64 / 1000000000.0),
                     index_dag: index_dag.map(|t| t.as_nanos() as f64 / 1000000000.0),
@@ -269,7 +687,13 @@ impl BenchmarkCase {
             }
             Algorithm::Naive => {
                 let mut enumerator = naive::naive::NaiveEnum::new(&automaton, &input);
-                let (count_matches, preprocess, enumerate) = self.measure(&mut enumerator);
+                let (
+                    count_matches,
+                    preprocess_stats,
+                    enumerate_stats,
+                    time_first_stats,
+                    peak_memory,
+                ) = self.measure(&mut enumerator, sampling);
                 let delays = self.measure_delays(count_matches, &enumerator, k);
 
                 Ok(BenchmarkResult {
@@ -280,17 +704,80 @@ impl BenchmarkCase {
                     num_used_matrices: 0,
                     matrix_avg_size: 0.0,
                     matrix_max_size: 0,
+                    matrix_avg_density: 0.0,
+                    matrix_density_p50: 0.0,
+                    matrix_density_p90: 0.0,
+                    matrix_density_p99: 0.0,
                     width_avg: 0.0,
                     width_max: 0,
-                    preprocess,
-                    enumerate,
+                    preprocess: preprocess_stats.mean,
+                    preprocess_stats,
+                    enumerate: enumerate_stats.mean,
+                    enumerate_stats,
+                    time_first: time_first_stats.mean,
+                    time_first_stats,
                     delays,
                     memory_usage: 0,
                     memory_dag_max: 0,
+                    peak_memory,
                     memory_dag: 0,
                     memory_matrices: 0,
+                    baselines: Vec::new(),
                     memory_jump_level: 0,
                     num_levels: 0,
+                    num_trimmed_levels: 0,
+                    num_untrimmed_levels: 0,
+                    jump_hist: Vec::new(),
+                    matrix_applications_avg: 0.0,
+                    create_dag: None,
+                    trim_dag: None,
+                    index_dag: None,
+                })
+            }
+            Algorithm::PODS18 => {
+                let mut enumerator = DeterministicEva::new(automaton, &input);
+                let (
+                    count_matches,
+                    preprocess_stats,
+                    enumerate_stats,
+                    time_first_stats,
+                    peak_memory,
+                ) = self.measure(&mut enumerator, sampling);
+                let delays = self.measure_delays(count_matches, &enumerator, k);
+
+                Ok(BenchmarkResult {
+                    benchmark: self.clone(),
+                    num_states,
+                    num_results: count_matches,
+                    num_matrices: 0,
+                    num_used_matrices: 0,
+                    matrix_avg_size: 0.0,
+                    matrix_max_size: 0,
+                    matrix_avg_density: 0.0,
+                    matrix_density_p50: 0.0,
+                    matrix_density_p90: 0.0,
+                    matrix_density_p99: 0.0,
+                    width_avg: 0.0,
+                    width_max: 0,
+                    preprocess: preprocess_stats.mean,
+                    preprocess_stats,
+                    enumerate: enumerate_stats.mean,
+                    enumerate_stats,
+                    time_first: time_first_stats.mean,
+                    time_first_stats,
+                    delays,
+                    memory_usage: enumerator.get_memory_usage(),
+                    memory_dag_max: 0,
+                    peak_memory,
+                    memory_dag: 0,
+                    memory_matrices: 0,
+                    baselines: Vec::new(),
+                    memory_jump_level: 0,
+                    num_levels: 0,
+                    num_trimmed_levels: 0,
+                    num_untrimmed_levels: 0,
+                    jump_hist: Vec::new(),
+                    matrix_applications_avg: 0.0,
                     create_dag: None,
                     trim_dag: None,
                     index_dag: None,
@@ -299,7 +786,13 @@ impl BenchmarkCase {
             Algorithm::NaiveQuadratic => {
                 let mut enumerator =
                     naive::naive_quadratic::NaiveEnumQuadratic::new(&self.regex, &input);
-                let (count_matches, preprocess, enumerate) = self.measure(&mut enumerator);
+                let (
+                    count_matches,
+                    preprocess_stats,
+                    enumerate_stats,
+                    time_first_stats,
+                    peak_memory,
+                ) = self.measure(&mut enumerator, sampling);
                 let delays = self.measure_delays(count_matches, &enumerator, k);
 
                 Ok(BenchmarkResult {
@@ -310,17 +803,31 @@ impl BenchmarkCase {
                     num_used_matrices: 0,
                     matrix_avg_size: 0.0,
                     matrix_max_size: 0,
+                    matrix_avg_density: 0.0,
+                    matrix_density_p50: 0.0,
+                    matrix_density_p90: 0.0,
+                    matrix_density_p99: 0.0,
                     width_avg: 0.0,
                     width_max: 0,
-                    preprocess,
-                    enumerate,
+                    preprocess: preprocess_stats.mean,
+                    preprocess_stats,
+                    enumerate: enumerate_stats.mean,
+                    enumerate_stats,
+                    time_first: time_first_stats.mean,
+                    time_first_stats,
                     delays,
                     memory_usage: 0,
                     memory_dag_max: 0,
+                    peak_memory,
                     memory_dag: 0,
                     memory_matrices: 0,
+                    baselines: Vec::new(),
                     memory_jump_level: 0,
                     num_levels: 0,
+                    num_trimmed_levels: 0,
+                    num_untrimmed_levels: 0,
+                    jump_hist: Vec::new(),
+                    matrix_applications_avg: 0.0,
                     create_dag: None,
                     trim_dag: None,
                     index_dag: None,
@@ -329,7 +836,13 @@ impl BenchmarkCase {
             Algorithm::NaiveCubic => {
                 let mut enumerator =
                     naive::naive_cubic::NaiveEnumCubic::new(&self.regex, &input).unwrap();
-                let (count_matches, preprocess, enumerate) = self.measure(&mut enumerator);
+                let (
+                    count_matches,
+                    preprocess_stats,
+                    enumerate_stats,
+                    time_first_stats,
+                    peak_memory,
+                ) = self.measure(&mut enumerator, sampling);
                 let delays = self.measure_delays(count_matches, &enumerator, k);
 
                 Ok(BenchmarkResult {
@@ -340,17 +853,31 @@ impl BenchmarkCase {
                     num_used_matrices: 0,
                     matrix_avg_size: 0.0,
                     matrix_max_size: 0,
+                    matrix_avg_density: 0.0,
+                    matrix_density_p50: 0.0,
+                    matrix_density_p90: 0.0,
+                    matrix_density_p99: 0.0,
                     width_avg: 0.0,
                     width_max: 0,
-                    preprocess,
-                    enumerate,
+                    preprocess: preprocess_stats.mean,
+                    preprocess_stats,
+                    enumerate: enumerate_stats.mean,
+                    enumerate_stats,
+                    time_first: time_first_stats.mean,
+                    time_first_stats,
                     delays,
                     memory_usage: 0,
                     memory_dag_max: 0,
+                    peak_memory,
                     memory_dag: 0,
                     memory_matrices: 0,
+                    baselines: Vec::new(),
                     memory_jump_level: 0,
                     num_levels: 0,
+                    num_trimmed_levels: 0,
+                    num_untrimmed_levels: 0,
+                    jump_hist: Vec::new(),
+                    matrix_applications_avg: 0.0,
                     create_dag: None,
                     trim_dag: None,
                     index_dag: None,
@@ -358,4 +885,87 @@ impl BenchmarkCase {
             }
         }
     }
+
+    /// Run the benchmark with all algorithms and compare their results to the
+    /// ones of ICDT19.
+    pub fn compare(
+        &self,
+        k: usize,
+        sampling: Sampling,
+        max_size: Option<u64>,
+    ) -> Result<Comparison, InputError> {
+        let mut runs = Vec::with_capacity(Algorithm::ALL.len());
+
+        for &algorithm in &Algorithm::ALL {
+            runs.push((algorithm, self.run_algorithm(algorithm, k, sampling, max_size)?));
+        }
+
+        let reference = &runs[0].1;
+        let reference_time = reference.preprocess + reference.enumerate;
+        let reference_results = reference.num_results;
+
+        let results: Vec<ComparedResult> = runs
+            .into_iter()
+            .map(|(algorithm, result)| ComparedResult {
+                algorithm: algorithm.get_name().to_string(),
+                icdt19_speedup: (result.preprocess + result.enumerate) / reference_time,
+                consistent: result.num_results == reference_results,
+                num_results: result.num_results,
+                preprocess: result.preprocess,
+                enumerate: result.enumerate,
+                time_first: result.time_first,
+                delays: result.delays,
+            })
+            .collect();
+
+        Ok(Comparison {
+            benchmark: self.clone(),
+            consistent: results.iter().all(|result| result.consistent),
+            results,
+            baselines: self.run_baselines(sampling, max_size)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use enum_spanner_rs::algorithm::Algorithm;
+
+    use super::{BenchmarkCase, InputError, Sampling};
+
+    #[test]
+    fn invalid_cases() {
+        let dir = std::env::temp_dir().join(format!("benchmark-cases-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("text.txt"), "a@b").unwrap();
+        fs::write(
+            dir.join("cases.json"),
+            r#"[
+                {"name": "good", "comment": "", "filename": "text.txt", "regex": "(?P<x>a)"},
+                {"name": "no comment", "filename": "text.txt", "regex": "a"},
+                {"name": "unclosed", "comment": "", "filename": "text.txt", "regex": "(?P<x>a"},
+                {"name": "repeated", "comment": "", "filename": "text.txt", "regex": "(?P<x>a)*"}
+            ]"#,
+        )
+        .unwrap();
+
+        let cases = BenchmarkCase::read_from_file(&dir.join("cases.json")).unwrap();
+
+        // The cases that can't be read don't prevent the others from being
+        // read, and the cases that can't be run from being run.
+        assert_eq!(cases.len(), 4);
+        assert!(cases[1].is_err());
+
+        let sampling = Sampling { warmup: 0, samples: 1 };
+        let run = |case: &BenchmarkCase| case.run(Algorithm::ICDT19, 0, sampling, None);
+        let cases: Vec<_> = cases.into_iter().flatten().collect();
+        assert_eq!(cases.len(), 3);
+        assert!(run(&cases[0]).is_ok());
+        assert!(matches!(run(&cases[1]), Err(InputError::InvalidPattern(_))));
+        assert!(matches!(run(&cases[2]), Err(InputError::InvalidPattern(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,27 @@
+//   ____ _            _
+//  / ___| | ___   ___| | __
+// | |   | |/ _ \ / __| |/ /
+// | |___| | (_) | (__|   <
+//  \____|_|\___/ \___|_|\_\
+//
+
+/// Monotonic clock used to time the preprocessing.
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+/// The wasm32-unknown-unknown target has no clock, durations measured there
+/// are always zero.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Copy)]
+pub struct Instant;
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(0)
+    }
+}
@@ -0,0 +1,80 @@
+//   ____ ______     __
+//  / ___/ ___\ \   / /
+// | |   \___ \\ \ / /
+// | |___ ___) |\ V /
+//  \____|____/  \_/
+//
+
+use std::io::{self, BufRead};
+
+/// Read the records of a CSV file, or of a TSV file with a tab delimiter.
+///
+/// Fields may be quoted with double quotes, in which case they can contain
+/// the delimiter, line breaks and double quotes written twice.
+pub struct CsvReader<R: BufRead> {
+    input: R,
+    delimiter: char,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(input: R, delimiter: char) -> CsvReader<R> {
+        CsvReader { input, delimiter }
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<Vec<String>>> {
+        let mut line = String::new();
+
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+
+        loop {
+            let mut chars = line.chars().peekable();
+
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if in_quotes && chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' if in_quotes => in_quotes = false,
+                    '"' if field.is_empty() => in_quotes = true,
+                    '\r' | '\n' if !in_quotes => break,
+                    c if c == self.delimiter && !in_quotes => {
+                        fields.push(std::mem::take(&mut field));
+                    }
+                    c => field.push(c),
+                }
+            }
+
+            // A quoted field goes on over the next line.
+            if !in_quotes {
+                break;
+            }
+
+            line.clear();
+
+            if self.input.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "unterminated quoted field",
+                ));
+            }
+        }
+
+        fields.push(field);
+        Ok(Some(fields))
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<String>>> {
+        self.read_record().transpose()
+    }
+}
@@ -0,0 +1,154 @@
+//   ____                _   _
+//  / ___| __ _ _______| |_| |_ ___  ___ _ __
+// | |  _ / _` |_  / _ \ __| __/ _ \/ _ \ '__|
+// | |_| | (_| |/ /  __/ |_| ||  __/  __/ |
+//  \____|\__,_/___\___|\__|\__\___|\___|_|
+//
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::iter;
+use std::path::Path;
+use std::sync::Arc;
+
+use aho_corasick::AhoCorasick;
+use regex_syntax::hir;
+
+use super::automaton::atom::Atom;
+use super::automaton::{Automaton, Label};
+use super::mapping::{Mapping, Marker, Variable};
+
+/// Spanner matching the entries of a dictionary, each occurrence of an entry
+/// in a text being assigned to a single group, named "match" by default.
+///
+/// The automaton of a gazetteer is the trie of its entries, which is the goto
+/// function of the Aho-Corasick automaton, surrounded by loops over any
+/// character. It has one state per prefix of an entry, while an alternation of
+/// the entries written as a regex would get one state per character. It can be
+/// evaluated by any enumerator, and `find_all` directly gives the occurrences
+/// with the Aho-Corasick algorithm, without indexing the text.
+#[derive(Clone)]
+pub struct Gazetteer {
+    entries: Vec<String>,
+    group: String,
+    matcher: AhoCorasick,
+}
+
+impl Gazetteer {
+    /// Dictionary of the given entries, empty and duplicated entries are
+    /// ignored.
+    pub fn new<I, S>(entries: I) -> Gazetteer
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut entries: Vec<String> = (entries.into_iter())
+            .map(Into::into)
+            .filter(|entry| !entry.is_empty())
+            .collect();
+
+        entries.sort();
+        entries.dedup();
+
+        Gazetteer {
+            matcher: AhoCorasick::new(&entries),
+            entries,
+            group: "match".to_string(),
+        }
+    }
+
+    /// Read a dictionary with one entry per line.
+    pub fn read_from_file(filename: &Path) -> io::Result<Gazetteer> {
+        let mut entries = Vec::new();
+
+        for line in BufReader::new(File::open(filename)?).lines() {
+            entries.push(line?.trim_end_matches('\r').to_string());
+        }
+
+        Ok(Gazetteer::new(entries))
+    }
+
+    /// Name of the group assigned to the occurrences of the entries.
+    pub fn with_group(mut self, group: &str) -> Gazetteer {
+        self.group = group.to_string();
+        self
+    }
+
+    pub fn get_entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn get_group(&self) -> &str {
+        &self.group
+    }
+
+    /// Automaton assigning the group to each occurrence of an entry, its
+    /// states are the initial loop, the nodes of the trie and the final loop.
+    pub fn get_automaton(&self) -> Automaton {
+        let any = match hir::Hir::any(false).into_kind() {
+            hir::HirKind::Class(class) => Arc::new(Label::Atom(Atom::Class(class))),
+            _ => panic!("LibHir broken!"),
+        };
+
+        let var = Arc::new(Variable::new(self.group.clone(), 0));
+        let open = Arc::new(Label::Assignation(Marker::Open(var.clone())));
+        let close = Arc::new(Label::Assignation(Marker::Close(var)));
+
+        // Children of each node of the trie, whose root is state 1.
+        let mut trie: Vec<BTreeMap<char, usize>> = vec![BTreeMap::new()];
+        let mut terminals = Vec::new();
+
+        for entry in &self.entries {
+            let mut node = 0;
+
+            for character in entry.chars() {
+                let next = trie.len();
+                node = *trie[node].entry(character).or_insert(next);
+
+                if node == next {
+                    trie.push(BTreeMap::new());
+                }
+            }
+
+            terminals.push(node);
+        }
+
+        // Assignations must go from a state to a larger one.
+        let last = trie.len() + 1;
+        let mut transitions = vec![(0, any.clone(), 0), (0, open, 1), (last, any, last)];
+
+        for (node, children) in trie.iter().enumerate() {
+            for (&character, &child) in children {
+                let atom = Atom::Literal(hir::Literal::Unicode(character));
+                transitions.push((node + 1, Arc::new(Label::Atom(atom)), child + 1));
+            }
+        }
+
+        terminals.sort_unstable();
+        terminals.dedup();
+        transitions.extend(terminals.into_iter().map(|node| (node + 1, close.clone(), last)));
+
+        Automaton::new(last + 1, transitions.into_iter(), iter::once(last))
+    }
+
+    /// All the occurrences of the entries in a text, including overlapping
+    /// ones, ordered by their end.
+    pub fn find_all<'t>(&self, text: &'t str) -> Vec<Mapping<'t>> {
+        let var = Arc::new(Variable::new(self.group.clone(), 0));
+
+        // The mappings have as many slots as those enumerated from the
+        // automaton, which are counted by `Automaton::num_vars`.
+        let num_vars = Marker::Close(var.clone()).get_id() + 1;
+
+        (self.matcher.find_overlapping_iter(text))
+            .map(|hit| {
+                let markers = vec![
+                    (Marker::Open(var.clone()), hit.start()),
+                    (Marker::Close(var.clone()), hit.end()),
+                ];
+                Mapping::from_markers(text, markers.into_iter(), num_vars)
+            })
+            .collect()
+    }
+}
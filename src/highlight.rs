@@ -0,0 +1,109 @@
+//  _   _ _       _     _ _       _     _
+// | | | (_) __ _| |__ | (_) __ _| |__ | |_
+// | |_| | |/ _` | '_ \| | |/ _` | '_ \| __|
+// |  _  | | (_| | | | | | | (_| | | | | |_
+// |_| |_|_|\__, |_| |_|_|_|\__, |_| |_|\__|
+//          |___/           |___/
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use ansi_term::{Colour, Style};
+
+use super::mapping::Mapping;
+
+/// Colors given to groups, in their order of appearance.
+static COLORS: &[Colour] = &[
+    Colour::Red,
+    Colour::Green,
+    Colour::Yellow,
+    Colour::Blue,
+    Colour::Purple,
+    Colour::Cyan,
+];
+
+/// Display the lines of a text that contain a mapping, with each group
+/// highlighted in its own color.
+///
+/// A group keeps the same color for all mappings displayed by a highlighter.
+pub struct Highlighter<'t> {
+    text: &'t str,
+    colors: HashMap<String, Colour>,
+}
+
+impl<'t> Highlighter<'t> {
+    pub fn new(text: &'t str) -> Highlighter<'t> {
+        Highlighter {
+            text,
+            colors: HashMap::new(),
+        }
+    }
+
+    fn get_style(&mut self, name: &str) -> Style {
+        let next_color = COLORS[self.colors.len() % COLORS.len()];
+        let color = self.colors.entry(name.to_string()).or_insert(next_color);
+        color.bold()
+    }
+
+    /// Render the lines of the text spanned by a mapping followed by the
+    /// legend of its groups, the mapping must assign at least one group.
+    pub fn render(&mut self, mapping: &Mapping) -> String {
+        let span = mapping
+            .main_span()
+            .expect("A mapping should assign a group");
+
+        let line_start = self.text[..span.start]
+            .rfind('\n')
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        let line_end = self.text[span.end..]
+            .find('\n')
+            .map(|pos| pos + span.end)
+            .unwrap_or_else(|| self.text.len());
+
+        let groups: Vec<(Style, Range<usize>)> = mapping
+            .iter_groups()
+            .map(|(name, range)| (self.get_style(name), range))
+            .collect();
+
+        // Each char is painted with the innermost group containing it, that is
+        // the group starting last.
+        let style_at = |pos: usize| {
+            groups
+                .iter()
+                .filter(|(_, range)| range.contains(&pos))
+                .max_by_key(|(_, range)| (range.start, std::cmp::Reverse(range.end)))
+                .map(|(style, _)| *style)
+                .unwrap_or_default()
+        };
+
+        let mut output = String::new();
+        let mut segment_start = line_start;
+        let mut segment_style = style_at(line_start);
+        let line = self.text[line_start..line_end].trim_end_matches('\r');
+
+        for (offset, _) in line.char_indices().skip(1) {
+            let pos = line_start + offset;
+            let style = style_at(pos);
+
+            if style != segment_style {
+                output += &segment_style.paint(&self.text[segment_start..pos]).to_string();
+                segment_start = pos;
+                segment_style = style;
+            }
+        }
+
+        let segment_end = line_start + line.len();
+        output += &segment_style
+            .paint(&self.text[segment_start..segment_end])
+            .to_string();
+
+        let legend: Vec<String> = groups
+            .iter()
+            .zip(mapping.iter_groups())
+            .map(|((style, _), (name, _))| style.paint(name).to_string())
+            .collect();
+
+        format!("{}  [{}]", output, legend.join(" "))
+    }
+}
@@ -0,0 +1,38 @@
+//! Constant-delay enumeration of all the matches of a regular expression with
+//! named groups over a text.
+//!
+//! A pattern is compiled into an automaton with `regex::compile`, which is
+//! then evaluated over a text by any implementation of `SpannerEnumerator`,
+//! such as `mapping::IndexedDag`, `mapping::DeterministicEva` or the naive
+//! algorithms of `naive`, which all support counting the mappings, finding the
+//! first one and reporting statistics on their preprocessing.
+//!
+//! To match the same pattern against many documents, `spanner::CompiledSpanner`
+//! compiles the pattern once and indexes each document with its own copy of the
+//! automaton, and `batch::Batch` spreads a collection of documents over a pool
+//! of workers.
+
+extern crate bit_vec;
+extern crate regex as lib_regex;
+extern crate regex_syntax;
+
+pub mod algebra;
+pub mod algorithm;
+pub mod automaton;
+pub mod batch;
+mod clock;
+pub mod gazetteer;
+pub mod mapping;
+pub mod matrix;
+pub mod naive;
+pub mod offsets;
+pub mod predicate;
+pub mod progress;
+pub mod regex;
+pub mod semiring;
+pub mod spanner;
+
+pub use mapping::{EnumeratorStats, ExactIter, Mapping, OwnedMapping, SpannerEnumerator};
+
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
@@ -1,46 +1,144 @@
-mod automaton;
+mod allocator;
+mod arrow;
+mod baseline;
 mod benchmark;
-mod mapping;
-mod matrix;
-mod naive;
-mod progress;
-mod regex;
+mod csv;
+mod highlight;
+mod merge;
+mod output;
+mod parquet;
+mod pattern_tests;
+mod recipe;
+mod replace;
+mod report;
+mod rules;
+mod serve;
+mod units;
+mod verify;
+mod yaml;
 
-extern crate bit_vec;
 extern crate clap;
-extern crate regex as lib_regex;
-extern crate regex_syntax;
+extern crate enum_spanner_rs;
 
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::stdin;
+use std::io::{self, stdin, BufReader, BufWriter};
+use std::iter;
+use std::panic;
 use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
 use std::time;
 
-use benchmark::BenchmarkCase;
-use clap::{App, Arg};
-use mapping::indexed_dag::{IndexedDag, TrimmingStrategy};
-use mapping::SpannerEnumerator;
+use enum_spanner_rs::{
+    algebra, algorithm, automaton, batch, gazetteer, mapping, matrix, naive, offsets, predicate,
+    regex, semiring, spanner,
+};
+
+use algebra::Plan;
+use algorithm::Algorithm;
+use automaton::Automaton;
+use arrow::ArrowWriter;
+use batch::Batch;
+use benchmark::{BenchmarkCase, Sampling};
+use clap::{App, AppSettings, Arg, SubCommand};
+use gazetteer::Gazetteer;
+use mapping::indexed_dag::{IndexedDag, JumpSchedule, TrimmingStrategy};
+use mapping::{
+    DeterministicEva, LengthBounds, Mapping, Order, SlidingWindow, SpannerEnumerator, Weights,
+};
+use highlight::Highlighter;
+use offsets::{LineIndex, OffsetTable, OffsetUnit};
+use output::Output;
+use parquet::ParquetWriter;
+use pattern_tests::PatternTest;
+use predicate::Predicates;
+use recipe::Recipe;
+use regex::Construction;
+use replace::Template;
+use report::Report;
+use rules::RuleSet;
+use semiring::{Annotation, Provenance, Semiring};
+use spanner::CompiledSpanner;
+use serve::Server;
+use units::{JsonFields, Unit};
+use verify::Verification;
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOCATOR: allocator::CountingAllocator = allocator::CountingAllocator;
+
+/// Matches are sent to the thread writing the output by batches of this size,
+/// or more often if the enumeration is slow, and at most this number of
+/// batches wait for the output.
+const PIPELINE_BATCH_SIZE: usize = 1024;
+const PIPELINE_BATCH_DELAY: time::Duration = time::Duration::from_millis(10);
+const PIPELINE_SIZE: usize = 4;
 
-#[derive(PartialEq, Eq)]
 enum DisplayFormat {
     /// Only display the count of matches
     Count,
     /// Display in the re-compare format: https://github.com/gchase/re-compare
     CompareFormat,
-    /// Human-readable format
-    Verbose { show_offset: bool },
-}
-
-#[derive(Clone, Copy)]
-pub enum Algorithm {
-    ICDT19,
-    Naive,
-    NaiveQuadratic,
-    NaiveCubic,
+    /// Human-readable format, with offsets in the given unit or lines and
+    /// columns instead of texts, the score of each match if annotated and
+    /// the number of runs of the automaton producing it if given
+    Verbose {
+        show_offset: Option<OffsetUnit>,
+        line_column: bool,
+        annotation: Option<Annotation>,
+        runs: Option<Automaton>,
+    },
+    /// Lines of the text containing each match, with groups highlighted
+    Highlight,
+    /// The text where non-overlapping matches are substituted by a template
+    Replace { template: Template },
+    /// Maximal regions covered by overlapping or adjacent matches
+    Merge { show_offset: Option<OffsetUnit> },
+    /// The most frequent texts assigned to a group, or matched if no group is
+    /// given
+    Top { k: usize, group: Option<String> },
+    /// One JSON object per match, with offsets in bytes and optionally in
+    /// another unit and as lines and columns, the score of each match if
+    /// annotated and the number of runs of the automaton producing it if
+    /// given
+    Json {
+        offset_unit: OffsetUnit,
+        line_column: bool,
+        annotation: Option<Annotation>,
+        runs: Option<Automaton>,
+        schema: Vec<String>,
+    },
+    /// Distinct projections of the matches where the token groups are left
+    /// unbound, each with its provenance, as text or as JSON objects
+    Provenance { tokens: Vec<String>, json: bool },
+    /// Raw text of each match, terminated by a NUL byte
+    Null,
+    /// HTML page written to a file, with the spans of groups highlighted in
+    /// the text and a table of matches
+    Html { path: String },
+    /// Arrow IPC stream written to a file or to stdout, with the main span and
+    /// the text of each group of the pattern
+    Arrow {
+        path: String,
+        file: String,
+        groups: Vec<String>,
+    },
+    /// Parquet file with the main span and the text of each group of the
+    /// pattern
+    Parquet {
+        path: String,
+        file: String,
+        groups: Vec<String>,
+    },
 }
 
 fn main() {
+    // Escape sequences are not interpreted by default on Windows consoles.
+    #[cfg(windows)]
+    let _ = ansi_term::enable_ansi_support();
+
     //  ____
     // |  _ \ __ _ _ __ ___  ___ _ __
     // | |_) / _` | '__/ __|/ _ \ '__|
@@ -48,10 +146,112 @@ fn main() {
     // |_|   \__,_|_|  |___/\___|_|
     //
     let matches = App::new("Enumerate matchings")
-        .version("0.1")
+        .version(env!("CARGO_PKG_VERSION"))
         .author("Rémi Dupré <remi.dupre@ens-paris-saclay.fr>\n\
 		         Matthias Niewerth <matthias.niewerth@uni-bayreuth.de>")
         .about("Enumerate all matches of a regular document spanner on a text.")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::DisableVersion)
+        .arg(
+            Arg::with_name("version")
+                .short("V")
+                .long("version")
+                .help("Prints version information"),
+        )
+        .arg(
+            Arg::with_name("features")
+                .long("features")
+                .requires("version")
+                .help("With --version, print a JSON report of the capabilities of this build."),
+        )
+        .subcommand(
+            SubCommand::with_name("test-patterns")
+                .about("Run a suite of patterns against texts and check the expected mappings.")
+                .arg(
+                    Arg::with_name("file")
+                        .help("A file in JSON syntax, or in YAML syntax if its extension is \
+                               .yaml or .yml, holding a list of cases, each case is an object with \
+                               fields `pattern`, `text` and `expected`, which is the list of \
+                               expected mappings. A variable is mapped either to its span \
+                               `[start, end]` or to its text.")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rules")
+                .about("Extract values from files with the rules of a file, which are all \
+                        evaluated in one pass over each document. Matches are output as JSON \
+                        lines with the name of their rule.")
+                .arg(
+                    Arg::with_name("file")
+                        .help("A file in JSON syntax holding a list of rules, each rule is an \
+                               object with fields `name`, `pattern` and optionally `options`, \
+                               an object with fields `unique` to skip the matches whose texts \
+                               were already output by the rule and `construction`.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("paths")
+                        .help("Files and directories to be read, directories are read \
+                               recursively. If none is specified, STDIN is used.")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .long("jobs")
+                        .short("j")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number of files processed in parallel. The output is in the same \
+                               order as with a single job."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("recipe")
+                .about("Extract common kinds of values from files with a predefined pattern, \
+                        matches are output as JSON lines and duplicated values are only \
+                        output once.")
+                .arg(
+                    Arg::with_name("name")
+                        .help("Name of the recipe to use.")
+                        .required_unless("list"),
+                )
+                .arg(
+                    Arg::with_name("paths")
+                        .help("Files and directories to be read, directories are read \
+                               recursively. If none is specified, STDIN is used.")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .long("list")
+                        .help("List available recipes."),
+                )
+                .arg(
+                    Arg::with_name("recipes")
+                        .long("recipes")
+                        .takes_value(true)
+                        .help("Read additional recipes from a file in JSON syntax. Each recipe is \
+                               an object with fields `name`, `description` and `pattern`."),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .long("jobs")
+                        .short("j")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number of files processed in parallel. The output is in the same \
+                               order as with a single job."),
+                ),
+        )
+        .arg(
+            Arg::with_name("serve")
+                .long("serve")
+                .conflicts_with_all(&["regex", "regex_opt", "regex_file", "file", "benchmark-file"])
+                .help("Keep running and answer requests in JSON syntax read from STDIN, one per \
+                       line, with the results written to STDOUT. Compiled patterns are cached \
+                       between requests."),
+        )
         .arg(
             Arg::with_name("benchmark")
                 .long("benchmark")
@@ -63,11 +263,194 @@ fn main() {
                 .help("Read a set of benchmarks from a file in JSON syntax. Implies --benchmark")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("compare_algorithms")
+                .long("compare-algorithms")
+                .conflicts_with("algorithm")
+                .help("Run benchmarks with each algorithm and report their relative speedups and \
+                       whether they agree on the number of results."),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Also run the pattern with another regex engine in benchmarks and report its \
+                       timings: `regex` for the regex crate, `pcre2` for pcre2grep, or \
+                       `NAME=COMMAND ARGS...` for a command reading the text on STDIN and printing \
+                       one line per match, with `{regex}` replaced by the pattern in its \
+                       arguments. Can be repeated."),
+        )
+        .arg(
+            Arg::with_name("window")
+                .long("window")
+                .takes_value(true)
+                .value_name("BYTES")
+                .conflicts_with_all(&[
+                    "algorithm", "benchmark", "verify", "count", "json", "compare", "highlight",
+                    "replace", "merge", "top", "unique", "first", "provenance", "diagnose",
+                    "time_first", "dump_dag",
+                ])
+                .help("Process the input as a stream, only keeping this number of bytes in memory. \
+                       Matches are printed as soon as they are found and only matches spanning \
+                       at most this number of bytes are enumerated."),
+        )
+        .arg(
+            Arg::with_name("json_field")
+                .long("json-field")
+                .takes_value(true)
+                .value_name("FIELD")
+                .conflicts_with_all(&[
+                    "algorithm", "benchmark", "verify", "window", "json", "compare", "highlight",
+                    "replace", "merge", "top", "null", "html", "arrow", "output_parquet",
+                ])
+                .help("Read the input as JSON Lines and match the pattern against a string field \
+                       of each record, given by its name or by a JSON pointer such as \
+                       /payload/message. Each record with matches is printed with an additional \
+                       field `matches` listing them, with offsets relative to the field."),
+        )
+        .arg(
+            Arg::with_name("csv_column")
+                .long("csv-column")
+                .takes_value(true)
+                .value_name("COLUMN")
+                .conflicts_with_all(&[
+                    "algorithm", "benchmark", "verify", "window", "compare", "highlight",
+                    "replace", "merge", "top", "null", "html", "arrow", "output_parquet",
+                    "json_field",
+                ])
+                .help("Read the input as CSV and match the pattern against a column of each \
+                       row, given by its number starting from 1 or by its name in the header \
+                       row. Matches are printed with the number of their row and offsets \
+                       relative to the cell."),
+        )
+        .arg(
+            Arg::with_name("csv_delimiter")
+                .long("csv-delimiter")
+                .takes_value(true)
+                .value_name("CHAR")
+                .requires("csv_column")
+                .help("Delimiter of the fields of CSV input, e.g. \\t or tab for TSV, defaults to a comma."),
+        )
+        .arg(
+            Arg::with_name("record_separator")
+                .long("record-separator")
+                .takes_value(true)
+                .value_name("STR")
+                .conflicts_with_all(&[
+                    "algorithm", "benchmark", "verify", "window", "compare", "highlight",
+                    "replace", "merge", "top", "null", "html", "arrow", "output_parquet",
+                    "json_field", "csv_column", "expr",
+                ])
+                .help("Split the text into records separated by this string, e.g. \\n\\n for \
+                       blank lines or \\f for form feeds, and match the pattern against each \
+                       record, such that no match spans several records. Matches are printed \
+                       with the number of their record and offsets relative to the record."),
+        )
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .conflicts_with_all(&["algorithm", "benchmark"])
+                .help("Check the matches enumerated by the indexed algorithm against the naive \
+                       algorithm and report the mappings on which they disagree."),
+        )
+        .arg(
+            Arg::with_name("verify_sample")
+                .long("verify-sample")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10")
+                .help("Number of mappings of each kind of disagreement displayed by --verify."),
+        )
+        .arg(
+            Arg::with_name("max_file_size")
+                .long("max-file-size")
+                .help("Reject the input files of benchmarks that are larger than this number of bytes.")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("regex")
-                .help("The pattern to look for.")
-                .required(true)
-                .conflicts_with("benchmark-file"),
+                .help("The pattern to look for. Prefer --regex, which also accepts patterns \
+                       starting with a dash.")
+                .required_unless_one(&["regex_opt", "regex_file", "expr", "gazetteer", "serve"])
+                .conflicts_with_all(&["benchmark-file", "version"]),
+        )
+        .arg(
+            Arg::with_name("regex_opt")
+                .short("r")
+                .long("regex")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .conflicts_with("regex_file")
+                .help("The pattern to look for. The first positional argument is then the file \
+                       to be read."),
+        )
+        .arg(
+            Arg::with_name("expr")
+                .long("expr")
+                .takes_value(true)
+                .value_name("EXPR")
+                .conflicts_with_all(&[
+                    "regex_opt", "regex_file", "algorithm", "benchmark", "verify", "window",
+                    "json_field", "csv_column", "dry_run", "explain_run", "runs", "arrow",
+                    "output_parquet", "first",
+                ])
+                .help("Compose patterns with the spanner algebra instead of giving a single \
+                       pattern, e.g. `join(p1, p2) | project(x, y)` where p1 and p2 are given \
+                       with --define or --define-dictionary. The operators are union, join, \
+                       concat, contains, near and project, and patterns can also be written \
+                       between quotes. The first positional argument is then the file to be \
+                       read."),
+        )
+        .arg(
+            Arg::with_name("define")
+                .long("define")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME=REGEX")
+                .requires("expr")
+                .help("Name a pattern used in the expression given with --expr."),
+        )
+        .arg(
+            Arg::with_name("define_dictionary")
+                .long("define-dictionary")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME=FILE")
+                .requires("expr")
+                .help("Name a dictionary used in the expression given with --expr, read from a \
+                       file with one entry per line. Its occurrences are assigned to a group \
+                       with the same name."),
+        )
+        .arg(
+            Arg::with_name("gazetteer")
+                .long("gazetteer")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["regex_opt", "regex_file", "expr", "benchmark"])
+                .help("Look for the entries of a dictionary, read from a file with one entry per \
+                       line, instead of a pattern. Each occurrence of an entry is a match, even \
+                       if it overlaps another one. The first positional argument is then the \
+                       file to be read. Large dictionaries may need --compressed-levels."),
+        )
+        .arg(
+            Arg::with_name("gazetteer_group")
+                .long("gazetteer-group")
+                .takes_value(true)
+                .value_name("NAME")
+                .requires("gazetteer")
+                .help("Name of the group assigned to the entries of the dictionary given with \
+                       --gazetteer, \"match\" by default."),
+        )
+        .arg(
+            Arg::with_name("regex_file")
+                .long("regex-file")
+                .takes_value(true)
+                .help("Read the pattern to look for from a file. The first positional argument \
+                       is then the file to be read."),
         )
         .arg(
             Arg::with_name("file")
@@ -77,7 +460,48 @@ fn main() {
             Arg::with_name("count")
                 .short("c")
                 .long("count")
-                .help("Display the number of matches instead."),
+                .help("Display the number of matches instead. They are counted in a single pass \
+                       over the text without being enumerated, unless --unique, --top-k or \
+                       another algorithm than pods18 is given."),
+        )
+        .arg(
+            Arg::with_name("diagnose")
+                .long("diagnose")
+                .help("When the pattern has no match, display where the longest partial match \
+                       starts and dies, and the characters its runs expected there."),
+        )
+        .arg(
+            Arg::with_name("runs")
+                .long("runs")
+                .help("Display the number of accepting runs of the automaton producing each \
+                       match, which is more than one when the pattern is ambiguous. Runs are \
+                       counted before equivalent positions of the automaton are merged, so \
+                       that a*a* has three runs over aa. Only applies to the default and JSON \
+                       outputs."),
+        )
+        .arg(
+            Arg::with_name("explain_run")
+                .long("explain-run")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["verify", "benchmark"])
+                .help("Display the N-th match, counting from 1, along with the transitions of an \
+                       accepting run of the automaton producing it, including where each marker \
+                       is assigned."),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("Compile the pattern and display statistics on the automaton along with an \
+                       estimate of the memory of the index, without reading the text. They are \
+                       given as a JSON object with --json."),
+        )
+        .arg(
+            Arg::with_name("first")
+                .long("first")
+                .conflicts_with_all(&["unique", "top_k", "dump_dag"])
+                .help("Stop as soon as one match is found. The text is only indexed up to the \
+                       point where a match can be completed whatever follows."),
         )
         .arg(
             Arg::with_name("bytes_offset")
@@ -85,27 +509,378 @@ fn main() {
                 .long("bytes-offset")
                 .help("Print the 0-based offset of each matching part and groups."),
         )
+        .arg(
+            Arg::with_name("char_offset")
+                .long("char-offset")
+                .conflicts_with_all(&["bytes_offset", "window"])
+                .help("Print the 0-based offset of each matching part and groups, counted in \
+                       Unicode scalar values rather than in bytes."),
+        )
+        .arg(
+            Arg::with_name("utf16_offset")
+                .long("utf16-offset")
+                .conflicts_with_all(&["bytes_offset", "char_offset", "window"])
+                .help("Print the 0-based offset of each matching part and groups, counted in \
+                       UTF-16 code units as in LSP and JavaScript."),
+        )
+        .arg(
+            Arg::with_name("line_column")
+                .long("line-column")
+                .conflicts_with_all(&["bytes_offset", "char_offset", "utf16_offset", "window"])
+                .help("Print the 1-based line and column of the start and end of each matching \
+                       part and groups, columns are counted in characters and the end is the \
+                       position right after the match. JSON output gets additional fields."),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Output one JSON object per match, holding the span and text of each group."),
+        )
+        .arg(
+            Arg::with_name("highlight")
+                .long("highlight")
+                .conflicts_with_all(&["count", "json", "compare"])
+                .help("Display the lines containing each match, with each group highlighted in its own color."),
+        )
+        .arg(
+            Arg::with_name("replace")
+                .long("replace")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .conflicts_with_all(&["count", "json", "compare", "highlight"])
+                .help("Print the text where matches are substituted by a template, which can reference \
+                       groups with ${group}. Overlapping matches are selected from left to right, \
+                       preferring longest matches."),
+        )
+        .arg(
+            Arg::with_name("merge")
+                .long("merge")
+                .conflicts_with_all(&["count", "json", "compare", "highlight", "replace"])
+                .help("Merge overlapping or adjacent matches into maximal regions, and print each \
+                       region with the number of matches it covers."),
+        )
+        .arg(
+            Arg::with_name("provenance")
+                .long("provenance")
+                .takes_value(true)
+                .value_name("GROUPS")
+                .use_delimiter(true)
+                .conflicts_with_all(&["count", "compare", "highlight", "replace", "merge", "top"])
+                .help("Use the comma-separated list of groups as provenance tokens: they are \
+                       removed from the matches, and each distinct match is output once with \
+                       its provenance, the sum over the matches it comes from of the product of \
+                       the tokens they bind. E.g. tokens can be put around the branches of an \
+                       alternation to know which branches produced a match."),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&[
+                    "html", "arrow", "output_parquet", "window", "benchmark", "verify", "dry_run",
+                    "explain_run",
+                ])
+                .help("Write the matches to a file instead of STDOUT, through a large buffer. \
+                       The file is written under a temporary name and renamed once complete."),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .takes_value(true)
+                .value_name("K")
+                .conflicts_with_all(&["count", "json", "compare", "highlight", "replace", "merge"])
+                .help("Only print the K most frequent texts assigned to the group given with \
+                       --top-group, or the K most frequent matched texts, with their number of \
+                       occurrences."),
+        )
+        .arg(
+            Arg::with_name("null")
+                .short("0")
+                .long("null")
+                .conflicts_with_all(&[
+                    "count", "json", "compare", "highlight", "replace", "merge", "top", "window",
+                ])
+                .help("Print the text of each match as is, terminated by a NUL byte instead of a \
+                       newline, e.g. to be read by `xargs -0`."),
+        )
+        .arg(
+            Arg::with_name("html")
+                .long("html")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&[
+                    "count", "json", "compare", "highlight", "replace", "merge", "top", "null",
+                    "window",
+                ])
+                .help("Write an HTML report to FILE, where the spans of each group are \
+                       highlighted in the text in their own color, followed by a table of the \
+                       matches. Hovering a span lists the groups covering it."),
+        )
+        .arg(
+            Arg::with_name("arrow")
+                .long("arrow")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&[
+                    "count", "json", "compare", "highlight", "replace", "merge", "top", "null",
+                    "html", "window",
+                ])
+                .help("Write the matches to FILE, or to stdout if FILE is -, in the Arrow IPC \
+                       streaming format. Record batches have the columns file, span_start and \
+                       span_end, and a column holding the text of each group, which is null \
+                       when the group is not bound."),
+        )
+        .arg(
+            Arg::with_name("output_parquet")
+                .long("output-parquet")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&[
+                    "count", "json", "compare", "highlight", "replace", "merge", "top", "null",
+                    "html", "arrow", "window",
+                ])
+                .help("Write the matches to a Parquet file with the columns file, span_start and \
+                       span_end, and an optional column holding the text of each group."),
+        )
+        .arg(
+            Arg::with_name("top_group")
+                .long("top-group")
+                .takes_value(true)
+                .value_name("GROUP")
+                .requires("top")
+                .help("Group whose texts are counted by --top."),
+        )
+        .arg(
+            Arg::with_name("unique")
+                .long("unique")
+                .takes_value(true)
+                .value_name("GROUPS")
+                .min_values(0)
+                .require_equals(true)
+                .use_delimiter(true)
+                .help("Skip matches whose texts were already output, comparing the texts of the \
+                       comma-separated list of groups if given or of all groups otherwise."),
+        )
+        .arg(
+            Arg::with_name("groups")
+                .long("groups")
+                .takes_value(true)
+                .value_name("GROUPS")
+                .use_delimiter(true)
+                .conflicts_with_all(&[
+                    "expr", "window", "verify", "explain_run", "benchmark", "runs",
+                ])
+                .help("Only output the groups of a comma-separated list, in this order. Matches \
+                       that only differ by other groups are output once: these groups are removed \
+                       from the automaton, unless --where uses them, which requires to keep all \
+                       the projected matches in memory."),
+        )
+        .arg(
+            Arg::with_name("skip_partial")
+                .long("skip-partial")
+                .conflicts_with_all(&[
+                    "expr", "window", "verify", "explain_run", "first", "benchmark",
+                ])
+                .help("Skip the matches which leave some groups unbound, such as the groups of \
+                       an alternative that was not taken, or only some of the groups selected \
+                       with --groups. Otherwise, unbound groups are null in JSON outputs."),
+        )
+        .arg(
+            Arg::with_name("where")
+                .long("where")
+                .takes_value(true)
+                .value_name("PREDICATES")
+                .conflicts_with_all(&[
+                    "expr", "window", "verify", "explain_run", "first", "benchmark",
+                ])
+                .help("Only output the matches whose groups satisfy predicates joined with &&, \
+                       e.g. `port:[0-9]+ && port<=65535 && len(host)<64`. A predicate is either \
+                       a regex that must match the whole text of a group, a comparison of the \
+                       text of a group read as a number, or a comparison of the length in \
+                       bytes of a group, which also prunes the enumeration with the icdt19 and \
+                       jump-pointers algorithms. Groups left unbound satisfy their predicates."),
+        )
+        .arg(
+            Arg::with_name("offset_unit")
+                .long("offset-unit")
+                .takes_value(true)
+                .default_value("bytes")
+                .possible_value("bytes")
+                .possible_value("utf16")
+                .possible_value("chars")
+                .help("Unit of the offsets reported alongside byte offsets in JSON output."),
+        )
         .arg(Arg::with_name("compare")
                 .long("compare")
                 .help("Output matches in a format suitable with re-compare: \
                        https://github.com/gchase/re-compare")
         )
         .arg(
-            Arg::with_name("use_naive")
-                .long("naive")
-                .help("Use a naive algorithm to equivalently print all matches"),
+            Arg::with_name("algorithm")
+                .long("algorithm")
+                .takes_value(true)
+                .possible_values(&[
+                    "icdt19",
+                    "jump-pointers",
+                    "pods18",
+                    "naive",
+                    "naive-quadratic",
+                    "naive-cubic",
+                ])
+                .help("Algorithm used to enumerate the matches, in normal and benchmark modes. \
+                       The default, icdt19, enumerates them with constant delay after a linear \
+                       preprocessing. jump-pointers builds the same index with predecessor lists \
+                       instead of matrices, which takes less memory but has a larger delay. \
+                       pods18 also has constant delay, it determinizes the automaton on the fly \
+                       while reading the text once. The naive algorithms print the same \
+                       matches: naive runs the automaton from each position, naive-quadratic \
+                       checks each subword in time O(|regex||text|²) and naive-cubic in time \
+                       O(|text|³ + exp(|regex|))."),
+        )
+        .arg(
+            Arg::with_name("rank")
+                .long("rank")
+                .conflicts_with_all(&["algorithm", "window", "verify", "compare_algorithms"])
+                .help("Enumerate the matches by non-increasing weight with the pods18 algorithm. \
+                       The weight of a match is the sum over its groups of the length of their \
+                       span times their weight given with --weight, plus their priority given \
+                       with --priority. Without any of these options, each group has weight 1, \
+                       which prefers longer spans."),
+        )
+        .arg(
+            Arg::with_name("weight")
+                .long("weight")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("GROUP=W")
+                .conflicts_with_all(&["algorithm", "window", "verify", "compare_algorithms"])
+                .help("Weight of each byte of the span of a group, which may be negative to \
+                       prefer shorter spans. Implies --rank."),
+        )
+        .arg(
+            Arg::with_name("priority")
+                .long("priority")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("GROUP=P")
+                .conflicts_with_all(&["algorithm", "window", "verify", "compare_algorithms"])
+                .help("Weight added to the matches that bind a group. Implies --rank."),
+        )
+        .arg(
+            Arg::with_name("top_k")
+                .long("top-k")
+                .takes_value(true)
+                .value_name("K")
+                .conflicts_with_all(&["algorithm", "window", "verify", "compare_algorithms"])
+                .help("Only enumerate the K first matches in the ranked order, which are the K \
+                       matches of highest weight unless --span-order is given. Implies --rank \
+                       otherwise."),
+        )
+        .arg(
+            Arg::with_name("semiring")
+                .long("semiring")
+                .takes_value(true)
+                .possible_values(&["probability", "viterbi", "tropical"])
+                .default_value("probability")
+                .help("Semiring in which the scores of matches given by --group-score and \
+                       --char-score are computed: probabilities, probabilities combined by \
+                       maximum or costs combined by minimum. The score of a match is the product \
+                       of the scores of its groups and of their characters."),
+        )
+        .arg(
+            Arg::with_name("group_score")
+                .long("group-score")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("GROUP=S")
+                .help("Score of binding a group, e.g. the confidence of a rule. The score of each \
+                       match is then reported in verbose and JSON output."),
+        )
+        .arg(
+            Arg::with_name("char_score")
+                .long("char-score")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("GROUP=S")
+                .help("Score of each character of the span of a group. The score of each match \
+                       is then reported in verbose and JSON output."),
+        )
+        .arg(
+            Arg::with_name("span_order")
+                .long("span-order")
+                .takes_value(true)
+                .possible_values(&["shortest", "longest"])
+                .conflicts_with_all(&[
+                    "rank", "weight", "priority", "algorithm", "window", "verify",
+                    "compare_algorithms",
+                ])
+                .help("Enumerate the matches by length of their main span, shortest or longest \
+                       first, with the pods18 algorithm."),
+        )
+        .arg(
+            Arg::with_name("min_len")
+                .long("min-len")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("[GROUP=]N")
+                .conflicts_with_all(&[
+                    "rank", "weight", "priority", "top_k", "span_order", "window", "verify",
+                    "compare_algorithms",
+                ])
+                .help("Only enumerate the matches whose main span, or the span of the given \
+                       group, is at least N bytes long. Branches of the enumeration that can't \
+                       satisfy the bounds are cut, with the icdt19 and jump-pointers algorithms \
+                       only."),
+        )
+        .arg(
+            Arg::with_name("max_len")
+                .long("max-len")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("[GROUP=]N")
+                .conflicts_with_all(&[
+                    "rank", "weight", "priority", "top_k", "span_order", "window", "verify",
+                    "compare_algorithms",
+                ])
+                .help("Only enumerate the matches whose main span, or the span of the given \
+                       group, is at most N bytes long. Branches of the enumeration that can't \
+                       satisfy the bounds are cut, with the icdt19 and jump-pointers algorithms \
+                       only."),
+        )
+        .arg(
+            Arg::with_name("dot")
+                .long("dot")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .help("Render the automaton as a dotfile, `automaton.dot` by default. Use `--dot=-` to write it to STDOUT."),
+        )
+        .arg(
+            Arg::with_name("dump_dag")
+                .long("dump-dag")
+                .takes_value(true)
+                .help("Write the indexed product DAG of the automaton and the text after preprocessing, use `-` for STDOUT. Only meaningful for small inputs."),
         )
         .arg(
-            Arg::with_name("use_naive_cubic")
-                .long("naive-cubic")
-                .help("Use a naive algorithm to enumerate all subwords that match the input regex. \
-                       This algorithm runs in time O(|text|³ + exp(|regex|))"),
+            Arg::with_name("dag_format")
+                .long("dag-format")
+                .takes_value(true)
+                .possible_values(&["dot", "graphml"])
+                .default_value("dot")
+                .help("Format used by --dump-dag."),
         )
         .arg(
-            Arg::with_name("use_naive_quadratic")
-                .long("naive-quadratic")
-                .help("Use a naive algorithm to enumerate all subwords that match the input regex. \
-                       This algorithm runs in time O(|regex||text|²)"),
+            Arg::with_name("no_progress")
+                .long("no-progress")
+                .help("Don't display progress bars during preprocessing, they are already hidden when STDERR is not a terminal."),
         )
         .arg(
             Arg::with_name("debug_infos")
@@ -122,6 +897,39 @@ fn main() {
                        is a trade-off between pre-processing and enumeration time. Bigger values mean \
                        faster preprocessing and possibly slower enumeration."),
 		)
+        .arg(
+            Arg::with_name("jump_schedule")
+            .long("jump-schedule")
+            .takes_value(true)
+            .default_value("uniform")
+            .possible_value("uniform")
+            .possible_value("exponential")
+            .help("Spacing of the jump targets. With `exponential`, targets are spaced exponentially \
+                   back from each level, giving a logarithmic number of matrices per level and \
+                   logarithmic jump chains. The jump distance is then ignored."),
+        )
+        .arg(
+            Arg::with_name("single_pass")
+            .long("single-pass")
+            .help("Build and index the DAG in a single pass over the text, which only stores the \
+                   levels kept in the index. The DAG is not trimmed, so this may keep more \
+                   levels than the default when most levels can be jumped to."),
+        )
+        .arg(
+            Arg::with_name("mmap_levels")
+            .long("mmap-levels")
+            .help("Store the levels of the DAG, which take one bit per state of the automaton and \
+                   per character of the text, in a temporary memory-mapped file instead of RAM. \
+                   The file is created in the directory given by TMPDIR."),
+        )
+        .arg(
+            Arg::with_name("compressed_levels")
+            .long("compressed-levels")
+            .conflicts_with("mmap_levels")
+            .help("Store each level of the DAG as a sorted array of states when it has few states, \
+                   as Roaring bitmaps do, rather than as a bitmap over all the states of the \
+                   automaton."),
+        )
         .arg(
             Arg::with_name("trimming_strategy")
             .long("trimming")
@@ -131,7 +939,62 @@ fn main() {
             .possible_value("full")
             .possible_value("partial")
             .possible_value("no")
-            .help("Should the DAG be trimmed? Useful for benchmarking the effect of trimming."),
+            .possible_value("adaptive")
+            .possible_value("lazy")
+            .help("Should the DAG be trimmed? Useful for benchmarking the effect of trimming. \
+                   The adaptive strategy only trims levels that are wide enough, the lazy \
+                   strategy prunes dead vertices during the enumeration instead."),
+        )
+        .arg(
+            Arg::with_name("trimming_threshold")
+            .long("trimming-threshold")
+            .takes_value(true)
+            .default_value("32")
+            .help("Minimal number of vertices in a level for it to be trimmed by the adaptive \
+                   trimming strategy."),
+        )
+        .arg(
+            Arg::with_name("construction")
+            .long("construction")
+            .takes_value(true)
+            .default_value("glushkov")
+            .possible_value("glushkov")
+            .possible_value("thompson")
+            .help("Algorithm used to translate the regex into an automaton."),
+        )
+        .arg(
+            Arg::with_name("max_memory")
+            .long("max-memory")
+            .takes_value(true)
+            .help("Abort if the estimated memory usage of the index exceeds this number of bytes."),
+        )
+        .arg(
+            Arg::with_name("memory_stats")
+            .long("memory-stats")
+            .help("Display the memory used by the DAG, the matrices and the jump levels of the \
+                   index after preprocessing and after enumeration, along with the peak heap \
+                   usage if the crate is built with the alloc-stats feature."),
+        )
+        .arg(
+            Arg::with_name("time_first")
+            .long("time-first")
+            .help("Display the time elapsed from the start of the preprocessing until the first \
+                   mapping is produced."),
+        )
+        .arg(
+            Arg::with_name("auto_degrade")
+            .long("auto-degrade")
+            .requires("max_memory")
+            .help("When the memory budget is exceeded, retry with larger jump distances instead \
+                   of aborting, or match windows of the text if the DAG itself exceeds the \
+                   budget, which only gives the matches shorter than the windows."),
+        )
+        .arg(
+            Arg::with_name("naive_fallback")
+            .long("naive-fallback")
+            .requires("max_memory")
+            .help("When the memory budget is exceeded, enumerate the matches with the naive \
+                   algorithm, which builds no index, instead of aborting."),
         )
         .arg(
             Arg::with_name("repetitions")
@@ -140,96 +1003,724 @@ fn main() {
             .default_value("0")
             .help("Enables a detailed delay analysis if >0. The parameter gives the number of repetitions used to filter outliers."),
         )
+        .arg(
+            Arg::with_name("delay_samples")
+            .long("delay-samples")
+            .takes_value(true)
+            .value_name("FILE")
+            .requires("repetitions")
+            .help("Write all the delays measured by the delay analysis to a CSV file, with one row \
+                   per match and repetition."),
+        )
+        .arg(
+            Arg::with_name("warmup")
+            .long("warmup")
+            .takes_value(true)
+            .default_value("0")
+            .help("Number of runs of each benchmark that are done before measuring."),
+        )
+        .arg(
+            Arg::with_name("samples")
+            .long("samples")
+            .takes_value(true)
+            .default_value("1")
+            .help("Number of measured runs of each benchmark, the mean, standard deviation and \
+                   confidence interval of their timings are reported."),
+        )
         .get_matches();
 
-    // Extract parameters
-    let benchmark = matches.is_present("benchmark");
-    let repetitions = match matches.value_of("repetitions") {
-        None => 0,
-        Some(s) => match s.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => panic!("Not a number: {}", s),
-        },
-    };
-    let count = matches.is_present("count");
-    let show_offset = matches.is_present("bytes_offset");
-    let compare_format = matches.is_present("compare");
-
-    let algorithm = if matches.is_present("use_naive") {
-        Algorithm::Naive
-    } else if matches.is_present("use_naive_cubic") {
-        Algorithm::NaiveCubic
-    } else if matches.is_present("use_naive_quadratic") {
-        Algorithm::NaiveQuadratic
-    } else {
-        Algorithm::ICDT19
-    };
+    if matches.is_present("version") {
+        if matches.is_present("features") {
+            println!("{}", serde_json::to_string_pretty(&build_report()).unwrap());
+        } else {
+            println!("Enumerate matchings {}", env!("CARGO_PKG_VERSION"));
+        }
 
-    let debug_infos = matches.is_present("debug_infos");
+        return;
+    }
 
-    let trimming_strategy_str = matches.value_of("trimming_strategy");
-    let trimming_strategy = match trimming_strategy_str {
-        None => TrimmingStrategy::FullTrimming,
-        Some("full") => TrimmingStrategy::FullTrimming,
-        Some("partial") => TrimmingStrategy::PartialTrimming,
-        Some("no") => TrimmingStrategy::NoTrimming,
-        Some(s) => panic!("Invalid option for trimming: {}", s),
-    };
+    //  _____         _
+    // |_   _|__  ___| |_ ___
+    //   | |/ _ \/ __| __/ __|
+    //   | |  __/\__ \ |_\__ \
+    //   |_|\___||___/\__|___/
+    //
 
-    let jump_distance_str = matches.value_of("jump_distance");
-    let jump_distance = match jump_distance_str {
-        None => 1,
-        Some(s) => match s.parse::<usize>() {
-            Ok(n) => n,
-            Err(_) => panic!("Not a number: {}", s),
-        },
-    };
+    if let Some(matches) = matches.subcommand_matches("test-patterns") {
+        let path = Path::new(matches.value_of("file").unwrap());
+        let cases = match PatternTest::read_from_file(path) {
+            Ok(cases) => cases,
+            Err(err) => {
+                eprintln!("Could not read the pattern tests of {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        };
+        let mut nb_failures = 0;
 
-    let display_format = match (count, compare_format, show_offset) {
-        (true, _, _) => DisplayFormat::Count,
-        (_, true, _) => DisplayFormat::CompareFormat,
-        _ => DisplayFormat::Verbose { show_offset },
-    };
+        for case in &cases {
+            let result = case.run();
 
-    //  ____                  _                          _
-    // | __ )  ___ _ __   ___| |__  _ __ ___   __ _ _ __| | __
-    // |  _ \ / _ \ '_ \ / __| '_ \| '_ ` _ \ / _` | '__| |/ /
-    // | |_) |  __/ | | | (__| | | | | | | | | (_| | |  |   <
-    // |____/ \___|_| |_|\___|_| |_|_| |_| |_|\__,_|_|  |_|\_\
-    //
+            if result.is_success() {
+                println!("ok     {}", case.name());
+            } else {
+                nb_failures += 1;
+                println!("FAILED {}", case.name());
+                print!("{}", result);
+            }
+        }
 
-    let benchmark_file = matches.value_of("benchmark-file");
+        println!(
+            "\n{} passed; {} failed",
+            cases.len() - nb_failures,
+            nb_failures
+        );
 
-    if benchmark_file != None {
-        print!("[");
-        let path = Path::new(benchmark_file.unwrap());
-        let benchmarks = benchmark::BenchmarkCase::read_from_file(&path).unwrap();
-        let mut first = true;
-        for benchmark in benchmarks {
-            println!("{}", if first { "" } else { "," });
-            let result = benchmark.run(algorithm, repetitions).unwrap();
-            print!("{}", serde_json::to_string_pretty(&result).unwrap());
-            first = false;
+        if nb_failures > 0 {
+            std::process::exit(1);
         }
-        println!("\n]");
+
         return;
     }
 
-    let regex_str = matches.value_of("regex").unwrap();
+    //  ____           _
+    // |  _ \ ___  ___(_)_ __   ___  ___
+    // | |_) / _ \/ __| | '_ \ / _ \/ __|
+    // |  _ <  __/ (__| | |_) |  __/\__ \
+    // |_| \_\___|\___|_| .__/ \___||___/
+    //                  |_|
 
-    if benchmark {
+    if let Some(matches) = matches.subcommand_matches("recipe") {
+        // User-defined recipes take precedence over builtin ones.
+        let mut recipes = match matches.value_of("recipes") {
+            Some(filename) => Recipe::read_from_file(Path::new(filename)).unwrap(),
+            None => Vec::new(),
+        };
+        recipes.extend(Recipe::builtin());
+
+        if matches.is_present("list") {
+            for recipe in &recipes {
+                println!("{:<20} {}", recipe.get_name(), recipe.get_description());
+                println!("{:<20} {}", "", recipe.get_pattern());
+            }
+
+            return;
+        }
+
+        let name = matches.value_of("name").unwrap();
+        let recipe = match recipes.iter().find(|recipe| recipe.get_name() == name) {
+            Some(recipe) => recipe,
+            None => panic!("Unknown recipe: {}", name),
+        };
+
+        let jobs = match matches.value_of("jobs").unwrap().parse::<usize>() {
+            Ok(jobs) if jobs > 0 => jobs,
+            _ => panic!("Invalid number of jobs: {}", matches.value_of("jobs").unwrap()),
+        };
+
+        let batch = Batch::new(recipe.get_pattern(), jobs);
+        let paths: Vec<&Path> = matches.values_of("paths").into_iter().flatten().map(Path::new).collect();
+        let mut seen = HashSet::new();
+        let mut failures = Vec::new();
+        let documents = read_documents(&paths, &mut failures);
+
+        batch.run(
+            documents,
+            |spanner, (_, text)| {
+                let enumerator = spanner.index(text);
+                let extracted: Vec<_> = enumerator
+                    .iter()
+                    .map(|mapping| {
+                        let values: Vec<_> = mapping.iter_groups_text().collect();
+                        let groups = mapping_to_json(&mapping, text, None);
+                        (format!("{:?}", values), groups)
+                    })
+                    .collect();
+                extracted
+            },
+            |(filename, _), extracted| {
+                for (values, groups) in extracted {
+                    if !seen.insert(values) {
+                        continue;
+                    }
+
+                    let mut line = serde_json::Map::new();
+                    line.insert("file".to_string(), filename.clone().into());
+                    line.insert("groups".to_string(), groups.into());
+                    println!("{}", serde_json::Value::from(line));
+                }
+            },
+        );
+
+        exit_on_read_failures(&failures);
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("rules") {
+        let rules = match RuleSet::read_from_file(Path::new(matches.value_of("file").unwrap())) {
+            Ok(rules) => rules,
+            Err(err) => panic!("Could not read the rules: {}", err),
+        };
+
+        let jobs = match matches.value_of("jobs").unwrap().parse::<usize>() {
+            Ok(jobs) if jobs > 0 => jobs,
+            _ => panic!("Invalid number of jobs: {}", matches.value_of("jobs").unwrap()),
+        };
+
+        let spanner = CompiledSpanner::from_automaton(rules.get_automaton().clone());
+        let batch = Batch::from_spanner(spanner, jobs);
+        let paths: Vec<&Path> =
+            matches.values_of("paths").into_iter().flatten().map(Path::new).collect();
+        let mut seen = HashSet::new();
+        let mut failures = Vec::new();
+        let documents = read_documents(&paths, &mut failures);
+
+        batch.run(
+            documents,
+            |spanner, (_, text)| {
+                let extracted: Vec<_> = (spanner.index(text).iter())
+                    .map(|mapping| {
+                        let (rule, groups) = rules.extract(text, &mapping);
+                        let values: Vec<_> = groups.iter_groups_text().collect();
+                        let span = groups.main_span().map(|span| vec![span.start, span.end]);
+                        (rule, span, format!("{:?}", values), mapping_to_json(&groups, text, None))
+                    })
+                    .collect();
+                extracted
+            },
+            |(filename, _), extracted| {
+                for (rule, span, values, groups) in extracted {
+                    if rule.get_options().unique && !seen.insert((rule.get_name(), values)) {
+                        continue;
+                    }
+
+                    let mut line = serde_json::Map::new();
+                    line.insert("file".to_string(), filename.clone().into());
+                    line.insert("rule".to_string(), rule.get_name().into());
+                    line.insert("span".to_string(), serde_json::json!(span));
+                    line.insert("groups".to_string(), groups.into());
+                    println!("{}", serde_json::Value::from(line));
+                }
+            },
+        );
+
+        exit_on_read_failures(&failures);
+        return;
+    }
+
+    //  ____
+    // / ___|  ___ _ ____   _____
+    // \___ \ / _ \ '__\ \ / / _ \
+    //  ___) |  __/ |   \ V /  __/
+    // |____/ \___|_|    \_/ \___|
+    //
+
+    if matches.is_present("serve") {
+        let stdin = stdin();
+        let stdout = std::io::stdout();
+        Server::new().run(stdin.lock(), stdout.lock()).unwrap();
+        return;
+    }
+
+    // Extract parameters
+    let benchmark = matches.is_present("benchmark");
+    let repetitions = match matches.value_of("repetitions") {
+        None => 0,
+        Some(s) => match s.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => panic!("Not a number: {}", s),
+        },
+    };
+    let sampling = Sampling {
+        warmup: match matches.value_of("warmup") {
+            None => 0,
+            Some(s) => match s.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => panic!("Not a number: {}", s),
+            },
+        },
+        samples: match matches.value_of("samples") {
+            None => 1,
+            Some(s) => match s.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => panic!("Not a positive number: {}", s),
+            },
+        },
+    };
+    let max_file_size = match matches.value_of("max_file_size") {
+        None => None,
+        Some(s) => match s.parse::<u64>() {
+            Ok(n) => Some(n),
+            Err(_) => panic!("Not a number: {}", s),
+        },
+    };
+    let count = matches.is_present("count");
+    let show_offset = if matches.is_present("char_offset") {
+        Some(OffsetUnit::Chars)
+    } else if matches.is_present("utf16_offset") {
+        Some(OffsetUnit::Utf16)
+    } else if matches.is_present("bytes_offset") {
+        Some(OffsetUnit::Bytes)
+    } else {
+        None
+    };
+    let line_column = matches.is_present("line_column");
+    let compare_format = matches.is_present("compare");
+    let json_format = matches.is_present("json");
+    let offset_unit = match matches.value_of("offset_unit") {
+        None | Some("bytes") => OffsetUnit::Bytes,
+        Some("utf16") => OffsetUnit::Utf16,
+        Some("chars") => OffsetUnit::Chars,
+        Some(s) => panic!("Invalid option for offset unit: {}", s),
+    };
+
+    let ranked = ["rank", "weight", "priority", "top_k", "span_order"]
+        .iter()
+        .any(|arg| matches.is_present(arg));
+
+    let weights = if matches.is_present("weight") || matches.is_present("priority") {
+        let mut weights = Weights::new();
+
+        for value in matches.values_of("weight").into_iter().flatten() {
+            let (group, weight) = parse_group_value(value);
+            weights = weights.with_length_weight(group, weight);
+        }
+
+        for value in matches.values_of("priority").into_iter().flatten() {
+            let (group, priority) = parse_group_value(value);
+            weights = weights.with_priority(group, priority);
+        }
+
+        Some(weights)
+    } else {
+        None
+    };
+
+    let span_order = match matches.value_of("span_order") {
+        None => None,
+        Some("shortest") => Some(Order::ShortestFirst),
+        Some("longest") => Some(Order::LongestFirst),
+        Some(s) => panic!("Invalid option for span order: {}", s),
+    };
+
+    let annotation = if matches.is_present("group_score") || matches.is_present("char_score") {
+        let semiring = matches.value_of("semiring").unwrap();
+        let semiring = match Semiring::from_name(semiring) {
+            Some(semiring) => semiring,
+            None => panic!("Invalid option for semiring: {}", semiring),
+        };
+        let mut annotation = Annotation::new(semiring);
+
+        for value in matches.values_of("group_score").into_iter().flatten() {
+            let (group, score) = parse_group_value(value);
+            annotation = annotation.with_group_score(group, score);
+        }
+
+        for value in matches.values_of("char_score").into_iter().flatten() {
+            let (group, score) = parse_group_value(value);
+            annotation = annotation.with_char_score(group, score);
+        }
+
+        Some(annotation)
+    } else {
+        None
+    };
+
+    let length_bounds = if matches.is_present("min_len") || matches.is_present("max_len") {
+        let mut bounds = LengthBounds::new();
+
+        for value in matches.values_of("min_len").into_iter().flatten() {
+            let (group, len) = parse_length_bound(value);
+            bounds = bounds.with_min_len(group, len);
+        }
+
+        for value in matches.values_of("max_len").into_iter().flatten() {
+            let (group, len) = parse_length_bound(value);
+            bounds = bounds.with_max_len(group, len);
+        }
+
+        Some(bounds)
+    } else {
+        None
+    };
+
+    let top_k = matches.value_of("top_k").map(|s| match s.parse::<usize>() {
+        Ok(n) => n,
+        Err(_) => panic!("Not a number: {}", s),
+    });
+
+    let algorithm = match matches.value_of("algorithm") {
+        None if ranked => Algorithm::PODS18,
+        None => Algorithm::ICDT19,
+        Some(s) => match Algorithm::from_name(s) {
+            Some(algorithm) => algorithm,
+            None => panic!("Invalid option for algorithm: {}", s),
+        },
+    };
+
+    if length_bounds.is_some()
+        && algorithm != Algorithm::ICDT19
+        && algorithm != Algorithm::JumpPointers
+    {
+        panic!("Length bounds are only supported by the icdt19 and jump-pointers algorithms.");
+    }
+
+    let predicates = matches.value_of("where").map(|expr| match Predicates::parse(expr) {
+        Ok(predicates) => predicates,
+        Err(err) => panic!("{}", err),
+    });
+
+    // Predicates on lengths also prune the enumeration if it supports length
+    // bounds, the matches are still filtered with all the predicates.
+    let length_bounds = match &predicates {
+        Some(predicates)
+            if (algorithm == Algorithm::ICDT19 || algorithm == Algorithm::JumpPointers)
+                && top_k.is_none()
+                && !matches.is_present("span_order") =>
+        {
+            Some(predicates.restrict_lengths(length_bounds.unwrap_or_default()))
+        }
+        _ => length_bounds,
+    };
+
+    if matches.is_present("gazetteer")
+        && (algorithm == Algorithm::NaiveCubic || algorithm == Algorithm::NaiveQuadratic)
+    {
+        panic!("Dictionaries are not supported by the naive-cubic and naive-quadratic \
+                algorithms, which parse a pattern.");
+    }
+
+    let memory_stats = matches.is_present("memory_stats");
+
+    if memory_stats && algorithm != Algorithm::ICDT19 && algorithm != Algorithm::JumpPointers {
+        panic!("Memory statistics are only supported by the icdt19 and jump-pointers algorithms.");
+    }
+
+    let time_first = matches.is_present("time_first");
+    let output = matches.value_of("output");
+    let first = matches.is_present("first");
+
+    if first && algorithm != Algorithm::ICDT19 && algorithm != Algorithm::JumpPointers {
+        panic!("Stopping at the first match is only supported by the icdt19 and jump-pointers \
+                algorithms.");
+    }
+
+    let unique = match matches.values_of("unique") {
+        Some(groups) => Some(groups.map(String::from).collect()),
+        None if matches.is_present("unique") => Some(Vec::new()),
+        None => None,
+    };
+
+    let skip_partial = matches.is_present("skip_partial");
+
+    // The groups required by --skip-partial are known once the pattern is
+    // read.
+    let mut filters = MatchFilters {
+        unique,
+        predicates,
+        groups: matches.values_of("groups").map(|groups| groups.map(String::from).collect()),
+        required: None,
+    };
+
+    let debug_infos = matches.is_present("debug_infos");
+    let compare_algorithms = matches.is_present("compare_algorithms");
+    let show_progress = !matches.is_present("no_progress") && atty::is(atty::Stream::Stderr);
+
+    let trimming_threshold = match matches.value_of("trimming_threshold") {
+        None => 0,
+        Some(s) => match s.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => panic!("Not a number: {}", s),
+        },
+    };
+
+    let trimming_strategy_str = matches.value_of("trimming_strategy");
+    let trimming_strategy = match trimming_strategy_str {
+        None => TrimmingStrategy::FullTrimming,
+        Some("full") => TrimmingStrategy::FullTrimming,
+        Some("partial") => TrimmingStrategy::PartialTrimming,
+        Some("no") => TrimmingStrategy::NoTrimming,
+        Some("adaptive") => TrimmingStrategy::AdaptiveTrimming(trimming_threshold),
+        Some("lazy") => TrimmingStrategy::LazyTrimming,
+        Some(s) => panic!("Invalid option for trimming: {}", s),
+    };
+
+    let construction = match matches.value_of("construction") {
+        None | Some("glushkov") => Construction::Glushkov,
+        Some("thompson") => Construction::Thompson,
+        Some(s) => panic!("Invalid option for construction: {}", s),
+    };
+
+    let jump_schedule = match matches.value_of("jump_schedule") {
+        None | Some("uniform") => JumpSchedule::Uniform,
+        Some("exponential") => JumpSchedule::Exponential,
+        Some(s) => panic!("Invalid option for jump schedule: {}", s),
+    };
+
+    let single_pass = matches.is_present("single_pass");
+    let mapped_levels = matches.is_present("mmap_levels");
+    let compressed_levels = matches.is_present("compressed_levels");
+
+    let max_memory = match matches.value_of("max_memory") {
+        None => None,
+        Some(s) => match s.parse::<usize>() {
+            Ok(n) => Some(n),
+            Err(_) => panic!("Not a number: {}", s),
+        },
+    };
+    let auto_degrade = matches.is_present("auto_degrade");
+    let naive_fallback = matches.is_present("naive_fallback");
+
+    let jump_distance_str = matches.value_of("jump_distance");
+    let jump_distance = match jump_distance_str {
+        None => 1,
+        Some(s) => match s.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => panic!("Not a positive number: {}", s),
+        },
+    };
+
+    let mut display_format = match (count, compare_format, json_format) {
+        (true, _, _) => DisplayFormat::Count,
+        (_, true, _) => DisplayFormat::CompareFormat,
+        _ if matches.is_present("provenance") => DisplayFormat::Provenance {
+            tokens: matches.values_of("provenance").unwrap().map(String::from).collect(),
+            json: json_format,
+        },
+        (_, _, true) => DisplayFormat::Json {
+            offset_unit,
+            line_column,
+            annotation,
+            runs: None,
+            schema: Vec::new(),
+        },
+        _ if matches.is_present("highlight") => DisplayFormat::Highlight,
+        _ if matches.is_present("replace") => {
+            match Template::parse(matches.value_of("replace").unwrap()) {
+                Ok(template) => DisplayFormat::Replace { template },
+                Err(err) => panic!("{}", err),
+            }
+        }
+        _ if matches.is_present("merge") => DisplayFormat::Merge { show_offset },
+        _ if matches.is_present("null") => DisplayFormat::Null,
+        _ if matches.is_present("html") => DisplayFormat::Html {
+            path: matches.value_of("html").unwrap().to_string(),
+        },
+        // The input file and the groups are known once the pattern is read.
+        _ if matches.is_present("arrow") => DisplayFormat::Arrow {
+            path: matches.value_of("arrow").unwrap().to_string(),
+            file: String::new(),
+            groups: Vec::new(),
+        },
+        _ if matches.is_present("output_parquet") => DisplayFormat::Parquet {
+            path: matches.value_of("output_parquet").unwrap().to_string(),
+            file: String::new(),
+            groups: Vec::new(),
+        },
+        _ if matches.is_present("top") => {
+            let k = matches.value_of("top").unwrap();
+
+            DisplayFormat::Top {
+                k: match k.parse::<usize>() {
+                    Ok(n) => n,
+                    Err(_) => panic!("Not a number: {}", k),
+                },
+                group: matches.value_of("top_group").map(String::from),
+            }
+        }
+        _ => DisplayFormat::Verbose { show_offset, line_column, annotation, runs: None },
+    };
+
+    //  ____                  _                          _
+    // | __ )  ___ _ __   ___| |__  _ __ ___   __ _ _ __| | __
+    // |  _ \ / _ \ '_ \ / __| '_ \| '_ ` _ \ / _` | '__| |/ /
+    // | |_) |  __/ | | | (__| | | | | | | | | (_| | |  |   <
+    // |____/ \___|_| |_|\___|_| |_|_| |_| |_|\__,_|_|  |_|\_\
+    //
+
+    let benchmark_file = matches.value_of("benchmark-file");
+    let baselines: Vec<String> = match matches.values_of("baseline") {
+        Some(baselines) => baselines.map(String::from).collect(),
+        None => Vec::new(),
+    };
+
+    if let Some(benchmark_file) = benchmark_file {
+        let benchmarks = match benchmark::BenchmarkCase::read_from_file(Path::new(benchmark_file))
+        {
+            Ok(benchmarks) => benchmarks,
+            Err(err) => {
+                eprintln!("Could not read the benchmark file {}: {}", benchmark_file, err);
+                std::process::exit(1);
+            }
+        };
+
+        print!("[");
+        let mut first = true;
+        let mut failures = Vec::new();
+        let mut delay_samples = create_delay_samples_file(&matches);
+
+        for (index, benchmark) in benchmarks.into_iter().enumerate() {
+            let benchmark = match benchmark {
+                Ok(benchmark) => benchmark.with_baselines(baselines.clone()),
+                Err(err) => {
+                    failures.push((format!("case {}", index + 1), err.to_string()));
+                    continue;
+                }
+            };
+
+            let result = if compare_algorithms {
+                benchmark
+                    .compare(repetitions, sampling, max_file_size)
+                    .map(|result| {
+                        if let Some(file) = &mut delay_samples {
+                            result.write_delay_samples(file).unwrap();
+                        }
+
+                        serde_json::to_string_pretty(&result).unwrap()
+                    })
+            } else {
+                benchmark
+                    .run(algorithm, repetitions, sampling, max_file_size)
+                    .map(|result| {
+                        if let Some(file) = &mut delay_samples {
+                            result.write_delay_samples(algorithm, file).unwrap();
+                        }
+
+                        serde_json::to_string_pretty(&result).unwrap()
+                    })
+            };
+
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    let case = format!("{} ({})", benchmark.get_name(), benchmark.get_filename());
+                    failures.push((case, err.to_string()));
+                    continue;
+                }
+            };
+
+            println!("{}", if first { "" } else { "," });
+            print!("{}", result);
+            first = false;
+        }
+        println!("\n]");
+
+        // Report all the cases that could not be run at once.
+        if !failures.is_empty() {
+            eprintln!("{} benchmark(s) could not be run:", failures.len());
+
+            for (case, err) in &failures {
+                eprintln!("  {}: {}", case, err);
+            }
+
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    // If the pattern is given by an option, the only positional argument is
+    // the file to be read.
+    if (matches.is_present("regex_opt")
+        || matches.is_present("regex_file")
+        || matches.is_present("expr")
+        || matches.is_present("gazetteer"))
+        && matches.is_present("file")
+    {
+        panic!("Too many positional arguments when the pattern is given as an option.");
+    }
+
+    let (regex_string, file) = match (matches.value_of("regex_opt"), matches.value_of("regex_file"))
+    {
+        (Some(regex), _) => (regex.to_string(), matches.value_of("regex")),
+        (None, Some(filename)) => {
+            let mut regex = String::new();
+            File::open(filename)
+                .and_then(|mut file| file.read_to_string(&mut regex))
+                .expect("Could not read the regex file.");
+
+            while regex.ends_with('\n') || regex.ends_with('\r') {
+                regex.pop();
+            }
+
+            (regex, matches.value_of("regex"))
+        }
+        // The patterns of an expression are compiled with its plan.
+        (None, None) if matches.is_present("expr") => (String::new(), matches.value_of("regex")),
+        (None, None) if matches.is_present("gazetteer") => {
+            (String::new(), matches.value_of("regex"))
+        }
+        (None, None) => (
+            matches.value_of("regex").unwrap().to_string(),
+            matches.value_of("file"),
+        ),
+    };
+
+    let regex_str = regex_string.as_str();
+
+    let gazetteer = matches.value_of("gazetteer").map(|filename| {
+        let gazetteer = Gazetteer::read_from_file(Path::new(filename))
+            .expect("Could not read the gazetteer file.");
+
+        match matches.value_of("gazetteer_group") {
+            Some(group) => gazetteer.with_group(group),
+            None => gazetteer,
+        }
+    });
+
+    // The automaton of the gazetteer replaces the pattern if one is given.
+    let compile = || match &gazetteer {
+        Some(gazetteer) => gazetteer.get_automaton(),
+        None => match regex::try_compile_with(regex_str, construction) {
+            Ok(automaton) => automaton,
+            Err(message) => panic!("{}", message),
+        },
+    };
+
+    if benchmark {
         let benchmark_case = BenchmarkCase::new(
             "CLI Benchmark".to_string(),
             "Benchmark invoked by CLI.".to_string(),
-            matches.value_of("file").unwrap().to_string(),
+            file.unwrap().to_string(),
             regex_str.to_string(),
             jump_distance,
             trimming_strategy,
-        );
-        let result = benchmark_case.run(algorithm, repetitions).unwrap();
+            construction,
+        )
+        .with_jump_schedule(jump_schedule)
+        .with_single_pass(single_pass)
+        .with_mapped_levels(mapped_levels)
+        .with_compressed_levels(compressed_levels)
+        .with_baselines(baselines);
+        let mut delay_samples = create_delay_samples_file(&matches);
+        let result = if compare_algorithms {
+            benchmark_case
+                .compare(repetitions, sampling, max_file_size)
+                .map(|result| {
+                    if let Some(file) = &mut delay_samples {
+                        result.write_delay_samples(file).unwrap();
+                    }
 
-        print!("{}", serde_json::to_string_pretty(&result).unwrap());
+                    serde_json::to_string_pretty(&result).unwrap()
+                })
+        } else {
+            benchmark_case
+                .run(algorithm, repetitions, sampling, max_file_size)
+                .map(|result| {
+                    if let Some(file) = &mut delay_samples {
+                        result.write_delay_samples(algorithm, file).unwrap();
+                    }
+
+                    serde_json::to_string_pretty(&result).unwrap()
+                })
+        };
+
+        print!("{}", result.unwrap());
+
+        return;
+    }
 
+    if matches.is_present("dry_run") {
+        print_dry_run(&compile(), json_format);
         return;
     }
 
@@ -240,9 +1731,114 @@ fn main() {
     // |___|_| |_| .__/ \__,_|\__|___/
     //           |_|
 
+    if let Some(window) = matches.value_of("window") {
+        let window = match window.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => panic!("Not a positive number: {}", window),
+        };
+
+        let automaton = compile();
+        let mut stream = SlidingWindow::new(automaton, window)
+            .with_index_options(jump_distance, trimming_strategy);
+        let mut input: Box<dyn BufRead> = match file {
+            Some(filename) => Box::new(BufReader::new(File::open(filename).unwrap())),
+            None => Box::new(BufReader::new(stdin())),
+        };
+
+        let mut count = 0;
+        let mut print_match = |offset: usize, mapping: &Mapping| {
+            count += 1;
+            print!("{} -", count);
+
+            if show_offset.is_some() {
+                for (name, range) in mapping.iter_groups() {
+                    print!(" {}:{},{}", name, offset + range.start, offset + range.end);
+                }
+            } else {
+                for (name, text) in mapping.iter_groups_text() {
+                    print!(" {}:{:?}", name, text);
+                }
+            }
+
+            println!();
+        };
+
+        let mut line = String::new();
+        while input.read_line(&mut line).unwrap() > 0 {
+            stream.push(&line, &mut print_match);
+            line.clear();
+        }
+
+        stream.finish(&mut print_match);
+        return;
+    }
+
+    if matches.is_present("json_field")
+        || matches.is_present("csv_column")
+        || matches.is_present("record_separator")
+    {
+        let automaton = compile();
+        let schema = filters.check_groups(&automaton);
+        let spanner = CompiledSpanner::from_automaton(filters.project_automaton(automaton))
+            .with_index_options(jump_distance, trimming_strategy);
+
+        if skip_partial {
+            filters.required = Some(schema.clone());
+        }
+
+        let mut input: Box<dyn BufRead> = match file {
+            Some(filename) => Box::new(BufReader::new(File::open(filename).unwrap())),
+            None => Box::new(BufReader::new(stdin())),
+        };
+
+        let mut out = Output::create(output).expect("Could not create the output file.");
+        let mut invalid_lines = Vec::new();
+
+        if let Some(field) = matches.value_of("json_field") {
+            let mut fields = JsonFields::new(input, field);
+            handle_units(&mut fields, &spanner, &schema, &filters, count, json_format, &mut out);
+            invalid_lines = fields.get_invalid_lines().to_vec();
+        } else if let Some(column) = matches.value_of("csv_column") {
+            let delimiter = match matches.value_of("csv_delimiter").unwrap_or(",") {
+                "\\t" | "tab" => '\t',
+                s if s.chars().count() == 1 => s.chars().next().unwrap(),
+                s => panic!("Invalid CSV delimiter: {}", s),
+            };
+
+            let cells = units::csv_cells(input, delimiter, column);
+            handle_units(cells, &spanner, &schema, &filters, count, json_format, &mut out);
+        } else if let Some(separator) = matches.value_of("record_separator") {
+            let separator = unescape(separator);
+
+            if separator.is_empty() {
+                panic!("The record separator can't be empty.");
+            }
+
+            let mut text = String::new();
+            input.read_to_string(&mut text).unwrap();
+            trim_trailing_newlines(&mut text);
+
+            let records = units::records(&text, &separator);
+            handle_units(records, &spanner, &schema, &filters, count, json_format, &mut out);
+        }
+
+        out.commit().expect("Could not write the matches.");
+
+        if !invalid_lines.is_empty() {
+            eprintln!(
+                "{} line(s) are not JSON objects: {:?}",
+                invalid_lines.len(),
+                invalid_lines
+            );
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     // Read the text
     let mut text = String::new();
-    match matches.value_of("file") {
+    match file {
         Some(filename) => {
             let mut file = File::open(filename).unwrap();
             file.read_to_string(&mut text).unwrap()
@@ -250,106 +1846,847 @@ fn main() {
         None => stdin().read_to_string(&mut text).unwrap(),
     };
 
-    // Remove trailing newlines
-    while text.as_bytes().last() == Some(&b'\n') {
-        text.pop();
+    trim_trailing_newlines(&mut text);
+
+    if let Some(expr) = matches.value_of("expr") {
+        let mut definitions = HashMap::new();
+
+        for definition in matches.values_of("define").into_iter().flatten() {
+            match definition.split_once('=') {
+                Some((name, regex)) => definitions.insert(name.to_string(), regex.to_string()),
+                None => panic!("Expected a definition of the form NAME=REGEX: {}", definition),
+            };
+        }
+
+        let mut dictionaries = HashMap::new();
+
+        for definition in matches.values_of("define_dictionary").into_iter().flatten() {
+            let (name, filename) = match definition.split_once('=') {
+                Some(definition) => definition,
+                None => panic!("Expected a definition of the form NAME=FILE: {}", definition),
+            };
+
+            let gazetteer = Gazetteer::read_from_file(Path::new(filename))
+                .expect("Could not read the dictionary file.");
+            dictionaries.insert(name.to_string(), gazetteer.with_group(name));
+        }
+
+        let plan = match Plan::parse_with(expr, &definitions, &dictionaries) {
+            Ok(plan) => plan,
+            Err(err) => panic!("{}", err),
+        };
+
+        let timer = time::Instant::now();
+        let events = plan.eval(&text).into_iter().map(|mapping| (Some(mapping), timer.elapsed()));
+        let end = iter::once_with(|| (None, timer.elapsed()));
+        write_matches(events.chain(end), &text, display_format, output);
+        return;
+    }
+
+    //  __  __       _       _
+    // |  \/  | __ _| |_ ___| |__
+    // | |\/| |/ _` | __/ __| '_ \
+    // | |  | | (_| | || (__| | | |
+    // |_|  |_|\__,_|\__\___|_| |_|
+    //
+
+    let automaton = compile();
+    let schema = filters.check_groups(&automaton);
+    let automaton = filters.project_automaton(automaton);
+
+    if skip_partial {
+        filters.required = Some(schema.clone());
+    }
+
+    // The schema of structured outputs lists all the groups, even those which
+    // are never bound, or the selected ones.
+    if let DisplayFormat::Json { schema: json_schema, .. } = &mut display_format {
+        *json_schema = schema.clone();
     }
 
-    //  __  __       _       _
-    // |  \/  | __ _| |_ ___| |__
-    // | |\/| |/ _` | __/ __| '_ \
-    // | |  | | (_| | || (__| | | |
-    // |_|  |_|\__,_|\__\___|_| |_|
-    //
+    match &mut display_format {
+        DisplayFormat::Arrow { file: output_file, groups, .. }
+        | DisplayFormat::Parquet { file: output_file, groups, .. } => {
+            *output_file = file.unwrap_or("-").to_string();
+            *groups = schema.clone();
+        }
+        DisplayFormat::Provenance { tokens, .. } => {
+            for token in tokens.iter() {
+                if !automaton.get_variables().iter().any(|var| var.get_name() == token) {
+                    panic!("The pattern has no group named {}", token);
+                }
+            }
+        }
+        // The runs producing each match are counted by the output, on an
+        // automaton whose equivalent positions are not merged.
+        DisplayFormat::Verbose { runs, .. } | DisplayFormat::Json { runs, .. }
+            if matches.is_present("runs") =>
+        {
+            *runs = Some(match &gazetteer {
+                Some(_) => automaton.clone(),
+                None => regex::compile_unmerged_with(regex_str, construction),
+            });
+        }
+        _ => (),
+    }
+
+    match matches.value_of("dot") {
+        None if matches.is_present("dot") => automaton
+            .render("automaton.dot")
+            .expect("Could not create the dotfile."),
+        None => (),
+        Some("-") => automaton
+            .write_dot(&mut std::io::stdout())
+            .expect("Could not write the dotfile."),
+        Some(path) => automaton
+            .render(path)
+            .expect("Could not create the dotfile."),
+    }
+
+    // Options shared by all the indexes of the text.
+    let index_builder = |jump_distance| {
+        IndexedDag::builder(automaton.clone(), &text)
+            .with_jump_distance(jump_distance)
+            .with_jump_schedule(jump_schedule)
+            .with_trimming(trimming_strategy)
+            .with_single_pass(single_pass)
+            .with_mapped_levels(mapped_levels)
+            .with_compressed_levels(compressed_levels)
+            .with_progress(show_progress)
+    };
+
+    if matches.is_present("verify") {
+        let sample_size = match matches.value_of("verify_sample").unwrap().parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => panic!("Not a number: {}", matches.value_of("verify_sample").unwrap()),
+        };
+
+        let mut reference = naive::naive::NaiveEnum::new(&automaton, &text);
+        reference.preprocess();
+
+        let mut enumerator = index_builder(jump_distance).build();
+        enumerator.preprocess();
+
+        let verification = Verification::new(reference.iter(), enumerator.iter());
+        verification.print(sample_size);
+
+        std::process::exit(if verification.is_consistent() { 0 } else { 1 });
+    }
+
+    if let Some(index) = matches.value_of("explain_run") {
+        let index = match index.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => panic!("Not a positive number: {}", index),
+        };
+
+        let mut enumerator = index_builder(jump_distance).build();
+        enumerator.preprocess();
+
+        let mapping = match enumerator.iter().nth(index - 1) {
+            Some(mapping) => mapping,
+            None => panic!("There are less than {} matches", index),
+        };
+
+        let run = enumerator
+            .explain_run(&mapping)
+            .expect("No run of the automaton produces an enumerated match");
+
+        println!("===== Run of match {} =====", index);
+        println!("{}", mapping);
+
+        for step in run {
+            println!("{}", step);
+        }
+
+        std::process::exit(0);
+    }
+
+    let timer = time::Instant::now();
+
+    /// Matches skipped by the output, given by --unique, --where and
+    /// --skip-partial, and groups kept in the output, given by --groups.
+    struct MatchFilters {
+        unique: Option<Vec<String>>,
+        predicates: Option<Predicates>,
+        groups: Option<Vec<String>>,
+        required: Option<Vec<String>>,
+    }
+
+    impl MatchFilters {
+        /// Check that the groups the filters are about are groups of the
+        /// pattern, and give the groups of the output, which are the selected
+        /// ones or all of them.
+        fn check_groups(&self, automaton: &Automaton) -> Vec<String> {
+            let variables: Vec<String> = (automaton.get_variables().iter())
+                .map(|var| var.get_name().to_string())
+                .collect();
+
+            let groups = (self.groups.iter().flatten().map(String::as_str))
+                .chain(self.predicates.iter().flat_map(Predicates::get_groups));
+
+            for group in groups {
+                if !variables.iter().any(|name| name == group) {
+                    panic!("The pattern has no group named {}", group);
+                }
+            }
+
+            self.groups.clone().unwrap_or(variables)
+        }
+
+        /// Groups used by predicates which are not selected.
+        fn hidden_groups(&self) -> impl Iterator<Item = &str> {
+            (self.predicates.iter().flat_map(Predicates::get_groups))
+                .filter(move |group| !self.groups.iter().flatten().any(|name| name == group))
+        }
+
+        /// Remove from the automaton the groups that are not selected, unless
+        /// predicates use them, so that matches which only differ by these
+        /// groups are enumerated once.
+        fn project_automaton(&self, automaton: Automaton) -> Automaton {
+            match &self.groups {
+                None => automaton,
+                Some(groups) => {
+                    let kept: Vec<String> = (groups.iter().cloned())
+                        .chain(self.hidden_groups().map(String::from))
+                        .collect();
+                    automaton.project(&kept)
+                }
+            }
+        }
+
+        /// Keep the matches of a text passing the filters, with only their
+        /// selected groups. The keys of the matches already output for
+        /// --unique are kept in `seen`, which can be shared by several texts.
+        fn apply<'a, 't: 'a>(
+            &'a self,
+            text: &'t str,
+            matches: impl Iterator<Item = Mapping<'t>> + 'a,
+            seen: &'a mut HashSet<Vec<(String, Option<String>)>>,
+        ) -> impl Iterator<Item = Mapping<'t>> + 'a {
+            let mut projected = HashSet::new();
+            let has_hidden = self.groups.is_some() && self.hidden_groups().next().is_some();
+
+            // Predicates may be about groups that are not selected, which are
+            // kept by the automaton, and mappings that only differ by these
+            // groups are the same once projected.
+            let matches = matches
+                .filter(move |mapping| self.predicates.iter().all(|pred| pred.accepts(mapping)))
+                .map(move |mapping| self.project(text, mapping))
+                .filter(move |mapping| !has_hidden || projected.insert(mapping.clone()))
+                .filter(move |mapping| {
+                    (self.required.iter().flatten()).all(|group| mapping.get(group).is_some())
+                });
+
+            matches.filter(move |mapping| {
+                let key: Vec<(String, Option<String>)> = match &self.unique {
+                    None => return true,
+                    Some(groups) if groups.is_empty() => mapping
+                        .iter_groups_text()
+                        .map(|(name, text)| (name.to_string(), Some(text.to_string())))
+                        .collect(),
+                    Some(groups) => groups
+                        .iter()
+                        .map(|name| (name.clone(), mapping.get_text(name).map(String::from)))
+                        .collect(),
+                };
+
+                seen.insert(key)
+            })
+        }
+
+        /// Only keep the selected groups of a mapping, in the order they were
+        /// given.
+        fn project<'t>(&self, text: &'t str, mapping: Mapping<'t>) -> Mapping<'t> {
+            match &self.groups {
+                None => mapping,
+                Some(groups) => {
+                    let kept = (mapping.iter_groups())
+                        .filter(|(name, _)| groups.iter().any(|group| group == name));
+                    Mapping::from_groups(text, groups, kept)
+                }
+            }
+        }
+    }
+
+    /// Enumerate the matches and send them to a thread formatting them, so
+    /// that the enumeration doesn't wait for the output, unless a single
+    /// core is available. Each match is given with the time at which it was
+    /// found, and the end of the enumeration is given as `None`.
+    fn handle_matches<'t>(
+        enumerator: &impl SpannerEnumerator<'t>,
+        text: &'t str,
+        timer: &time::Instant,
+        display_format: DisplayFormat,
+        filters: &MatchFilters,
+        time_first: bool,
+        output: Option<&str>,
+    ) {
+        let mut seen = HashSet::new();
+        let mut found = false;
+        let matches = filters.apply(text, enumerator.iter(), &mut seen);
+
+        // The time to the first match is displayed as soon as it is known.
+        let matches = matches.inspect(|_| {
+            if time_first && !found {
+                eprintln!("Time to first match: {:.6}s", timer.elapsed().as_secs_f64());
+            }
+
+            found = true;
+        });
+
+        if cfg!(not(feature = "parallel"))
+            || thread::available_parallelism().map_or(true, |cores| cores.get() == 1)
+        {
+            let events = matches.map(|mapping| (Some(mapping), timer.elapsed()));
+            let end = iter::once_with(|| (None, timer.elapsed()));
+            write_matches(events.chain(end), text, display_format, output);
+            return;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(PIPELINE_SIZE);
+
+        thread::scope(|scope| {
+            let events = receiver.into_iter().flatten();
+            let writer = scope.spawn(move || write_matches(events, text, display_format, output));
+
+            let mut batch = Vec::with_capacity(PIPELINE_BATCH_SIZE);
+            let mut last_sent = time::Duration::default();
+
+            for mapping in matches {
+                let time = timer.elapsed();
+                batch.push((Some(mapping), time));
+
+                if batch.len() < PIPELINE_BATCH_SIZE && time - last_sent < PIPELINE_BATCH_DELAY {
+                    continue;
+                }
+
+                // The output thread only stops receiving if it failed.
+                let next_batch = Vec::with_capacity(PIPELINE_BATCH_SIZE);
+
+                if sender.send(std::mem::replace(&mut batch, next_batch)).is_err() {
+                    break;
+                }
+
+                last_sent = time;
+            }
+
+            batch.push((None, timer.elapsed()));
+            let _ = sender.send(batch);
+
+            if let Err(err) = writer.join() {
+                panic::resume_unwind(err);
+            }
+        });
+    }
+
+    /// Match the pattern against each unit of the input and write the matches
+    /// passing the filters with the number of their unit, as JSON objects or
+    /// in a human-readable format, or appended to their JSON record.
+    fn handle_units<'a>(
+        units: impl Iterator<Item = Unit<'a>>,
+        spanner: &CompiledSpanner,
+        schema: &[String],
+        filters: &MatchFilters,
+        count: bool,
+        json: bool,
+        out: &mut Output,
+    ) {
+        let mut seen = HashSet::new();
+        let mut num_matches = 0;
+
+        for unit in units {
+            let text = unit.text.as_ref();
+            let index = spanner.index(text);
+            let found: Vec<Mapping> = filters.apply(text, index.iter(), &mut seen).collect();
+
+            if count {
+                num_matches += found.len();
+                continue;
+            }
+
+            let to_json = |mapping: &Mapping| {
+                json_in_order(&with_nulls(mapping_to_json(mapping, text, None), schema), schema)
+            };
+
+            if let Some(record) = &unit.record {
+                if !found.is_empty() {
+                    let found: Vec<String> = found.iter().map(to_json).collect();
+                    writeln!(out, "{}", record.with_matches(&found))
+                        .expect("Could not write the matches.");
+                }
+
+                continue;
+            }
+
+            for mapping in found {
+                num_matches += 1;
+
+                let result = if json {
+                    let (kind, number) = (unit.kind, unit.number);
+                    writeln!(out, r#"{{"{}":{},"groups":{}}}"#, kind, number, to_json(&mapping))
+                } else {
+                    let groups = (mapping.iter_groups_text())
+                        .map(|(name, text)| format!(" {}:{:?}", name, text))
+                        .collect::<String>();
+                    writeln!(out, "{} - {}:{}{}", num_matches, unit.kind, unit.number, groups)
+                };
 
-    let automaton = regex::compile(regex_str);
-    automaton
-        .render("automaton.dot")
-        .expect("Could not create the dotfile.");
+                result.expect("Could not write the matches.");
+            }
+        }
 
-    let timer = time::Instant::now();
+        if count {
+            writeln!(out, "{}", num_matches).expect("Could not write the matches.");
+        }
+    }
 
-    fn handle_matches<'t>(
-        enumerator: &mut impl SpannerEnumerator<'t>,
-        text: &str,
-        timer: &time::Instant,
+    /// Write the matches in the given format as they are received, to the
+    /// given file or to STDOUT.
+    fn write_matches<'t>(
+        events: impl Iterator<Item = (Option<Mapping<'t>>, time::Duration)>,
+        text: &'t str,
         display_format: DisplayFormat,
+        output: Option<&str>,
     ) {
-        enumerator.preprocess();
-        let matches = enumerator.iter();
+        let mut out = Output::create(output).expect("Could not create the output file.");
+        write_matches_to(&mut out, events, text, display_format)
+            .and_then(|_| out.commit())
+            .expect("Could not write the matches.");
+    }
+
+    fn write_matches_to<'t>(
+        out: &mut Output,
+        mut events: impl Iterator<Item = (Option<Mapping<'t>>, time::Duration)>,
+        text: &'t str,
+        display_format: DisplayFormat,
+    ) -> io::Result<()> {
+        let offset_table = |unit| match unit {
+            Some(OffsetUnit::Bytes) | None => None,
+            Some(unit) => Some(OffsetTable::new(text, unit)),
+        };
+
+        let matches = events.by_ref().map_while(|(mapping, _)| mapping);
 
         match display_format {
             DisplayFormat::Count => {
                 let count = matches.count();
-                println!("{}", count)
+                writeln!(out, "{}", count)?
             }
             DisplayFormat::CompareFormat => {
-                for mapping in matches {
-                    let span = mapping
-                        .main_span()
-                        .expect("A mapping should never be empty");
+                // Matches are given with the time at which they were found.
+                for (mapping, time) in events {
+                    let mapping = match mapping {
+                        Some(mapping) => mapping,
+                        None => {
+                            writeln!(out,
+                                r#">>>>{{"match": "EOF", "span": [-1,-1], "time": {}}}"#,
+                                time.as_millis()
+                            )?;
+                            break;
+                        }
+                    };
 
-                    println!(
+                    let span = match mapping.main_span() {
+                        Some(span) => span,
+                        None => continue,
+                    };
+
+                    writeln!(out,
                         r#">>>>{{"match": {:?}, "span": [{},{}], "time": {}}}"#,
                         &text[span.clone()],
                         span.start,
                         span.end,
-                        timer.elapsed().as_millis()
-                    )
+                        time.as_millis()
+                    )?
                 }
-
-                println!(
-                    r#">>>>{{"match": "EOF", "span": [-1,-1], "time": {}}}"#,
-                    timer.elapsed().as_millis()
-                );
             }
-            DisplayFormat::Verbose { show_offset } => {
+            DisplayFormat::Verbose { show_offset, line_column, annotation, runs } => {
+                let offset_table = offset_table(show_offset);
+                let convert = |pos| offset_table.as_ref().map_or(pos, |table| table.convert(pos));
+                let line_index = if line_column { Some(LineIndex::new(text)) } else { None };
+                let mut line = Vec::new();
+
                 for (count, mapping) in matches.enumerate() {
-                    print!("{} -", count + 1);
+                    line.clear();
+                    output::push_int(&mut line, count + 1);
+                    line.extend_from_slice(b" -");
 
-                    if show_offset {
-                        for (name, range) in mapping.iter_groups() {
-                            print!(" {}:{},{}", name, range.start, range.end);
+                    for (name, range) in mapping.iter_groups() {
+                        line.push(b' ');
+                        line.extend_from_slice(name.as_bytes());
+                        line.push(b':');
+
+                        if let Some(line_index) = &line_index {
+                            let (start_line, start_column) = line_index.position(range.start);
+                            let (end_line, end_column) = line_index.position(range.end);
+                            output::push_int(&mut line, start_line);
+                            line.push(b':');
+                            output::push_int(&mut line, start_column);
+                            line.push(b'-');
+                            output::push_int(&mut line, end_line);
+                            line.push(b':');
+                            output::push_int(&mut line, end_column);
+                        } else if show_offset.is_some() {
+                            output::push_int(&mut line, convert(range.start));
+                            line.push(b',');
+                            output::push_int(&mut line, convert(range.end));
+                        } else {
+                            output::push_quoted(&mut line, &text[range]);
                         }
+                    }
+
+                    if let Some(annotation) = &annotation {
+                        write!(line, " score:{}", annotation.score(&mapping))?;
+                    }
+
+                    if let Some(automaton) = &runs {
+                        line.extend_from_slice(b" runs:");
+                        output::push_int(&mut line, mapping.count_runs(automaton));
+                    }
+
+                    line.push(b'\n');
+                    out.write_all(&line)?;
+                }
+            }
+            DisplayFormat::Highlight => {
+                let mut highlighter = Highlighter::new(text);
+
+                let matches = matches.filter(|mapping| mapping.main_span().is_some());
+
+                for (count, mapping) in matches.enumerate() {
+                    writeln!(out, "{} - {}", count + 1, highlighter.render(&mapping))?;
+                }
+            }
+            DisplayFormat::Replace { template } => {
+                writeln!(out, "{}", replace::replace(text, matches, &template))?;
+            }
+            DisplayFormat::Merge { show_offset } => {
+                let offset_table = offset_table(show_offset);
+                let convert = |pos| offset_table.as_ref().map_or(pos, |table| table.convert(pos));
+                let spans = matches.filter_map(|mapping| mapping.main_span());
+
+                for (count, region) in merge::merge(spans).into_iter().enumerate() {
+                    if show_offset.is_some() {
+                        let (start, end) = (convert(region.span.start), convert(region.span.end));
+                        write!(out, "{} - {},{}", count + 1, start, end)?;
                     } else {
-                        for (name, text) in mapping.iter_groups_text() {
-                            print!(" {}:{:?}", name, text);
-                        }
+                        write!(out, "{} - {:?}", count + 1, &text[region.span])?;
+                    }
+
+                    match region.count {
+                        1 => writeln!(out, " (1 match)")?,
+                        count => writeln!(out, " ({} matches)", count)?,
+                    }
+                }
+            }
+            DisplayFormat::Top { k, group } => {
+                let mut counts: HashMap<&str, usize> = HashMap::new();
+
+                for mapping in matches {
+                    let value = match &group {
+                        Some(group) => mapping.get(group),
+                        None => mapping.main_span(),
+                    };
+
+                    if let Some(range) = value {
+                        *counts.entry(&text[range]).or_insert(0) += 1;
+                    }
+                }
+
+                let mut counts: Vec<_> = counts.into_iter().collect();
+                counts.sort_by(|(value1, count1), (value2, count2)| {
+                    count2.cmp(count1).then(value1.cmp(value2))
+                });
+
+                for (value, count) in counts.into_iter().take(k) {
+                    writeln!(out, "{} {:?}", count, value)?;
+                }
+            }
+            DisplayFormat::Json { offset_unit, line_column, annotation, runs, schema } => {
+                let offset_table = offset_table(Some(offset_unit));
+                let line_index = if line_column { Some(LineIndex::new(text)) } else { None };
+
+                for mapping in matches {
+                    let groups = mapping_to_json(&mapping, text, offset_table.as_ref());
+                    let groups = match &line_index {
+                        Some(line_index) => with_line_columns(groups, &mapping, line_index),
+                        None => groups,
+                    };
+                    let mut groups = with_nulls(groups, &schema);
+
+                    if let Some(annotation) = &annotation {
+                        groups.insert("score".to_string(), annotation.score(&mapping).into());
+                    }
+
+                    if let Some(automaton) = &runs {
+                        groups.insert("runs".to_string(), mapping.count_runs(automaton).into());
+                    }
+
+                    writeln!(out, "{}", json_in_order(&groups, &schema))?;
+                }
+            }
+            DisplayFormat::Provenance { tokens, json } => {
+                let projections = Provenance::project(matches, &tokens);
+
+                for (count, (mapping, provenance)) in projections.into_iter().enumerate() {
+                    if json {
+                        let mut groups = mapping_to_json(&mapping, text, None);
+                        groups.insert("provenance".to_string(), provenance.to_string().into());
+                        writeln!(out, "{}", serde_json::Value::from(groups))?;
+                        continue;
+                    }
+
+                    write!(out, "{} -", count + 1)?;
+
+                    for (name, text) in mapping.iter_groups_text() {
+                        write!(out, " {}:{:?}", name, text)?;
                     }
 
-                    println!();
+                    writeln!(out, " provenance:{}", provenance)?;
+                }
+            }
+            DisplayFormat::Null => {
+                for span in matches.filter_map(|mapping| mapping.main_span()) {
+                    out.write_all(text[span].as_bytes())?;
+                    out.write_all(b"\0")?;
+                }
+            }
+            DisplayFormat::Html { path } => {
+                let mut report = Report::new(text);
+
+                for mapping in matches {
+                    report.add(&mapping);
+                }
+
+                std::fs::write(&path, report.render()).expect("Could not write the HTML report.");
+            }
+            DisplayFormat::Arrow { path, file, groups } => {
+                let output: Box<dyn Write> = match path.as_str() {
+                    "-" => Box::new(std::io::stdout()),
+                    path => Box::new(File::create(path).expect("Could not create the Arrow file.")),
+                };
+
+                let mut writer = ArrowWriter::new(BufWriter::new(output), &file, groups)
+                    .expect("Could not write the Arrow stream.");
+
+                for mapping in matches {
+                    writer.write(&mapping).expect("Could not write the Arrow stream.");
+                }
+
+                writer.finish().expect("Could not write the Arrow stream.");
+            }
+            DisplayFormat::Parquet { path, file, groups } => {
+                let output = File::create(&path).expect("Could not create the Parquet file.");
+                let mut writer = ParquetWriter::new(BufWriter::new(output), &file, groups)
+                    .expect("Could not write the Parquet file.");
+
+                for mapping in matches {
+                    writer.write(&mapping).expect("Could not write the Parquet file.");
                 }
+
+                writer.finish().expect("Could not write the Parquet file.");
             }
         }
-    }
 
-    match algorithm {
-        Algorithm::Naive => handle_matches(
-            &mut naive::naive::NaiveEnum::new(&automaton, &text),
-            &text,
-            &timer,
-            display_format,
-        ),
-        Algorithm::NaiveCubic => handle_matches(
-            &mut naive::naive_cubic::NaiveEnumCubic::new(regex_str, &text).unwrap(),
-            &text,
-            &timer,
-            display_format,
-        ),
-        Algorithm::NaiveQuadratic => handle_matches(
-            &mut naive::naive_quadratic::NaiveEnumQuadratic::new(regex_str, &text),
-            &text,
-            &timer,
-            display_format,
-        ),
-        Algorithm::ICDT19 => handle_matches(
-            &mut IndexedDag::new(automaton, &text, jump_distance, trimming_strategy, true),
-            &text,
-            &timer,
-            display_format,
-        ),
+        Ok(())
     }
 
+    // Mappings are counted without enumerating them unless they are filtered
+    // or another algorithm is asked for.
+    let fast_count = count
+        && filters.unique.is_none()
+        && filters.predicates.is_none()
+        && filters.groups.is_none()
+        && filters.required.is_none()
+        && top_k.is_none()
+        && length_bounds.is_none()
+        && !matches.is_present("dump_dag")
+        && !memory_stats
+        && !time_first
+        && !first
+        && matches!(matches.value_of("algorithm"), None | Some("pods18"));
+
+    // The index is kept for debug infos.
+    let indexed_dag = match algorithm {
+        _ if fast_count => {
+            let count = DeterministicEva::new(automaton.clone(), &text).count_runs();
+            let mut out = Output::create(output).expect("Could not create the output file.");
+            writeln!(out, "{}", count)
+                .and_then(|_| out.commit())
+                .expect("Could not write the matches.");
+            None
+        }
+        _ if first => {
+            let mut enumerator = index_builder(jump_distance)
+                .with_jump_pointers(algorithm == Algorithm::JumpPointers)
+                .with_memory_budget(max_memory)
+                .with_length_bounds(length_bounds.clone())
+                .build();
+
+            let mut mapping = enumerator.find_first();
+
+            if enumerator.is_memory_budget_exceeded() {
+                exit_on_memory_budget(max_memory.unwrap(), naive_fallback);
+                mapping = naive::naive::NaiveEnum::new(&automaton, &text).first();
+            }
+
+            if time_first && mapping.is_some() {
+                eprintln!("Time to first match: {:.6}s", timer.elapsed().as_secs_f64());
+            }
+
+            let events = (mapping.into_iter())
+                .map(|mapping| (Some(filters.project(&text, mapping)), timer.elapsed()));
+            let end = iter::once_with(|| (None, timer.elapsed()));
+            write_matches(events.chain(end), &text, display_format, output);
+
+            // The index built only covers a prefix of the text.
+            None
+        }
+        Algorithm::Naive => {
+            let mut enumerator = naive::naive::NaiveEnum::new(&automaton, &text);
+            enumerator.preprocess();
+            handle_matches(
+                &enumerator, &text, &timer, display_format, &filters, time_first, output,
+            );
+            None
+        }
+        Algorithm::PODS18 => {
+            let mut enumerator = DeterministicEva::new(automaton.clone(), &text);
+
+            if let Some(order) = &span_order {
+                enumerator = enumerator.with_order(order.clone());
+            } else if ranked {
+                // By default, each group weighs the length of its span.
+                let weights = weights.clone().unwrap_or_else(|| {
+                    automaton
+                        .get_variables()
+                        .iter()
+                        .fold(Weights::new(), |weights, var| {
+                            weights.with_length_weight(var.get_name(), 1)
+                        })
+                });
+                enumerator = enumerator.with_weights(weights);
+            }
+
+            if let Some(k) = top_k {
+                enumerator = enumerator.with_top_k(k);
+            }
+
+            enumerator.preprocess();
+            handle_matches(
+                &enumerator, &text, &timer, display_format, &filters, time_first, output,
+            );
+            None
+        }
+        Algorithm::NaiveCubic => {
+            let mut enumerator =
+                naive::naive_cubic::NaiveEnumCubic::new(regex_str, &text).unwrap();
+            enumerator.preprocess();
+            handle_matches(
+                &enumerator, &text, &timer, display_format, &filters, time_first, output,
+            );
+            None
+        }
+        Algorithm::NaiveQuadratic => {
+            let mut enumerator =
+                naive::naive_quadratic::NaiveEnumQuadratic::new(regex_str, &text);
+            enumerator.preprocess();
+            handle_matches(
+                &enumerator, &text, &timer, display_format, &filters, time_first, output,
+            );
+            None
+        }
+        Algorithm::ICDT19 | Algorithm::JumpPointers => {
+            let memory = allocator::start_phase();
+            let mut jump_distance = jump_distance;
+            let mut enumerator = index_builder(jump_distance)
+                .with_jump_pointers(algorithm == Algorithm::JumpPointers)
+                .with_memory_budget(max_memory)
+                .with_length_bounds(length_bounds.clone())
+                .build();
+            enumerator.preprocess();
+
+            // Larger jump distances lead to less matrices in the index, but
+            // don't shrink the levels of the DAG.
+            while enumerator.is_memory_budget_exceeded()
+                && auto_degrade
+                && !enumerator.is_levels_budget_exceeded()
+                && jump_distance < text.len()
+            {
+                jump_distance *= 4;
+                eprintln!(
+                    "Memory budget exceeded, retrying with a jump distance of {}.",
+                    jump_distance
+                );
+
+                enumerator = index_builder(jump_distance)
+                    .with_jump_pointers(algorithm == Algorithm::JumpPointers)
+                    .with_memory_budget(max_memory)
+                    .with_length_bounds(length_bounds.clone())
+                    .build();
+                enumerator.preprocess();
+            }
+
+            if enumerator.is_memory_budget_exceeded() && auto_degrade {
+                // The levels of a window of the text only take a part of the
+                // budget, with one bit per state and character.
+                let window = max_memory.unwrap() * 2 / automaton.get_nb_states();
+                let window = std::cmp::max(1, window);
+                eprintln!(
+                    "Memory budget exceeded, matching windows of {} bytes of the text, only \
+                     matches spanning at most {} bytes are enumerated.",
+                    window, window
+                );
+
+                let found =
+                    match_windows(&automaton, &text, window, jump_distance, trimming_strategy);
+                let mut seen = HashSet::new();
+                let events = (filters.apply(&text, found.into_iter(), &mut seen))
+                    .map(|mapping| (Some(mapping), timer.elapsed()));
+                let end = iter::once_with(|| (None, timer.elapsed()));
+                write_matches(events.chain(end), &text, display_format, output);
+                None
+            } else if enumerator.is_memory_budget_exceeded() {
+                exit_on_memory_budget(max_memory.unwrap(), naive_fallback);
+                let enumerator = naive::naive::NaiveEnum::new(&automaton, &text);
+                handle_matches(
+                    &enumerator, &text, &timer, display_format, &filters, time_first, output,
+                );
+                None
+            } else {
+                if memory_stats {
+                    print_memory_stats("after preprocessing", &enumerator, memory);
+                }
+
+                if let Some(path) = matches.value_of("dump_dag") {
+                    let result = match (matches.value_of("dag_format"), path) {
+                        (Some("graphml"), "-") => {
+                            enumerator.write_graphml(&mut std::io::stdout())
+                        }
+                        (Some("graphml"), _) => File::create(path)
+                            .and_then(|mut file| enumerator.write_graphml(&mut file)),
+                        (_, "-") => enumerator.write_dot(&mut std::io::stdout()),
+                        (_, _) => {
+                            File::create(path).and_then(|mut file| enumerator.write_dot(&mut file))
+                        }
+                    };
+
+                    result.expect("Could not write the product DAG.");
+                }
+
+                handle_matches(
+                    &enumerator, &text, &timer, display_format, &filters, time_first, output,
+                );
+
+                if memory_stats {
+                    print_memory_stats("after enumeration", &enumerator, memory);
+                }
+
+                Some(enumerator)
+            }
+        }
+    };
+
     //  ____       _                   ___        __
     // |  _ \  ___| |__  _   _  __ _  |_ _|_ __  / _| ___  ___
     // | | | |/ _ \ '_ \| | | |/ _` |  | || '_ \| |_ / _ \/ __|
@@ -359,8 +2696,456 @@ fn main() {
 
     if debug_infos {
         eprintln!("===== Debug Infos =====");
-        // eprintln!(" - Levels count: {}", compiled_matches.get_nb_levels());
+        eprintln!(
+            " - Automaton: {} states, {} transitions",
+            automaton.get_nb_states(),
+            automaton.get_nb_transitions()
+        );
+        eprintln!(" - Text: {} bytes", text.len());
+
+        if let Some(indexed_dag) = &indexed_dag {
+            print_debug_infos(indexed_dag, &text);
+        }
+    }
+
+    if matches.is_present("diagnose") {
+        let mut indexed_dag = IndexedDag::builder(automaton.clone(), &text).build();
+
+        if let Some(no_match) = indexed_dag.explain_no_match() {
+            eprintln!("===== No Match =====");
+            eprint!("{}", no_match);
+        }
     }
 
     std::process::exit(0);
 }
+
+/// Display statistics on the automaton of a pattern. The memory of the index
+/// is bounded for each character by a level of the DAG over all the states
+/// and by the reach matrix and its transpose between the jump states of two
+/// levels, which trimming usually makes much smaller.
+fn print_dry_run(automaton: &Automaton, json_format: bool) {
+    let stats = automaton.get_stats();
+    let level_memory = stats.num_states.div_ceil(64) * 8;
+    let matrix_memory = 2 * stats.num_jump_states * stats.num_jump_states.div_ceil(64) * 8;
+
+    if json_format {
+        let mut json = serde_json::to_value(&stats).unwrap();
+        json["index_memory_per_char"] = (level_memory + matrix_memory).into();
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+
+    let variables = automaton.get_variables();
+    let names: Vec<_> = variables.iter().map(|var| var.get_name()).collect();
+
+    println!(
+        " - Automaton: {} states ({} final), {} transitions ({} reading a character, {} \
+         assigning a marker)",
+        stats.num_states,
+        stats.num_finals,
+        stats.num_transitions,
+        stats.num_atom_transitions,
+        stats.num_assignation_transitions
+    );
+    println!(" - Variables: {} ({})", stats.num_variables, names.join(", "));
+    println!(
+        " - Jump states: {}, closures of assignations of {:.1} states on average and {} at most",
+        stats.num_jump_states, stats.closure_size_avg, stats.closure_size_max
+    );
+    println!(" - Functional: {}", if stats.functional { "yes" } else { "no" });
+    println!(
+        " - Index: at most {} bytes per character, {} for the DAG and {} for the matrices",
+        level_memory + matrix_memory,
+        level_memory,
+        matrix_memory
+    );
+}
+
+/// Display statistics about the index built by the preprocessing.
+fn print_debug_infos(indexed_dag: &IndexedDag, text: &str) {
+    let seconds = |time: Option<time::Duration>| match time {
+        Some(time) => format!("{:.3}s", time.as_secs_f64()),
+        None => String::from("-"),
+    };
+
+    let (create_dag, trim_dag, index_dag) = indexed_dag.get_times();
+    eprintln!(
+        " - Preprocessing: {} to build the DAG, {} to trim it, {} to index it",
+        seconds(create_dag),
+        seconds(trim_dag),
+        seconds(index_dag)
+    );
+
+    let num_levels = match indexed_dag.num_levels() {
+        Some(num_levels) => num_levels,
+        None => {
+            eprintln!(" - Index: not built, the text has no match");
+            return;
+        }
+    };
+
+    eprintln!(
+        " - Levels: {} kept out of {} (jump distance {})",
+        num_levels,
+        text.chars().count() + 1,
+        indexed_dag.get_jump_distance()
+    );
+
+    if let Some((trimmed, skipped)) = indexed_dag.get_trim_counts() {
+        eprintln!(" - Trimming: {} levels trimmed, {} skipped", trimmed, skipped);
+    }
+
+    let (num_matrices, num_used_matrices, matrix_avg_size, matrix_max_size, width_max, width_avg) =
+        indexed_dag.get_statistics().unwrap();
+    eprintln!(
+        " - Matrices: {} ({} used during enumeration), {:.1} matrices per level on average and {} at most",
+        num_matrices, num_used_matrices, width_avg, width_max
+    );
+    eprintln!(
+        " - Matrix sizes: {:.1} entries on average, {} at most, density {:.3}",
+        matrix_avg_size,
+        matrix_max_size,
+        indexed_dag.get_matrix_density().unwrap()
+    );
+
+    let densities = indexed_dag.get_matrix_densities().unwrap();
+
+    if let Some(max_density) = densities.last() {
+        eprintln!(
+            " - Matrix densities: {:.3} for the median matrix, {:.3} at most",
+            densities[densities.len() / 2],
+            max_density
+        );
+    }
+
+    let (dag_mem_max, dag_mem, matrices_mem, jump_level_mem) =
+        indexed_dag.get_memory_usage().unwrap();
+    eprintln!(
+        " - Memory: {} bytes in total, {} for the DAG ({} before truncation), {} for matrices, {} for jump levels",
+        dag_mem + matrices_mem + jump_level_mem,
+        dag_mem,
+        dag_mem_max,
+        matrices_mem,
+        jump_level_mem
+    );
+}
+
+/// Enumerate the matches of an automaton spanning at most `window` bytes of a
+/// text, by indexing windows of the text one after the other.
+fn match_windows<'t>(
+    automaton: &Automaton,
+    text: &'t str,
+    window: usize,
+    jump_distance: usize,
+    trimming_strategy: TrimmingStrategy,
+) -> Vec<Mapping<'t>> {
+    let schema: Vec<String> = (automaton.get_variables().iter())
+        .map(|var| var.get_name().to_string())
+        .collect();
+
+    let mut stream = SlidingWindow::new(automaton.clone(), window)
+        .with_index_options(jump_distance, trimming_strategy);
+    let mut found = Vec::new();
+    let mut push_match = |offset: usize, mapping: &Mapping| {
+        let groups = (mapping.iter_groups())
+            .map(|(name, range)| (name, offset + range.start..offset + range.end));
+        found.push(Mapping::from_groups(text, &schema, groups));
+    };
+
+    let mut start = 0;
+
+    while start < text.len() {
+        let mut end = std::cmp::min(start + window, text.len());
+
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+
+        stream.push(&text[start..end], &mut push_match);
+        start = end;
+    }
+
+    stream.finish(&mut push_match);
+    found
+}
+
+/// Abort once the memory budget is exceeded by the index, unless the naive
+/// algorithm is used instead.
+fn exit_on_memory_budget(max_memory: usize, naive_fallback: bool) {
+    if naive_fallback {
+        eprintln!("Memory budget exceeded, falling back to the naive algorithm.");
+        return;
+    }
+
+    eprintln!(
+        "Memory budget of {} bytes exceeded while indexing the text, --auto-degrade \
+         or --naive-fallback may help.",
+        max_memory
+    );
+    std::process::exit(1);
+}
+
+/// Display the memory used by the index at some stage, with the peak heap
+/// usage since the start of the preprocessing.
+fn print_memory_stats(stage: &str, indexed_dag: &IndexedDag, memory: usize) {
+    eprintln!("===== Memory {} =====", stage);
+
+    match indexed_dag.get_memory_usage() {
+        Some((dag_mem_max, dag_mem, matrices_mem, jump_level_mem)) => {
+            eprintln!(" - DAG: {} bytes ({} before truncation)", dag_mem, dag_mem_max);
+            eprintln!(" - Matrices: {} bytes", matrices_mem);
+            eprintln!(" - Jump levels: {} bytes", jump_level_mem);
+            eprintln!(" - Total: {} bytes", dag_mem + matrices_mem + jump_level_mem);
+        }
+        None => eprintln!(" - Index: not built, the text has no match"),
+    }
+
+    if allocator::is_enabled() {
+        eprintln!(" - Peak heap usage: {} bytes", allocator::phase_peak(memory));
+    } else {
+        eprintln!(" - Peak heap usage: not tracked, build with the alloc-stats feature");
+    }
+}
+
+/// Create the CSV file where raw delay samples are written, if requested.
+fn create_delay_samples_file(matches: &clap::ArgMatches) -> Option<File> {
+    matches.value_of("delay_samples").map(|filename| {
+        let mut file = File::create(filename).expect("Could not create the delay samples file.");
+        writeln!(file, "{}", benchmark::DELAY_SAMPLES_HEADER).unwrap();
+        file
+    })
+}
+
+/// Read the documents of the files given as input, directories are read
+/// recursively and STDIN is read if no file is given. Each document is given
+/// with its name, and the files which could not be read are reported in
+/// `failures`.
+fn read_documents<'a>(
+    paths: &[&Path],
+    failures: &'a mut Vec<(String, io::Error)>,
+) -> impl Iterator<Item = (String, String)> + 'a {
+    let files = recipe::collect_files(paths).unwrap();
+
+    // Inputs are only read once a worker is available for them.
+    let stdin_input = if files.is_empty() { Some("-".to_string()) } else { None };
+
+    let stdin_inputs = stdin_input.into_iter().map(|filename| {
+        let mut text = String::new();
+        let result = stdin().read_to_string(&mut text).map(|_| text);
+        (filename, result)
+    });
+
+    let file_inputs = files.into_iter().map(|file| {
+        let mut text = String::new();
+        let result = File::open(&file)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map(|_| text);
+        (file.display().to_string(), result)
+    });
+
+    stdin_inputs
+        .chain(file_inputs)
+        .filter_map(move |(filename, text)| match text {
+            Ok(mut text) => {
+                trim_trailing_newlines(&mut text);
+                Some((filename, text))
+            }
+            Err(err) => {
+                failures.push((filename, err));
+                None
+            }
+        })
+}
+
+/// Exit with an error if some input files could not be read.
+fn exit_on_read_failures(failures: &[(String, io::Error)]) {
+    if !failures.is_empty() {
+        eprintln!("{} file(s) could not be read:", failures.len());
+
+        for (filename, err) in failures {
+            eprintln!("  {}: {}", filename, err);
+        }
+
+        std::process::exit(1);
+    }
+}
+
+/// Remove trailing newlines, which may be CRLF on Windows.
+fn trim_trailing_newlines(text: &mut String) {
+    while text.ends_with('\n') {
+        text.pop();
+
+        if text.ends_with('\r') {
+            text.pop();
+        }
+    }
+}
+
+/// Parse a value of the form `GROUP=N`, as given to --weight, --priority and
+/// the scores of groups.
+fn parse_group_value<T: std::str::FromStr>(value: &str) -> (&str, T) {
+    let (group, n) = match value.split_once('=') {
+        Some(pair) => pair,
+        None => panic!("Expected a value of the form GROUP=N: {}", value),
+    };
+
+    match n.parse::<T>() {
+        Ok(n) => (group, n),
+        Err(_) => panic!("Not a number: {}", n),
+    }
+}
+
+/// Parse a bound on the length of spans, of the form `N` for the main span or
+/// `GROUP=N` for a group.
+fn parse_length_bound(value: &str) -> (Option<&str>, usize) {
+    if value.contains('=') {
+        let (group, len) = parse_group_value(value);
+        (Some(group), len)
+    } else {
+        match value.parse::<usize>() {
+            Ok(len) => (None, len),
+            Err(_) => panic!("Not a number: {}", value),
+        }
+    }
+}
+
+/// Replace the escape sequences \n, \r, \t, \f and \\ of a string given on
+/// the command line by the characters they stand for.
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('f') => result.push('\x0c'),
+            Some('\\') => result.push('\\'),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+/// Represent a mapping as a JSON object holding the span and text of each
+/// group, offsets are also given in another unit if a table is provided.
+fn mapping_to_json(
+    mapping: &Mapping,
+    text: &str,
+    offset_table: Option<&OffsetTable>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut groups = serde_json::Map::new();
+
+    for (name, range) in mapping.iter_groups() {
+        let mut group = serde_json::Map::new();
+        group.insert("start".to_string(), range.start.into());
+        group.insert("end".to_string(), range.end.into());
+
+        if let Some(offset_table) = offset_table {
+            let unit = offset_table.get_unit();
+            group.insert(format!("start_{}", unit), offset_table.convert(range.start).into());
+            group.insert(format!("end_{}", unit), offset_table.convert(range.end).into());
+        }
+
+        group.insert("text".to_string(), text[range].into());
+        groups.insert(name.to_string(), group.into());
+    }
+
+    groups
+}
+
+/// Give a null value in the JSON object of a mapping to the groups of the
+/// schema it leaves unbound, such that all objects have the same keys.
+fn with_nulls(
+    mut groups: serde_json::Map<String, serde_json::Value>,
+    schema: &[String],
+) -> serde_json::Map<String, serde_json::Value> {
+    for name in schema {
+        groups.entry(name.clone()).or_insert(serde_json::Value::Null);
+    }
+
+    groups
+}
+
+/// Serialize the JSON object of a mapping with the groups of the schema first,
+/// in its order, as the objects of serde_json are sorted by key.
+fn json_in_order(groups: &serde_json::Map<String, serde_json::Value>, schema: &[String]) -> String {
+    let keys = (schema.iter())
+        .filter(|name| groups.contains_key(name.as_str()))
+        .chain(groups.keys().filter(|key| !schema.contains(key)));
+
+    let fields: Vec<String> = keys
+        .map(|key| format!("{}:{}", serde_json::Value::from(key.as_str()), groups[key]))
+        .collect();
+
+    format!("{{{}}}", fields.join(","))
+}
+
+/// Add the line and column of the start and end of each group to the JSON
+/// object of a mapping.
+fn with_line_columns(
+    mut groups: serde_json::Map<String, serde_json::Value>,
+    mapping: &Mapping,
+    line_index: &LineIndex,
+) -> serde_json::Map<String, serde_json::Value> {
+    for (name, range) in mapping.iter_groups() {
+        if let Some(serde_json::Value::Object(group)) = groups.get_mut(name) {
+            let (start_line, start_column) = line_index.position(range.start);
+            let (end_line, end_column) = line_index.position(range.end);
+            group.insert("start_line".to_string(), start_line.into());
+            group.insert("start_column".to_string(), start_column.into());
+            group.insert("end_line".to_string(), end_line.into());
+            group.insert("end_column".to_string(), end_column.into());
+        }
+    }
+
+    groups
+}
+
+/// Description of the capabilities of this build, for scripts that need to
+/// check them before running the tool.
+fn build_report() -> serde_json::Value {
+    let names = |syntax: &[(&'static str, &str)]| -> Vec<&'static str> {
+        syntax.iter().map(|(name, _)| *name).collect()
+    };
+
+    serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "features": {
+            "simd": matrix::simd::kernel_name(),
+            "serde": true,
+            "parallel": cfg!(feature = "parallel"),
+            "alloc_stats": allocator::is_enabled(),
+            "mmap_levels": cfg!(all(unix, feature = "mmap-levels")),
+        },
+        "defaults": {
+            "algorithm": "icdt19",
+            "construction": "glushkov",
+            "trimming": "full",
+            "trimming_threshold": 32,
+            "jump_distance": 1,
+            "jump_schedule": "uniform",
+        },
+        "algorithms": Algorithm::ALL.iter().map(Algorithm::get_name).collect::<Vec<_>>(),
+        "constructions": ["glushkov", "thompson"],
+        "trimming": ["full", "partial", "no", "adaptive", "lazy"],
+        "jump_schedules": ["uniform", "exponential"],
+        "regex": {
+            "supported": names(&regex::SUPPORTED_SYNTAX),
+            "unsupported": names(&regex::UNSUPPORTED_SYNTAX),
+        },
+    })
+}
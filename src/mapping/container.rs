@@ -0,0 +1,178 @@
+//   ____            _        _
+//  / ___|___  _ __ | |_ __ _(_)_ __   ___ _ __
+// | |   / _ \| '_ \| __/ _` | | '_ \ / _ \ '__|
+// | |__| (_) | | | | || (_| | | | | |  __/ |
+//  \____\___/|_| |_|\__\__,_|_|_| |_|\___|_|
+//
+
+use std::mem;
+
+/// Set of vertices of a level, laid out as the containers of Roaring bitmaps:
+/// a sorted array of vertices while the level is sparse, or a bitmap when the
+/// array would take more space than the bitmap.
+#[derive(Clone)]
+pub enum Container {
+    Array(Vec<u32>),
+    Bitmap(Box<[u32]>),
+}
+
+impl Container {
+    pub fn new() -> Container {
+        Container::Array(Vec::new())
+    }
+
+    /// Build the smallest container holding the vertices of a bitmap.
+    pub fn from_words(words: &[u32]) -> Container {
+        let len: usize = words.iter().map(|word| word.count_ones() as usize).sum();
+
+        if len > words.len() {
+            return Container::Bitmap(words.into());
+        }
+
+        let mut vertices = Vec::with_capacity(len);
+
+        for (i, &word) in words.iter().enumerate() {
+            let mut word = word;
+
+            while word != 0 {
+                vertices.push(32 * i as u32 + word.trailing_zeros());
+                word &= word - 1;
+            }
+        }
+
+        Container::Array(vertices)
+    }
+
+    /// Write the vertices of the container into a bitmap.
+    pub fn write_words(&self, words: &mut [u32]) {
+        match self {
+            Container::Array(vertices) => {
+                for word in words.iter_mut() {
+                    *word = 0;
+                }
+
+                for &vertex in vertices {
+                    words[vertex as usize / 32] |= 1 << (vertex % 32);
+                }
+            }
+            Container::Bitmap(bitmap) => words.copy_from_slice(bitmap),
+        }
+    }
+
+    /// Number of vertices in the container.
+    pub fn len(&self) -> usize {
+        match self {
+            Container::Array(vertices) => vertices.len(),
+            Container::Bitmap(bitmap) => bitmap.iter().map(|word| word.count_ones() as usize).sum(),
+        }
+    }
+
+    /// Insert a vertex in the container, where `num_words` is the size of a
+    /// bitmap that can hold any vertex.
+    pub fn insert(&mut self, vertex: usize, num_words: usize) {
+        match self {
+            Container::Array(vertices) => {
+                if let Err(pos) = vertices.binary_search(&(vertex as u32)) {
+                    vertices.insert(pos, vertex as u32);
+                }
+
+                // The array may allocate more than its length as it grows.
+                if vertices.capacity() > num_words {
+                    let mut bitmap = vec![0; num_words];
+                    self.write_words(&mut bitmap);
+                    *self = Container::Bitmap(bitmap.into_boxed_slice());
+                }
+            }
+            Container::Bitmap(bitmap) => bitmap[vertex / 32] |= 1 << (vertex % 32),
+        }
+    }
+
+    /// Memory allocated on the heap for the container.
+    pub fn get_memory_usage(&self) -> usize {
+        match self {
+            Container::Array(vertices) => vertices.capacity() * 4,
+            Container::Bitmap(bitmap) => bitmap.len() * 4,
+        }
+    }
+}
+
+/// Sequence of containers, which keeps track of the memory they allocate.
+pub struct Containers {
+    containers: Vec<Container>,
+    heap_usage: usize,
+}
+
+impl Containers {
+    /// Split a sequence of bitmaps of `num_words` words into containers.
+    pub fn from_words(words: &[u32], num_words: usize) -> Containers {
+        let containers: Vec<_> = words.chunks(num_words).map(Container::from_words).collect();
+        let heap_usage = containers.iter().map(Container::get_memory_usage).sum();
+
+        Containers {
+            containers,
+            heap_usage,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.containers.len()
+    }
+
+    pub fn get(&self, index: usize) -> &Container {
+        &self.containers[index]
+    }
+
+    pub fn set(&mut self, index: usize, container: Container) {
+        self.heap_usage += container.get_memory_usage();
+        self.heap_usage -= self.containers[index].get_memory_usage();
+        self.containers[index] = container;
+    }
+
+    pub fn insert(&mut self, index: usize, vertex: usize, num_words: usize) {
+        let container = &mut self.containers[index];
+        self.heap_usage -= container.get_memory_usage();
+        container.insert(vertex, num_words);
+        self.heap_usage += container.get_memory_usage();
+    }
+
+    /// Shorten or extend the sequence with empty containers.
+    pub fn resize(&mut self, len: usize) {
+        for container in self.containers.iter().skip(len) {
+            self.heap_usage -= container.get_memory_usage();
+        }
+
+        self.containers.resize_with(len, Container::new);
+
+        if self.containers.capacity() > len {
+            self.containers.shrink_to_fit();
+        }
+    }
+
+    pub fn get_memory_usage(&self) -> usize {
+        self.containers.capacity() * mem::size_of::<Container>() + self.heap_usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smallest_container() {
+        let num_words = 10;
+        let mut container = Container::new();
+
+        for vertex in 0..32 * num_words {
+            container.insert(vertex, num_words);
+
+            assert!(container.get_memory_usage() <= 4 * num_words);
+            assert_eq!(container.len(), vertex + 1);
+        }
+
+        assert!(matches!(container, Container::Bitmap(_)));
+
+        let mut words = vec![0; num_words];
+        words[3] = 0b101;
+        assert!(matches!(Container::from_words(&words), Container::Array(_)));
+    }
+}
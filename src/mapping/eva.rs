@@ -0,0 +1,887 @@
+//  ____       _                      _       _     _   _      _____
+// |  _ \  ___| |_ ___ _ __ _ __ ___ (_)_ __ (_)___| |_(_) ___| ____|_   ____ _
+// | | | |/ _ \ __/ _ \ '__| '_ ` _ \| | '_ \| / __| __| |/ __|  _| \ \ / / _` |
+// | |_| |  __/ ||  __/ |  | | | | | | | | | | \__ \ |_| | (__| |___ \ V / (_| |
+// |____/ \___|\__\___|_|  |_| |_| |_|_|_| |_|_|___/\__|_|\___|_____| \_/ \__,_|
+//
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use bit_set::BitSet;
+
+use super::super::automaton::Automaton;
+use super::{EnumeratorStats, ExactIter, Mapping, Marker, SpannerEnumerator};
+
+/// Index of the node or list end which doesn't exist.
+const NONE: usize = usize::MAX;
+
+/// Enumerate the matches of a variable automaton over a text with the
+/// algorithm of Florenzano, Riveros, Ugarte, Vansummeren and Vrgoč (PODS
+/// 2018).
+///
+/// The automaton is turned on the fly into a deterministic extended automaton,
+/// whose transitions either read a character or assign a set of markers at
+/// once. A single pass over the text then builds, for each state, a list of
+/// nodes representing the distinct runs reaching it. As runs of a
+/// deterministic automaton produce distinct outputs, the mappings are then
+/// enumerated from the lists of final states with constant delay and without
+/// any duplicate check.
+///
+/// When an `Order` is given, the mappings are instead ranked: the
+/// preprocessing also stores the best score of the runs following each node,
+/// which bounds exactly the score of any mapping extending a partial run.
+pub struct DeterministicEva<'t> {
+    automaton: Automaton,
+    text: &'t str,
+    num_vars: usize,
+
+    /// States of the deterministic automaton as sets of states of the variable
+    /// automaton.
+    states: Vec<BitSet>,
+    state_ids: HashMap<BitSet, usize>,
+    /// Target of the reading transitions computed so far.
+    reads: HashMap<(usize, char), Option<usize>>,
+    /// Marker set and target of the assignation transitions of each state.
+    captures: Vec<Option<Vec<(usize, usize)>>>,
+    /// Sets of markers labeling the assignation transitions.
+    marker_sets: Vec<Vec<Marker>>,
+    marker_set_ids: HashMap<Vec<usize>, usize>,
+
+    /// Nodes built by the preprocessing, the first one ends all runs.
+    nodes: Vec<Node>,
+    /// List of nodes of each final state once the text is read.
+    finals: Vec<List>,
+
+    /// Order ranking the mappings, if any.
+    order: Option<Order>,
+    /// Maximal number of mappings enumerated.
+    top_k: Option<usize>,
+    /// Weight of each marker at position `pos` as `a * pos + b`, indexed by
+    /// the id of the marker.
+    marker_weights: Vec<(i64, i64)>,
+    /// Best score of the runs following each node, only stored when the
+    /// mappings are ranked.
+    best: Vec<i64>,
+}
+
+/// Order in which ranked mappings are enumerated.
+#[derive(Clone, Debug)]
+pub enum Order {
+    /// By non-increasing weight.
+    Weight(Weights),
+    /// By increasing length of their main span.
+    ShortestFirst,
+    /// By decreasing length of their main span.
+    LongestFirst,
+}
+
+impl Order {
+    /// Best score of the bottom node, which ends all runs.
+    ///
+    /// Scores of nodes are the best weight of the runs following them when
+    /// ranking by weight. Otherwise they are the earliest start of these runs
+    /// when looking for the longest spans, negated to be maximized, and the
+    /// latest start when looking for the shortest spans. Lists take the
+    /// maximal score of their nodes, thus the score of the bottom node is
+    /// chosen for its lists to tell whether it is one of their nodes.
+    fn bottom_best(&self) -> i64 {
+        match self {
+            Order::Weight(_) => 0,
+            Order::ShortestFirst => i64::MAX,
+            Order::LongestFirst => i64::MIN,
+        }
+    }
+
+    /// Score of the runs starting with a node at `pos`.
+    fn start_best(&self, pos: usize) -> i64 {
+        match self {
+            Order::Weight(_) => 0,
+            Order::ShortestFirst => pos as i64,
+            Order::LongestFirst => -(pos as i64),
+        }
+    }
+
+    /// Weight of the runs ending with a node at `pos`, which adds up with
+    /// the best score of the runs following this node to their priority.
+    fn end_weight(&self, pos: usize) -> i64 {
+        match self {
+            Order::Weight(_) => 0,
+            Order::ShortestFirst => -(pos as i64),
+            Order::LongestFirst => pos as i64,
+        }
+    }
+}
+
+/// Weights of the groups, ranking a mapping by the sum over its bound groups
+/// of the length of their span in bytes times their weight, plus their
+/// priority.
+#[derive(Clone, Debug, Default)]
+pub struct Weights {
+    lengths: HashMap<String, i64>,
+    priorities: HashMap<String, i64>,
+}
+
+impl Weights {
+    pub fn new() -> Weights {
+        Weights::default()
+    }
+
+    /// Add `weight` for each byte of the span of a group.
+    pub fn with_length_weight(mut self, group: &str, weight: i64) -> Weights {
+        self.lengths.insert(group.to_string(), weight);
+        self
+    }
+
+    /// Add `priority` to the mappings binding a group.
+    pub fn with_priority(mut self, group: &str, priority: i64) -> Weights {
+        self.priorities.insert(group.to_string(), priority);
+        self
+    }
+
+    pub fn get_length_weight(&self, group: &str) -> i64 {
+        self.lengths.get(group).copied().unwrap_or(0)
+    }
+
+    pub fn get_priority(&self, group: &str) -> i64 {
+        self.priorities.get(group).copied().unwrap_or(0)
+    }
+
+    /// Weight of a mapping.
+    pub fn weight(&self, mapping: &Mapping) -> i64 {
+        mapping
+            .iter_groups()
+            .map(|(group, range)| {
+                self.get_length_weight(group) * (range.end - range.start) as i64
+                    + self.get_priority(group)
+            })
+            .sum()
+    }
+
+    /// Weight of a marker at position `pos` as `a * pos + b`, the weights of
+    /// the markers of a mapping sum up to its weight.
+    fn marker_weight(&self, marker: &Marker) -> (i64, i64) {
+        let group = marker.variable().get_name();
+
+        match marker {
+            Marker::Open(_) => (-self.get_length_weight(group), self.get_priority(group)),
+            Marker::Close(_) => (self.get_length_weight(group), 0),
+        }
+    }
+}
+
+/// Assignation of a set of markers at a position in the text, which follows
+/// any of the runs in `list`.
+struct Node {
+    marker_set: usize,
+    pos: usize,
+    list: List,
+    /// Next node of the list holding this node.
+    next: usize,
+}
+
+/// List of nodes linked by their field `next`, from `start` to `end`, along
+/// with the best weight and the number of the runs following its nodes.
+///
+/// Lists are copied by value and share their nodes: appending a list only
+/// links the last node of the other list, which is never read by the copies
+/// that end at this node.
+#[derive(Clone, Copy)]
+struct List {
+    start: usize,
+    end: usize,
+    best: i64,
+    /// Number of runs following the nodes, saturating at `u128::MAX`.
+    runs: u128,
+}
+
+impl List {
+    const EMPTY: List = List {
+        start: NONE,
+        end: NONE,
+        best: i64::MIN,
+        runs: 0,
+    };
+
+    fn is_empty(&self) -> bool {
+        self.start == NONE
+    }
+
+    /// Add a node at the start of the list, the best run following it has
+    /// weight `best`.
+    fn push(&mut self, nodes: &mut [Node], node: usize, best: i64) {
+        nodes[node].next = self.start;
+        self.start = node;
+        self.best = self.best.max(best);
+
+        // The first node ends exactly one run.
+        let runs = if node == 0 { 1 } else { nodes[node].list.runs };
+        self.runs = self.runs.saturating_add(runs);
+
+        if self.end == NONE {
+            self.end = node;
+        }
+    }
+
+    /// Add the nodes of another list at the end of the list.
+    fn append(&mut self, nodes: &mut [Node], other: List) {
+        if self.is_empty() {
+            *self = other;
+        } else if !other.is_empty() {
+            nodes[self.end].next = other.start;
+            self.end = other.end;
+            self.best = self.best.max(other.best);
+            self.runs = self.runs.saturating_add(other.runs);
+        }
+    }
+
+    /// Iterate over the nodes of the list.
+    fn iter<'n>(&self, nodes: &'n [Node]) -> impl Iterator<Item = usize> + 'n {
+        let end = self.end;
+        let mut node = self.start;
+
+        std::iter::from_fn(move || {
+            if node == NONE {
+                return None;
+            }
+
+            let curr = node;
+            node = if curr == end { NONE } else { nodes[curr].next };
+            Some(curr)
+        })
+    }
+}
+
+impl<'t> DeterministicEva<'t> {
+    pub fn new(automaton: Automaton, text: &'t str) -> DeterministicEva<'t> {
+        DeterministicEva {
+            num_vars: automaton.num_vars(),
+            automaton,
+            text,
+            states: Vec::new(),
+            state_ids: HashMap::new(),
+            reads: HashMap::new(),
+            captures: Vec::new(),
+            marker_sets: Vec::new(),
+            marker_set_ids: HashMap::new(),
+            nodes: Vec::new(),
+            finals: Vec::new(),
+            order: None,
+            top_k: None,
+            marker_weights: Vec::new(),
+            best: Vec::new(),
+        }
+    }
+
+    /// Enumerate the mappings in the given order.
+    pub fn with_order(mut self, order: Order) -> DeterministicEva<'t> {
+        self.order = Some(order);
+        self
+    }
+
+    /// Enumerate the mappings by non-increasing weight.
+    pub fn with_weights(self, weights: Weights) -> DeterministicEva<'t> {
+        self.with_order(Order::Weight(weights))
+    }
+
+    /// Only enumerate the `k` first mappings, which are the `k` best ones
+    /// when they are ranked.
+    pub fn with_top_k(mut self, k: usize) -> DeterministicEva<'t> {
+        self.top_k = Some(k);
+        self
+    }
+
+    /// Iterate over the mappings along with their weight, or the length of
+    /// their main span when they are ranked by length, from the best one.
+    /// The k best mappings are thus given by the k first items, and the
+    /// delay between two items is logarithmic in the number of mappings
+    /// already enumerated.
+    pub fn iter_ranked<'i>(&'i self) -> impl Iterator<Item = (i64, Mapping<'t>)> + 'i {
+        let order = self
+            .order
+            .as_ref()
+            .expect("Mappings can only be ranked when an order is given");
+
+        let mut iterator = RankedIterator {
+            eva: self,
+            order,
+            heap: BinaryHeap::new(),
+            num_candidates: 0,
+        };
+
+        for list in &self.finals {
+            for node in list.iter(&self.nodes) {
+                let weight = order.end_weight(self.nodes[node].pos);
+                iterator.push(weight, node, None);
+            }
+        }
+
+        iterator
+    }
+
+    /// Number of states of the deterministic automaton explored while reading
+    /// the text.
+    pub fn num_states(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Number of nodes built by the preprocessing.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of runs reaching a final state once the text is read, which
+    /// are as many as the mappings, saturating at `u128::MAX`.
+    fn num_runs(&self) -> u128 {
+        self.finals.iter().fold(0, |count: u128, list| count.saturating_add(list.runs))
+    }
+
+    /// Rough estimation of the memory used by the nodes, which make most of
+    /// the index.
+    pub fn get_memory_usage(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node>()
+            + self.finals.capacity() * std::mem::size_of::<List>()
+            + self.best.capacity() * std::mem::size_of::<i64>()
+    }
+
+    fn get_state(&mut self, states: BitSet) -> usize {
+        if let Some(&id) = self.state_ids.get(&states) {
+            return id;
+        }
+
+        let id = self.states.len();
+        self.states.push(states.clone());
+        self.state_ids.insert(states, id);
+        self.captures.push(None);
+        id
+    }
+
+    /// Target of the transition reading a character from a state.
+    fn read(&mut self, state: usize, x: char) -> Option<usize> {
+        if let Some(&target) = self.reads.get(&(state, x)) {
+            return target;
+        }
+
+        let adj = self.automaton.get_adj_for_char(x);
+        let mut targets = BitSet::new();
+
+        for source in self.states[state].iter() {
+            for &target in &adj[source] {
+                targets.insert(target);
+            }
+        }
+
+        let target = if targets.is_empty() {
+            None
+        } else {
+            Some(self.get_state(targets))
+        };
+
+        self.reads.insert((state, x), target);
+        target
+    }
+
+    /// Transitions assigning a non-empty set of markers from a state, each of
+    /// them follows a path of assignations in the variable automaton.
+    fn capture(&mut self, state: usize) -> &[(usize, usize)] {
+        if self.captures[state].is_none() {
+            let assignations = self.automaton.get_assignations();
+            let mut targets: BTreeMap<Vec<usize>, (Vec<Marker>, BitSet)> = BTreeMap::new();
+            let mut stack: Vec<_> = self.states[state].iter().map(|s| (s, Vec::new())).collect();
+
+            while let Some((source, markers)) = stack.pop() {
+                for (label, target) in &assignations[source] {
+                    let mut markers: Vec<Marker> = markers.clone();
+                    markers.push(label.get_marker().unwrap().clone());
+
+                    let mut ids: Vec<usize> = markers.iter().map(Marker::get_id).collect();
+                    ids.sort_unstable();
+
+                    targets
+                        .entry(ids)
+                        .or_insert_with(|| (markers.clone(), BitSet::new()))
+                        .1
+                        .insert(*target);
+
+                    stack.push((*target, markers));
+                }
+            }
+
+            let mut captures = Vec::with_capacity(targets.len());
+
+            for (ids, (markers, targets)) in targets {
+                let marker_set = match self.marker_set_ids.get(&ids) {
+                    Some(&id) => id,
+                    None => {
+                        self.marker_sets.push(markers);
+                        self.marker_set_ids.insert(ids, self.marker_sets.len() - 1);
+                        self.marker_sets.len() - 1
+                    }
+                };
+
+                captures.push((marker_set, self.get_state(targets)));
+            }
+
+            self.captures[state] = Some(captures);
+        }
+
+        self.captures[state].as_ref().unwrap()
+    }
+
+    /// Number of mappings, computed by counting the runs reaching each state
+    /// while reading the text, without building the nodes nor enumerating
+    /// the mappings, unlike `SpannerEnumerator::count` which needs the index.
+    pub fn count_runs(&mut self) -> u128 {
+        let add = |count: &mut u128, other: u128| {
+            *count = count
+                .checked_add(other)
+                .expect("The number of mappings overflows 128 bits");
+        };
+
+        let initial = self.get_state(Some(self.automaton.get_initial()).into_iter().collect());
+        let mut counts = vec![0; self.states.len()];
+        let mut active = vec![initial];
+        counts[initial] = 1;
+
+        let positions = self.text.chars().map(Some);
+
+        for x in positions.chain(Some(None)) {
+            let snapshots: Vec<_> = active.iter().map(|&state| (state, counts[state])).collect();
+
+            for (state, snapshot) in snapshots {
+                for i in 0..self.capture(state).len() {
+                    let (_, target) = self.capture(state)[i];
+                    counts.resize(self.states.len(), 0);
+
+                    if counts[target] == 0 {
+                        active.push(target);
+                    }
+
+                    add(&mut counts[target], snapshot);
+                }
+            }
+
+            let x = match x {
+                Some(x) => x,
+                None => break,
+            };
+
+            let mut next_counts = vec![0; self.states.len()];
+            let mut next_active = Vec::new();
+
+            for state in active {
+                if let Some(target) = self.read(state, x) {
+                    next_counts.resize(self.states.len(), 0);
+
+                    if next_counts[target] == 0 {
+                        next_active.push(target);
+                    }
+
+                    add(&mut next_counts[target], counts[state]);
+                }
+            }
+
+            counts = next_counts;
+            active = next_active;
+        }
+
+        let mut total = 0;
+
+        for state in active {
+            if !self.states[state].is_disjoint(&self.automaton.finals) {
+                add(&mut total, counts[state]);
+            }
+        }
+
+        total
+    }
+
+    /// Weight of the markers of a node.
+    fn node_weight(&self, marker_set: usize, pos: usize) -> i64 {
+        self.marker_sets[marker_set]
+            .iter()
+            .map(|marker| {
+                let (a, b) = self.marker_weights[marker.get_id()];
+                a * pos as i64 + b
+            })
+            .sum()
+    }
+}
+
+impl<'t> SpannerEnumerator<'t> for DeterministicEva<'t> {
+    fn iter<'i>(&'i self) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i> {
+        let top_k = self.top_k.unwrap_or(usize::MAX);
+        let count = usize::try_from(self.num_runs()).ok();
+
+        let iterator: Box<dyn Iterator<Item = Mapping<'t>> + 'i> = match &self.order {
+            Some(_) => Box::new(self.iter_ranked().map(|(_, mapping)| mapping)),
+            None => Box::new(DeterministicEvaIterator {
+                eva: self,
+                finals: self.finals.clone(),
+                stack: Vec::new(),
+                to_skip: 0,
+            }),
+        };
+
+        match count {
+            Some(count) => Box::new(ExactIter::new(iterator, count).take(top_k)),
+            None => Box::new(iterator.take(top_k)),
+        }
+    }
+
+    /// Skip whole lists of runs whose number is known from the
+    /// preprocessing, unless the mappings are ranked.
+    fn iter_range<'i>(
+        &'i self,
+        offset: usize,
+        limit: usize,
+    ) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i>
+    where
+        't: 'i,
+    {
+        if self.order.is_some() {
+            return Box::new(self.iter().skip(offset).take(limit));
+        }
+
+        let top_k = self.top_k.unwrap_or(usize::MAX);
+        let limit = limit.min(top_k.saturating_sub(offset));
+        let iterator = DeterministicEvaIterator {
+            eva: self,
+            finals: self.finals.clone(),
+            stack: Vec::new(),
+            to_skip: offset as u128,
+        };
+
+        match usize::try_from(self.num_runs()) {
+            Ok(count) => {
+                Box::new(ExactIter::new(iterator, count.saturating_sub(offset)).take(limit))
+            }
+            Err(_) => Box::new(iterator.take(limit)),
+        }
+    }
+
+    /// Number of mappings, which is known from the preprocessing.
+    fn count(&self) -> u128 {
+        let top_k = self.top_k.map_or(u128::MAX, |top_k| top_k as u128);
+        self.num_runs().min(top_k)
+    }
+
+    fn stats(&self) -> EnumeratorStats {
+        EnumeratorStats {
+            preprocess_time: None,
+            memory_usage: Some(self.get_memory_usage()),
+            index_size: Some(self.num_nodes()),
+        }
+    }
+
+    /// Read the text once, building the lists of runs reaching each state.
+    fn preprocess(&mut self) {
+        self.nodes = vec![Node {
+            marker_set: NONE,
+            pos: 0,
+            list: List::EMPTY,
+            next: NONE,
+        }];
+        self.finals = Vec::new();
+        self.best = Vec::new();
+
+        let order = self.order.clone();
+        let bottom_best = order.as_ref().map_or(0, Order::bottom_best);
+
+        if let Some(order) = &order {
+            if let Order::Weight(weights) = order {
+                self.marker_weights = vec![(0, 0); 2 * self.num_vars];
+
+                for var in self.automaton.get_variables() {
+                    let var = Arc::new(var);
+
+                    for marker in [Marker::Open(var.clone()), Marker::Close(var)] {
+                        self.marker_weights[marker.get_id()] = weights.marker_weight(&marker);
+                    }
+                }
+            }
+
+            self.best.push(bottom_best);
+        }
+
+        let initial = self.get_state(Some(self.automaton.get_initial()).into_iter().collect());
+        let mut lists = vec![List::EMPTY; self.states.len()];
+        let mut active = vec![initial];
+        lists[initial].push(&mut self.nodes, 0, bottom_best);
+
+        let positions = self.text.char_indices().map(|(pos, x)| (pos, Some(x)));
+
+        for (pos, x) in positions.chain(Some((self.text.len(), None))) {
+            // Assign markers, runs that just did can't assign any other
+            // marker before reading the next character.
+            let snapshots: Vec<_> = active.iter().map(|&state| (state, lists[state])).collect();
+
+            for (state, snapshot) in snapshots {
+                for i in 0..self.capture(state).len() {
+                    let (marker_set, target) = self.capture(state)[i];
+
+                    self.nodes.push(Node {
+                        marker_set,
+                        pos,
+                        list: snapshot,
+                        next: NONE,
+                    });
+
+                    lists.resize(self.states.len(), List::EMPTY);
+
+                    if lists[target].is_empty() {
+                        active.push(target);
+                    }
+
+                    let node = self.nodes.len() - 1;
+                    let mut best = 0;
+
+                    if let Some(order) = &order {
+                        best = match order {
+                            Order::Weight(_) => self.node_weight(marker_set, pos) + snapshot.best,
+                            _ if snapshot.best == bottom_best => order.start_best(pos),
+                            _ => snapshot.best,
+                        };
+                        self.best.push(best);
+                    }
+
+                    lists[target].push(&mut self.nodes, node, best);
+                }
+            }
+
+            let x = match x {
+                Some(x) => x,
+                None => break,
+            };
+
+            // Read the next character.
+            let mut next_lists = vec![List::EMPTY; self.states.len()];
+            let mut next_active = Vec::new();
+
+            for state in active {
+                let list = lists[state];
+
+                if let Some(target) = self.read(state, x) {
+                    next_lists.resize(self.states.len(), List::EMPTY);
+
+                    if next_lists[target].is_empty() {
+                        next_active.push(target);
+                    }
+
+                    next_lists[target].append(&mut self.nodes, list);
+                }
+            }
+
+            lists = next_lists;
+            active = next_active;
+        }
+
+        for state in active {
+            if !self.states[state].is_disjoint(&self.automaton.finals) {
+                self.finals.push(lists[state]);
+            }
+        }
+    }
+}
+
+//  ___ _                 _
+// |_ _| |_ ___ _ __ __ _| |_ ___  _ __
+//  | || __/ _ \ '__/ _` | __/ _ \| '__|
+//  | || ||  __/ | | (_| | || (_) | |
+// |___|\__\___|_|  \__,_|\__\___/|_|
+//
+
+struct DeterministicEvaIterator<'i, 't> {
+    eva: &'i DeterministicEva<'t>,
+    /// Lists of the final states that were not enumerated yet.
+    finals: Vec<List>,
+    /// Current node and end of the list of each node of the run being
+    /// enumerated, from the end of the text.
+    stack: Vec<(usize, usize)>,
+    /// Number of runs left to skip before the first mapping is given.
+    to_skip: u128,
+}
+
+impl<'i, 't> DeterministicEvaIterator<'i, 't> {
+    /// Move to the next run, dropping the nodes of the stack whose list is
+    /// over.
+    fn advance(&mut self) {
+        while let Some((node, end)) = self.stack.pop() {
+            if node != end {
+                self.stack.push((self.eva.nodes[node].next, end));
+                return;
+            }
+        }
+    }
+}
+
+impl<'i, 't> Iterator for DeterministicEvaIterator<'i, 't> {
+    type Item = Mapping<'t>;
+
+    fn next(&mut self) -> Option<Mapping<'t>> {
+        let nodes = &self.eva.nodes;
+
+        loop {
+            if self.stack.is_empty() {
+                let list = self.finals.pop()?;
+                self.stack.push((list.start, list.end));
+            }
+
+            let (node, _) = *self.stack.last().unwrap();
+
+            // Skip all the runs following the node at once if possible.
+            if self.to_skip > 0 {
+                let runs = if node == 0 { 1 } else { nodes[node].list.runs };
+
+                if runs <= self.to_skip {
+                    self.to_skip -= runs;
+                    self.advance();
+                    continue;
+                }
+            }
+
+            // The first node ends the runs, the stack then holds a whole run.
+            if node == 0 {
+                let markers = self.stack[..self.stack.len() - 1].iter().flat_map(|&(node, _)| {
+                    let node = &nodes[node];
+                    let markers = &self.eva.marker_sets[node.marker_set];
+                    markers.iter().map(move |marker| (marker.clone(), node.pos))
+                });
+                let mapping = Mapping::from_markers(self.eva.text, markers, self.eva.num_vars);
+
+                self.advance();
+                return Some(mapping);
+            }
+
+            let list = nodes[node].list;
+            self.stack.push((list.start, list.end));
+        }
+    }
+}
+
+//  ____             _            _
+// |  _ \ __ _ _ __ | | _____  __| |
+// | |_) / _` | '_ \| |/ / _ \/ _` |
+// |  _ < (_| | | | |   <  __/ (_| |
+// |_| \_\__,_|_| |_|_|\_\___|\__,_|
+//
+
+/// Node of a partial run, from the end of the text, shared by the candidates
+/// extending it.
+struct RunNode {
+    node: usize,
+    prev: Option<Rc<RunNode>>,
+}
+
+/// A partial run ending with `node`, whose other nodes have weight `weight`.
+/// Its priority is the score of the best run extending it.
+struct Candidate {
+    priority: i64,
+    /// Order of insertion, which breaks ties in favor of the oldest
+    /// candidate.
+    id: Reverse<usize>,
+    weight: i64,
+    node: usize,
+    run: Option<Rc<RunNode>>,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        (self.priority, self.id).cmp(&(other.priority, other.id))
+    }
+}
+
+/// Enumerate the runs by non-increasing score with a best-first search: as
+/// the priority of a candidate is exactly the score of its best extension,
+/// complete runs are popped from the heap in order.
+struct RankedIterator<'i, 't> {
+    eva: &'i DeterministicEva<'t>,
+    order: &'i Order,
+    heap: BinaryHeap<Candidate>,
+    num_candidates: usize,
+}
+
+impl<'i, 't> RankedIterator<'i, 't> {
+    fn push(&mut self, weight: i64, node: usize, run: Option<Rc<RunNode>>) {
+        // The score of the bottom node depends on the node it follows.
+        let best = match &run {
+            _ if node != 0 => self.eva.best[node],
+            Some(run) => self.order.start_best(self.eva.nodes[run.node].pos),
+            None => -weight,
+        };
+
+        self.heap.push(Candidate {
+            priority: weight + best,
+            id: Reverse(self.num_candidates),
+            weight,
+            node,
+            run,
+        });
+        self.num_candidates += 1;
+    }
+}
+
+impl<'i, 't> Iterator for RankedIterator<'i, 't> {
+    type Item = (i64, Mapping<'t>);
+
+    fn next(&mut self) -> Option<(i64, Mapping<'t>)> {
+        let nodes = &self.eva.nodes;
+
+        loop {
+            let candidate = self.heap.pop()?;
+
+            // The first node ends the runs, the candidate is then complete.
+            if candidate.node == 0 {
+                let mut markers = Vec::new();
+                let mut run = candidate.run.as_ref();
+
+                while let Some(run_node) = run {
+                    let node = &nodes[run_node.node];
+                    let marker_set = &self.eva.marker_sets[node.marker_set];
+                    markers.extend(marker_set.iter().map(|marker| (marker.clone(), node.pos)));
+                    run = run_node.prev.as_ref();
+                }
+
+                let mapping =
+                    Mapping::from_markers(self.eva.text, markers.into_iter(), self.eva.num_vars);
+                let score = match self.order {
+                    Order::ShortestFirst => -candidate.priority,
+                    _ => candidate.priority,
+                };
+
+                return Some((score, mapping));
+            }
+
+            let node = &nodes[candidate.node];
+            let weight = match self.order {
+                Order::Weight(_) => candidate.weight + self.eva.best[candidate.node] - node.list.best,
+                _ => candidate.weight,
+            };
+            let run = Some(Rc::new(RunNode {
+                node: candidate.node,
+                prev: candidate.run,
+            }));
+
+            for next in node.list.iter(nodes) {
+                self.push(weight, next, run.clone());
+            }
+        }
+    }
+}
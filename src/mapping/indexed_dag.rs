@@ -1,12 +1,20 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
 use std::iter;
-
-use super::super::automaton::Automaton;
-use super::super::mapping::{Mapping, Marker, SpannerEnumerator};
-use super::super::progress::Progress;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::super::automaton::{Automaton, Label};
+use super::super::clock::Instant;
+use super::super::mapping::{EnumeratorStats, Mapping, Marker, SpannerEnumerator};
+use super::super::offsets::{OffsetTable, OffsetUnit};
+use super::super::progress::{Phase, PhaseProgress, Progress, ProgressCallback};
 use super::jump::Jump;
 use bit_set::BitSet;
 use serde::{Deserialize, Serialize};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 //  ___           _                   _ ____
 // |_ _|_ __   __| | _____  _____  __| |  _ \  __ _  __ _
@@ -24,12 +32,34 @@ pub struct IndexedDag<'t> {
     automaton: Automaton,
     text: &'t str,
     jump_distance: usize,
+    jump_schedule: JumpSchedule,
     trimming_strategy: TrimmingStrategy,
+    single_pass: bool,
+    mapped_levels: bool,
+    compressed_levels: bool,
+    jump_pointers: bool,
     jump: Option<Jump>,
     toggle_progress: bool,
+    /// Called with the progress of the preprocessing.
+    progress_callback: Option<ProgressCallback<'t>>,
     create_dag_time: Option<Duration>,
     trim_time: Option<Duration>,
     index_time: Option<Duration>,
+    /// Number of levels that went through the backward trimming pass and number
+    /// of levels that were skipped.
+    trim_counts: Option<(usize, usize)>,
+    /// Maximal estimated memory usage in bytes of the index.
+    memory_budget: Option<usize>,
+    memory_budget_exceeded: bool,
+    /// The levels of the DAG alone exceeded the memory budget.
+    levels_budget_exceeded: bool,
+    /// Bounds on the length of spans, enforced during the enumeration.
+    length_bounds: Option<LengthBounds>,
+    /// Conversion of the offsets of spans into the unit they are given in.
+    offsets: OffsetTable,
+    /// Vertices of each level from which no mapping can be completed, found
+    /// during the enumeration with lazy trimming.
+    dead_vertices: RefCell<HashMap<usize, BitSet>>,
 }
 
 #[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Copy)]
@@ -37,29 +67,83 @@ pub enum TrimmingStrategy {
     NoTrimming,
     PartialTrimming,
     FullTrimming,
+    /// Trim the last level and only the levels containing at least the given
+    /// number of vertices, narrower levels are not worth the cost of trimming.
+    AdaptiveTrimming(usize),
+    /// Only trim the last level, dead vertices of other levels are detected
+    /// during the enumeration and cached to never be explored again.
+    LazyTrimming,
+}
+
+/// Choice of the jump targets for which reachability matrices are stored.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum JumpSchedule {
+    /// Targets are kept every `jump_distance` levels.
+    Uniform,
+    /// Targets are spaced exponentially back from each level, which gives
+    /// O(log n) matrices per level and jumps going through O(log n) matrices.
+    /// The jump distance is ignored.
+    Exponential,
 }
 
 impl<'t> IndexedDag<'t> {
-    pub fn new(
-        automaton: Automaton,
-        text: &str,
-        jump_distance: usize,
-        trimming_strategy: TrimmingStrategy,
-        toggle_progress: bool,
-    ) -> IndexedDag {
+    /// Configure an index of a text, all the options have defaults.
+    pub fn builder(automaton: Automaton, text: &'t str) -> IndexedDagBuilder<'t> {
+        IndexedDagBuilder::new(automaton, text)
+    }
+
+    fn new(options: IndexedDagBuilder<'t>) -> IndexedDag<'t> {
         IndexedDag {
-            automaton,
-            text,
-            jump_distance,
-            trimming_strategy,
-            toggle_progress,
+            offsets: OffsetTable::new(options.text, options.offset_unit),
+            automaton: options.automaton,
+            text: options.text,
+            jump_distance: options.jump_distance,
+            jump_schedule: options.jump_schedule,
+            trimming_strategy: options.trimming_strategy,
+            single_pass: options.single_pass,
+            mapped_levels: options.mapped_levels,
+            compressed_levels: options.compressed_levels,
+            jump_pointers: options.jump_pointers,
+            toggle_progress: options.toggle_progress,
+            progress_callback: options.progress_callback,
             jump: None,
             create_dag_time: None,
             trim_time: None,
             index_time: None,
+            trim_counts: None,
+            memory_budget: options.memory_budget,
+            memory_budget_exceeded: false,
+            levels_budget_exceeded: false,
+            length_bounds: options.length_bounds,
+            dead_vertices: RefCell::new(HashMap::new()),
         }
     }
 
+    pub fn get_jump_schedule(&self) -> JumpSchedule {
+        self.jump_schedule
+    }
+
+    /// Unit of the offsets of the spans given by `spans` and `convert_span`.
+    pub fn get_offset_unit(&self) -> OffsetUnit {
+        self.offsets.get_unit()
+    }
+
+    /// Convert a span of a mapping, which is in bytes, into the offset unit of
+    /// the index.
+    pub fn convert_span(&self, span: Range<usize>) -> Range<usize> {
+        self.offsets.convert(span.start)..self.offsets.convert(span.end)
+    }
+
+    pub fn is_memory_budget_exceeded(&self) -> bool {
+        self.memory_budget_exceeded
+    }
+
+    /// Check if the memory budget was exceeded by the levels of the DAG
+    /// alone, which a larger jump distance doesn't help with.
+    pub fn is_levels_budget_exceeded(&self) -> bool {
+        self.levels_budget_exceeded
+    }
+
     pub fn num_levels(&self) -> Option<usize> {
         self.jump.as_ref().map(|j| j.num_levels())
     }
@@ -68,6 +152,12 @@ impl<'t> IndexedDag<'t> {
         (self.create_dag_time, self.trim_time, self.index_time)
     }
 
+    /// Number of levels that were trimmed and skipped by the backward trimming
+    /// pass.
+    pub fn get_trim_counts(&self) -> Option<(usize, usize)> {
+        self.trim_counts
+    }
+
     fn next_level<'a>(&'a self, gamma: BitSet) -> NextLevelIterator<'a> {
         let adj = self.automaton.get_rev_assignations();
 
@@ -95,9 +185,70 @@ impl<'t> IndexedDag<'t> {
             }
         }
 
+        expected_markers.sort_by_key(|marker| marker.get_id());
         NextLevelIterator::explore(&self.automaton, expected_markers, gamma)
     }
 
+    /// Number of mappings reachable from a set of states of a level, which
+    /// doesn't depend on the markers assigned at the levels above. Counts of
+    /// the levels met are kept in `counts`, the levels are explored in
+    /// depth-first order with an explicit stack as the DAG may be deep.
+    fn count_from(
+        &self,
+        level: usize,
+        gamma: &BitSet,
+        counts: &mut HashMap<(usize, BitSet), u128>,
+    ) -> u128 {
+        let jump = self.jump.as_ref().unwrap();
+        let initial = self.automaton.get_initial();
+        let key = (level, gamma.clone());
+
+        // Levels being counted, with the levels they jump to that are left to
+        // count and the number of mappings found so far.
+        let mut stack = vec![(key.clone(), Vec::new(), 0u128, false)];
+
+        while let Some((key, children, count, expanded)) = stack.last_mut() {
+            if counts.contains_key(key) {
+                stack.pop();
+                continue;
+            }
+
+            if !*expanded {
+                *expanded = true;
+                let (level, gamma) = key.clone();
+
+                for (_, mut new_gamma) in self.next_level(gamma) {
+                    if new_gamma.is_empty() {
+                        continue;
+                    }
+
+                    if level == 0 {
+                        *count += new_gamma.contains(initial) as u128;
+                    } else if let Some(jump_level) = jump.jump(level, &mut new_gamma) {
+                        children.push((jump_level, new_gamma));
+                    }
+                }
+            }
+
+            match children.last() {
+                Some(child) if !counts.contains_key(child) => {
+                    let child = child.clone();
+                    stack.push((child, Vec::new(), 0, false));
+                }
+                Some(_) => {
+                    let child = children.pop().unwrap();
+                    *count = count.saturating_add(counts[&child]);
+                }
+                None => {
+                    let (key, _, count, _) = stack.pop().unwrap();
+                    counts.insert(key, count);
+                }
+            }
+        }
+
+        counts[&key]
+    }
+
     pub fn get_memory_usage(&self) -> Option<(usize, usize, usize, usize)> {
         self.jump.as_ref().map(|j| j.get_memory_usage())
     }
@@ -105,16 +256,182 @@ impl<'t> IndexedDag<'t> {
     pub fn get_statistics(&self) -> Option<(usize, usize, f64, usize, usize, f64)> {
         self.jump.as_ref().map(|j| j.get_statistics())
     }
+
+    pub fn get_jump_stats(&self) -> Option<(Vec<usize>, usize)> {
+        self.jump.as_ref().map(|j| j.get_jump_stats())
+    }
+
+    pub fn get_matrix_density(&self) -> Option<f64> {
+        self.jump.as_ref().map(|j| j.get_matrix_density())
+    }
+
+    /// Density of each matrix of the index, in increasing order.
+    pub fn get_matrix_densities(&self) -> Option<Vec<f64>> {
+        self.jump.as_ref().map(|j| j.get_matrix_densities())
+    }
+
+    pub fn get_jump_distance(&self) -> usize {
+        self.jump_distance
+    }
 }
 
-impl<'t> SpannerEnumerator<'t> for IndexedDag<'t> {
-    fn iter<'i>(&'i self) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i> {
-        Box::new(IndexedDagIterator::init(self))
+//  ____        _ _     _
+// | __ ) _   _(_) | __| | ___ _ __
+// |  _ \| | | | | |/ _` |/ _ \ '__|
+// | |_) | |_| | | | (_| |  __/ |
+// |____/ \__,_|_|_|\__,_|\___|_|
+//
+
+/// Options of an `IndexedDag`, which default to a jump distance of 1, full
+/// trimming, byte offsets, no memory budget and no progress bar.
+pub struct IndexedDagBuilder<'t> {
+    automaton: Automaton,
+    text: &'t str,
+    jump_distance: usize,
+    jump_schedule: JumpSchedule,
+    trimming_strategy: TrimmingStrategy,
+    single_pass: bool,
+    mapped_levels: bool,
+    compressed_levels: bool,
+    jump_pointers: bool,
+    toggle_progress: bool,
+    progress_callback: Option<ProgressCallback<'t>>,
+    memory_budget: Option<usize>,
+    length_bounds: Option<LengthBounds>,
+    offset_unit: OffsetUnit,
+}
+
+impl<'t> IndexedDagBuilder<'t> {
+    pub fn new(automaton: Automaton, text: &'t str) -> IndexedDagBuilder<'t> {
+        IndexedDagBuilder {
+            automaton,
+            text,
+            jump_distance: 1,
+            jump_schedule: JumpSchedule::Uniform,
+            trimming_strategy: TrimmingStrategy::FullTrimming,
+            single_pass: false,
+            mapped_levels: false,
+            compressed_levels: false,
+            jump_pointers: false,
+            toggle_progress: false,
+            progress_callback: None,
+            memory_budget: None,
+            length_bounds: None,
+            offset_unit: OffsetUnit::Bytes,
+        }
     }
 
-    /// Compute the index of matches of an automaton over input text.
-    fn preprocess(&mut self) {
-        // Compute the jump function
+    /// Number of levels between two jump targets, which must be positive.
+    pub fn with_jump_distance(mut self, jump_distance: usize) -> IndexedDagBuilder<'t> {
+        self.jump_distance = jump_distance;
+        self
+    }
+
+    pub fn with_jump_schedule(mut self, jump_schedule: JumpSchedule) -> IndexedDagBuilder<'t> {
+        self.jump_schedule = jump_schedule;
+        self
+    }
+
+    pub fn with_trimming(mut self, trimming_strategy: TrimmingStrategy) -> IndexedDagBuilder<'t> {
+        self.trimming_strategy = trimming_strategy;
+        self
+    }
+
+    /// Build and index the DAG in a single pass, only storing the levels that
+    /// can be jumped to. The trimming strategy is then ignored and no level is
+    /// trimmed.
+    pub fn with_single_pass(mut self, single_pass: bool) -> IndexedDagBuilder<'t> {
+        self.single_pass = single_pass;
+        self
+    }
+
+    /// Store the levels of the DAG, which take one bit per state of the
+    /// automaton and per character of the text, in a temporary memory-mapped
+    /// file instead of RAM.
+    pub fn with_mapped_levels(mut self, mapped_levels: bool) -> IndexedDagBuilder<'t> {
+        self.mapped_levels = mapped_levels;
+        self
+    }
+
+    /// Store each level of the DAG in a container that only takes a few bytes
+    /// when the level has few vertices, which is the common case after
+    /// trimming large automata. This can't be combined with mapped levels.
+    pub fn with_compressed_levels(mut self, compressed_levels: bool) -> IndexedDagBuilder<'t> {
+        self.compressed_levels = compressed_levels;
+        self
+    }
+
+    /// Index each level with the list of vertices of the previous level that
+    /// each vertex can reach, instead of reach matrices. This saves most of
+    /// the memory of the index for wide automata, but the delay is no longer
+    /// constant as a jump walks through all the levels down to its target.
+    pub fn with_jump_pointers(mut self, jump_pointers: bool) -> IndexedDagBuilder<'t> {
+        self.jump_pointers = jump_pointers;
+        self
+    }
+
+    /// Display a progress bar of the preprocessing on STDERR.
+    pub fn with_progress(mut self, toggle_progress: bool) -> IndexedDagBuilder<'t> {
+        self.toggle_progress = toggle_progress;
+        self
+    }
+
+    /// Report the phase, percentage and elapsed time of the preprocessing to a
+    /// callback, whether or not the progress bar is displayed.
+    pub fn with_progress_callback<F>(mut self, callback: F) -> IndexedDagBuilder<'t>
+    where
+        F: FnMut(&PhaseProgress) + Send + 't,
+    {
+        self.progress_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Abort the preprocessing as soon as the estimated memory usage of the
+    /// index exceeds the budget, in which case no match will be enumerated.
+    pub fn with_memory_budget(mut self, budget: Option<usize>) -> IndexedDagBuilder<'t> {
+        self.memory_budget = budget;
+        self
+    }
+
+    /// Only enumerate the mappings whose spans satisfy some bounds on their
+    /// lengths.
+    pub fn with_length_bounds(
+        mut self,
+        length_bounds: Option<LengthBounds>,
+    ) -> IndexedDagBuilder<'t> {
+        self.length_bounds = length_bounds;
+        self
+    }
+
+    /// Unit of the offsets of the spans given by the index, mappings still
+    /// hold byte ranges of the text.
+    pub fn with_offset_unit(mut self, offset_unit: OffsetUnit) -> IndexedDagBuilder<'t> {
+        self.offset_unit = offset_unit;
+        self
+    }
+
+    /// Create the index, which still has to be preprocessed.
+    pub fn build(self) -> IndexedDag<'t> {
+        assert!(self.jump_distance > 0, "The jump distance must be positive");
+        IndexedDag::new(self)
+    }
+}
+
+/// Reason why the forward pass over the text did not produce a DAG.
+enum BuildError {
+    MemoryBudgetExceeded,
+    /// No run of the automaton reads the whole text.
+    Disconnected,
+}
+
+impl<'t> IndexedDag<'t> {
+    /// Forward pass building the levels of the product DAG, which is the first
+    /// step of the preprocessing.
+    fn build_dag(
+        &mut self,
+        memory_budget: usize,
+        progress: &mut Progress,
+    ) -> Result<Jump, BuildError> {
         let mut jump = Jump::new(
             iter::once(self.automaton.get_initial()),
             self.automaton.get_closure_for_assignations(),
@@ -122,64 +439,1108 @@ impl<'t> SpannerEnumerator<'t> for IndexedDag<'t> {
             self.text.len() + 1,
             self.automaton.get_nb_states(),
             self.jump_distance,
+            self.jump_schedule,
         );
 
+        if self.mapped_levels {
+            jump = jump.with_mapped_levels();
+        }
+
+        if self.compressed_levels {
+            jump = jump.with_compressed_levels();
+        }
+
+        if self.jump_pointers {
+            jump = jump.with_jump_pointers();
+        }
+
+        if jump.get_estimated_memory_usage() > memory_budget {
+            self.levels_budget_exceeded = jump.get_levels_memory_usage() > memory_budget;
+            return Err(BuildError::MemoryBudgetExceeded);
+        }
+
+        for curr_char in progress.track(Phase::Build, self.text.chars()) {
+            let adj_for_char = self.automaton.get_adj_for_char_with_closure(curr_char);
+            jump.init_next_level(adj_for_char);
+
+            if jump.is_disconnected() {
+                return Err(BuildError::Disconnected);
+            }
+
+            // The levels grow with the text, the budget may be exceeded long
+            // before the index is built.
+            if jump.get_estimated_memory_usage() > memory_budget {
+                self.levels_budget_exceeded = jump.get_levels_memory_usage() > memory_budget;
+                return Err(BuildError::MemoryBudgetExceeded);
+            }
+        }
+
+        Ok(jump)
+    }
+
+    /// Build and index the levels in a single forward pass, without trimming.
+    /// Only the levels that can be jumped to are stored, but as they are not
+    /// trimmed this may be every level of the text.
+    fn preprocess_single_pass(&mut self, memory_budget: usize, progress: &mut Progress) {
         let closure_for_assignations = self.automaton.get_closure_for_assignations().clone();
+        let mut jump = Jump::new(
+            iter::once(self.automaton.get_initial()),
+            &closure_for_assignations,
+            self.automaton.get_jump_states(),
+            2,
+            self.automaton.get_nb_states(),
+            self.jump_distance,
+            self.jump_schedule,
+        )
+        .with_single_pass(self.text.chars().count());
 
-        let start_time = Instant::now();
+        if self.mapped_levels {
+            jump = jump.with_mapped_levels();
+        }
 
-        let chars = self.text.chars();
-        let mut progress = Progress::from_iter(chars).auto_refresh(self.toggle_progress);
+        if self.compressed_levels {
+            jump = jump.with_compressed_levels();
+        }
+
+        if self.jump_pointers {
+            jump = jump.with_jump_pointers();
+        }
+
+        let start_time = Instant::now();
+        jump.init_levels();
 
-        while let Some(curr_char) = progress.next() {
+        for (level, curr_char) in (1..).zip(progress.track(Phase::Index, self.text.chars())) {
             let adj_for_char = self.automaton.get_adj_for_char_with_closure(curr_char);
             jump.init_next_level(adj_for_char);
 
             if jump.is_disconnected() {
                 return;
             }
+
+            let adj_for_char = self.automaton.get_adj_for_char(curr_char);
+            jump.init_reach(level, curr_char, adj_for_char, &closure_for_assignations);
+
+            if jump.get_estimated_memory_usage() > memory_budget {
+                self.memory_budget_exceeded = true;
+                self.levels_budget_exceeded = jump.get_levels_memory_usage() > memory_budget;
+                return;
+            }
         }
 
+        self.index_time = Some(start_time.elapsed());
+        self.jump = Some(jump);
+    }
+
+    /// Run the passes of the preprocessing over the text, reporting their
+    /// progress.
+    fn compile(&mut self, progress: &mut Progress) {
+        let closure_for_assignations = self.automaton.get_closure_for_assignations().clone();
+        let memory_budget = self.memory_budget.unwrap_or(usize::MAX);
+        self.memory_budget_exceeded = false;
+        self.levels_budget_exceeded = false;
+
+        if self.single_pass {
+            self.preprocess_single_pass(memory_budget, progress);
+            return;
+        }
+
+        let start_time = Instant::now();
+
+        let mut jump = match self.build_dag(memory_budget, progress) {
+            Ok(jump) => jump,
+            Err(BuildError::MemoryBudgetExceeded) => {
+                self.memory_budget_exceeded = true;
+                return;
+            }
+            Err(BuildError::Disconnected) => return,
+        };
+
         self.create_dag_time = Some(start_time.elapsed());
 
         let start_time = Instant::now();
 
-        if self.trimming_strategy == TrimmingStrategy::FullTrimming {
-            jump.trim_last_level(&self.automaton.finals, &closure_for_assignations);
+        match self.trimming_strategy {
+            TrimmingStrategy::FullTrimming
+            | TrimmingStrategy::AdaptiveTrimming(_)
+            | TrimmingStrategy::LazyTrimming => {
+                jump.trim_last_level(&self.automaton.finals, &closure_for_assignations)
+            }
+            TrimmingStrategy::NoTrimming | TrimmingStrategy::PartialTrimming => {}
         }
 
         if jump.is_disconnected() {
             return;
         }
 
-        if self.trimming_strategy != TrimmingStrategy::NoTrimming {
-            let chars = self.text.chars();
+        if let Some(trim_threshold) = self.trim_threshold() {
             let mut level = jump.get_last_level();
-            let mut progress = Progress::from_iter(chars.rev()).auto_refresh(self.toggle_progress);
+            let (mut trimmed, mut skipped) = (0, 0);
+
+            for curr_char in progress.track(Phase::Trim, self.text.chars().rev()) {
+                if jump.level_width(level - 1) >= trim_threshold {
+                    let rev_rows_for_char =
+                        self.automaton.get_rev_rows_for_char_with_closure(curr_char);
+                    jump.trim_level(level, rev_rows_for_char);
+                    trimmed += 1;
+                } else {
+                    skipped += 1;
+                }
 
-            while let Some(curr_char) = progress.next() {
-                let rev_adj_for_char = self.automaton.get_rev_adj_for_char_with_closure(curr_char);
-                jump.trim_level(level, rev_adj_for_char);
                 level -= 1;
             }
+
+            self.trim_counts = Some((trimmed, skipped));
         }
 
         self.trim_time = Some(start_time.elapsed());
         let start_time = Instant::now();
-        let chars = self.text.chars();
-        let mut progress = Progress::from_iter(chars).auto_refresh(self.toggle_progress);
-        let mut level = 1;
+        jump.init_levels();
 
-        while let Some(curr_char) = progress.next() {
+        for (level, curr_char) in (1..).zip(progress.track(Phase::Index, self.text.chars())) {
             let adj_for_char = self.automaton.get_adj_for_char(curr_char);
             jump.init_reach(level, curr_char, adj_for_char, &closure_for_assignations);
-            level += 1;
+
+            if jump.get_estimated_memory_usage() > memory_budget {
+                self.memory_budget_exceeded = true;
+                self.levels_budget_exceeded = jump.get_levels_memory_usage() > memory_budget;
+                return;
+            }
         }
 
         self.index_time = Some(start_time.elapsed());
 
         self.jump = Some(jump);
     }
+
+    /// Minimal width of the levels trimmed by the backward pass, if it is run.
+    fn trim_threshold(&self) -> Option<usize> {
+        match self.trimming_strategy {
+            TrimmingStrategy::NoTrimming | TrimmingStrategy::LazyTrimming => None,
+            TrimmingStrategy::PartialTrimming | TrimmingStrategy::FullTrimming => Some(0),
+            TrimmingStrategy::AdaptiveTrimming(threshold) => Some(threshold),
+        }
+    }
+
+    /// Check if there is at least one match in the text. Only the forward pass
+    /// of the preprocessing is run, which is enough to tell if a final state
+    /// can be reached, and the text is no longer read once all runs died.
+    pub fn has_match(&mut self) -> bool {
+        let callback = self.progress_callback.take();
+        let mut progress = Progress::new(vec![Phase::Build], self.toggle_progress, callback);
+
+        let has_match = match self.build_dag(usize::MAX, &mut progress) {
+            Ok(jump) => !jump.finals().is_disjoint(&self.automaton.finals),
+            Err(_) => false,
+        };
+
+        self.progress_callback = progress.finish();
+        has_match
+    }
+
+    /// Follow the runs of the automaton over the text to explain why it has no
+    /// mapping, `None` is returned if there is one.
+    ///
+    /// The states alive at every level of the DAG, such as the loop over any
+    /// character in front of an unanchored pattern, are idle: the runs that
+    /// went through other states made some progress into the pattern. The
+    /// explanation is given for the longest range of levels where some runs
+    /// made progress, that is the longest partial match.
+    pub fn explain_no_match(&mut self) -> Option<NoMatch> {
+        let initial = self.automaton.get_initial();
+        let text = self.text;
+        let levels = text.chars().count();
+
+        // First pass: check for a match and collect the idle states.
+        let mut idle: Option<BitSet> = None;
+        let mut alive = self.initial_alive();
+
+        for character in text.chars() {
+            alive = self.next_alive(&alive, character);
+
+            match &mut idle {
+                Some(idle) => idle.intersect_with(&alive),
+                None => idle = Some(alive.clone()),
+            }
+
+            if alive.is_empty() {
+                break;
+            }
+        }
+
+        if !alive.is_disjoint(&self.automaton.finals) {
+            return None;
+        }
+
+        let mut idle = idle.unwrap_or_default();
+        idle.insert(initial);
+
+        // Second pass: find the longest range of levels where some runs made
+        // progress, as `(first level, last level + 1)`, which is empty if no
+        // run made any.
+        let mut longest = (0, 0);
+        let mut current: Option<usize> = None;
+        let mut alive = self.initial_alive();
+        let mut chars = text.chars();
+
+        for level in 0..=levels + 1 {
+            // No run makes progress past the end of the text.
+            let progress = level <= levels && alive.difference(&idle).next().is_some();
+
+            match (progress, current) {
+                (true, None) => current = Some(level),
+                (false, Some(first)) => {
+                    if longest.1 - longest.0 < level - first {
+                        longest = (first, level);
+                    }
+
+                    current = None;
+                }
+                _ => {}
+            }
+
+            if let Some(character) = chars.next() {
+                alive = self.next_alive(&alive, character);
+            }
+        }
+
+        if longest.1 == 0 {
+            return Some(NoMatch::NotStarted {
+                alive: self.expected_labels(&self.initial_alive(), &idle),
+            });
+        }
+
+        let (first, end) = longest;
+
+        // Third pass: collect the states of the partial match at its last
+        // level.
+        let start = match first {
+            0 => 0,
+            _ => text.char_indices().nth(first - 1).unwrap().0,
+        };
+
+        let mut alive = self.initial_alive();
+        let mut char_indices = text.char_indices();
+
+        for _ in 0..end - 1 {
+            alive = self.next_alive(&alive, char_indices.next().unwrap().1);
+        }
+
+        alive.difference_with(&idle);
+        let alive = self.expected_labels(&alive, &idle);
+
+        Some(match char_indices.next() {
+            Some((offset, character)) => NoMatch::Disconnected {
+                start,
+                level: end - 1,
+                offset,
+                character,
+                alive,
+            },
+            None => NoMatch::NoFinalState { start, alive },
+        })
+    }
+
+    /// States alive at the first level of the DAG.
+    fn initial_alive(&self) -> BitSet {
+        let closure = self.automaton.get_closure_for_assignations();
+        let initial = self.automaton.get_initial();
+        iter::once(initial).chain(closure[initial].iter().copied()).collect()
+    }
+
+    /// States alive at the next level of the DAG after reading a character.
+    fn next_alive(&mut self, alive: &BitSet, character: char) -> BitSet {
+        let adj = self.automaton.get_adj_for_char_with_closure(character);
+        alive.iter().flat_map(|source| adj[source].iter().copied()).collect()
+    }
+
+    /// Labels of the characters read by the transitions leaving some states,
+    /// or the end of the text for final states. The transitions to idle
+    /// states are skipped, and so are the states which only assign markers.
+    fn expected_labels(&self, states: &BitSet, idle: &BitSet) -> Vec<(usize, Vec<String>)> {
+        let adj = self.automaton.get_adj();
+
+        states
+            .iter()
+            .map(|state| {
+                let mut labels: Vec<_> = adj[state]
+                    .iter()
+                    .filter(|(label, target)| {
+                        matches!(**label, Label::Atom(_)) && !idle.contains(*target)
+                    })
+                    .map(|(label, _)| label.to_string())
+                    .collect();
+
+                if self.automaton.finals.contains(state) {
+                    labels.push("the end of the text".to_string());
+                }
+
+                (state, labels)
+            })
+            .filter(|(_, labels)| !labels.is_empty())
+            .collect()
+    }
+
+    /// Reconstruct an accepting run of the automaton over the text which
+    /// assigns exactly the spans of a mapping, `None` is returned if there is
+    /// none. The index doesn't need to be built.
+    pub fn explain_run(&self, mapping: &Mapping) -> Option<Vec<RunStep>> {
+        let adj = self.automaton.get_adj();
+
+        let markers = mapping.markers_by_position();
+
+        // Runs are followed through vertices `(state, assigned)` at each
+        // position, where `assigned` is a mask over the markers to assign at
+        // this position which are already assigned. The parent of a vertex is
+        // in the same level if it was reached through a marker and in the
+        // previous level otherwise.
+        let mut offsets = Vec::new();
+        let mut parents: Vec<RunParents> = vec![HashMap::new()];
+        let mut curr = vec![(self.automaton.get_initial(), 0)];
+        let positions = self.text.char_indices().map(Some).chain(iter::once(None));
+
+        for next in positions {
+            let pos = next.map_or(self.text.len(), |(pos, _)| pos);
+            let expected = markers.get(&pos).map_or(&[][..], Vec::as_slice);
+            assert!(expected.len() < 64, "Too many markers assigned at the same position");
+            let complete = (1u64 << expected.len()) - 1;
+            offsets.push(pos);
+
+            // Assign the expected markers.
+            let level_parents = parents.last_mut().unwrap();
+            let mut seen: HashSet<_> = curr.iter().copied().collect();
+            let mut heap = curr.clone();
+
+            while let Some((state, assigned)) = heap.pop() {
+                for (label, target) in &adj[state] {
+                    let marker = match &**label {
+                        Label::Assignation(marker) => marker.get_id(),
+                        _ => continue,
+                    };
+
+                    let bit = match expected.iter().position(|&id| id == marker) {
+                        Some(index) if assigned & (1 << index) == 0 => 1 << index,
+                        _ => continue,
+                    };
+
+                    let vertex = (*target, assigned | bit);
+
+                    if seen.insert(vertex) {
+                        level_parents.insert(vertex, ((state, assigned), label));
+                        heap.push(vertex);
+                    }
+                }
+            }
+
+            let assigned = seen.into_iter().filter(|&(_, assigned)| assigned == complete);
+
+            // Read the next character, or stop in a final state.
+            let character = match next {
+                Some((_, character)) => character,
+                None => {
+                    let last = assigned
+                        .into_iter()
+                        .find(|&(state, _)| self.automaton.finals.contains(state))?;
+                    return Some(Self::rebuild_run(&parents, &offsets, last));
+                }
+            };
+
+            let mut next_parents = HashMap::new();
+
+            for (state, assigned) in assigned {
+                for (label, target) in &adj[state] {
+                    if let Label::Atom(atom) = &**label {
+                        let vertex = (*target, 0);
+
+                        if atom.is_match(&character) && !next_parents.contains_key(&vertex) {
+                            next_parents.insert(vertex, ((state, assigned), label));
+                        }
+                    }
+                }
+            }
+
+            if next_parents.is_empty() {
+                return None;
+            }
+
+            curr = next_parents.keys().copied().collect();
+            parents.push(next_parents);
+        }
+
+        None
+    }
+
+    /// Follow the parents of the vertices computed by `explain_run` from the
+    /// last vertex of a run back to the initial state.
+    fn rebuild_run(
+        parents: &[RunParents],
+        offsets: &[usize],
+        last: (usize, u64),
+    ) -> Vec<RunStep> {
+        let mut run = Vec::new();
+        let mut level = parents.len() - 1;
+        let mut vertex = last;
+
+        while let Some(&(parent, label)) = parents[level].get(&vertex) {
+            // Only markers are read inside of a level.
+            if vertex.1 == 0 {
+                level -= 1;
+            }
+
+            run.push(RunStep {
+                offset: offsets[level],
+                source: parent.0,
+                label: label.to_string(),
+                target: vertex.0,
+            });
+
+            vertex = parent;
+        }
+
+        run.reverse();
+        run
+    }
+
+    /// Find one mapping while reading as little of the text as possible. The
+    /// text is read until a run assigned a marker and reached a state that
+    /// accepts any suffix, then only the prefix read so far is indexed. If
+    /// runs only accept at the end of the text, the whole text is indexed.
+    pub fn find_first(&mut self) -> Option<Mapping<'t>> {
+        let end = self.accepting_prefix_len()?;
+
+        // Bounds on the spans may reject all the mappings of the prefix.
+        if end == self.text.len() || self.length_bounds.is_some() {
+            self.preprocess();
+            return self.first();
+        }
+
+        let mut automaton = self.automaton.clone();
+        automaton.finals = self.automaton.get_universal_finals();
+
+        let mut prefix = IndexedDag::builder(automaton, &self.text[..end])
+            .with_jump_distance(self.jump_distance)
+            .with_jump_schedule(self.jump_schedule)
+            .with_trimming(self.trimming_strategy)
+            .with_single_pass(self.single_pass)
+            .with_mapped_levels(self.mapped_levels)
+            .with_compressed_levels(self.compressed_levels)
+            .with_jump_pointers(self.jump_pointers)
+            .with_progress(self.toggle_progress)
+            .with_memory_budget(self.memory_budget)
+            .with_offset_unit(self.get_offset_unit())
+            .build();
+
+        prefix.progress_callback = self.progress_callback.take();
+        prefix.preprocess();
+        self.progress_callback = prefix.progress_callback.take();
+        self.memory_budget_exceeded = prefix.memory_budget_exceeded;
+
+        // Spans of the prefix are the same in the whole text.
+        let text = self.text;
+        prefix.first().map(|mapping| Mapping { text, ..mapping })
+    }
+
+    /// Length in bytes of the shortest prefix of the text after which a run
+    /// that assigned a marker can be in a universal final state, or the length
+    /// of the text if there is none but some run accepts the text. Runs are
+    /// followed without building the DAG.
+    fn accepting_prefix_len(&mut self) -> Option<usize> {
+        let nb_states = self.automaton.get_nb_states();
+        let universal = self.automaton.get_universal_finals();
+        let closure = self.automaton.get_closure_for_assignations().clone();
+
+        // States reached by runs that did not assign any marker yet and by
+        // runs that assigned some.
+        let mut unmarked = BitSet::with_capacity(nb_states);
+        let mut marked = BitSet::with_capacity(nb_states);
+        unmarked.insert(self.automaton.get_initial());
+
+        let callback = self.progress_callback.take();
+        let mut progress = Progress::new(vec![Phase::Build], self.toggle_progress, callback);
+        let mut chars = progress.track(Phase::Build, self.text.char_indices());
+        let mut result = None;
+
+        loop {
+            let sources: Vec<_> = unmarked.union(&marked).collect();
+
+            for source in sources {
+                marked.extend(closure[source].iter().copied());
+            }
+
+            let (pos, curr_char) = match chars.next() {
+                Some(next) => next,
+                None => {
+                    if !marked.is_disjoint(&self.automaton.finals)
+                        || !unmarked.is_disjoint(&self.automaton.finals)
+                    {
+                        result = Some(self.text.len());
+                    }
+
+                    break;
+                }
+            };
+
+            if !marked.is_disjoint(&universal) {
+                result = Some(pos);
+                break;
+            }
+
+            let adj = self.automaton.get_adj_for_char(curr_char);
+            let step = |states: &BitSet| {
+                states
+                    .iter()
+                    .flat_map(|source| adj[source].iter().copied())
+                    .collect::<BitSet>()
+            };
+
+            unmarked = step(&unmarked);
+            marked = step(&marked);
+
+            if unmarked.is_empty() && marked.is_empty() {
+                break;
+            }
+        }
+
+        self.progress_callback = progress.finish();
+        result
+    }
+}
+
+/// Explanation of why an automaton has no run over a text, given for the
+/// longest partial match.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NoMatch {
+    /// No run of the partial match can read the character at some position
+    /// of the text.
+    Disconnected {
+        /// Position in bytes where the partial match starts.
+        start: usize,
+        /// Level of the DAG where the last runs of the partial match died,
+        /// which is the number of characters read before.
+        level: usize,
+        /// Position of the character in bytes.
+        offset: usize,
+        character: char,
+        /// States of the partial match that were alive before reading the
+        /// character, with the labels of the characters they could read.
+        alive: Vec<(usize, Vec<String>)>,
+    },
+    /// Some runs of the partial match read the whole text but none ends in a
+    /// final state.
+    NoFinalState {
+        start: usize,
+        alive: Vec<(usize, Vec<String>)>,
+    },
+    /// No run makes any progress into the pattern, the states are the ones
+    /// alive at the start of the text.
+    NotStarted { alive: Vec<(usize, Vec<String>)> },
+}
+
+impl fmt::Display for NoMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let alive = match self {
+            NoMatch::Disconnected {
+                start,
+                level,
+                offset,
+                character,
+                alive,
+            } => {
+                writeln!(f, " - The longest partial match starts at byte {}", start)?;
+                writeln!(
+                    f,
+                    " - It stays alive up to level {}, no run can read {:?} at byte {}",
+                    level, character, offset
+                )?;
+                writeln!(f, " - States alive before reading it:")?;
+                alive
+            }
+            NoMatch::NoFinalState { start, alive } => {
+                writeln!(f, " - The longest partial match starts at byte {}", start)?;
+                writeln!(f, " - It reads the whole text, but no run ends in a final state")?;
+                writeln!(f, " - States alive at the end of the text:")?;
+                alive
+            }
+            NoMatch::NotStarted { alive } => {
+                writeln!(f, " - No run reads any character of the pattern")?;
+                writeln!(f, " - States alive at the start of the text:")?;
+                alive
+            }
+        };
+
+        for (state, expected) in alive {
+            writeln!(f, "   - {}: expecting {}", state, expected.join(" or "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parent of each vertex `(state, assigned)` of a level explored by
+/// `IndexedDag::explain_run`, with the label of the transition between them.
+type RunParents<'a> = HashMap<(usize, u64), ((usize, u64), &'a Arc<Label>)>;
+
+/// Transition taken by a run of the automaton.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RunStep {
+    /// Position in bytes where the transition is taken: the character read
+    /// starts there, and markers are assigned there.
+    pub offset: usize,
+    pub source: usize,
+    /// Label of the transition, a character class or a marker.
+    pub label: String,
+    pub target: usize,
+}
+
+impl fmt::Display for RunStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6}: {} -- {} --> {}",
+            self.offset, self.source, self.label, self.target
+        )
+    }
+}
+
+/// Position in the text where the main span of a mapping is anchored.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Anchor {
+    Start(usize),
+    End(usize),
+}
+
+/// Minimal and maximal length of a span.
+type Bounds = (usize, usize);
+
+/// Bounds on the length in bytes of the main span of mappings and of the
+/// spans of some groups. A mapping that doesn't bind a group satisfies its
+/// bounds.
+#[derive(Clone, Debug)]
+pub struct LengthBounds {
+    main: Bounds,
+    groups: HashMap<String, Bounds>,
+}
+
+impl LengthBounds {
+    pub fn new() -> LengthBounds {
+        LengthBounds {
+            main: (0, usize::MAX),
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Minimal length of the span of a group, or of the main span if no group
+    /// is given.
+    pub fn with_min_len(mut self, group: Option<&str>, len: usize) -> LengthBounds {
+        self.get_bounds_mut(group).0 = len;
+        self
+    }
+
+    /// Maximal length of the span of a group, or of the main span if no group
+    /// is given.
+    pub fn with_max_len(mut self, group: Option<&str>, len: usize) -> LengthBounds {
+        self.get_bounds_mut(group).1 = len;
+        self
+    }
+
+    /// Intersect the bounds of the span of a group, or of the main span if no
+    /// group is given, with other bounds.
+    pub fn restrict(mut self, group: Option<&str>, min: usize, max: usize) -> LengthBounds {
+        let bounds = self.get_bounds_mut(group);
+        *bounds = (bounds.0.max(min), bounds.1.min(max));
+        self
+    }
+
+    /// Check if the spans of a mapping satisfy the bounds.
+    pub fn accepts(&self, mapping: &Mapping) -> bool {
+        let within = |(min, max): Bounds, span: Range<usize>| {
+            min <= span.len() && span.len() <= max
+        };
+
+        mapping
+            .main_span()
+            .map(|span| within(self.main, span))
+            .unwrap_or(self.main.0 == 0)
+            && mapping.iter_groups().all(|(group, span)| {
+                self.groups
+                    .get(group)
+                    .map(|&bounds| within(bounds, span))
+                    .unwrap_or(true)
+            })
+    }
+
+    fn get_bounds_mut(&mut self, group: Option<&str>) -> &mut Bounds {
+        match group {
+            None => &mut self.main,
+            Some(group) => self
+                .groups
+                .entry(group.to_string())
+                .or_insert((0, usize::MAX)),
+        }
+    }
+
+    /// Bounds of each variable of an automaton, indexed by their ids.
+    fn by_variable(&self, automaton: &Automaton) -> Vec<Bounds> {
+        let mut bounds = vec![(0, usize::MAX); automaton.num_vars()];
+
+        for var in automaton.get_variables() {
+            if let Some(&var_bounds) = self.groups.get(var.get_name()) {
+                bounds[var.get_id()] = var_bounds;
+            }
+        }
+
+        bounds
+    }
+}
+
+impl Default for LengthBounds {
+    fn default() -> LengthBounds {
+        LengthBounds::new()
+    }
+}
+
+impl<'t> IndexedDag<'t> {
+    /// Enumerate the main spans of all mappings, without duplicates, in the
+    /// offset unit of the index. This is lighter than building the mappings
+    /// when their groups are not needed.
+    pub fn spans<'i>(&'i self) -> impl Iterator<Item = Range<usize>> + 'i {
+        let mut seen = HashSet::new();
+
+        IndexedDagIterator::init(self, None)
+            .into_spans()
+            .filter(move |span| seen.insert((span.start, span.end)))
+            .map(move |span| self.convert_span(span))
+    }
+
+    /// Enumerate the mappings whose main span starts or ends at the given
+    /// offset.
+    ///
+    /// Branches of the enumeration that can't assign a marker at the anchor
+    /// are cut as soon as a jump leads before it, thus mappings ending far
+    /// before an anchored end are never explored.
+    pub fn iter_anchored<'i>(
+        &'i self,
+        anchor: Anchor,
+    ) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i> {
+        Box::new(IndexedDagIterator::init(self, Some(anchor)))
+    }
+
+    /// Enumerate the mappings with an iterator whose state can be saved at
+    /// any time with `IndexedDagIterator::cursor`.
+    pub fn iter_resumable<'i>(&'i self) -> IndexedDagIterator<'i, 't> {
+        IndexedDagIterator::init(self, None)
+    }
+
+    /// Resume an enumeration from a cursor saved over the same index, which
+    /// may have been built by another process.
+    pub fn resume<'i>(&'i self, cursor: &Cursor) -> IndexedDagIterator<'i, 't> {
+        let mismatch = "The cursor was saved from another index";
+        assert_eq!(self.num_levels(), cursor.num_levels, "{}", mismatch);
+
+        let mut markers = vec![None; 2 * self.automaton.num_vars()];
+
+        for (label, _) in self.automaton.get_rev_assignations().iter().flatten() {
+            let marker = label.get_marker().unwrap();
+            markers[marker.get_id()] = Some(marker);
+        }
+
+        let get_mapping = |mapping: &[(usize, usize)]| -> Vec<(&'i Marker, usize)> {
+            mapping
+                .iter()
+                .map(|&(id, pos)| (markers.get(id).copied().flatten().expect(mismatch), pos))
+                .collect()
+        };
+
+        let mut iterator = IndexedDagIterator::init(self, cursor.anchor);
+        iterator.stack = cursor
+            .stack
+            .iter()
+            .map(|(level, gamma, mapping)| {
+                (*level, gamma.iter().copied().collect(), get_mapping(mapping))
+            })
+            .collect();
+
+        // The assignations of the current level are explored again up to
+        // where the enumeration stopped.
+        iterator.curr_level = cursor.curr_level;
+        iterator.curr_mapping = get_mapping(&cursor.curr_mapping);
+        iterator.curr_next_level = self.next_level(cursor.curr_gamma.iter().copied().collect());
+        iterator.curr_next_level.by_ref().take(cursor.num_consumed).for_each(drop);
+        iterator.num_consumed = cursor.num_consumed;
+        iterator.num_found = cursor.num_found;
+        iterator
+    }
+}
+
+impl<'t> SpannerEnumerator<'t> for IndexedDag<'t> {
+    fn iter<'i>(&'i self) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i> {
+        Box::new(IndexedDagIterator::init(self, None))
+    }
+
+    /// Skip the levels of the stack of the enumeration whose mappings are
+    /// all skipped, which are counted without being enumerated.
+    fn iter_range<'i>(
+        &'i self,
+        offset: usize,
+        limit: usize,
+    ) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i>
+    where
+        't: 'i,
+    {
+        let mut iterator = IndexedDagIterator::init(self, None);
+        iterator.to_skip = offset;
+        Box::new(iterator.take(limit))
+    }
+
+    fn stats(&self) -> EnumeratorStats {
+        let times = [self.create_dag_time, self.trim_time, self.index_time];
+
+        EnumeratorStats {
+            preprocess_time: times.iter().flatten().copied().reduce(|x, y| x + y),
+            memory_usage: self.jump.as_ref().map(|j| j.get_estimated_memory_usage()),
+            index_size: self.num_levels(),
+        }
+    }
+
+    /// Compute the index of matches of an automaton over input text.
+    fn preprocess(&mut self) {
+        let phases = if self.single_pass {
+            vec![Phase::Index]
+        } else if self.trim_threshold().is_some() {
+            vec![Phase::Build, Phase::Trim, Phase::Index]
+        } else {
+            vec![Phase::Build, Phase::Index]
+        };
+
+        let callback = self.progress_callback.take();
+        let mut progress = Progress::new(phases, self.toggle_progress, callback);
+        self.compile(&mut progress);
+        self.progress_callback = progress.finish();
+    }
+}
+
+//  _____                       _
+// | ____|_  ___ __   ___  _ __| |_
+// |  _| \ \/ / '_ \ / _ \| '__| __|
+// | |___ >  <| |_) | (_) | |  | |_
+// |_____/_/\_\ .__/ \___/|_|   \__|
+//            |_|
+
+/// Vertex of the product DAG, as exported for debugging purposes.
+struct ExportedVertex {
+    level: usize,
+    state: usize,
+    jump_level: Option<usize>,
+}
+
+/// Edge of the product DAG, as exported for debugging purposes. Assignation
+/// edges stay inside of a level while reach edges link a vertex of a sublevel
+/// to the vertices it can reach without assignation.
+enum ExportedEdge {
+    Assignation(ExportedVertex, ExportedVertex, String),
+    Reach(ExportedVertex, ExportedVertex),
+}
+
+impl ExportedVertex {
+    fn id(&self) -> String {
+        format!("l{}_q{}", self.level, self.state)
+    }
+}
+
+impl<'t> IndexedDag<'t> {
+    /// List the levels of the index, with their position in the text and
+    /// their vertices.
+    fn export_levels(&self) -> Vec<(usize, Vec<ExportedVertex>)> {
+        let jump = match &self.jump {
+            None => return Vec::new(),
+            Some(jump) => jump,
+        };
+
+        (0..jump.num_levels())
+            .map(|level| {
+                let jump_levels = jump.get_jump_levels(level);
+                let vertices = jump
+                    .get_level_vertices(level)
+                    .iter()
+                    .enumerate()
+                    .map(|(index, state)| ExportedVertex {
+                        level,
+                        state,
+                        jump_level: jump_levels
+                            .get(index)
+                            .filter(|&&jl| jl != usize::MAX)
+                            .cloned(),
+                    })
+                    .collect();
+
+                (jump.get_pos(level), vertices)
+            })
+            .collect()
+    }
+
+    fn export_edges(&self) -> Vec<ExportedEdge> {
+        let jump = match &self.jump {
+            None => return Vec::new(),
+            Some(jump) => jump,
+        };
+
+        let vertex = |level: usize, state: usize| ExportedVertex {
+            level,
+            state,
+            jump_level: None,
+        };
+
+        let mut edges = Vec::new();
+
+        for level in 0..jump.num_levels() {
+            let states: Vec<usize> = jump.get_level_vertices(level).iter().collect();
+
+            for &source in &states {
+                for (label, target) in &self.automaton.get_assignations()[source] {
+                    if states.contains(target) {
+                        edges.push(ExportedEdge::Assignation(
+                            vertex(level, source),
+                            vertex(level, *target),
+                            label.get_marker().unwrap().to_string(),
+                        ));
+                    }
+                }
+            }
+
+            for (sublevel, pairs) in jump.get_reach_pairs(level) {
+                let substates: Vec<usize> = jump.get_level_vertices(sublevel).iter().collect();
+
+                for (source, target) in pairs {
+                    edges.push(ExportedEdge::Reach(
+                        vertex(sublevel, substates[source]),
+                        vertex(level, states[target]),
+                    ));
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Write the indexed product DAG in the dot format, this must be called
+    /// after `preprocess`.
+    pub fn write_dot<W: Write>(&self, buf: &mut W) -> std::io::Result<()> {
+        writeln!(buf, "digraph dag {{")?;
+        writeln!(buf, "\trankdir=LR")?;
+
+        for (level, (pos, vertices)) in self.export_levels().iter().enumerate() {
+            writeln!(buf, "\n\tsubgraph cluster_{} {{", level)?;
+            writeln!(buf, "\t\tlabel=\"level {} (offset {})\"", level, pos)?;
+
+            for vertex in vertices {
+                let jump_level = match vertex.jump_level {
+                    Some(jl) => jl.to_string(),
+                    None => String::from("-"),
+                };
+
+                writeln!(
+                    buf,
+                    "\t\t{} [label=\"q{}\\njl={}\"]",
+                    vertex.id(),
+                    vertex.state,
+                    jump_level
+                )?;
+            }
+
+            writeln!(buf, "\t}}")?;
+        }
+
+        writeln!(buf)?;
+
+        for edge in self.export_edges() {
+            match edge {
+                ExportedEdge::Assignation(source, target, label) => writeln!(
+                    buf,
+                    "\t{} -> {} [label=\" {} \"]",
+                    source.id(),
+                    target.id(),
+                    label.escape_debug()
+                )?,
+                ExportedEdge::Reach(source, target) => writeln!(
+                    buf,
+                    "\t{} -> {} [style=dashed]",
+                    source.id(),
+                    target.id()
+                )?,
+            }
+        }
+
+        writeln!(buf, "}}")
+    }
+
+    /// Write the indexed product DAG in the GraphML format, this must be
+    /// called after `preprocess`.
+    pub fn write_graphml<W: Write>(&self, buf: &mut W) -> std::io::Result<()> {
+        writeln!(buf, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            buf,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+
+        let keys = [
+            ("level", "node", "int"),
+            ("offset", "node", "int"),
+            ("state", "node", "int"),
+            ("jump_level", "node", "int"),
+            ("kind", "edge", "string"),
+            ("label", "edge", "string"),
+        ];
+
+        for (name, domain, kind) in &keys {
+            writeln!(
+                buf,
+                r#"  <key id="{0}" for="{1}" attr.name="{0}" attr.type="{2}"/>"#,
+                name, domain, kind
+            )?;
+        }
+
+        writeln!(buf, r#"  <graph id="dag" edgedefault="directed">"#)?;
+
+        for (pos, vertices) in self.export_levels() {
+            for vertex in vertices {
+                writeln!(buf, r#"    <node id="{}">"#, vertex.id())?;
+                writeln!(buf, r#"      <data key="level">{}</data>"#, vertex.level)?;
+                writeln!(buf, r#"      <data key="offset">{}</data>"#, pos)?;
+                writeln!(buf, r#"      <data key="state">{}</data>"#, vertex.state)?;
+
+                if let Some(jump_level) = vertex.jump_level {
+                    writeln!(buf, r#"      <data key="jump_level">{}</data>"#, jump_level)?;
+                }
+
+                writeln!(buf, "    </node>")?;
+            }
+        }
+
+        for edge in self.export_edges() {
+            let (source, target, kind, label) = match edge {
+                ExportedEdge::Assignation(source, target, label) => {
+                    (source, target, "assignation", Some(label))
+                }
+                ExportedEdge::Reach(source, target) => (source, target, "reach", None),
+            };
+
+            writeln!(
+                buf,
+                r#"    <edge source="{}" target="{}">"#,
+                source.id(),
+                target.id()
+            )?;
+            writeln!(buf, r#"      <data key="kind">{}</data>"#, kind)?;
+
+            if let Some(label) = label {
+                writeln!(buf, r#"      <data key="label">{}</data>"#, label)?;
+            }
+
+            writeln!(buf, "    </edge>")?;
+        }
+
+        writeln!(buf, "  </graph>")?;
+        writeln!(buf, "</graphml>")
+    }
 }
 
 //  ___           _                   _
@@ -194,18 +1555,69 @@ impl<'t> SpannerEnumerator<'t> for IndexedDag<'t> {
 // |____/ \__,_|\__, |
 //              |___/
 
-struct IndexedDagIterator<'i, 't> {
+/// Markers assigned by a partial mapping given by their ids, with their
+/// positions.
+type MarkerIds = Vec<(usize, usize)>;
+
+/// State of an enumeration over an `IndexedDag`, which can be saved to disk
+/// to resume the enumeration later with `IndexedDag::resume`. Markers are
+/// identified by their id and sets of states are given as lists.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Cursor {
+    /// Number of levels of the index, to check that it is resumed over the
+    /// same index.
+    num_levels: Option<usize>,
+    anchor: Option<Anchor>,
+    /// Levels left to explore, with their states and the markers assigned
+    /// so far.
+    stack: Vec<(usize, Vec<usize>, MarkerIds)>,
+    /// Level being explored, with the states it was explored from and the
+    /// number of assignations of the level that were already visited.
+    curr_level: usize,
+    curr_gamma: Vec<usize>,
+    curr_mapping: MarkerIds,
+    num_consumed: usize,
+    num_found: usize,
+}
+
+impl Cursor {
+    /// Number of mappings enumerated before the cursor was saved.
+    pub fn num_found(&self) -> usize {
+        self.num_found
+    }
+}
+
+pub struct IndexedDagIterator<'i, 't> {
     indexed_dag: &'i IndexedDag<'t>,
     stack: Vec<(usize, BitSet, Vec<(&'i Marker, usize)>)>,
 
     curr_level: usize,
     curr_mapping: Vec<(&'i Marker, usize)>,
     curr_next_level: NextLevelIterator<'i>,
+    /// Number of assignations taken from `curr_next_level`.
+    num_consumed: usize,
     num_vars: usize,
+    anchor: Option<Anchor>,
+    /// Bounds on the length of the main span and of the span of each
+    /// variable, if the enumeration is restricted by length bounds.
+    length_bounds: Option<(Bounds, Vec<Bounds>)>,
+
+    /// With lazy trimming, levels being explored with the size of the stack
+    /// when they were popped and the number of mappings found before.
+    explored: Vec<(usize, usize, BitSet, usize)>,
+    num_found: usize,
+
+    /// Number of mappings left to skip, and the number of mappings that can
+    /// be reached from the levels of the stack that were counted so far.
+    to_skip: usize,
+    counts: HashMap<(usize, BitSet), u128>,
 }
 
 impl<'i, 't> IndexedDagIterator<'i, 't> {
-    fn init(indexed_dag: &'i IndexedDag<'t>) -> IndexedDagIterator<'i, 't> {
+    fn init(
+        indexed_dag: &'i IndexedDag<'t>,
+        anchor: Option<Anchor>,
+    ) -> IndexedDagIterator<'i, 't> {
         IndexedDagIterator {
             indexed_dag,
             stack: match &indexed_dag.jump {
@@ -223,18 +1635,248 @@ impl<'i, 't> IndexedDagIterator<'i, 't> {
             curr_next_level: NextLevelIterator::empty(&indexed_dag.automaton),
             curr_level: usize::default(),
             curr_mapping: Vec::default(),
+            num_consumed: 0,
             num_vars: indexed_dag.automaton.num_vars(),
+            anchor,
+            length_bounds: indexed_dag.length_bounds.as_ref().map(|bounds| {
+                (bounds.main, bounds.by_variable(&indexed_dag.automaton))
+            }),
+            explored: Vec::new(),
+            num_found: 0,
+            to_skip: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Save the state of the enumeration, the mappings enumerated next by
+    /// the iterator will be the ones enumerated after resuming the cursor.
+    pub fn cursor(&self) -> Cursor {
+        let ids = |mapping: &[(&Marker, usize)]| {
+            mapping.iter().map(|(marker, pos)| (marker.get_id(), *pos)).collect()
+        };
+
+        Cursor {
+            num_levels: self.indexed_dag.num_levels(),
+            anchor: self.anchor,
+            stack: self
+                .stack
+                .iter()
+                .map(|(level, gamma, mapping)| (*level, gamma.iter().collect(), ids(mapping)))
+                .collect(),
+            curr_level: self.curr_level,
+            curr_gamma: self.curr_next_level.gamma.iter().collect(),
+            curr_mapping: ids(&self.curr_mapping),
+            num_consumed: self.num_consumed,
+            num_found: self.num_found,
+        }
+    }
+
+    /// Check if all the mappings reachable from a level of the stack are
+    /// skipped, in which case they are accounted as found. Mappings are only
+    /// counted when the enumeration is not restricted by an anchor or by
+    /// length bounds, which depend on the markers assigned so far.
+    fn skips_level(&mut self, level: usize, gamma: &BitSet) -> bool {
+        if self.to_skip == 0 || self.anchor.is_some() || self.length_bounds.is_some() {
+            return false;
+        }
+
+        let count = self.indexed_dag.count_from(level, gamma, &mut self.counts);
+
+        if count > self.to_skip as u128 {
+            return false;
+        }
+
+        self.to_skip -= count as usize;
+        self.num_found += count as usize;
+        true
+    }
+
+    /// Dead vertices are only cached with lazy trimming, as other strategies
+    /// already removed them. Anchored or length bounded enumerations cut
+    /// branches with alive vertices and can't tell dead vertices apart.
+    fn caches_dead_vertices(&self) -> bool {
+        self.indexed_dag.trimming_strategy == TrimmingStrategy::LazyTrimming
+            && self.anchor.is_none()
+            && self.length_bounds.is_none()
+    }
+
+    /// Remove the vertices of a level which are known to be dead.
+    fn remove_dead_vertices(&self, level: usize, gamma: &mut BitSet) {
+        if let Some(dead) = self.indexed_dag.dead_vertices.borrow().get(&level) {
+            gamma.difference_with(dead);
+        }
+    }
+
+    /// Mark as dead the vertices of the levels whose exploration is over and
+    /// didn't lead to any mapping.
+    fn close_explored_levels(&mut self) {
+        while let Some((stack_len, _, _, _)) = self.explored.last() {
+            if *stack_len < self.stack.len() {
+                break;
+            }
+
+            let (_, level, gamma, num_found) = self.explored.pop().unwrap();
+
+            if num_found == self.num_found {
+                let mut dead_vertices = self.indexed_dag.dead_vertices.borrow_mut();
+                dead_vertices
+                    .entry(level)
+                    .or_default()
+                    .union_with(&gamma);
+            }
+        }
+    }
+
+    /// Check if markers assigned at the current level can still lead to a
+    /// mapping satisfying the anchor. Markers are assigned from the end of
+    /// the text, thus the first markers of a mapping close its main span and
+    /// the last ones open it.
+    fn accepts_markers(&self, assigned: bool, pos: usize) -> bool {
+        match self.anchor {
+            None => true,
+            Some(_) if !assigned => true,
+            Some(Anchor::End(end)) => !self.curr_mapping.is_empty() || pos == end,
+            Some(Anchor::Start(start)) => pos >= start,
+        }
+    }
+
+    /// Check if a mapping can still satisfy the anchor once the enumeration
+    /// jumps to a level at position `jump_pos`.
+    fn accepts_jump(&self, mapping: &[(&'i Marker, usize)], jump_pos: usize) -> bool {
+        match self.anchor {
+            None => true,
+            Some(Anchor::End(end)) => !mapping.is_empty() || jump_pos >= end,
+            Some(Anchor::Start(start)) => {
+                jump_pos >= start || mapping.last().map(|(_, pos)| *pos) == Some(start)
+            }
         }
     }
+
+    /// Check if the main span of a complete mapping satisfies the anchor.
+    fn accepts_span(&self, span: Option<Range<usize>>) -> bool {
+        match (self.anchor, span) {
+            (None, _) => true,
+            (Some(Anchor::Start(start)), Some(span)) => span.start == start,
+            (Some(Anchor::End(end)), Some(span)) => span.end == end,
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Check if markers assigned at position `pos` keep the spans within
+    /// their length bounds. As markers are assigned from the end of the text,
+    /// the span of a variable is known when it is opened, and the end of the
+    /// main span is the position of the first marker.
+    fn accepts_lengths(&self, markers: &[&'i Marker], pos: usize) -> bool {
+        let (main, vars) = match &self.length_bounds {
+            None => return true,
+            Some(bounds) => bounds,
+        };
+
+        if markers.is_empty() {
+            return true;
+        }
+
+        let end = self.curr_mapping.first().map(|(_, end)| *end).unwrap_or(pos);
+
+        if end - pos > main.1 {
+            return false;
+        }
+
+        markers.iter().all(|marker| match marker {
+            Marker::Close(_) => true,
+            Marker::Open(var) => {
+                let var_end = self
+                    .curr_mapping
+                    .iter()
+                    .find(|(marker, _)| marker.get_id() == 2 * var.get_id() + 1)
+                    .map(|(_, end)| *end)
+                    .unwrap_or(pos);
+                let (min, max) = vars[var.get_id()];
+                min <= var_end - pos && var_end - pos <= max
+            }
+        })
+    }
+
+    /// Check if a mapping can still satisfy the length bounds once the
+    /// enumeration jumps to a level at position `jump_pos`: the variables
+    /// that are closed but not opened yet will be opened at or before this
+    /// position, and so will the main span.
+    fn accepts_jump_lengths(&self, mapping: &[(&'i Marker, usize)], jump_pos: usize) -> bool {
+        let (main, vars) = match &self.length_bounds {
+            None => return true,
+            Some(bounds) => bounds,
+        };
+
+        let mut pending = mapping.iter().filter(|(marker, _)| match marker {
+            Marker::Open(_) => false,
+            Marker::Close(var) => !mapping
+                .iter()
+                .any(|(other, _)| other.get_id() == 2 * var.get_id()),
+        });
+
+        // The first marker of the mapping is at the end of its main span.
+        pending.all(|(marker, end)| {
+            end - jump_pos <= vars[marker.variable().get_id()].1
+                && mapping[0].1 - jump_pos <= main.1
+        })
+    }
+
+    /// Check if the main span of a complete mapping satisfies the length
+    /// bounds.
+    fn accepts_span_length(&self, span: Option<Range<usize>>) -> bool {
+        match (&self.length_bounds, span) {
+            (None, _) => true,
+            (Some(((min, max), _)), Some(span)) => *min <= span.len() && span.len() <= *max,
+            (Some(((min, _), _)), None) => *min == 0,
+        }
+    }
+}
+
+impl<'i, 't> IndexedDagIterator<'i, 't> {
+    fn into_spans(mut self) -> impl Iterator<Item = Range<usize>> + 'i
+    where
+        't: 'i,
+    {
+        iter::from_fn(move || self.next_markers().map(|markers| markers_span(&markers)))
+            .flatten()
+    }
+}
+
+/// Smallest span containing all the positions of a list of markers.
+fn markers_span(markers: &[(&Marker, usize)]) -> Option<Range<usize>> {
+    let start = markers.iter().map(|(_, pos)| *pos).min()?;
+    let end = markers.iter().map(|(_, pos)| *pos).max()?;
+    Some(start..end)
 }
 
 impl<'i, 't> Iterator for IndexedDagIterator<'i, 't> {
     type Item = Mapping<'t>;
 
     fn next(&mut self) -> Option<Mapping<'t>> {
+        let markers = self.next_markers()?;
+
+        // Re-align level indexes with utf8 coding
+        let aligned_markers = markers
+            .into_iter()
+            .map(|(marker, pos)| (marker.clone(), pos));
+
+        // Create the new mapping
+        Some(Mapping::from_markers(
+            self.indexed_dag.text,
+            aligned_markers,
+            self.num_vars,
+        ))
+    }
+}
+
+impl<'i, 't> IndexedDagIterator<'i, 't> {
+    /// Get the markers assigned by the next mapping, with their positions.
+    fn next_markers(&mut self) -> Option<Vec<(&'i Marker, usize)>> {
         loop {
             // First, consume curr_next_level.
             while let Some((s_p, mut new_gamma)) = self.curr_next_level.next() {
+                self.num_consumed += 1;
+
                 if new_gamma.is_empty() {
                     continue;
                 }
@@ -248,51 +1890,77 @@ impl<'i, 't> Iterator for IndexedDagIterator<'i, 't> {
                 //				}
                 //				println!("");
 
+                let jump = self.indexed_dag.jump.as_ref().unwrap();
+                let curr_pos = jump.get_pos(self.curr_level);
+
+                if !self.accepts_markers(!s_p.is_empty(), curr_pos)
+                    || !self.accepts_lengths(&s_p, curr_pos)
+                {
+                    continue;
+                }
+
                 let mut new_mapping = self.curr_mapping.clone();
                 for marker in s_p {
-                    new_mapping.push((
-                        marker,
-                        self.indexed_dag
-                            .jump
-                            .as_ref()
-                            .unwrap()
-                            .get_pos(self.curr_level),
-                    ));
+                    new_mapping.push((marker, curr_pos));
                 }
 
                 if self.curr_level == 0 {
-                    if new_gamma.contains(self.indexed_dag.automaton.get_initial()) {
-                        // Re-align level indexes with utf8 coding
-                        let aligned_markers = new_mapping
-                            .into_iter()
-                            .map(|(marker, pos)| (marker.clone(), pos));
-
-                        // Create the new mapping
-                        return Some(Mapping::from_markers(
-                            self.indexed_dag.text,
-                            aligned_markers,
-                            self.num_vars,
-                        ));
+                    let span = markers_span(&new_mapping);
+
+                    if new_gamma.contains(self.indexed_dag.automaton.get_initial())
+                        && self.accepts_span(span.clone())
+                        && self.accepts_span_length(span)
+                    {
+                        self.num_found += 1;
+
+                        if self.to_skip > 0 {
+                            self.to_skip -= 1;
+                            continue;
+                        }
+
+                        return Some(new_mapping);
+                    }
+                } else if let Some(jump_level) = jump.jump(self.curr_level, &mut new_gamma) {
+                    if self.caches_dead_vertices() {
+                        self.remove_dead_vertices(jump_level, &mut new_gamma);
+
+                        if new_gamma.is_empty() {
+                            continue;
+                        }
+                    }
+
+                    let jump_pos = jump.get_pos(jump_level);
+
+                    if self.accepts_jump(&new_mapping, jump_pos)
+                        && self.accepts_jump_lengths(&new_mapping, jump_pos)
+                    {
+                        self.stack.push((jump_level, new_gamma, new_mapping));
                     }
-                } else if let Some(jump_level) = self
-                    .indexed_dag
-                    .jump
-                    .as_ref()
-                    .unwrap()
-                    .jump(self.curr_level, &mut new_gamma)
-                {
-                    self.stack.push((jump_level, new_gamma, new_mapping));
                 }
             }
 
+            if self.caches_dead_vertices() {
+                self.close_explored_levels();
+            }
+
             // Overwise, read next element of the stack and init the new
             // `curr_next_level` before restarting the process.
             match self.stack.pop() {
                 None => return None,
                 Some((level, gamma, mapping)) => {
+                    if self.skips_level(level, &gamma) {
+                        continue;
+                    }
+
+                    if self.caches_dead_vertices() {
+                        let explored = (self.stack.len(), level, gamma.clone(), self.num_found);
+                        self.explored.push(explored);
+                    }
+
                     self.curr_level = level;
                     self.curr_mapping = mapping;
-                    self.curr_next_level = self.indexed_dag.next_level(gamma)
+                    self.curr_next_level = self.indexed_dag.next_level(gamma);
+                    self.num_consumed = 0;
                 }
             }
         }
@@ -313,6 +1981,12 @@ impl<'i, 't> Iterator for IndexedDagIterator<'i, 't> {
 
 /// Explore all feasible variable associations in a level from a set of states
 /// and resulting possible states reached for theses associations.
+///
+/// The order of the associations is canonical, so that the mappings are
+/// always enumerated in the same order: the expected markers are sorted by id
+/// and the subsets of markers are explored depth-first, each marker being left
+/// out before it is assigned. The markers of each association are sorted by
+/// id.
 struct NextLevelIterator<'a> {
     automaton: &'a Automaton,
 
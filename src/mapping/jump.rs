@@ -1,7 +1,9 @@
 use bit_set::BitSet;
+use std::cell::{Cell, RefCell};
 use std::cmp::max;
 
-use super::super::matrix::Matrix;
+use super::super::matrix::{Matrix, MatrixArena};
+use super::indexed_dag::JumpSchedule;
 use super::levelset::LevelSet;
 
 /// Holds for some level the id,
@@ -11,6 +13,52 @@ struct Level {
     id: usize,
     jl: Vec<usize>,
     reach: Vec<(usize, Matrix)>,
+    /// With jump pointers, the vertices of the previous level reachable from
+    /// each vertex.
+    preds: PredLists,
+}
+
+/// Lists of vertex indices for each vertex of a level, stored contiguously.
+#[derive(Default)]
+struct PredLists {
+    /// Start of the list of each vertex, followed by the end of the last one.
+    offsets: Vec<u32>,
+    preds: Vec<u32>,
+}
+
+impl PredLists {
+    fn from_sets(sets: &[BitSet]) -> PredLists {
+        let mut offsets = Vec::with_capacity(sets.len() + 1);
+        let mut preds = Vec::with_capacity(sets.iter().map(BitSet::len).sum());
+        offsets.push(0);
+
+        for set in sets {
+            preds.extend(set.iter().map(|pred| pred as u32));
+            offsets.push(preds.len() as u32);
+        }
+
+        PredLists { offsets, preds }
+    }
+
+    fn get(&self, vertex: usize) -> &[u32] {
+        &self.preds[self.offsets[vertex] as usize..self.offsets[vertex + 1] as usize]
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    fn get_memory_usage(&self) -> usize {
+        (self.offsets.capacity() + self.preds.capacity()) * std::mem::size_of::<u32>()
+    }
+}
+
+/// Adjacency of a level being indexed with the last level kept in the index.
+enum Reach {
+    /// Reach matrix and its transpose.
+    Matrices(Matrix, Matrix),
+    /// Set of reachable vertices for each vertex.
+    Pointers(Vec<BitSet>),
 }
 
 //      _
@@ -46,17 +94,54 @@ pub struct Jump {
     /// where i is the last jumpable level init_reach was run on and j is the last level
     /// init_reach was called on. Is empty if i==j.
     reach_matrix: Matrix,
+
+    /// Counterpart of `reach_matrix` with jump pointers, holding the set of
+    /// vertices of level i reachable from each vertex of level j.
+    reach_sets: Vec<BitSet>,
+
+    /// Set if the index stores predecessor lists instead of matrices.
+    jump_pointers: bool,
+
+    /// Owns the storage of all the matrices of the index.
+    arena: MatrixArena,
     last_jl: Vec<usize>,
     last_level_was_jump_target: bool,
 
     /// distance between jump targets
     jump_distance: usize,
 
+    /// Spacing of the jump targets kept in each level
+    jump_schedule: JumpSchedule,
+
     dag_mem_before_trunk: usize,
 
+    /// Memory used by the levels built so far, as estimated by
+    /// `get_matrix_usage` and `get_jl_usage`.
+    index_mem: usize,
+
     /// stores the offset between unicode charcter points and the position in the input string.
     /// is always 0 if the input is ASCII
     offset: usize,
+
+    /// Set if levels are indexed as soon as they are built.
+    single_pass: Option<SinglePass>,
+
+    /// Number of jumps taken during enumeration for each distance in levels
+    /// of the index, and number of matrices applied by these jumps.
+    jump_hist: RefCell<Vec<usize>>,
+    matrix_applications: Cell<usize>,
+}
+
+/// In single-pass mode, only the levels kept in the index and the two levels
+/// being processed are stored in the level set. A level which is not kept is
+/// overwritten as soon as the next level has been indexed.
+struct SinglePass {
+    /// Level reached at the end of the text.
+    last_text_level: usize,
+    /// Some level of the text and its slot in the level set, the next levels
+    /// are stored in the following slots.
+    base_level: usize,
+    base_slot: usize,
 }
 
 impl Jump {
@@ -67,22 +152,36 @@ impl Jump {
         num_levels: usize,
         num_vertices: usize,
         jump_distance: usize,
+        jump_schedule: JumpSchedule,
     ) -> Jump
     where
         T: Iterator<Item = usize>,
     {
+        assert!(jump_distance > 0, "The jump distance must be positive");
+
+        let mut arena = MatrixArena::new();
+        let reach_matrix = Matrix::new(1, 1, &mut arena);
+
         let mut jump = Jump {
             dag_bitmap: LevelSet::new(num_levels, num_vertices),
             last_level: 0,
             jump_vertices: jump_vertices.clone(),
             levels: Vec::new(),
             num_vertices,
-            reach_matrix: Matrix::new(1, 1),
+            reach_matrix,
+            reach_sets: Vec::new(),
+            jump_pointers: false,
+            arena,
             jump_distance,
+            jump_schedule,
             last_jl: Vec::new(),
             dag_mem_before_trunk: 0,
+            index_mem: 0,
             last_level_was_jump_target: true,
             offset: 0,
+            single_pass: None,
+            jump_hist: RefCell::new(Vec::new()),
+            matrix_applications: Cell::new(0),
         };
 
         for state in initial_level {
@@ -95,6 +194,61 @@ impl Jump {
         jump
     }
 
+    /// Switch to single-pass mode, where each level must be indexed with
+    /// `init_reach` right after it is built with `init_next_level`, instead
+    /// of building all levels first. The level set then only grows with the
+    /// number of levels kept in the index, but the levels can't be trimmed.
+    pub fn with_single_pass(mut self, last_text_level: usize) -> Jump {
+        self.dag_bitmap.truncate(2);
+        self.single_pass = Some(SinglePass {
+            last_text_level,
+            base_level: 0,
+            base_slot: 0,
+        });
+        self
+    }
+
+    /// Store the levels of the DAG in a temporary memory-mapped file instead
+    /// of RAM, they are then paged in on demand by the system.
+    pub fn with_mapped_levels(mut self) -> Jump {
+        self.dag_bitmap
+            .map_to_file()
+            .unwrap_or_else(|err| panic!("Could not map levels to a file: {}", err));
+        self
+    }
+
+    /// Store each level of the DAG in a container whose size depends on the
+    /// number of vertices in the level, as in Roaring bitmaps, rather than in
+    /// a bitmap over all the states of the automaton.
+    pub fn with_compressed_levels(mut self) -> Jump {
+        self.dag_bitmap.compress();
+        self
+    }
+
+    /// Only store for each vertex the list of vertices of the previous level
+    /// of the index it can reach, instead of reach matrices to several jump
+    /// targets. This takes much less memory for wide automata, but a jump
+    /// then goes through all the levels of the index down to its target.
+    pub fn with_jump_pointers(mut self) -> Jump {
+        self.jump_pointers = true;
+        self
+    }
+
+    /// Slot of a level in the level set.
+    fn slot(&self, level: usize) -> usize {
+        match &self.single_pass {
+            None => level,
+            Some(single_pass) => single_pass.base_slot + level - single_pass.base_level,
+        }
+    }
+
+    fn is_last_text_level(&self, level: usize) -> bool {
+        match &self.single_pass {
+            None => level == self.last_level,
+            Some(single_pass) => level == single_pass.last_text_level,
+        }
+    }
+
     pub fn get_last_level(&self) -> usize {
         self.last_level
     }
@@ -111,10 +265,16 @@ impl Jump {
     /// current level to the next one and adjacency list of non-jumpable
     /// edges inside the next level.
     pub fn init_next_level(&mut self, jump_adj: &Vec<Vec<usize>>) {
-        let dag_bitmap = &mut self.dag_bitmap;
+        let last_level = self.slot(self.last_level);
+        let next_level = self.slot(self.last_level + 1);
 
-        let last_level = self.last_level;
-        let next_level = self.last_level + 1;
+        if self.single_pass.is_some() {
+            // The slot may hold a level which is not needed anymore.
+            self.dag_bitmap.reserve_levels(next_level + 1);
+            self.dag_bitmap.clear_level(next_level);
+        }
+
+        let dag_bitmap = &mut self.dag_bitmap;
 
         // NOTE: this clone is only necessary for the borrow checker.
         let last_level_vertices = dag_bitmap.get_level(last_level).clone();
@@ -127,45 +287,92 @@ impl Jump {
         }
 
         // If at some point the next level is not reached, the output will be empty
-        // anyway.
-        if dag_bitmap.get_level(next_level).is_empty() {
-            return;
-        }
-
-        self.last_level = next_level;
+        // anyway: the new level is still registered so that `is_disconnected` can
+        // report it.
+        self.last_level += 1;
     }
 
+    /// Only keep the vertices of the last level from which a final state can
+    /// be reached through assignations, the closure being transitive.
     pub fn trim_last_level(&mut self, final_states: &BitSet, nonjump_adj: &Vec<Vec<usize>>) {
-        let mut keep = final_states.clone();
-        for source in 0..nonjump_adj.len() {
-            for &target in &nonjump_adj[source] {
-                if keep.contains(target) {
-                    keep.insert(source);
-                }
-            }
-        }
+        let keep: BitSet = (self.dag_bitmap.get_level(self.last_level).iter())
+            .filter(|&source| {
+                final_states.contains(source)
+                    || nonjump_adj[source].iter().any(|&target| final_states.contains(target))
+            })
+            .collect();
 
         self.dag_bitmap.keep_only(self.last_level, &keep);
     }
 
-    pub fn trim_level(&mut self, level: usize, rev_jump_adj: &Vec<Vec<usize>>) {
+    /// Only keep the vertices of the level before `level` which have a
+    /// successor in `level`, the union of the rows of sources of its vertices
+    /// is computed word by word.
+    pub fn trim_level(&mut self, level: usize, rev_jump_rows: &[BitSet]) {
         let dag_bitmap = &mut self.dag_bitmap;
         let next_level = dag_bitmap.get_level(level);
         let mut keep = BitSet::with_capacity(self.num_vertices);
 
         for target in next_level.iter() {
-            for &source in &rev_jump_adj[target] {
-                keep.insert(source);
-            }
+            keep.union_with(&rev_jump_rows[target]);
         }
 
-        //		println!("keep level: {} curr: {:?} next: {:?} keep {:?}",level, dag_bitmap.get_level(level-1), next_level, keep);
-
         dag_bitmap.keep_only(level - 1, &keep);
     }
 
+    /// Number of vertices that are currently part of a level.
+    pub fn level_width(&self, level: usize) -> usize {
+        self.dag_bitmap.level_width(level)
+    }
+
     pub fn is_disconnected(&self) -> bool {
-        self.dag_bitmap.get_level(self.last_level).is_empty()
+        self.dag_bitmap.get_level(self.slot(self.last_level)).is_empty()
+    }
+
+    /// Vertices of a level, their indices in the level follow the order of the
+    /// set.
+    pub fn get_level_vertices(&self, level: usize) -> BitSet {
+        self.dag_bitmap.get_level(level)
+    }
+
+    /// Jump level of each vertex of a level given by its index, or
+    /// `usize::MAX` if no assignation can be reached from the vertex.
+    pub fn get_jump_levels(&self, level: usize) -> &[usize] {
+        &self.levels[level].jl
+    }
+
+    /// List for each sublevel stored in the reach matrices of a level the
+    /// pairs of vertex indices (in the sublevel, in the level) connected by a
+    /// path without assignation.
+    pub fn get_reach_pairs(&self, level: usize) -> Vec<(usize, Vec<(usize, usize)>)> {
+        if self.jump_pointers && level > 0 {
+            let preds = &self.levels[level].preds;
+            let pairs = (0..preds.len())
+                .flat_map(|target| {
+                    preds.get(target).iter().map(move |&source| (source as usize, target))
+                })
+                .collect();
+
+            return vec![(level - 1, pairs)];
+        }
+
+        self.levels[level]
+            .reach
+            .iter()
+            .map(|(sublevel, matrix)| {
+                let mut pairs = Vec::new();
+
+                for row in 0..matrix.get_height() {
+                    for col in 0..matrix.get_width() {
+                        if matrix.get(&self.arena, row, col) {
+                            pairs.push((row, col));
+                        }
+                    }
+                }
+
+                (*sublevel, pairs)
+            })
+            .collect()
     }
 
     /// Jump to the next relevant level from vertices in gamma at a given level.
@@ -190,19 +397,50 @@ impl Jump {
         }
 
         let mut current_level = level_id;
+        let mut jump_hist = self.jump_hist.borrow_mut();
+        let distance = level_id - jump_level.unwrap();
+
+        if jump_hist.len() <= distance {
+            jump_hist.resize(distance + 1, 0);
+        }
+
+        jump_hist[distance] += 1;
+
+        let mut preds = BitSet::new();
 
         while current_level > jump_level.unwrap() {
-            if let Some((l, matrix)) = level
-                .reach
-                .iter()
-                .find(|&&(id, _)| id >= jump_level.unwrap())
-            {
-                matrix.col_mul_inplace(gamma);
+            // The matrices of a level always include one to the previous
+            // level of the index, the farthest one not beyond the target is
+            // applied. Without matrices, as with jump pointers, the
+            // predecessors of the vertices are followed one level at a time.
+            let step = (level.reach.iter())
+                .filter(|&&(id, _)| id >= jump_level.unwrap())
+                .min_by_key(|&&(id, _)| id);
+
+            if let Some((l, matrix)) = step {
+                matrix.col_mul_inplace(&self.arena, gamma);
+                self.matrix_applications.set(self.matrix_applications.get() + 1);
                 current_level = *l;
-                level = &self.levels[current_level];
+            } else if self.jump_pointers {
+                preds.clear();
+
+                for vertex in gamma.iter() {
+                    for &pred in level.preds.get(vertex) {
+                        preds.insert(pred as usize);
+                    }
+                }
+
+                std::mem::swap(gamma, &mut preds);
+                current_level -= 1;
             } else {
-                panic!("No suitable matrix found for jump.");
+                panic!(
+                    "No suitable matrix found for jump from level {} to {}.",
+                    current_level,
+                    jump_level.unwrap()
+                );
             }
+
+            level = &self.levels[current_level];
         }
 
         self.dag_bitmap
@@ -217,7 +455,7 @@ impl Jump {
             return BitSet::new();
         }
 
-        self.dag_bitmap.get_level(self.last_level).clone()
+        self.dag_bitmap.get_level(self.slot(self.last_level)).clone()
     }
 
     /// Extend current level by reading non-jumpable edges inside the given
@@ -280,7 +518,7 @@ impl Jump {
     }
 
     fn compute_reach(
-        &self,
+        &mut self,
         curr_level: &BitSet,
         prev_level: &BitSet,
         jump_adj: &Vec<Vec<usize>>,
@@ -289,7 +527,7 @@ impl Jump {
         // Compute the adjacency between current level and the previous one.
         let prev_level_len = prev_level.len();
         let mut prev_level_iter = prev_level.iter();
-        let mut new_reach_t = Matrix::new(curr_level.len(), prev_level_len);
+        let mut new_reach_t = Matrix::new(curr_level.len(), prev_level_len, &mut self.arena);
         let mut targets = BitSet::with_capacity(self.num_vertices);
 
         // init new_reach_t to point to last level
@@ -302,32 +540,101 @@ impl Jump {
             }
 
             for id in targets.iter() {
-                new_reach_t.insert(id, id_source);
+                new_reach_t.insert(&mut self.arena, id, id_source);
             }
             targets.clear();
         }
 
         // compute new_reach to point to reach_level
         let new_reach = if self.last_level_was_jump_target {
-            new_reach_t.transpose()
+            new_reach_t.transpose(&mut self.arena)
         } else {
-            &self.reach_matrix * &new_reach_t
+            self.reach_matrix.mul(&new_reach_t, &mut self.arena)
         };
 
         (new_reach, new_reach_t)
     }
 
-    fn init_levels(&mut self) {
+    /// Compute for each vertex of the current level the set of vertices of the
+    /// last level kept in the index that it can reach, given by their indices.
+    fn compute_preds(
+        &self,
+        curr_level: &BitSet,
+        prev_level: &BitSet,
+        jump_adj: &[Vec<usize>],
+        t_to_i: &[usize],
+    ) -> Vec<BitSet> {
+        let mut preds = vec![BitSet::new(); curr_level.len()];
+
+        for (source_index, source) in prev_level.iter().enumerate() {
+            for &target in &jump_adj[source] {
+                let target_index = t_to_i[target];
+
+                if target_index == usize::MAX {
+                    continue;
+                }
+
+                if self.last_level_was_jump_target {
+                    preds[target_index].insert(source_index);
+                } else {
+                    preds[target_index].union_with(&self.reach_sets[source_index]);
+                }
+            }
+        }
+
+        preds
+    }
+
+    /// Initialize the first level of the index, this must be called before
+    /// the first call to `init_reach`.
+    pub fn init_levels(&mut self) {
         self.levels = Vec::new();
         self.levels.push(Level {
             id: 0,
             jl: vec![0; self.dag_bitmap.get_level(0).len()],
             reach: Vec::new(),
+            preds: PredLists::default(),
         })
     }
 
     /// Compute reach and rlevel, that is the effective jump points to all levels
     /// reachable from the current level.
+    /// Compute the adjacency of the level being built with one of its
+    /// sublevels, given the transposed adjacency `reach_t` with the previous
+    /// level.
+    ///
+    /// If the previous level doesn't store a matrix for the sublevel, which
+    /// happens when the DAG is not trimmed, the matrix is composed along the
+    /// closest jumps towards the sublevel. This always succeeds as each level
+    /// stores a matrix to the level right before it.
+    fn reach_sublevel(
+        &mut self,
+        prev_level_no: usize,
+        sublevel: usize,
+        reach_t: &Matrix,
+    ) -> Matrix {
+        let mut current = prev_level_no;
+        let mut composed_t: Option<Matrix> = None;
+
+        loop {
+            let reach = &self.levels[current].reach;
+            let (next, matrix) = &reach[reach.partition_point(|&(id, _)| id < sublevel)];
+            let product = matrix.mul(composed_t.as_ref().unwrap_or(reach_t), &mut self.arena);
+
+            if let Some(composed_t) = composed_t.take() {
+                composed_t.release(&mut self.arena);
+            }
+
+            if *next == sublevel {
+                return product;
+            }
+
+            current = *next;
+            composed_t = Some(product.transpose(&mut self.arena));
+            product.release(&mut self.arena);
+        }
+    }
+
     pub fn init_reach(
         &mut self,
         level: usize,
@@ -335,14 +642,10 @@ impl Jump {
         jump_adj: &Vec<Vec<usize>>,
         nonjump_adj: &Vec<Vec<usize>>,
     ) {
-        if level == 1 {
-            self.init_levels();
-        }
-
         let prev_level_no = self.levels.len() - 1;
 
-        let curr_level = self.dag_bitmap.get_level(level);
-        let prev_level = self.dag_bitmap.get_level(level - 1);
+        let curr_level = self.dag_bitmap.get_level(self.slot(level));
+        let prev_level = self.dag_bitmap.get_level(self.slot(level - 1));
         let last_level = self.levels.last().unwrap();
 
         let jl = if self.last_level_was_jump_target {
@@ -359,12 +662,36 @@ impl Jump {
 
         let new_jl = self.compute_jl(&curr_level, &prev_level, jump_adj, nonjump_adj, jl, &t_to_i);
 
-        let (new_reach, mut new_reach_t) =
-            self.compute_reach(&curr_level, &prev_level, jump_adj, &t_to_i);
+        let reach = if self.jump_pointers {
+            Reach::Pointers(self.compute_preds(&curr_level, &prev_level, jump_adj, &t_to_i))
+        } else {
+            let (new_reach, new_reach_t) =
+                self.compute_reach(&curr_level, &prev_level, jump_adj, &t_to_i);
+            Reach::Matrices(new_reach, new_reach_t)
+        };
 
         // no rlevel will point to this level
-        if curr_level.is_disjoint(&self.jump_vertices) && (level < self.last_level) {
-            self.reach_matrix = new_reach;
+        if curr_level.is_disjoint(&self.jump_vertices) && !self.is_last_text_level(level) {
+            match reach {
+                Reach::Matrices(new_reach, new_reach_t) => {
+                    std::mem::replace(&mut self.reach_matrix, new_reach).release(&mut self.arena);
+                    new_reach_t.release(&mut self.arena);
+                }
+                Reach::Pointers(preds) => self.reach_sets = preds,
+            }
+
+            // In single-pass mode, the slot of the previous level can be
+            // reused if it was not kept.
+            if self.single_pass.is_some() && !self.last_level_was_jump_target {
+                let (curr_slot, prev_slot) = (self.slot(level), self.slot(level - 1));
+                self.dag_bitmap.move_level(curr_slot, prev_slot);
+
+                if let Some(single_pass) = &mut self.single_pass {
+                    single_pass.base_level = level;
+                    single_pass.base_slot = prev_slot;
+                }
+            }
+
             self.last_jl = new_jl;
             self.last_level_was_jump_target = false;
             self.offset += character.len_utf8() - 1;
@@ -372,20 +699,61 @@ impl Jump {
         }
 
         // we remove all levels that cannot be jumped to
-        self.dag_bitmap.move_level(level, prev_level_no + 1);
-        if level == self.last_level {
+        self.dag_bitmap.move_level(self.slot(level), prev_level_no + 1);
+
+        if let Some(single_pass) = &mut self.single_pass {
+            single_pass.base_level = level;
+            single_pass.base_slot = prev_level_no + 1;
+        }
+
+        if self.is_last_text_level(level) {
             self.dag_mem_before_trunk = self.dag_bitmap.get_memory_usage();
             self.last_level = prev_level_no + 1;
             self.dag_bitmap.truncate(prev_level_no + 2);
+            self.single_pass = None;
         }
 
+        let (matrices, preds) = match reach {
+            Reach::Matrices(new_reach, new_reach_t) => {
+                let matrices = self.compute_matrices(prev_level_no, &new_jl, new_reach, new_reach_t);
+                (matrices, PredLists::default())
+            }
+            Reach::Pointers(preds) => (Vec::new(), PredLists::from_sets(&preds)),
+        };
+
+        self.offset += character.len_utf8() - 1;
+
+        let new_level = Level {
+            id: level + self.offset,
+            jl: new_jl,
+            reach: matrices,
+            preds,
+        };
+
+        self.index_mem += Jump::get_level_usage(&new_level);
+
+        self.levels.push(new_level);
+
+        self.last_level_was_jump_target = true;
+    }
+
+    /// Compute the matrices of a new level of the index towards its jump
+    /// targets, given its adjacency with the previous level of the index.
+    fn compute_matrices(
+        &mut self,
+        prev_level_no: usize,
+        new_jl: &[usize],
+        new_reach: Matrix,
+        mut new_reach_t: Matrix,
+    ) -> Vec<(usize, Matrix)> {
         // if necessary, update new_reach_t
         if !self.last_level_was_jump_target {
-            new_reach_t = new_reach.transpose();
+            let transposed = new_reach.transpose(&mut self.arena);
+            std::mem::replace(&mut new_reach_t, transposed).release(&mut self.arena);
         }
 
         //all reachable levels
-        let mut rlev = new_jl.clone();
+        let mut rlev = new_jl.to_vec();
 
         rlev.sort();
         rlev.dedup();
@@ -396,41 +764,53 @@ impl Jump {
 
         let last = rlev[rlev.len() - 1];
 
-        rlev.retain(|&x| (x == last) || (x % self.jump_distance == 0));
+        let curr_level_no = prev_level_no + 1;
+
+        match self.jump_schedule {
+            JumpSchedule::Uniform => {
+                rlev.retain(|&x| (x == last) || (x % self.jump_distance == 0))
+            }
+            JumpSchedule::Exponential => {
+                rlev.retain(|&x| (x == last) || Jump::is_exponential_target(curr_level_no, x))
+            }
+        }
 
         // Compute by a dynamic algorithm the adjacency of current level with all its
         // sublevels.
-        let mut matrix_iterator = last_level.reach.iter();
-
         let mut matrices = Vec::with_capacity(rlev.len());
 
         for sublevel in rlev {
-            if sublevel == prev_level_no {
-                continue;
-            } else {
-                if let Some((_, matrix)) = matrix_iterator.find(|&&(l, _)| l == sublevel) {
-                    matrices.push((sublevel, matrix * &new_reach_t));
-                } else {
-                    panic!(
-                        "Matrix not found for sublevel {} level: {}",
-                        sublevel, level
-                    );
-                }
+            if sublevel != prev_level_no {
+                let matrix = self.reach_sublevel(prev_level_no, sublevel, &new_reach_t);
+                matrices.push((sublevel, matrix));
             }
         }
         matrices.push((prev_level_no, new_reach));
+        new_reach_t.release(&mut self.arena);
+
+        // Matrices are only read from now on, and may be stored as runs of
+        // identical rows.
+        matrices
+            .into_iter()
+            .map(|(sublevel, matrix)| (sublevel, matrix.compress(&mut self.arena)))
+            .collect()
+    }
 
-        self.offset += character.len_utf8() - 1;
-
-        let new_level = Level {
-            id: level + self.offset,
-            jl: new_jl,
-            reach: matrices,
-        };
-
-        self.levels.push(new_level);
+    /// With an exponential schedule, a level keeps a sublevel at distance `d`
+    /// if it is a multiple of the largest power of two not greater than `d`.
+    /// There are at most two such sublevels for each power of two, and as
+    /// this power can only decrease when the distance does, the previous level
+    /// also kept the sublevel, which is required to compute the matrices.
+    fn is_exponential_target(level: usize, sublevel: usize) -> bool {
+        let distance = level - sublevel;
+        let spacing = 1 << (usize::BITS - 1 - distance.leading_zeros());
+        sublevel.is_multiple_of(spacing)
+    }
 
-        self.last_level_was_jump_target = true;
+    /// Number of jumps taken so far for each distance in levels of the index,
+    /// and number of matrices they applied.
+    pub fn get_jump_stats(&self) -> (Vec<usize>, usize) {
+        (self.jump_hist.borrow().clone(), self.matrix_applications.get())
     }
 
     pub fn get_statistics(&self) -> (usize, usize, f64, usize, usize, f64) {
@@ -470,6 +850,31 @@ impl Jump {
         )
     }
 
+    /// Proportion of entries set over all the matrices of the index, or 0 if
+    /// they have no entry.
+    pub fn get_matrix_density(&self) -> f64 {
+        let (ones, size) = MatrixIterator::init(self).fold((0, 0), |(ones, size), x| {
+            (
+                ones + x.count_ones(&self.arena),
+                size + x.get_width() * x.get_height(),
+            )
+        });
+
+        ones as f64 / size.max(1) as f64
+    }
+
+    /// Proportion of entries set in each matrix of the index having entries,
+    /// in increasing order.
+    pub fn get_matrix_densities(&self) -> Vec<f64> {
+        let mut densities: Vec<f64> = MatrixIterator::init(self)
+            .filter(|x| x.get_width() * x.get_height() > 0)
+            .map(|x| x.count_ones(&self.arena) as f64 / (x.get_width() * x.get_height()) as f64)
+            .collect();
+
+        densities.sort_by(f64::total_cmp);
+        densities
+    }
+
     fn get_max_width(&self) -> usize {
         self.levels
             .iter()
@@ -490,13 +895,42 @@ impl Jump {
         )
     }
 
+    /// Rough estimation of the memory used so far, which is kept up to date
+    /// while the index is built.
+    /// Estimation of the memory used in RAM, levels mapped to a file are not
+    /// accounted for.
+    pub fn get_estimated_memory_usage(&self) -> usize {
+        self.get_levels_memory_usage() + self.index_mem
+    }
+
+    /// Memory used by the levels of the DAG, which doesn't depend on the jump
+    /// distance. Levels mapped to a file don't count.
+    pub fn get_levels_memory_usage(&self) -> usize {
+        if self.dag_bitmap.is_mapped() {
+            0
+        } else {
+            self.dag_bitmap.get_memory_usage()
+        }
+    }
+
+    fn get_level_usage(level: &Level) -> usize {
+        level.reach.iter().fold(
+            std::mem::size_of::<Level>() - std::mem::size_of::<Vec<usize>>(),
+            |acc, (_, matrix)| acc + matrix.get_memory_usage(),
+        ) + level.preds.get_memory_usage()
+            + std::mem::size_of::<Vec<usize>>()
+            + level.jl.capacity() * std::mem::size_of::<usize>()
+    }
+
+    /// Memory used by the matrices, or by the predecessor lists with jump
+    /// pointers.
     #[inline(never)]
     fn get_matrix_usage(&self) -> usize {
         self.levels.iter().fold(0, |acc, x| {
             acc + x.reach.iter().fold(
                 std::mem::size_of::<Level>() - std::mem::size_of::<Vec<usize>>(),
                 |acc2, (_, y)| acc2 + y.get_memory_usage(),
-            )
+            ) + x.preds.get_memory_usage()
         })
     }
 
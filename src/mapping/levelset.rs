@@ -1,10 +1,82 @@
 use bit_set::BitSet;
 use bit_vec::BitVec;
 use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
 
 use std::cell::Cell;
 use std::cell::RefCell;
 
+use super::container::{Container, Containers};
+#[cfg(all(unix, feature = "mmap-levels"))]
+use super::mmap::MappedWords;
+
+/// Storage of the levels of a level set.
+enum Levels {
+    /// All levels are stored as bitmaps of the same size, one after the other.
+    Dense(Words),
+    /// Each level is stored in its own container, which only takes a few
+    /// bytes when a level has few vertices.
+    Compressed(Containers),
+}
+
+/// Storage of the bits of dense levels.
+enum Words {
+    Memory(Vec<u32>),
+    #[cfg(all(unix, feature = "mmap-levels"))]
+    Mapped(MappedWords),
+}
+
+impl Words {
+    /// Grow or shrink the storage, new words are zeroed. As a mapped storage
+    /// is expensive to resize, it grows at least by a factor of two.
+    fn resize(&mut self, len: usize) {
+        match self {
+            Words::Memory(words) => {
+                words.resize(len, 0);
+
+                if words.capacity() > len {
+                    words.shrink_to_fit();
+                }
+            }
+            #[cfg(all(unix, feature = "mmap-levels"))]
+            Words::Mapped(words) => {
+                let len = if len > words.len() {
+                    len.max(2 * words.len())
+                } else {
+                    len
+                };
+
+                words
+                    .resize(len)
+                    .unwrap_or_else(|err| panic!("Could not resize mapped levels: {}", err));
+            }
+        }
+    }
+}
+
+impl Deref for Words {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        match self {
+            Words::Memory(words) => words,
+            #[cfg(all(unix, feature = "mmap-levels"))]
+            Words::Mapped(words) => words,
+        }
+    }
+}
+
+impl DerefMut for Words {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        match self {
+            Words::Memory(words) => words,
+            #[cfg(all(unix, feature = "mmap-levels"))]
+            Words::Mapped(words) => words,
+        }
+    }
+}
+
 /// Represent the partitioning into levels of a product graph.
 ///
 /// A same vertex can be store in several levels, and this level hierarchy can
@@ -13,8 +85,11 @@ pub struct LevelSet {
     num_vertices: usize,
     effective_level_size: usize,
     /// Index level contents: `level id` -> `vertex id's list`.
-    levels: BitVec,
-    temp_level: RefCell<BitVec>,
+    levels: Levels,
+    /// Set if the levels are stored in containers whenever this takes less
+    /// memory than dense levels.
+    compressed: bool,
+    temp_level: RefCell<RankedLevel>,
     temp_level_no: Cell<usize>,
     temp_levelset: RefCell<BitSet>,
 }
@@ -26,56 +101,194 @@ impl LevelSet {
         LevelSet {
             num_vertices,
             effective_level_size,
-            levels: BitVec::<u32>::from_elem(effective_level_size * 32 * num_levels, false),
-            temp_level: RefCell::new(BitVec::from_elem(effective_level_size * 32, false)),
-            temp_level_no: Cell::new(0),
+            levels: Levels::Dense(Words::Memory(vec![0; effective_level_size * num_levels])),
+            compressed: false,
+            temp_level: RefCell::new(RankedLevel::new(effective_level_size)),
+            temp_level_no: Cell::new(usize::MAX),
             temp_levelset: RefCell::new(BitSet::with_capacity(num_vertices)),
         }
     }
 
+    /// Move the levels to a temporary memory-mapped file, which is paged in
+    /// on demand rather than kept in RAM.
+    #[cfg(all(unix, feature = "mmap-levels"))]
+    pub fn map_to_file(&mut self) -> io::Result<()> {
+        match &self.levels {
+            Levels::Dense(words) => {
+                let mut mapped = MappedWords::new(words.len())?;
+                mapped.copy_from_slice(words);
+                self.levels = Levels::Dense(Words::Mapped(mapped));
+                Ok(())
+            }
+            Levels::Compressed(_) => Err(io::Error::other(
+                "compressed levels can't be mapped to a file",
+            )),
+        }
+    }
+
+    #[cfg(not(all(unix, feature = "mmap-levels")))]
+    pub fn map_to_file(&mut self) -> io::Result<()> {
+        Err(io::Error::other("memory-mapped levels are not supported by this build"))
+    }
+
+    /// Store each level in a container whose size depends on the number of
+    /// vertices of the level rather than on the total number of vertices.
+    /// The dense levels are kept as long as they take less memory, which is
+    /// checked again as levels are registered and truncated.
+    pub fn compress(&mut self) {
+        self.compressed = true;
+
+        if let Levels::Dense(words) = &self.levels {
+            let containers = Containers::from_words(words, self.effective_level_size);
+
+            if containers.get_memory_usage() < self.get_memory_usage() {
+                self.levels = Levels::Compressed(containers);
+            }
+        }
+    }
+
+    /// Go back to dense levels if the containers take more memory, which is
+    /// the case once most levels are stored as bitmaps.
+    fn decompress_if_larger(&mut self) {
+        if let Levels::Compressed(containers) = &self.levels {
+            let num_words = containers.len() * self.effective_level_size;
+
+            if containers.get_memory_usage() > num_words * 4 {
+                let mut words = vec![0; num_words];
+
+                for (level, level_words) in
+                    words.chunks_mut(self.effective_level_size).enumerate()
+                {
+                    containers.get(level).write_words(level_words);
+                }
+
+                self.levels = Levels::Dense(Words::Memory(words));
+            }
+        }
+    }
+
+    fn num_levels(&self) -> usize {
+        match &self.levels {
+            Levels::Dense(words) => words.len() / self.effective_level_size,
+            Levels::Compressed(containers) => containers.len(),
+        }
+    }
+
     pub fn truncate(&mut self, num_levels: usize) {
-        self.levels
-            .truncate(num_levels * self.effective_level_size * 32);
-        self.levels.shrink_to_fit();
+        match &mut self.levels {
+            Levels::Dense(words) => {
+                let len = num_levels * self.effective_level_size;
+
+                if words.len() > len {
+                    words.resize(len);
+                }
+            }
+            Levels::Compressed(containers) => {
+                if containers.len() > num_levels {
+                    containers.resize(num_levels);
+                }
+            }
+        }
+
+        // The remaining levels may have been trimmed since they were built.
+        if self.compressed {
+            self.compress();
+        }
+    }
+
+    /// Make room for at least `num_levels` levels.
+    pub fn reserve_levels(&mut self, num_levels: usize) {
+        match &mut self.levels {
+            Levels::Dense(words) => {
+                let len = num_levels * self.effective_level_size;
+
+                if words.len() < len {
+                    words.resize(len);
+                }
+            }
+            Levels::Compressed(containers) => {
+                if containers.len() < num_levels {
+                    containers.resize(num_levels);
+                }
+            }
+        }
+    }
+
+    /// Remove all the vertices of a level.
+    pub fn clear_level(&mut self, level: usize) {
+        match &mut self.levels {
+            Levels::Dense(words) => {
+                let start = level * self.effective_level_size;
+
+                for word in &mut words[start..start + self.effective_level_size] {
+                    *word = 0;
+                }
+            }
+            Levels::Compressed(containers) => containers.set(level, Container::new()),
+        }
     }
 
     pub fn move_level(&mut self, level: usize, target: usize) {
-        unsafe {
-            let levels_storage = self.levels.storage_mut();
+        match &mut self.levels {
+            Levels::Dense(words) => {
+                let start = level * self.effective_level_size;
 
-            for i in 0..self.effective_level_size {
-                levels_storage[target * self.effective_level_size + i] =
-                    levels_storage[level * self.effective_level_size + i];
+                words.copy_within(
+                    start..start + self.effective_level_size,
+                    target * self.effective_level_size,
+                );
+            }
+            Levels::Compressed(containers) => {
+                if level != target {
+                    containers.set(target, containers.get(level).clone());
+                }
             }
         }
     }
 
+    /// Write the bitmap of a level into `words`.
+    fn copy_level(&self, level: usize, words: &mut [u32]) {
+        match &self.levels {
+            Levels::Dense(levels_storage) => {
+                let start = level * self.effective_level_size;
+                words.copy_from_slice(&levels_storage[start..start + self.effective_level_size]);
+            }
+            Levels::Compressed(containers) => containers.get(level).write_words(words),
+        }
+    }
+
     pub fn get_level(&self, level: usize) -> BitSet {
         let mut levelset = BitVec::from_elem(self.num_vertices, false);
 
         unsafe {
-            let levels_storage = self.levels.storage();
-            let level_storage = levelset.storage_mut();
-            for i in 0..self.effective_level_size {
-                level_storage[i] = levels_storage[level * self.effective_level_size + i];
-            }
+            self.copy_level(level, levelset.storage_mut());
         }
 
         BitSet::from_bit_vec(levelset)
     }
 
-    fn set_temp(&self, level: usize) {
-        unsafe {
-            if self.temp_level_no.get() != level {
-                let levels_storage = self.levels.storage();
-                let mut temp = self.temp_level.borrow_mut();
-                let temp_storage = temp.storage_mut();
-
-                self.temp_level_no.set(level);
-                for i in 0..self.effective_level_size {
-                    temp_storage[i] = levels_storage[level * self.effective_level_size + i];
-                }
+    /// Number of vertices registered in a level.
+    pub fn level_width(&self, level: usize) -> usize {
+        match &self.levels {
+            Levels::Dense(levels_storage) => {
+                let start = level * self.effective_level_size;
+
+                levels_storage[start..start + self.effective_level_size]
+                    .iter()
+                    .map(|word| word.count_ones() as usize)
+                    .sum()
             }
+            Levels::Compressed(containers) => containers.get(level).len(),
+        }
+    }
+
+    fn set_temp(&self, level: usize) {
+        if self.temp_level_no.get() != level {
+            let mut temp = self.temp_level.borrow_mut();
+
+            self.temp_level_no.set(level);
+            self.copy_level(level, &mut temp.words);
+            temp.update_ranks();
         }
     }
 
@@ -85,33 +298,36 @@ impl LevelSet {
         indices.clear();
         let vertices = indices;
         self.set_temp(level);
-        let level_vec = &self.temp_level.borrow();
-        let mut level_iter = level_vec.iter().enumerate().filter(|&(_, x)| x == true);
-
-        let mut last = 0;
+        let ranked_level = self.temp_level.borrow();
 
-        for i in temp_indices.iter() {
-            let mut diff = i - last;
-            while diff > 0 {
-                level_iter.next();
-                diff -= 1;
-            }
-
-            vertices.insert(level_iter.next().unwrap().0);
-            last = i + 1;
+        for index in temp_indices.iter() {
+            vertices.insert(ranked_level.select(index));
         }
     }
 
     /// Used to trim the graph. Will change indices for the level.
     pub fn keep_only(&mut self, level: usize, vertices: &BitSet) {
-        let mut levelset = self.get_level(level);
-        levelset.intersect_with(vertices);
+        let keep = vertices.get_ref().storage();
+        let keep_word = |i: usize| keep.get(i).copied().unwrap_or(0);
+        let start = level * self.effective_level_size;
 
-        unsafe {
-            let levels_storage = self.levels.storage_mut();
-            let level_storage = levelset.get_ref().storage();
-            for i in 0..self.effective_level_size {
-                levels_storage[level * self.effective_level_size + i] = level_storage[i];
+        match &mut self.levels {
+            Levels::Dense(levels_storage) => {
+                let level_storage = &mut levels_storage[start..start + self.effective_level_size];
+
+                for (i, word) in level_storage.iter_mut().enumerate() {
+                    *word &= keep_word(i);
+                }
+            }
+            Levels::Compressed(containers) => {
+                let mut level_storage = vec![0; self.effective_level_size];
+                containers.get(level).write_words(&mut level_storage);
+
+                for (i, word) in level_storage.iter_mut().enumerate() {
+                    *word &= keep_word(i);
+                }
+
+                containers.set(level, Container::from_words(&level_storage));
             }
         }
     }
@@ -121,27 +337,12 @@ impl LevelSet {
         temp_vertices.clone_from(vertices);
         vertices.clear();
         let indices = vertices;
-        let mut count = 0;
-
         self.set_temp(level);
-        let level_vec = &self.temp_level.borrow();
-        let mut vertex = 0;
-        let mut level_iter = level_vec.iter().map(|x| {
-            if x {
-                count += 1
-            }
-            count
-        });
-
-        let mut cnt = level_iter.next().unwrap();
-
-        for v in temp_vertices.iter() {
-            if level_vec.get(v).unwrap() {
-                while vertex < v {
-                    vertex += 1;
-                    cnt = level_iter.next().unwrap();
-                }
-                indices.insert(cnt - 1);
+        let ranked_level = self.temp_level.borrow();
+
+        for vertex in temp_vertices.iter() {
+            if ranked_level.contains(vertex) {
+                indices.insert(ranked_level.rank(vertex));
             }
         }
     }
@@ -149,21 +350,143 @@ impl LevelSet {
     /// Save a vertex in a level, the vertex need to be unique inside this level
     /// but can be registered in other levels.
     pub fn register(&mut self, level: usize, vertex: usize) {
-        self.levels
-            .set(level * self.effective_level_size * 32 + vertex, true);
+        match &mut self.levels {
+            Levels::Dense(words) => {
+                words[level * self.effective_level_size + vertex / 32] |= 1 << (vertex % 32)
+            }
+            Levels::Compressed(containers) => {
+                containers.insert(level, vertex, self.effective_level_size);
+                self.decompress_if_larger();
+            }
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        match self.levels {
+            Levels::Dense(Words::Memory(_)) | Levels::Compressed(_) => false,
+            #[cfg(all(unix, feature = "mmap-levels"))]
+            Levels::Dense(Words::Mapped(_)) => true,
+        }
     }
 
     pub fn get_memory_usage(&self) -> usize {
-        self.levels.capacity() / 8
+        match &self.levels {
+            Levels::Dense(Words::Memory(words)) => words.capacity() * 4,
+            #[cfg(all(unix, feature = "mmap-levels"))]
+            Levels::Dense(Words::Mapped(words)) => words.len() * 4,
+            Levels::Compressed(containers) => containers.get_memory_usage(),
+        }
+    }
+}
+
+/// Copy of a level along with the number of vertices before each word, which
+/// gives the index of a vertex in the level (rank) and the vertex at some index
+/// (select) with a few popcounts.
+struct RankedLevel {
+    words: Vec<u32>,
+    ranks: Vec<u32>,
+}
+
+impl RankedLevel {
+    fn new(num_words: usize) -> RankedLevel {
+        RankedLevel {
+            words: vec![0; num_words],
+            ranks: vec![0; num_words],
+        }
+    }
+
+    fn update_ranks(&mut self) {
+        let mut count = 0;
+
+        for (word, rank) in self.words.iter().zip(self.ranks.iter_mut()) {
+            *rank = count;
+            count += word.count_ones();
+        }
+    }
+
+    fn contains(&self, vertex: usize) -> bool {
+        self.words[vertex / 32] & (1 << (vertex % 32)) != 0
+    }
+
+    /// Index of a vertex of the level.
+    fn rank(&self, vertex: usize) -> usize {
+        let word = vertex / 32;
+        let mask = (1u32 << (vertex % 32)) - 1;
+        self.ranks[word] as usize + (self.words[word] & mask).count_ones() as usize
+    }
+
+    /// Vertex at some index of the level.
+    fn select(&self, index: usize) -> usize {
+        // Empty words share their rank with the next word, the last word with
+        // a rank not exceeding the index holds the vertex.
+        let word = self.ranks.partition_point(|&rank| rank as usize <= index) - 1;
+        let mut bits = self.words[word];
+
+        for _ in 0..index - self.ranks[word] as usize {
+            bits &= bits - 1;
+        }
+
+        32 * word + bits.trailing_zeros() as usize
     }
 }
 
 impl fmt::Debug for LevelSet {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for level in 0..(self.levels.len() / self.effective_level_size) / 32 {
+        for level in 0..self.num_levels() {
             writeln!(f, "level {}: {:?}", level, self.get_level(level))?;
         }
 
         writeln!(f, "")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dense_when_smaller() {
+        // Two words per level are less than an empty container.
+        let mut levels = LevelSet::new(4, 40);
+        let dense_memory = levels.get_memory_usage();
+        levels.compress();
+        levels.register(1, 7);
+
+        assert_eq!(levels.get_memory_usage(), dense_memory);
+        assert_eq!(levels.get_level(1).iter().collect::<Vec<_>>(), [7]);
+    }
+
+    #[test]
+    fn compressed_when_smaller() {
+        let num_vertices = 100_000;
+        let mut levels = LevelSet::new(4, num_vertices);
+        let dense_memory = levels.get_memory_usage();
+        levels.compress();
+
+        for vertex in (0..num_vertices).step_by(1000) {
+            levels.register(2, vertex);
+        }
+
+        assert!(levels.get_memory_usage() < dense_memory / 10);
+        assert_eq!(levels.level_width(2), 100);
+
+        // Full levels are bitmaps, which are smaller without containers.
+        for level in 0..4 {
+            for vertex in 0..num_vertices {
+                levels.register(level, vertex);
+            }
+        }
+
+        assert_eq!(levels.get_memory_usage(), dense_memory);
+        assert_eq!(levels.level_width(3), num_vertices);
+
+        // Trimmed levels are compressed again once the levels are truncated.
+        for level in 0..3 {
+            levels.keep_only(level, &BitSet::new());
+        }
+
+        levels.truncate(3);
+        assert!(levels.get_memory_usage() < dense_memory / 10);
+        assert_eq!(levels.level_width(0), 0);
+    }
+}
@@ -0,0 +1,133 @@
+//  __  __
+// |  \/  |_ __ ___  _ __ ___   __ _ _ __
+// | |\/| | '_ ` _ \| '_ ` _ \ / _` | '_ \
+// | |  | | | | | | | | | | | | (_| | |_) |
+// |_|  |_|_| |_| |_|_| |_| |_|\__,_| .__/
+//                                  |_|
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use std::process;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of temporary files created so far, to give them unique names.
+static NUM_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Buffer of words stored in a temporary file which is mapped in memory. The
+/// system pages the buffer in on demand and can write it back to the disk
+/// under memory pressure, instead of holding it in RAM.
+pub struct MappedWords {
+    file: File,
+    data: *mut u32,
+    len: usize,
+}
+
+impl MappedWords {
+    /// Create a buffer of `len` zeroed words. The file is created in the
+    /// temporary directory, which can be set with `TMPDIR`, and unlinked right
+    /// away so that it is removed whenever the process exits.
+    pub fn new(len: usize) -> io::Result<MappedWords> {
+        let path = env::temp_dir().join(format!(
+            "enum-spanner-{}-{}.levels",
+            process::id(),
+            NUM_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        fs::remove_file(&path)?;
+
+        let mut words = MappedWords {
+            file,
+            data: ptr::null_mut(),
+            len: 0,
+        };
+
+        words.resize(len)?;
+        Ok(words)
+    }
+
+    /// Grow or shrink the buffer, new words are zeroed.
+    pub fn resize(&mut self, len: usize) -> io::Result<()> {
+        self.unmap();
+        self.file.set_len((len * mem::size_of::<u32>()) as u64)?;
+
+        if len > 0 {
+            let data = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len * mem::size_of::<u32>(),
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    self.file.as_raw_fd(),
+                    0,
+                )
+            };
+
+            if data == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            self.data = data as *mut u32;
+            self.len = len;
+        }
+
+        Ok(())
+    }
+
+    fn unmap(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(
+                    self.data as *mut libc::c_void,
+                    self.len * mem::size_of::<u32>(),
+                );
+            }
+        }
+
+        self.data = ptr::null_mut();
+        self.len = 0;
+    }
+}
+
+// The mapping is owned by the buffer and only accessed through it, as for a
+// `Vec<u32>`.
+unsafe impl Send for MappedWords {}
+unsafe impl Sync for MappedWords {}
+
+impl Deref for MappedWords {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        if self.len == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.data, self.len) }
+        }
+    }
+}
+
+impl DerefMut for MappedWords {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        if self.len == 0 {
+            &mut []
+        } else {
+            unsafe { slice::from_raw_parts_mut(self.data, self.len) }
+        }
+    }
+}
+
+impl Drop for MappedWords {
+    fn drop(&mut self) {
+        self.unmap();
+    }
+}
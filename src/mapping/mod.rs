@@ -1,19 +1,127 @@
+pub mod eva;
 pub mod indexed_dag;
+pub mod sliding_window;
 
+mod container;
 mod jump;
 mod levelset;
+#[cfg(all(unix, feature = "mmap-levels"))]
+mod mmap;
 
 use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::iter;
 use std::ops::Range;
-use std::rc::Rc;
-
-pub use indexed_dag::IndexedDag;
-
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::automaton::{Automaton, Label};
+
+pub use eva::{DeterministicEva, Order, Weights};
+pub use indexed_dag::{
+    Anchor, Cursor, IndexedDag, IndexedDagBuilder, IndexedDagIterator, LengthBounds, NoMatch,
+    RunStep,
+};
+pub use sliding_window::SlidingWindow;
+
+/// Enumeration of the mappings of an automaton over a text, implemented by
+/// the indexed algorithms as well as the naive ones, which can thus be used
+/// interchangeably.
+///
+/// The enumerator must be preprocessed before any of the other methods is
+/// called.
 pub trait SpannerEnumerator<'t> {
+    /// Build the structures used by the enumeration.
     fn preprocess(&mut self);
+
+    /// Enumerate all the mappings, each one being given once.
     fn iter<'i>(&'i self) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i>;
+
+    /// Number of mappings, by default by enumerating all of them.
+    fn count(&self) -> u128 {
+        self.iter().count() as u128
+    }
+
+    /// First mapping enumerated, if any.
+    fn first(&self) -> Option<Mapping<'t>> {
+        self.iter().next()
+    }
+
+    /// Enumerate at most `limit` mappings, after skipping the `offset` first
+    /// ones of `iter`. By default the skipped mappings are enumerated.
+    fn iter_range<'i>(
+        &'i self,
+        offset: usize,
+        limit: usize,
+    ) -> Box<dyn Iterator<Item = Mapping<'t>> + 'i>
+    where
+        't: 'i,
+    {
+        Box::new(self.iter().skip(offset).take(limit))
+    }
+
+    /// Enumerate all the mappings with an iterator knowing how many are left,
+    /// their number being given by `count` beforehand.
+    fn iter_exact<'i>(&'i self) -> ExactIter<Box<dyn Iterator<Item = Mapping<'t>> + 'i>> {
+        let count = usize::try_from(self.count()).expect("The number of mappings overflows usize");
+        ExactIter::new(self.iter(), count)
+    }
+
+    /// Statistics on the structures built by the preprocessing, none are
+    /// known by default.
+    fn stats(&self) -> EnumeratorStats {
+        EnumeratorStats::default()
+    }
+}
+
+/// Iterator over mappings whose number is known beforehand, which reports it
+/// through `size_hint` to let consumers preallocate buffers or display their
+/// progress.
+pub struct ExactIter<I> {
+    iterator: I,
+    remaining: usize,
+}
+
+impl<I> ExactIter<I> {
+    /// Wrap an iterator yielding exactly `count` items.
+    pub fn new(iterator: I, count: usize) -> ExactIter<I> {
+        ExactIter {
+            iterator,
+            remaining: count,
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for ExactIter<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.iterator.next()?;
+        self.remaining = self.remaining.saturating_sub(1);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<I: Iterator> ExactSizeIterator for ExactIter<I> {}
+
+/// Statistics reported by an enumerator once preprocessed, each of them being
+/// `None` if the enumerator doesn't keep track of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnumeratorStats {
+    /// Time spent by the preprocessing.
+    pub preprocess_time: Option<Duration>,
+    /// Rough estimation of the memory used by the index, in bytes.
+    pub memory_usage: Option<usize>,
+    /// Number of elements of the index: levels of the DAG or nodes of the
+    /// runs.
+    pub index_size: Option<usize>,
 }
 
 //  __  __                   _
@@ -59,6 +167,48 @@ impl<'t> Mapping<'t> {
         })
     }
 
+    /// Span assigned to a group, if the group is bound by the mapping.
+    pub fn get(&self, name: &str) -> Option<Range<usize>> {
+        self.maps.iter().find_map(|x| match x {
+            Some((key, range)) if key.get_name() == name => Some(range.clone()),
+            _ => None,
+        })
+    }
+
+    /// Text assigned to a group, if the group is bound by the mapping.
+    pub fn get_text(&self, name: &str) -> Option<&'t str> {
+        self.get(name).map(|range| &self.text[range])
+    }
+
+    /// Mapping binding groups of a schema, the variable of each group is
+    /// numbered after its position in the schema.
+    pub fn from_groups<'a, I>(text: &'t str, schema: &[String], groups: I) -> Mapping<'t>
+    where
+        I: IntoIterator<Item = (&'a str, Range<usize>)>,
+    {
+        let mut maps = vec![None; schema.len()];
+
+        for (name, range) in groups {
+            let id = match schema.iter().position(|group| group == name) {
+                Some(id) => id,
+                None => panic!("Group {} is not in the schema", name),
+            };
+
+            maps[id] = Some((Variable::new(name.to_string(), id), range));
+        }
+
+        Mapping { text, maps }
+    }
+
+    /// Mapping where the given groups are left unbound.
+    pub fn without_groups(&self, groups: &[String]) -> Mapping<'t> {
+        let maps = (self.maps.iter())
+            .map(|x| x.clone().filter(|(key, _)| !groups.iter().any(|g| g == key.get_name())))
+            .collect();
+
+        Mapping { text: self.text, maps }
+    }
+
     /// Return a canonical mapping for a classic semantic with no group, which
     /// will assign the whole match to a group called "match".
     pub fn from_single_match(text: &'t str, range: Range<usize>) -> Mapping<'t> {
@@ -90,6 +240,92 @@ impl<'t> Mapping<'t> {
 
         Mapping { text, maps }
     }
+
+    /// Ids of the markers assigned by the mapping at each position of the
+    /// text.
+    fn markers_by_position(&self) -> HashMap<usize, Vec<usize>> {
+        let mut markers: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        for (var, range) in self.maps.iter().flatten() {
+            markers.entry(range.start).or_default().push(2 * var.get_id());
+            markers.entry(range.end).or_default().push(2 * var.get_id() + 1);
+        }
+
+        markers
+    }
+
+    /// Number of distinct accepting runs of an automaton over the text which
+    /// produce this mapping, which is larger than one if the automaton is
+    /// ambiguous. Runs assigning the markers of a position in different
+    /// orders are counted as distinct, and the count saturates at
+    /// `u64::MAX`. Merging equivalent states of the automaton merges their
+    /// runs, see `regex::compile_unmerged_with`.
+    pub fn count_runs(&self, automaton: &Automaton) -> u64 {
+        let adj = automaton.get_adj();
+        let markers = self.markers_by_position();
+
+        // Number of runs reaching each vertex `(assigned, state)` of the
+        // current position, where `assigned` is a mask over the markers to
+        // assign at this position which are already assigned.
+        let mut curr: BTreeMap<(u64, usize), u64> = BTreeMap::new();
+        curr.insert((0, automaton.get_initial()), 1);
+        let positions = (self.text.char_indices())
+            .map(|(pos, x)| (pos, Some(x)))
+            .chain(iter::once((self.text.len(), None)));
+
+        for (pos, next) in positions {
+            let expected = markers.get(&pos).map_or(&[][..], Vec::as_slice);
+            assert!(expected.len() < 64, "Too many markers assigned at the same position");
+            let complete = (1u64 << expected.len()) - 1;
+            let mut assigned = Vec::new();
+
+            // Assigning a marker increases the mask, so that vertices are
+            // reached by increasing order of masks.
+            while let Some(((mask, state), count)) = curr.pop_first() {
+                if mask == complete {
+                    assigned.push((state, count));
+                }
+
+                for (label, target) in &adj[state] {
+                    let marker = match &**label {
+                        Label::Assignation(marker) => marker.get_id(),
+                        _ => continue,
+                    };
+
+                    match expected.iter().position(|&id| id == marker) {
+                        Some(index) if mask & (1 << index) == 0 => {
+                            let runs = curr.entry((mask | 1 << index, *target)).or_insert(0);
+                            *runs = runs.saturating_add(count);
+                        }
+                        _ => continue,
+                    }
+                }
+            }
+
+            let character = match next {
+                Some(character) => character,
+                None => {
+                    return assigned
+                        .into_iter()
+                        .filter(|&(state, _)| automaton.finals.contains(state))
+                        .fold(0, |total, (_, count)| total.saturating_add(count))
+                }
+            };
+
+            for (state, count) in assigned {
+                for (label, target) in &adj[state] {
+                    if let Label::Atom(atom) = &**label {
+                        if atom.is_match(&character) {
+                            let runs = curr.entry((0, *target)).or_insert(0);
+                            *runs = runs.saturating_add(count);
+                        }
+                    }
+                }
+            }
+        }
+
+        0
+    }
 }
 
 impl<'t> fmt::Display for Mapping<'t> {
@@ -115,6 +351,94 @@ impl<'t> std::hash::Hash for Mapping<'t> {
     }
 }
 
+/// Mapping which owns the text it refers to, so that it can outlive the
+/// document, be sent to other threads or be stored once the text is dropped.
+///
+/// Spans are still given as offsets in the whole document, but only the part
+/// of the text covered by the mapping needs to be kept. Mappings over the same
+/// document can instead share it through `with_text`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OwnedMapping {
+    text: Arc<str>,
+    /// Offset of the owned text in the document.
+    offset: usize,
+    maps: Vec<Option<(Variable, Range<usize>)>>,
+}
+
+impl OwnedMapping {
+    /// Owned mapping sharing the whole text of the document, which must be
+    /// the text of the mapping.
+    pub fn with_text(mapping: &Mapping, text: Arc<str>) -> OwnedMapping {
+        assert_eq!(&*text, mapping.text, "The mapping is not over this text");
+
+        OwnedMapping {
+            text,
+            offset: 0,
+            maps: mapping.maps.clone(),
+        }
+    }
+
+    pub fn main_span(&self) -> Option<Range<usize>> {
+        let mut spans = self.iter_groups().map(|(_, range)| range);
+        let first = spans.next()?;
+
+        Some(spans.fold(first, |acc, range| {
+            cmp::min(acc.start, range.start)..cmp::max(acc.end, range.end)
+        }))
+    }
+
+    pub fn iter_groups(&self) -> impl Iterator<Item = (&str, Range<usize>)> {
+        self.maps.iter().flatten().map(|(key, range)| (key.get_name(), range.clone()))
+    }
+
+    pub fn iter_groups_text(&self) -> impl Iterator<Item = (&str, &str)> {
+        (self.maps.iter().flatten()).map(move |(key, range)| (key.get_name(), self.slice(range)))
+    }
+
+    /// Span assigned to a group, if the group is bound by the mapping.
+    pub fn get(&self, name: &str) -> Option<Range<usize>> {
+        self.iter_groups().find(|(key, _)| *key == name).map(|(_, range)| range)
+    }
+
+    /// Text assigned to a group, if the group is bound by the mapping.
+    pub fn get_text(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|range| self.slice(&range))
+    }
+
+    fn slice(&self, range: &Range<usize>) -> &str {
+        &self.text[range.start - self.offset..range.end - self.offset]
+    }
+}
+
+impl<'t> From<&Mapping<'t>> for OwnedMapping {
+    /// Copy the part of the text covered by the mapping.
+    fn from(mapping: &Mapping<'t>) -> OwnedMapping {
+        let span = mapping.main_span().unwrap_or(0..0);
+
+        OwnedMapping {
+            text: Arc::from(&mapping.text[span.clone()]),
+            offset: span.start,
+            maps: mapping.maps.clone(),
+        }
+    }
+}
+
+impl<'t> From<Mapping<'t>> for OwnedMapping {
+    fn from(mapping: Mapping<'t>) -> OwnedMapping {
+        OwnedMapping::from(&mapping)
+    }
+}
+
+impl fmt::Display for OwnedMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (var, range) in self.maps.iter().flatten() {
+            write!(f, "{}: ({}, {}) ", var, range.start, range.end)?;
+        }
+
+        Ok(())
+    }
+}
+
 // __     __         _       _     _
 // \ \   / /_ _ _ __(_) __ _| |__ | | ___
 //  \ \ / / _` | '__| |/ _` | '_ \| |/ _ \
@@ -169,8 +493,8 @@ impl fmt::Display for Variable {
 //
 #[derive(Clone, Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub enum Marker {
-    Open(Rc<Variable>),
-    Close(Rc<Variable>),
+    Open(Arc<Variable>),
+    Close(Arc<Variable>),
 }
 
 impl Marker {
@@ -0,0 +1,124 @@
+//  ____  _ _     _ _                 __        ___           _
+// / ___|| (_) __| (_)_ __   __ _    \ \      / (_)_ __   __| | _____      __
+// \___ \| | |/ _` | | '_ \ / _` |____\ \ /\ / /| | '_ \ / _` |/ _ \ \ /\ / /
+//  ___) | | | (_| | | | | | (_| |_____\ V  V / | | | | | (_| | (_) \ V  V /
+// |____/|_|_|\__,_|_|_| |_|\__, |      \_/\_/  |_|_| |_|\__,_|\___/ \_/\_/
+//                          |___/
+
+use super::super::automaton::Automaton;
+use super::indexed_dag::{IndexedDag, TrimmingStrategy};
+use super::{Mapping, SpannerEnumerator};
+
+/// Enumerate the matches of an automaton over an unbounded stream of text,
+/// only keeping the last `window` bytes of the stream in memory.
+///
+/// Chunks pushed into the stream are buffered until `window` new bytes are
+/// available, then the buffer is indexed and the matches ending in the new
+/// bytes are enumerated with constant delay. The text and the index that are
+/// older than the window are dropped afterwards, thus only matches spanning at
+/// most `window` bytes are enumerated.
+///
+/// As the buffer is indexed on its own, `^` and `$` refer to the bounds of
+/// the buffer rather than of the stream.
+pub struct SlidingWindow {
+    automaton: Automaton,
+    window: usize,
+    jump_distance: usize,
+    trimming_strategy: TrimmingStrategy,
+
+    /// Text of the stream which is still in the window.
+    buffer: String,
+    /// Position of the start of the buffer in the stream.
+    offset: usize,
+    /// Position in the stream of the first possible end of a match that was
+    /// not enumerated yet.
+    next_end: usize,
+}
+
+impl SlidingWindow {
+    pub fn new(automaton: Automaton, window: usize) -> SlidingWindow {
+        assert!(window > 0, "The window can't be empty");
+
+        SlidingWindow {
+            automaton,
+            window,
+            jump_distance: 1,
+            trimming_strategy: TrimmingStrategy::FullTrimming,
+            buffer: String::new(),
+            offset: 0,
+            next_end: 0,
+        }
+    }
+
+    pub fn with_index_options(
+        mut self,
+        jump_distance: usize,
+        trimming_strategy: TrimmingStrategy,
+    ) -> SlidingWindow {
+        self.jump_distance = jump_distance;
+        self.trimming_strategy = trimming_strategy;
+        self
+    }
+
+    /// Append a chunk of text to the stream. The callback is given the
+    /// position of the text of mappings in the stream and each new match.
+    pub fn push<F>(&mut self, chunk: &str, callback: F)
+    where
+        F: FnMut(usize, &Mapping),
+    {
+        self.buffer.push_str(chunk);
+
+        if self.offset + self.buffer.len() >= self.next_end + self.window {
+            self.process(callback);
+        }
+    }
+
+    /// Enumerate the matches that are still pending at the end of the stream.
+    pub fn finish<F>(&mut self, callback: F)
+    where
+        F: FnMut(usize, &Mapping),
+    {
+        if self.offset + self.buffer.len() >= self.next_end {
+            self.process(callback);
+        }
+    }
+
+    fn process<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(usize, &Mapping),
+    {
+        let buffer_end = self.offset + self.buffer.len();
+
+        {
+            let mut index = IndexedDag::builder(self.automaton.clone(), &self.buffer)
+                .with_jump_distance(self.jump_distance)
+                .with_trimming(self.trimming_strategy)
+                .build();
+            index.preprocess();
+
+            for mapping in index.iter() {
+                let span = mapping
+                    .main_span()
+                    .expect("A mapping should never be empty");
+
+                if self.offset + span.end >= self.next_end && span.len() <= self.window {
+                    callback(self.offset, &mapping);
+                }
+            }
+        }
+
+        // A match ending after `buffer_end` spans at most `window` bytes, so
+        // it can't start before `buffer_end + 1 - window`.
+        self.next_end = buffer_end + 1;
+        let mut evicted = (self.next_end.saturating_sub(self.window))
+            .saturating_sub(self.offset)
+            .min(self.buffer.len());
+
+        while !self.buffer.is_char_boundary(evicted) {
+            evicted -= 1;
+        }
+
+        self.buffer.drain(..evicted);
+        self.offset += evicted;
+    }
+}
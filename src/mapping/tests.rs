@@ -1,19 +1,41 @@
 use std::collections::HashSet;
 
 use super::super::automaton::Automaton;
+use super::super::gazetteer::Gazetteer;
+use super::super::naive::naive;
+use super::super::offsets::OffsetUnit;
+use super::super::predicate::Predicates;
+use super::super::progress::Phase;
 use super::super::regex;
-use super::{naive, Mapping};
+use super::super::regex::Construction;
+use super::super::semiring::{Annotation, Provenance, Semiring};
+use super::indexed_dag::{
+    Anchor, Cursor, IndexedDag, JumpSchedule, LengthBounds, NoMatch, TrimmingStrategy,
+};
+use super::{
+    DeterministicEva, Mapping, Order, OwnedMapping, SlidingWindow, SpannerEnumerator, Weights,
+};
 
 /// Build a HashSet collecting results of naive algorithm.
-fn naive_results<'t>(regex: &Automaton, text: &'t str) -> HashSet<Mapping<'t>> {
-    naive::NaiveEnum::new(regex, text).collect()
+fn naive_results<'t>(regex: &'t Automaton, text: &'t str) -> HashSet<Mapping<'t>> {
+    naive::NaiveEnum::new(regex, text).iter().collect()
 }
 
 /// Build a HashSet collecting results of default algorithm.
 fn default_results<'t>(regex: &Automaton, text: &'t str) -> HashSet<Mapping<'t>> {
-    regex::compile_matches(regex.clone(), text, 1)
-        .iter()
-        .collect()
+    trimmed_results(regex, text, TrimmingStrategy::FullTrimming)
+}
+
+/// Build a HashSet collecting results of default algorithm with a given
+/// trimming strategy.
+fn trimmed_results<'t>(
+    regex: &Automaton,
+    text: &'t str,
+    trimming: TrimmingStrategy,
+) -> HashSet<Mapping<'t>> {
+    let mut dag = IndexedDag::builder(regex.clone(), text).with_trimming(trimming).build();
+    dag.preprocess();
+    dag.iter().collect()
 }
 
 #[test]
@@ -76,3 +98,1077 @@ fn some_utf8() {
         assert_eq!(naive_results(&regex, text), default_results(&regex, text));
     }
 }
+
+#[test]
+fn adaptive_trimming() {
+    let regex = regex::compile(r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)");
+    let text = "aa@aa a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+
+    for &threshold in &[0, 1, 4, 8, 1000] {
+        assert_eq!(
+            naive_results(&regex, text),
+            trimmed_results(&regex, text, TrimmingStrategy::AdaptiveTrimming(threshold))
+        );
+    }
+}
+
+#[test]
+fn lazy_trimming() {
+    let regex = regex::compile(r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)");
+    let text = "aa@aa a@a.a@a.a.a@a.a.a.a@a.a.a.a.a @a. a@";
+    let mut dag = IndexedDag::builder(regex.clone(), text)
+        .with_trimming(TrimmingStrategy::LazyTrimming)
+        .build();
+    dag.preprocess();
+
+    // The second enumeration uses dead vertices cached by the first one.
+    for _ in 0..2 {
+        assert_eq!(naive_results(&regex, text), dag.iter().collect());
+    }
+}
+
+#[cfg(all(unix, feature = "mmap-levels"))]
+#[test]
+fn mapped_levels() {
+    let regex = regex::compile(r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)");
+    let text = "aa@aa a@a.a@a.a.a@a.a.a.a@a.a.a.a.a @a. a@";
+
+    for &single_pass in &[false, true] {
+        let mut dag = IndexedDag::builder(regex.clone(), text)
+            .with_jump_distance(2)
+            .with_single_pass(single_pass)
+            .with_mapped_levels(true)
+            .build();
+        dag.preprocess();
+
+        assert_eq!(naive_results(&regex, text), dag.iter().collect());
+    }
+}
+
+#[test]
+fn compressed_levels() {
+    let regexes = [
+        r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.(com|org|net|fr))",
+        r"(?P<x>a)[^@]{0,40}(?P<y>b)",
+    ];
+    let text = "aa@aa.fr a@a.a@a.com.a@a.org.a@a.a.a.net ab ba bba@bab";
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+
+        for &single_pass in &[false, true] {
+            let mut dag = IndexedDag::builder(regex.clone(), text)
+                .with_jump_distance(2)
+                .with_single_pass(single_pass)
+                .with_compressed_levels(true)
+                .build();
+            dag.preprocess();
+
+            assert_eq!(naive_results(&regex, text), dag.iter().collect());
+        }
+    }
+}
+
+#[test]
+fn jump_pointers() {
+    let regexes = [
+        r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.(com|org|net|fr))",
+        r"(?P<x>a)[^@]{0,40}(?P<y>b)",
+    ];
+    let text = "aa@aa.fr a@a.a@a.com.a@a.org.a@a.a.a.net ab ba bba@bab";
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+
+        for &trimming in &[TrimmingStrategy::FullTrimming, TrimmingStrategy::NoTrimming] {
+            let mut dag = IndexedDag::builder(regex.clone(), text)
+                .with_jump_distance(3)
+                .with_trimming(trimming)
+                .with_jump_pointers(true)
+                .build();
+            dag.preprocess();
+
+            assert_eq!(naive_results(&regex, text), dag.iter().collect());
+        }
+    }
+}
+
+#[test]
+fn deterministic_eva() {
+    let regexes = [
+        r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)",
+        r"^(.*[^a])?(?P<block_a>a+)([^a].*[^b]|[^ab])?(?P<block_b>b+)([^b].*)?$",
+        r"(?P<x>a*)(?P<y>b*)",
+    ];
+    let text = "aa@aa abab a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+        let mut eva = DeterministicEva::new(regex.clone(), text);
+        eva.preprocess();
+
+        // Mappings are produced by distinct runs, thus never repeated.
+        let results: Vec<_> = eva.iter().collect();
+        let distinct: HashSet<_> = results.iter().cloned().collect();
+        assert_eq!(results.len(), distinct.len());
+        assert_eq!(naive_results(&regex, text), distinct);
+
+        // Counting the runs gives the number of mappings.
+        let count = DeterministicEva::new(regex.clone(), text).count_runs();
+        assert_eq!(count, results.len() as u128);
+    }
+}
+
+#[test]
+fn count_without_enumeration() {
+    let regexes = [
+        r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)",
+        r"(?P<x>a*)(?P<y>b*)",
+        r"((?P<x>a)|(?P<y>b))[ab]",
+        r"(?P<x>é+)?ê",
+        r"(?P<x>.*)",
+        r"z",
+    ];
+    let long_text = "ab".repeat(60);
+    let texts = ["", "aa@aa abab a@a.a@a.a.a", "éêéé ê aabb", &long_text];
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+
+        for text in texts.iter() {
+            let count = DeterministicEva::new(regex.clone(), text).count_runs();
+            let expected = default_results(&regex, text);
+            assert_eq!(count, expected.len() as u128);
+            assert_eq!(naive_results(&regex, text), expected);
+        }
+    }
+}
+
+#[test]
+fn ranked_enumeration() {
+    let regex = regex::compile(r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)");
+    let text = "aa@aa abab a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+    let weights = Weights::new()
+        .with_length_weight("login", 2)
+        .with_length_weight("server", -1)
+        .with_priority("server", 3);
+
+    let mut eva = DeterministicEva::new(regex.clone(), text).with_weights(weights.clone());
+    eva.preprocess();
+
+    // Mappings are given by non-increasing weight, which is computed right.
+    let results: Vec<_> = eva.iter_ranked().collect();
+
+    for (weight, mapping) in &results {
+        assert_eq!(*weight, weights.weight(mapping));
+    }
+
+    for pair in results.windows(2) {
+        assert!(pair[0].0 >= pair[1].0);
+    }
+
+    let distinct: HashSet<_> = results.into_iter().map(|(_, mapping)| mapping).collect();
+    assert_eq!(naive_results(&regex, text), distinct);
+}
+
+#[test]
+fn span_order() {
+    let regex = regex::compile(r"(?P<x>a*)(?P<y>b*)");
+    let text = "aa@aa abab a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+
+    for order in [Order::ShortestFirst, Order::LongestFirst].iter() {
+        let mut eva = DeterministicEva::new(regex.clone(), text).with_order(order.clone());
+        eva.preprocess();
+
+        let results: Vec<_> = eva.iter_ranked().collect();
+        let lengths: Vec<_> = results
+            .iter()
+            .map(|(length, mapping)| {
+                let span = mapping.main_span().unwrap();
+                assert_eq!(*length, (span.end - span.start) as i64);
+                length
+            })
+            .collect();
+
+        let mut sorted = lengths.clone();
+        sorted.sort();
+
+        if let Order::LongestFirst = order {
+            sorted.reverse();
+        }
+
+        assert_eq!(lengths, sorted);
+
+        let distinct: HashSet<_> = results.into_iter().map(|(_, mapping)| mapping).collect();
+        assert_eq!(naive_results(&regex, text), distinct);
+    }
+}
+
+#[test]
+fn semiring_scores() {
+    let regex = regex::compile(r"(?P<x>a+)@(?P<y>a+)?");
+    let text = "aa@aa";
+    let mappings: Vec<_> = default_results(&regex, text).into_iter().collect();
+
+    let annotation = Annotation::new(Semiring::Probability)
+        .with_group_score("x", 0.5)
+        .with_char_score("y", 0.1);
+
+    for mapping in &mappings {
+        let expected = match mapping.get("y") {
+            Some(span) => 0.5 * 0.1f64.powi(span.len() as i32),
+            None => 0.5,
+        };
+        assert!((annotation.score(mapping) - expected).abs() < 1e-9);
+    }
+
+    // Only the cheapest mapping counts in the tropical semiring.
+    let annotation = Annotation::new(Semiring::Tropical).with_char_score("x", 1.);
+    assert_eq!(annotation.total(mappings), 1.);
+}
+
+#[test]
+fn provenance() {
+    let regex = regex::compile(r"(?P<x>(?P<t>a)|(?P<u>[ab]))b?");
+    let mappings = default_results(&regex, "ab");
+    let tokens = vec!["t".to_string(), "u".to_string()];
+    let projections = Provenance::project(mappings, &tokens);
+
+    for (mapping, provenance) in &projections {
+        assert!(mapping.get("t").is_none() && mapping.get("u").is_none());
+
+        let expected = match mapping.get_text("x").unwrap() {
+            "a" => Provenance::token("t").add(&Provenance::token("u")),
+            _ => Provenance::token("u"),
+        };
+        assert_eq!(provenance, &expected);
+    }
+
+    assert_eq!(projections.len(), 2);
+
+    let sum = Provenance::token("t").add(&Provenance::token("u"));
+    assert_eq!(sum.to_string(), "t + u");
+
+    let square = sum.mul(&sum);
+    assert_eq!(square.to_string(), "t·t + 2·t·u + u·u");
+    assert_eq!(Provenance::one().add(&Provenance::zero()).to_string(), "1");
+}
+
+#[test]
+fn thompson_construction() {
+    let regexes = [
+        r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)",
+        r"^(.*[^a])?(?P<block_a>a+)([^a].*[^b]|[^ab])?(?P<block_b>b+)([^b].*)?$",
+        r"(?P<x>a*)(?P<y>b*)",
+    ];
+    let text = "aa@aa abab a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+
+    for regex in regexes.iter() {
+        let glushkov = regex::compile_with(regex, Construction::Glushkov);
+        let thompson = regex::compile_with(regex, Construction::Thompson);
+        assert_eq!(default_results(&glushkov, text), default_results(&thompson, text));
+    }
+}
+
+#[test]
+fn wide_matrices() {
+    // Matrices between levels are wider than 64 columns, and either fit in
+    // 128 bits or span several words.
+    let regexes = [
+        r"(?P<x>[ab]{1,80})@(?P<y>[ab]{1,80})",
+        r"(?P<x>[ab]{1,150})@",
+    ];
+    let long_text = "ab".repeat(100) + "@" + &"ba".repeat(10);
+    let texts = [
+        "abbab@abbbabbaabbbabaaaa@bbbabababbbbabababab@babaababbababbbbababbab",
+        &long_text,
+    ];
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+
+        for text in texts.iter() {
+            assert_eq!(naive_results(&regex, text), default_results(&regex, text));
+        }
+    }
+}
+
+#[test]
+fn compressed_matrices() {
+    // On periodic texts, matrices between levels have long runs of identical
+    // rows and are stored compressed.
+    let cases = [
+        (r"(?P<x>(ab){1,40})", "ab".repeat(60)),
+        (r"(?P<x>[ab]{1,80})@", "ab".repeat(50) + "@" + &"ba".repeat(5)),
+    ];
+
+    for (regex, text) in cases.iter() {
+        let regex = regex::compile(regex);
+        assert_eq!(naive_results(&regex, text), default_results(&regex, text));
+    }
+}
+
+#[test]
+fn progress_callback() {
+    let regex = regex::compile(r"(?P<x>a+)");
+    let text = "aaa baa ab";
+    let mut reports = Vec::new();
+
+    let mut dag = IndexedDag::builder(regex, text)
+        .with_progress_callback(|progress| reports.push(progress.clone()))
+        .build();
+    dag.preprocess();
+    drop(dag);
+
+    let mut phases: Vec<_> = reports.iter().map(|progress| progress.phase).collect();
+    phases.dedup();
+    assert_eq!(phases, vec![Phase::Build, Phase::Trim, Phase::Index]);
+
+    let percentages: Vec<_> = reports.iter().map(|progress| progress.percentage()).collect();
+    assert!(percentages.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(percentages.last(), Some(&100.));
+}
+
+#[test]
+fn export_dag() {
+    let regex = regex::compile(r"(?P<x>b)@(?P<y>b)");
+    let mut dag = IndexedDag::builder(regex, "ab@ba").build();
+    dag.preprocess();
+
+    let mut dot = Vec::new();
+    dag.write_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert_eq!(dot.matches("label=\" ⊢x \"").count(), 1);
+    assert_eq!(dot.matches("label=\" y⊣ \"").count(), 1);
+
+    let mut graphml = Vec::new();
+    dag.write_graphml(&mut graphml).unwrap();
+    let graphml = String::from_utf8(graphml).unwrap();
+    assert_eq!(graphml.matches("<node ").count(), dot.matches("jl=").count());
+}
+
+#[test]
+fn sliding_window() {
+    let regex = regex::compile(r"(?P<x>a+)@(?P<y>a+)");
+    let text = "aa@a  a@aaa@a aaaa@aa@a a@a";
+    let window = 6;
+
+    let spans = |offset: usize, mapping: &Mapping| -> Vec<(String, usize, usize)> {
+        mapping
+            .iter_groups()
+            .map(|(name, range)| (name.to_string(), offset + range.start, offset + range.end))
+            .collect()
+    };
+
+    let mut expected: Vec<_> = naive_results(&regex, text)
+        .iter()
+        .filter(|mapping| mapping.main_span().unwrap().len() <= window)
+        .map(|mapping| spans(0, mapping))
+        .collect();
+    expected.sort();
+
+    for chunk_size in [1, 4, 30].iter() {
+        let mut stream = SlidingWindow::new(regex.clone(), window);
+        let mut results = Vec::new();
+
+        for chunk in text.as_bytes().chunks(*chunk_size) {
+            let chunk = std::str::from_utf8(chunk).unwrap();
+            stream.push(chunk, |offset, mapping| results.push(spans(offset, mapping)));
+        }
+
+        stream.finish(|offset, mapping| results.push(spans(offset, mapping)));
+        results.sort();
+        assert_eq!(expected, results);
+    }
+}
+
+#[test]
+fn anchored() {
+    let regex = regex::compile(r"(?P<x>a+)@(?P<y>a+)");
+    let text = "aa@a a@aaa@a";
+    let mut dag = IndexedDag::builder(regex.clone(), text).build();
+    dag.preprocess();
+
+    for pos in 0..=text.len() {
+        let starting: HashSet<_> = dag.iter_anchored(Anchor::Start(pos)).collect();
+        let ending: HashSet<_> = dag.iter_anchored(Anchor::End(pos)).collect();
+        let all = default_results(&regex, text);
+
+        let expected_starting: HashSet<_> = all
+            .iter()
+            .filter(|mapping| mapping.main_span().unwrap().start == pos)
+            .cloned()
+            .collect();
+        let expected_ending: HashSet<_> = all
+            .iter()
+            .filter(|mapping| mapping.main_span().unwrap().end == pos)
+            .cloned()
+            .collect();
+
+        assert_eq!(expected_starting, starting);
+        assert_eq!(expected_ending, ending);
+    }
+}
+
+#[test]
+fn length_bounds() {
+    let regex = regex::compile(r"(?P<x>a+)@(?P<y>a*)");
+    let text = "aa@a a@aaa@ aaaa@aa";
+    let all = default_results(&regex, text);
+
+    let bounds = [
+        LengthBounds::new().with_max_len(None, 4),
+        LengthBounds::new().with_min_len(None, 5),
+        LengthBounds::new()
+            .with_max_len(Some("x"), 2)
+            .with_min_len(Some("y"), 1),
+        LengthBounds::new()
+            .with_min_len(None, 3)
+            .with_max_len(Some("y"), 0),
+    ];
+
+    for bounds in &bounds {
+        for &trimming in &[TrimmingStrategy::FullTrimming, TrimmingStrategy::LazyTrimming] {
+            let mut dag = IndexedDag::builder(regex.clone(), text)
+                .with_trimming(trimming)
+                .with_length_bounds(Some(bounds.clone()))
+                .build();
+            dag.preprocess();
+
+            let expected: HashSet<_> = all
+                .iter()
+                .filter(|mapping| bounds.accepts(mapping))
+                .cloned()
+                .collect();
+
+            assert_eq!(expected, dag.iter().collect());
+        }
+    }
+}
+
+#[test]
+fn spans() {
+    let regex = regex::compile(r"(?P<x>a*)@(?P<y>a*)");
+    let text = "aa@a a@aaa@a";
+    let mut dag = IndexedDag::builder(regex, text).build();
+    dag.preprocess();
+
+    let spans: Vec<_> = dag.spans().collect();
+    let expected: HashSet<_> = dag.iter().map(|mapping| mapping.main_span().unwrap()).collect();
+
+    assert_eq!(spans.len(), expected.len());
+    assert_eq!(spans.into_iter().collect::<HashSet<_>>(), expected);
+}
+
+#[test]
+fn group_lookup() {
+    let regex = regex::compile(r"(?P<x>a+)@((?P<y>b+)|c)");
+    let schema: Vec<_> = regex
+        .get_variables()
+        .into_iter()
+        .map(|var| var.get_name().to_string())
+        .collect();
+    assert_eq!(schema, vec!["x", "y"]);
+
+    let text = "aa@c";
+    let mappings = default_results(&regex, text);
+    let mapping = mappings.iter().find(|mapping| mapping.get("x") == Some(0..2)).unwrap();
+    assert_eq!(mapping.get_text("x"), Some("aa"));
+    assert_eq!(mapping.get("y"), None);
+    assert_eq!(mapping.get("z"), None);
+}
+
+#[test]
+fn exponential_jumps() {
+    let regexes = [
+        r"(?P<x>a+)@(?P<y>a+)",
+        r"(?P<x>a)[^@]*(?P<y>b)",
+        r"^(.*[^a])?(?P<block_a>a+)([^a].*)?$",
+    ];
+    let text = "aa@a bab@b a@abbaab@aab bbb@a aba@ba@b";
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+        let mut dag = IndexedDag::builder(regex.clone(), text)
+            .with_jump_schedule(JumpSchedule::Exponential)
+            .build();
+        dag.preprocess();
+
+        assert_eq!(naive_results(&regex, text), dag.iter().collect());
+    }
+}
+
+#[test]
+fn single_pass() {
+    let regexes = [
+        r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)",
+        r"(?P<x>a)[^@]*(?P<y>b)",
+        r"^(.*[^a])?(?P<block_a>a+)([^a].*)?$",
+        r"e{3}|ê{3}",
+    ];
+    let texts = ["aa@aa a@a.a@a.a.a@a.a.a.a@a.a.a.a.a", "eêêêeêêêe eeeb a", ""];
+
+    for regex in regexes.iter() {
+        let regex = regex::compile(regex);
+
+        for text in texts.iter() {
+            for &(jump_distance, schedule) in &[
+                (1, JumpSchedule::Uniform),
+                (3, JumpSchedule::Uniform),
+                (4, JumpSchedule::Exponential),
+            ] {
+                let mut dag = IndexedDag::builder(regex.clone(), text)
+                    .with_jump_distance(jump_distance)
+                    .with_trimming(TrimmingStrategy::NoTrimming)
+                    .with_jump_schedule(schedule)
+                    .with_single_pass(true)
+                    .build();
+                dag.preprocess();
+
+                assert_eq!(naive_results(&regex, text), dag.iter().collect());
+            }
+        }
+    }
+}
+
+#[test]
+fn single_pass_retained_levels() {
+    // Every level can be jumped to, none of them is dropped until the whole
+    // text is read, while trimming only keeps the last `a`.
+    let regex = regex::compile(r"(?P<x>a)b");
+    let text = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaab";
+
+    let build = |trimming_strategy, single_pass| {
+        let mut dag = IndexedDag::builder(regex.clone(), text)
+            .with_trimming(trimming_strategy)
+            .with_single_pass(single_pass)
+            .build();
+        dag.preprocess();
+        (dag.num_levels().unwrap(), dag.get_memory_usage().unwrap().1)
+    };
+
+    let single_pass = build(TrimmingStrategy::NoTrimming, true);
+    let untrimmed = build(TrimmingStrategy::NoTrimming, false);
+    let trimmed = build(TrimmingStrategy::FullTrimming, false);
+
+    assert_eq!(single_pass, untrimmed);
+    assert_eq!(single_pass.0, text.len() + 1);
+    assert!(trimmed.0 < single_pass.0 && trimmed.1 < single_pass.1);
+}
+
+#[test]
+fn interchangeable_enumerators() {
+    let regex = regex::compile(r"(?P<login>\w+)@(?P<server>\w+)");
+    let text = "a@b ab@cd e@";
+
+    let mut enumerators: Vec<Box<dyn SpannerEnumerator>> = vec![
+        Box::new(naive::NaiveEnum::new(&regex, text)),
+        Box::new(IndexedDag::builder(regex.clone(), text).build()),
+        Box::new(DeterministicEva::new(regex.clone(), text)),
+    ];
+
+    for enumerator in &mut enumerators {
+        enumerator.preprocess();
+
+        assert_eq!(enumerator.count(), 5);
+        assert_eq!(enumerator.first(), enumerator.iter().next());
+        assert_eq!(naive_results(&regex, text), enumerator.iter().collect());
+    }
+
+    // Only the indexed algorithms know the size of their index.
+    let sizes: Vec<_> = enumerators.iter().map(|e| e.stats().index_size.is_some()).collect();
+    assert_eq!(sizes, [false, true, true]);
+}
+
+#[test]
+fn exact_size() {
+    let regex = regex::compile(r"(?P<x>a*)(?P<y>b*)");
+    let text = "aabab";
+    let count = naive_results(&regex, text).len();
+
+    let mut eva = DeterministicEva::new(regex.clone(), text);
+    eva.preprocess();
+    let mut iter = eva.iter();
+    assert_eq!(iter.size_hint(), (count, Some(count)));
+    iter.next();
+    assert_eq!(iter.size_hint(), (count - 1, Some(count - 1)));
+
+    let mut eva = DeterministicEva::new(regex.clone(), text)
+        .with_order(Order::ShortestFirst)
+        .with_top_k(3);
+    eva.preprocess();
+    assert_eq!(eva.count(), 3);
+    assert_eq!(eva.iter().size_hint(), (3, Some(3)));
+
+    let mut dag = IndexedDag::builder(regex, text).build();
+    dag.preprocess();
+    assert_eq!(dag.iter_exact().len(), count);
+    assert_eq!(dag.iter_exact().count(), count);
+}
+
+#[test]
+fn resume_cursor() {
+    let regex = regex::compile(r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)");
+    let text = "aa@aa abab a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+
+    for trimming in [TrimmingStrategy::FullTrimming, TrimmingStrategy::LazyTrimming] {
+        let mut dag = IndexedDag::builder(regex.clone(), text)
+            .with_jump_distance(2)
+            .with_trimming(trimming)
+            .build();
+        dag.preprocess();
+        let expected: Vec<_> = dag.iter().collect();
+
+        for stop in 0..=expected.len() {
+            let mut iter = dag.iter_resumable();
+            let mut results: Vec<_> = iter.by_ref().take(stop).collect();
+
+            // The cursor is resumed over another index of the same text.
+            let cursor = serde_json::to_string(&iter.cursor()).unwrap();
+            let cursor: Cursor = serde_json::from_str(&cursor).unwrap();
+            assert_eq!(cursor.num_found(), stop);
+
+            let mut other = IndexedDag::builder(regex.clone(), text)
+                .with_jump_distance(2)
+                .with_trimming(trimming)
+                .build();
+            other.preprocess();
+            results.extend(other.resume(&cursor));
+            assert_eq!(results, expected);
+        }
+    }
+}
+
+#[test]
+fn iter_range() {
+    let regex = regex::compile(r"(?P<login>\w+(\.\w+)*)@(?P<server>\w+\.\w+)");
+    let text = "aa@aa abab a@a.a@a.a.a@a.a.a.a@a.a.a.a.a";
+
+    let mut enumerators: Vec<Box<dyn SpannerEnumerator>> = vec![
+        Box::new(IndexedDag::builder(regex.clone(), text).build()),
+        Box::new(
+            IndexedDag::builder(regex.clone(), text)
+                .with_jump_distance(2)
+                .with_trimming(TrimmingStrategy::LazyTrimming)
+                .build(),
+        ),
+        Box::new(DeterministicEva::new(regex.clone(), text)),
+        Box::new(DeterministicEva::new(regex.clone(), text).with_top_k(7)),
+    ];
+
+    for enumerator in &mut enumerators {
+        enumerator.preprocess();
+        let expected: Vec<_> = enumerator.iter().collect();
+
+        for offset in 0..=expected.len() + 1 {
+            for &limit in &[0, 1, 3, usize::MAX] {
+                let page: Vec<_> = enumerator.iter_range(offset, limit).collect();
+                let expected: Vec<_> = expected.iter().skip(offset).take(limit).cloned().collect();
+                assert_eq!(page, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn find_first() {
+    let tests = [
+        (r"(?P<x>a+)@(?P<y>b+)", "cc a@bb a@b"),
+        (r"(?P<x>a)$", "a ba"),
+        (r"(?P<x>a)?", "bab"),
+        (r"^(?P<x>\w+)", "ab cd"),
+    ];
+
+    for &(regex, text) in &tests {
+        let automaton = regex::compile(regex);
+        let mut dag = IndexedDag::builder(automaton.clone(), text).build();
+        let mapping = dag.find_first().unwrap();
+        assert!(default_results(&automaton, text).contains(&mapping));
+    }
+
+    let automaton = regex::compile(r"(?P<x>a+)@(?P<y>b+)");
+    let mut dag = IndexedDag::builder(automaton, "a@c b@b").build();
+    assert_eq!(dag.find_first(), None);
+}
+
+#[test]
+fn builder() {
+    let automaton = regex::compile(r"(?P<x>a+)@(?P<y>b+)");
+    let text = "aa@b a@bb @ a@";
+
+    let mut dag = IndexedDag::builder(automaton.clone(), text)
+        .with_jump_distance(2)
+        .with_trimming(TrimmingStrategy::AdaptiveTrimming(2))
+        .with_length_bounds(Some(LengthBounds::new().with_min_len(None, 4).with_max_len(None, 4)))
+        .build();
+    dag.preprocess();
+
+    let results: HashSet<_> = dag.iter().collect();
+    let expected: HashSet<_> = default_results(&automaton, text)
+        .into_iter()
+        .filter(|mapping| mapping.main_span().unwrap().len() == 4)
+        .collect();
+
+    assert_eq!(dag.get_jump_distance(), 2);
+    assert_eq!(results, expected);
+    assert_eq!(results.len(), 2);
+}
+
+#[test]
+fn offset_unit() {
+    let automaton = regex::compile(r"(?P<x>é+)@(?P<y>a)");
+    let text = "ééé@a é@a";
+
+    let spans = |offset_unit| {
+        let mut dag = IndexedDag::builder(automaton.clone(), text)
+            .with_offset_unit(offset_unit)
+            .build();
+        dag.preprocess();
+        assert_eq!(dag.get_offset_unit(), offset_unit);
+        dag.spans().collect::<HashSet<_>>()
+    };
+
+    let bytes = spans(OffsetUnit::Bytes);
+    assert!(bytes.contains(&(0..8)) && bytes.contains(&(9..13)));
+
+    let chars = spans(OffsetUnit::Chars);
+    assert!(chars.contains(&(0..5)) && chars.contains(&(6..9)));
+    assert_eq!(bytes.len(), chars.len());
+}
+
+#[test]
+fn explain_no_match() {
+    let explain = |regex, text| {
+        IndexedDag::builder(regex::compile(regex), text)
+            .build()
+            .explain_no_match()
+    };
+
+    assert_eq!(explain(r"(?P<x>a)@(?P<y>b)", "c a@b"), None);
+
+    match explain(r"^(?P<x>a)@(?P<y>b)", "a@c") {
+        Some(NoMatch::Disconnected { level, offset, character, alive, .. }) => {
+            assert_eq!((level, offset, character), (2, 2, 'c'));
+            assert!(alive.iter().any(|(_, expected)| expected == &["'b'"]));
+        }
+        other => panic!("Unexpected explanation: {:?}", other),
+    }
+
+    match explain(r"(?P<x>a)@(?P<y>b)", "xa@c a@@b a") {
+        Some(NoMatch::Disconnected { start, level, offset, character, alive }) => {
+            assert_eq!((start, level, offset, character), (1, 3, 3, 'c'));
+            assert_eq!(alive.len(), 1);
+            assert_eq!(alive[0].1, ["'b'"]);
+        }
+        other => panic!("Unexpected explanation: {:?}", other),
+    }
+
+    match explain(r"(?P<x>a)$", "ab") {
+        Some(NoMatch::Disconnected { start, level, alive, .. }) => {
+            assert_eq!((start, level), (0, 1));
+            assert!(alive.iter().any(|(_, expected)| expected == &["the end of the text"]));
+        }
+        other => panic!("Unexpected explanation: {:?}", other),
+    }
+
+    match explain(r"(?P<x>ab)", "xxa") {
+        Some(NoMatch::NoFinalState { start, alive }) => {
+            assert_eq!(start, 2);
+            assert_eq!(alive.len(), 1);
+            assert_eq!(alive[0].1, ["'b'"]);
+        }
+        other => panic!("Unexpected explanation: {:?}", other),
+    }
+
+    match explain(r"(?P<x>z)", "abc") {
+        Some(NoMatch::NotStarted { alive }) => {
+            assert_eq!(alive.len(), 1);
+            assert_eq!(alive[0].1, ["'z'"]);
+        }
+        other => panic!("Unexpected explanation: {:?}", other),
+    }
+}
+
+#[test]
+fn explain_run() {
+    let automaton = regex::compile(r"(?P<x>a)(?P<y>b*)");
+    let mut indexed_dag = IndexedDag::builder(automaton, "bab").build();
+    indexed_dag.preprocess();
+
+    for mapping in indexed_dag.iter() {
+        let run = indexed_dag.explain_run(&mapping).unwrap();
+        let markers: Vec<_> = run
+            .iter()
+            .filter(|step| step.label.contains('x') || step.label.contains('y'))
+            .map(|step| (step.offset, step.label.as_str()))
+            .collect();
+
+        let (x, y) = (mapping.get("x").unwrap(), mapping.get("y").unwrap());
+        assert_eq!(
+            markers,
+            vec![(x.start, "⊢x"), (x.end, "x⊣"), (y.start, "⊢y"), (y.end, "y⊣")]
+        );
+        assert!(run.windows(2).all(|steps| steps[0].target == steps[1].source));
+    }
+
+    let unbound = Mapping::from_markers("bab", std::iter::empty(), 2);
+    assert_eq!(indexed_dag.explain_run(&unbound), None);
+}
+
+#[test]
+fn count_runs() {
+    let automaton = regex::compile(r"(?P<x>(a|ab|b)*)");
+    let mut indexed_dag = IndexedDag::builder(automaton.clone(), "bab").build();
+    indexed_dag.preprocess();
+
+    for mapping in indexed_dag.iter() {
+        let expected = match mapping.get_text("x").unwrap() {
+            "ab" | "bab" => 2,
+            _ => 1,
+        };
+
+        assert_eq!(mapping.count_runs(&automaton), expected);
+    }
+
+    let unbound = Mapping::from_markers("bab", std::iter::empty(), 1);
+    assert_eq!(unbound.count_runs(&automaton), 0);
+
+    // Both loops of a*a* are merged by the reduction of the automaton.
+    let regex = r"(?P<x>a*a*)";
+    let merged = regex::compile(regex);
+    let mut indexed_dag = IndexedDag::builder(merged.clone(), "aa").build();
+    indexed_dag.preprocess();
+
+    for construction in &[Construction::Glushkov, Construction::Thompson] {
+        let unmerged = regex::compile_unmerged_with(regex, *construction);
+
+        for mapping in indexed_dag.iter() {
+            let expected = mapping.get_text("x").unwrap().len() as u64 + 1;
+            assert_eq!(mapping.count_runs(&merged), 1);
+            assert_eq!(mapping.count_runs(&unmerged), expected);
+        }
+    }
+}
+
+#[test]
+fn projected_automaton() {
+    let regex = r"(?P<x>a+)(?P<y>b*)(?P<z>b*)";
+    let dropped = ["y".to_string(), "z".to_string()];
+    let groups = |mapping: &Mapping| -> Vec<(String, usize, usize)> {
+        (mapping.iter_groups())
+            .map(|(name, range)| (name.to_string(), range.start, range.end))
+            .collect()
+    };
+
+    for construction in &[Construction::Glushkov, Construction::Thompson] {
+        let automaton = regex::compile_with(regex, *construction);
+        let projected = automaton.project(&["x".to_string()]);
+        let variables = projected.get_variables();
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].get_name(), "x");
+
+        for text in &["aabb", "abab", "ba"] {
+            let expected: HashSet<_> = (default_results(&automaton, text).iter())
+                .map(|mapping| groups(&mapping.without_groups(&dropped)))
+                .collect();
+
+            // Each projected match is enumerated once.
+            let mut indexed_dag = IndexedDag::builder(projected.clone(), text).build();
+            indexed_dag.preprocess();
+            let found: Vec<_> = indexed_dag.iter().map(|mapping| groups(&mapping)).collect();
+            assert_eq!(found.len(), expected.len());
+            assert_eq!(found.into_iter().collect::<HashSet<_>>(), expected);
+        }
+    }
+}
+
+#[test]
+fn owned_mapping() {
+    let automaton = regex::compile(r"(?P<x>a)(?P<y>b+)");
+    let text = String::from("abbcab");
+    let mut indexed_dag = IndexedDag::builder(automaton, &text).build();
+    indexed_dag.preprocess();
+
+    let mappings: Vec<Mapping> = indexed_dag.iter().collect();
+    let shared: std::sync::Arc<str> = std::sync::Arc::from(text.as_str());
+    let owned: Vec<OwnedMapping> = mappings.iter().map(OwnedMapping::from).collect();
+    let with_text: Vec<OwnedMapping> = (mappings.iter())
+        .map(|mapping| OwnedMapping::with_text(mapping, shared.clone()))
+        .collect();
+
+    let expected: Vec<_> = (mappings.iter())
+        .map(|mapping| {
+            let groups: Vec<_> = (mapping.iter_groups_text())
+                .map(|(name, text)| (name.to_string(), text.to_string()))
+                .collect();
+            (mapping.main_span(), mapping.get("y"), groups)
+        })
+        .collect();
+
+    drop(mappings);
+    drop(indexed_dag);
+    drop(text);
+
+    for owned in [owned, with_text] {
+        let actual: Vec<_> = (owned.iter())
+            .map(|mapping| {
+                let groups: Vec<_> = (mapping.iter_groups_text())
+                    .map(|(name, text)| (name.to_string(), text.to_string()))
+                    .collect();
+                (mapping.main_span(), mapping.get("y"), groups)
+            })
+            .collect();
+
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn memory_budget() {
+    let automaton = regex::compile(r"(?P<x>a+)@(?P<y>b+)");
+    let text = "aa@b a@bb @ a@";
+
+    for compressed in [false, true] {
+        let mut dag = IndexedDag::builder(automaton.clone(), text)
+            .with_compressed_levels(compressed)
+            .with_memory_budget(Some(1))
+            .build();
+        dag.preprocess();
+
+        assert!(dag.is_memory_budget_exceeded());
+        assert!(dag.is_levels_budget_exceeded());
+        assert_eq!(dag.iter().count(), 0);
+
+        // A budget fitting the levels of the DAG but not its index.
+        let mut dag = IndexedDag::builder(automaton.clone(), text)
+            .with_compressed_levels(compressed)
+            .build();
+        dag.preprocess();
+        let (levels_before_truncation, levels_memory, _, _) = dag.get_memory_usage().unwrap();
+        let levels_memory = std::cmp::max(levels_before_truncation, levels_memory);
+
+        let mut dag = IndexedDag::builder(automaton.clone(), text)
+            .with_compressed_levels(compressed)
+            .with_memory_budget(Some(levels_memory))
+            .build();
+        dag.preprocess();
+
+        assert!(dag.is_memory_budget_exceeded());
+        assert!(!dag.is_levels_budget_exceeded());
+
+        let mut dag = IndexedDag::builder(automaton.clone(), text)
+            .with_compressed_levels(compressed)
+            .with_memory_budget(Some(1 << 20))
+            .build();
+        dag.preprocess();
+
+        assert!(!dag.is_memory_budget_exceeded());
+        assert_eq!(dag.iter().collect::<HashSet<_>>(), default_results(&automaton, text));
+    }
+}
+
+#[test]
+#[should_panic(expected = "The jump distance must be positive")]
+fn zero_jump_distance() {
+    let automaton = regex::compile(r"(?P<x>a+)");
+    IndexedDag::builder(automaton, "aaa").with_jump_distance(0).build();
+}
+
+#[test]
+fn jump_combinations() {
+    let automaton = regex::compile(r"(?P<x>.)(?P<y>.*)(?P<z>a)");
+    let text = "abcabcabcabcaaabbbccc";
+    let expected = naive_results(&automaton, text);
+
+    let trimmings = [
+        TrimmingStrategy::NoTrimming,
+        TrimmingStrategy::AdaptiveTrimming(2),
+        TrimmingStrategy::LazyTrimming,
+    ];
+
+    for jump_distance in 1..5 {
+        for schedule in [JumpSchedule::Uniform, JumpSchedule::Exponential] {
+            for &trimming in &trimmings {
+                for jump_pointers in [false, true] {
+                    let mut dag = IndexedDag::builder(automaton.clone(), text)
+                        .with_jump_distance(jump_distance)
+                        .with_jump_schedule(schedule)
+                        .with_trimming(trimming)
+                        .with_jump_pointers(jump_pointers)
+                        .build();
+                    dag.preprocess();
+
+                    assert_eq!(dag.iter().collect::<HashSet<_>>(), expected);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn canonical_order() {
+    let automaton = regex::compile(r"(?P<x>a*)(?P<y>(?P<z>b)?a*)");
+    let text = "aabaab";
+
+    let order = |jump_distance, trimming| {
+        let mut dag = IndexedDag::builder(automaton.clone(), text)
+            .with_jump_distance(jump_distance)
+            .with_trimming(trimming)
+            .build();
+        dag.preprocess();
+        dag.iter().map(|mapping| mapping.to_string()).collect::<Vec<_>>()
+    };
+
+    let expected = order(1, TrimmingStrategy::FullTrimming);
+    assert_eq!(order(3, TrimmingStrategy::NoTrimming), expected);
+    assert_eq!(order(2, TrimmingStrategy::LazyTrimming), expected);
+
+    // Groups are left unbound before they are assigned at each level, from the
+    // end of the text.
+    assert_eq!(expected.first().map(String::as_str), Some("x: (6, 6) y: (6, 6) "));
+}
+
+#[test]
+fn gazetteer() {
+    let gazetteer = Gazetteer::new(vec!["foo", "bar", "foobar", "", "bar", "été"]);
+    let automaton = gazetteer.get_automaton();
+    let text = "xfoobarfoo étés barbar";
+
+    let expected = default_results(&regex::compile(r"(?P<match>foo|bar|foobar|été)"), text);
+    assert_eq!(expected.len(), 7);
+    assert_eq!(default_results(&automaton, text), expected);
+    assert_eq!(naive_results(&automaton, text), expected);
+    assert_eq!(gazetteer.find_all(text).into_iter().collect::<HashSet<_>>(), expected);
+    assert_eq!(gazetteer.get_entries().len(), 4);
+
+    let gazetteer = gazetteer.with_group("word");
+    let results = trimmed_results(&gazetteer.get_automaton(), text, TrimmingStrategy::NoTrimming);
+    assert_eq!(results.len(), 7);
+    assert!(results.iter().all(|mapping| mapping.iter_groups().all(|(name, _)| name == "word")));
+}
+
+#[test]
+fn predicates() {
+    let automaton = regex::compile(r"(?P<host>[a-z.]+):(?P<port>\w+)");
+    let text = "example.com:8080 a.io:99999 localhost:http";
+    let predicates = Predicates::parse("port:[0-9]+ && port <= 65535 && len(host)<5").unwrap();
+    assert_eq!(predicates.get_groups(), vec!["host", "port"]);
+
+    let expected: HashSet<_> = (default_results(&automaton, text).into_iter())
+        .filter(|mapping| predicates.accepts(mapping))
+        .collect();
+    assert_eq!(expected.len(), 32);
+    assert!(expected.iter().all(|mapping| mapping.get_text("port") != Some("99999")));
+
+    let mut dag = IndexedDag::builder(automaton, text)
+        .with_length_bounds(Some(predicates.restrict_lengths(LengthBounds::new())))
+        .build();
+    dag.preprocess();
+    let pruned: Vec<_> = dag.iter().collect();
+    assert!(pruned.iter().all(|mapping| mapping.get("host").unwrap().len() < 5));
+    assert_eq!(pruned.into_iter().filter(|mapping| predicates.accepts(mapping)).count(), 32);
+
+    assert!(Predicates::parse("port").is_err());
+    assert!(Predicates::parse("len(port)<x").is_err());
+    assert!(Predicates::parse("port:(").is_err());
+}
@@ -1,49 +1,175 @@
 use std::cmp::PartialEq;
-use std::ops::{BitAnd, BitOr, Index, Mul};
+use std::collections::BTreeMap;
+use std::iter;
+use std::ops::{BitAnd, BitOr, Range};
 
 use bit_set::BitSet;
 use std::cell::Cell;
 use std::fmt;
-use std::mem::{forget, size_of};
+use std::mem::size_of;
 use std::slice;
 
+/// Number of words reserved at once by an arena, bigger matrices get a chunk
+/// of their own.
+const CHUNK_WORDS: usize = 1 << 16;
+
+//     _
+//    / \   _ __ ___ _ __   __ _
+//   / _ \ | '__/ _ \ '_ \ / _` |
+//  / ___ \| | |  __/ | | | (_| |
+// /_/   \_\_|  \___|_| |_|\__,_|
+//
+
+/// Owner of the storage of all matrices that don't fit in a single word.
+///
+/// Memory is reserved by large chunks and the blocks of released matrices are
+/// reused by later matrices of the same size.
+pub struct MatrixArena {
+    chunks: Vec<Vec<u64>>,
+    /// Addresses of released blocks, indexed by their length in words.
+    free_blocks: BTreeMap<usize, Vec<u64>>,
+    /// Buffer in which the results of operations are built.
+    scratch: Vec<u64>,
+}
+
+impl MatrixArena {
+    pub fn new() -> MatrixArena {
+        MatrixArena {
+            chunks: Vec::new(),
+            free_blocks: BTreeMap::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Allocate a block of words filled with zeros. The address of the block
+    /// holds the index of its chunk in the upper half and its offset in the
+    /// chunk in the lower half.
+    fn alloc(&mut self, len: usize) -> u64 {
+        if let Some(addr) = self.free_blocks.get_mut(&len).and_then(|blocks| blocks.pop()) {
+            for word in self.get_block_mut(addr, len) {
+                *word = 0;
+            }
+
+            return addr;
+        }
+
+        let fits = match self.chunks.last() {
+            Some(chunk) => chunk.capacity() - chunk.len() >= len,
+            None => false,
+        };
+
+        if !fits {
+            self.chunks
+                .push(Vec::with_capacity(std::cmp::max(CHUNK_WORDS, len)));
+        }
+
+        let chunk_id = self.chunks.len() - 1;
+        let chunk = &mut self.chunks[chunk_id];
+        let offset = chunk.len();
+        chunk.resize(offset + len, 0);
+
+        ((chunk_id as u64) << 32) | offset as u64
+    }
+
+    fn release(&mut self, addr: u64, len: usize) {
+        self.free_blocks.entry(len).or_default().push(addr);
+    }
+
+    fn get_block(&self, addr: u64, len: usize) -> &[u64] {
+        let offset = (addr & 0xffff_ffff) as usize;
+        &self.chunks[(addr >> 32) as usize][offset..offset + len]
+    }
+
+    fn get_block_mut(&mut self, addr: u64, len: usize) -> &mut [u64] {
+        let offset = (addr & 0xffff_ffff) as usize;
+        &mut self.chunks[(addr >> 32) as usize][offset..offset + len]
+    }
+}
+
+impl Default for MatrixArena {
+    fn default() -> MatrixArena {
+        MatrixArena::new()
+    }
+}
+
+/// Unsigned types that a row of a matrix can be stored in.
+trait Word: BitOr + BitAnd + Copy {}
+
+impl Word for u8 {}
+impl Word for u16 {}
+impl Word for u32 {}
+impl Word for u64 {}
+
+/// View storage words as a slice of smaller words.
+fn cast<T: Word>(words: &[u64]) -> &[T] {
+    // The alignment of u64 is suitable for all smaller words.
+    unsafe {
+        slice::from_raw_parts(
+            words.as_ptr() as *const T,
+            std::mem::size_of_val(words) / size_of::<T>(),
+        )
+    }
+}
+
+fn cast_mut<T: Word>(words: &mut [u64]) -> &mut [T] {
+    unsafe {
+        slice::from_raw_parts_mut(
+            words.as_mut_ptr() as *mut T,
+            std::mem::size_of_val(words) / size_of::<T>(),
+        )
+    }
+}
+
+//  __  __       _        _
+// |  \/  | __ _| |_ _ __(_)_  __
+// | |\/| |/ _` | __| '__| \ \/ /
+// | |  | | (_| | |_| |  | |>  <
+// |_|  |_|\__,_|\__|_|  |_/_/\_\
+//
+
 /// Naive representation of a matrix as a single consecutive chunk of memory.
+///
+/// The storage of matrices wider than a word is owned by a `MatrixArena`,
+/// which must be given to all operations on the matrix.
+///
+/// A matrix made of long runs of identical rows, which is common on
+/// repetitive texts, can be compressed once it is built: its storage then
+/// holds the end of each run as a `u16`, followed by the row of each run
+/// padded to whole words. Compressed matrices can't be modified anymore.
 pub struct Matrix {
     height: u16,
     width: u16,
     usage_count: Cell<u16>,
-    /// if size<size_of<usize> this holds the matrix. Otherwise it holds a pointer to the matrix.
-    data: usize,
+    /// Number of runs of rows if the matrix is compressed, 0 otherwise.
+    runs: u16,
+    /// If the matrix fits in a single word, this holds the matrix. Otherwise it
+    /// holds the address of its storage in the arena.
+    data: u64,
 }
 
-impl<'a> Matrix {
+impl Matrix {
     /// Create a matrix filled with false.
-    pub fn new(height: usize, width: usize) -> Matrix {
-        let padded_width = Matrix::padded_width(width);
-
-        let size = height * padded_width;
-        let data;
-
-        //		if padded_width > 8 || height > 8 || width > 8 || size > 64 {
-        //			println!("Matrix size: {} {} {} {}", height, width, padded_width, size);
-        //		}
-
-        if size <= size_of::<usize>() * 8 {
-            data = 0;
-        } else {
-            //			panic!("Matrix size: {}", size);
-            let real_size = (size / (size_of::<usize>() * 8)) + 1;
-            let v: Vec<usize> = vec![0; real_size as usize];
-            let data_ptr = v.as_ptr() as *mut usize;
-            data = data_ptr as usize;
-            forget(v);
-        }
-
-        Matrix {
+    pub fn new(height: usize, width: usize, arena: &mut MatrixArena) -> Matrix {
+        let mut matrix = Matrix {
             width: width as u16,
             height: height as u16,
             usage_count: Cell::new(0),
-            data,
+            runs: 0,
+            data: 0,
+        };
+
+        if matrix.is_heap() {
+            matrix.data = arena.alloc(matrix.num_words());
+        }
+
+        matrix
+    }
+
+    /// Give back the storage of the matrix to the arena, which will reuse it
+    /// for a matrix of the same size.
+    pub fn release(self, arena: &mut MatrixArena) {
+        if self.is_heap() {
+            arena.release(self.data, self.num_words());
         }
     }
 
@@ -54,6 +180,7 @@ impl<'a> Matrix {
             9..=16 => 16,
             17..=32 => 32,
             33..=64 => 64,
+            65..=128 => 128,
             _ => (width / 64 + if (width & 63) == 0 { 0 } else { 1 }) * 64,
         }
     }
@@ -66,38 +193,47 @@ impl<'a> Matrix {
         (width, size)
     }
 
-    fn get_storage<T>(&self) -> &[T] {
-        let (_, size) = self.get_width_and_size();
-        let data_ptr: *const T;
-
-        if size <= 64 {
-            data_ptr = &self.data as *const usize as *const T;
-        } else {
-            data_ptr = self.data as *const usize as *const T;
-        }
-        let data;
-        unsafe {
-            data = slice::from_raw_parts(data_ptr, size as usize / size_of::<T>());
+    fn num_words(&self) -> usize {
+        if self.runs > 0 {
+            return Matrix::compressed_words(self.runs as usize, self.row_words());
         }
 
-        data
+        let (_, size) = self.get_width_and_size();
+        size.div_ceil(64)
     }
 
-    fn get_storage_mut<T>(&mut self) -> &mut [T] {
+    fn is_heap(&self) -> bool {
         let (_, size) = self.get_width_and_size();
-        let data_ptr: *mut T;
+        self.runs > 0 || size > 64
+    }
+
+    /// Number of words of a row in the storage of a compressed matrix.
+    fn row_words(&self) -> usize {
+        std::cmp::max(1, Matrix::padded_width(self.width as usize) / 64)
+    }
+
+    fn compressed_words(runs: usize, row_words: usize) -> usize {
+        runs.div_ceil(4) + runs * row_words
+    }
 
-        if size <= 64 {
-            data_ptr = &mut self.data as *mut usize as *mut T;
+    fn get_words<'a>(&'a self, arena: &'a MatrixArena) -> &'a [u64] {
+        if self.is_heap() {
+            arena.get_block(self.data, self.num_words())
         } else {
-            data_ptr = self.data as *mut usize as *mut T;
+            slice::from_ref(&self.data)
         }
-        let data;
-        unsafe {
-            data = slice::from_raw_parts_mut(data_ptr, size as usize / size_of::<T>());
+    }
+
+    fn get_words_mut<'a>(&'a mut self, arena: &'a mut MatrixArena) -> &'a mut [u64] {
+        if self.is_heap() {
+            arena.get_block_mut(self.data, self.num_words())
+        } else {
+            slice::from_mut(&mut self.data)
         }
+    }
 
-        data
+    fn get_storage<'a, T: Word>(&'a self, arena: &'a MatrixArena) -> &'a [T] {
+        cast(self.get_words(arena))
     }
 
     pub fn get_height(&self) -> usize {
@@ -108,62 +244,106 @@ impl<'a> Matrix {
         self.width as usize
     }
 
-    pub fn insert(&mut self, row: usize, col: usize) {
+    pub fn insert(&mut self, arena: &mut MatrixArena, row: usize, col: usize) {
+        debug_assert_eq!(self.runs, 0, "compressed matrices can't be modified");
         let (padded_width, _) = self.get_width_and_size();
+        Matrix::insert_in_words(self.get_words_mut(arena), padded_width, row, col);
+    }
 
+    /// Set a bit in the storage of a matrix of given padded width.
+    fn insert_in_words(words: &mut [u64], padded_width: usize, row: usize, col: usize) {
         match padded_width {
-            8 => {
-                let storage = self.get_storage_mut::<u8>();
-                storage[row] |= 1 << col;
-            }
-            16 => {
-                let storage = self.get_storage_mut::<u16>();
-                storage[row] |= 1 << col;
-            }
-            32 => {
-                let storage = self.get_storage_mut::<u32>();
-                storage[row] |= 1 << col;
-            }
-            64 => {
-                let storage = self.get_storage_mut::<u64>();
-                storage[row] |= 1 << col;
-            }
+            8 => cast_mut::<u8>(words)[row] |= 1 << col,
+            16 => cast_mut::<u16>(words)[row] |= 1 << col,
+            32 => cast_mut::<u32>(words)[row] |= 1 << col,
+            64 => words[row] |= 1 << col,
             _ => {
-                let storage = self.get_storage_mut::<u64>();
+                let i = col / 64;
+                let j = col % 64;
+                let effective_width = padded_width / 64;
+
+                words[row * effective_width + i] |= 1 << j;
+            }
+        }
+    }
+
+    pub fn get(&self, arena: &MatrixArena, row: usize, col: usize) -> bool {
+        if self.runs > 0 {
+            let (ends, rows) = self.get_runs(arena);
+            let run = ends.partition_point(|&end| end as usize <= row);
+            return (rows[run * self.row_words() + col / 64] & (1 << (col % 64))) != 0;
+        }
 
+        let (padded_width, _) = self.get_width_and_size();
+
+        match padded_width {
+            8 => (self.get_storage::<u8>(arena)[row] & (1 << col)) != 0,
+            16 => (self.get_storage::<u16>(arena)[row] & (1 << col)) != 0,
+            32 => (self.get_storage::<u32>(arena)[row] & (1 << col)) != 0,
+            64 => (self.get_storage::<u64>(arena)[row] & (1 << col)) != 0,
+            _ => {
+                let storage = self.get_storage::<u64>(arena);
                 let i = col / 64;
                 let j = col % 64;
                 let effective_width = padded_width / 64;
 
-                storage[row * effective_width + i] |= 1 << j;
+                (storage[row * effective_width + i] & (1 << j)) != 0
             }
         }
     }
 
-    pub fn col_mul_inplace(&self, column: &mut BitSet) {
+    pub fn col_mul_inplace(&self, arena: &MatrixArena, column: &mut BitSet) {
         self.usage_count.set(self.usage_count.get() + 1);
-        //		println!("col_mul: width: {} height: {}, column_height: {}", self.width, self.height, column.capacity());
 
         let (padded_width, _) = self.get_width_and_size();
-        if padded_width <= 64 {
-            let col = column.get_ref().storage()[0] as u64
-                + if column.capacity() > 32 {
-                    (column.get_ref().storage()[1] as u64) << 32
-                } else {
-                    0
-                };
+        if self.runs > 0 {
+            let col_storage = column.get_ref().storage();
+            let block = |i: usize| col_storage.get(i).map(|&x| x as u64).unwrap_or(0);
+            let col: Vec<u64> = (0..self.row_words())
+                .map(|i| block(2 * i) | block(2 * i + 1) << 32)
+                .collect();
+            column.clear();
+            let result = column;
+
+            for (rows, row) in self.iter_runs(arena) {
+                if row.iter().zip(&col).any(|(x, y)| x & y != 0) {
+                    for i in rows {
+                        result.insert(i);
+                    }
+                }
+            }
+        } else if padded_width <= 64 {
+            // The storage may hold less words than the capacity suggests.
+            let col_storage = column.get_ref().storage();
+            let col = col_storage.first().map(|&x| x as u64).unwrap_or(0)
+                + col_storage.get(1).map(|&x| (x as u64) << 32).unwrap_or(0);
             column.clear();
             let result = column;
 
             match padded_width {
-                8 => self.col_mul(col as u8, result),
-                16 => self.col_mul(col as u16, result),
-                32 => self.col_mul(col as u32, result),
-                64 => self.col_mul(col as u64, result),
+                8 => self.col_mul(arena, col as u8, result),
+                16 => self.col_mul(arena, col as u16, result),
+                32 => self.col_mul(arena, col as u32, result),
+                64 => self.col_mul(arena, col, result),
                 width => panic!("invalid matrix effective width {}", width),
             }
+        } else if padded_width == 128 {
+            let col = column
+                .get_ref()
+                .storage()
+                .iter()
+                .take(4)
+                .enumerate()
+                .fold(0, |col, (k, &x)| col | (x as u128) << (32 * k));
+            column.clear();
+            let result = column;
+
+            for (i, row) in self.rows128(arena).enumerate() {
+                if row & col != 0 {
+                    result.insert(i);
+                }
+            }
         } else {
-            //			panic!("col_mul_in_place not working for width > 64");
             let mut col: Vec<u64> = vec![0; padded_width / 8 + 1];
             let col_storage = column.get_ref().storage();
             for i in 0..std::cmp::min(col_storage.len(), padded_width / 4 + 1) {
@@ -177,16 +357,16 @@ impl<'a> Matrix {
             column.clear();
             let result = column;
 
-            self.col_mul_wide(&col, result);
+            self.col_mul_wide(arena, &col, result);
         }
     }
 
-    fn col_mul<T>(&self, column: T, result: &mut BitSet)
+    fn col_mul<T>(&self, arena: &MatrixArena, column: T, result: &mut BitSet)
     where
-        T: BitOr + BitAnd + Copy + fmt::Display,
+        T: Word + fmt::Display,
         <T as BitAnd>::Output: PartialEq + From<u8>,
     {
-        let storage = self.get_storage::<T>();
+        let storage = self.get_storage::<T>(arena);
         for i in 0..self.height {
             if (storage[i as usize] & column) != <T as BitAnd>::Output::from(0 as u8) {
                 result.insert(i as usize);
@@ -194,32 +374,60 @@ impl<'a> Matrix {
         }
     }
 
-    fn col_mul_wide(&self, column: &[u64], result: &mut BitSet) {
-        let storage = self.get_storage::<u64>();
+    /// Rows of a matrix of padded width 128, which are stored in two words
+    /// as in wider matrices: the storage may not be aligned for `u128`.
+    fn rows128<'a>(&'a self, arena: &'a MatrixArena) -> impl Iterator<Item = u128> + 'a {
+        self.get_words(arena)
+            .chunks_exact(2)
+            .map(|row| row[0] as u128 | (row[1] as u128) << 64)
+    }
+
+    fn col_mul_wide(&self, arena: &MatrixArena, column: &[u64], result: &mut BitSet) {
+        let storage = self.get_storage::<u64>(arena);
         let (padded_width, _) = self.get_width_and_size();
         let effective_width = padded_width / 64;
+        let intersects = simd::select_intersects();
+        let column = &column[..effective_width];
 
-        for i in 0..self.height {
-            for k in 0..effective_width {
-                if (storage[i as usize * effective_width + k] & column[k as usize]) != 0 {
-                    result.insert(i as usize);
-                    break;
-                }
+        for i in 0..self.height as usize {
+            let row = &storage[i * effective_width..(i + 1) * effective_width];
+
+            if unsafe { intersects(row, column) } {
+                result.insert(i);
             }
         }
     }
 
-    pub fn transpose(&self) -> Matrix {
-        let mut result = Matrix::new(self.width as usize, self.height as usize);
-        for i in 0..self.height as usize {
-            for j in 0..self.width as usize {
-                if self[(i, j)] {
-                    result.insert(j, i);
+    pub fn transpose(&self, arena: &mut MatrixArena) -> Matrix {
+        let result = Matrix::new(self.width as usize, self.height as usize, arena);
+        let (padded_width, _) = result.get_width_and_size();
+
+        result.fill(arena, |arena, words| {
+            for i in 0..self.height as usize {
+                for j in 0..self.width as usize {
+                    if self.get(arena, i, j) {
+                        Matrix::insert_in_words(words, padded_width, j, i);
+                    }
                 }
             }
+        })
+    }
+
+    /// Number of entries of the matrix that are set.
+    pub fn count_ones(&self, arena: &MatrixArena) -> usize {
+        if self.runs > 0 {
+            return self
+                .iter_runs(arena)
+                .map(|(rows, row)| {
+                    rows.len() * row.iter().map(|word| word.count_ones() as usize).sum::<usize>()
+                })
+                .sum();
         }
 
-        result
+        self.get_words(arena)
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
     }
 
     pub fn get_usage_count(&self) -> usize {
@@ -227,90 +435,194 @@ impl<'a> Matrix {
     }
 
     pub fn get_memory_usage(&self) -> usize {
-        let (_padded_width, size) = self.get_width_and_size();
+        std::mem::size_of::<Matrix>()
+            + if self.is_heap() {
+                self.num_words() * size_of::<u64>()
+            } else {
+                0
+            }
+    }
 
-        std::mem::size_of::<Matrix>() + if size <= 64 { 0 } else { size / 8 }
+    /// Fill the storage of the matrix with a function that has read access to
+    /// the arena. Heap matrices are built in the scratch buffer of the arena
+    /// before being copied.
+    fn fill<F>(mut self, arena: &mut MatrixArena, f: F) -> Matrix
+    where
+        F: FnOnce(&MatrixArena, &mut [u64]),
+    {
+        if self.is_heap() {
+            let mut scratch = std::mem::take(&mut arena.scratch);
+            scratch.clear();
+            scratch.resize(self.num_words(), 0);
+            f(arena, &mut scratch);
+            self.get_words_mut(arena).copy_from_slice(&scratch);
+            arena.scratch = scratch;
+        } else {
+            f(arena, slice::from_mut(&mut self.data));
+        }
+
+        self
     }
 
-    fn mulx<T>(&self, other: &Matrix, result: &mut Matrix)
+    fn mulx<T>(&self, other: &Matrix, arena: &MatrixArena, result: &mut [u64], result_width: usize)
     where
-        T: BitOr + BitAnd + Copy,
+        T: Word,
         <T as BitAnd>::Output: PartialEq + From<u8>,
     {
-        let self_storage = self.get_storage::<T>();
-        let other_storage = other.get_storage::<T>();
+        let self_storage = self.get_storage::<T>(arena);
+        let other_storage = other.get_storage::<T>(arena);
 
         for i in 0..self.height as usize {
             for j in 0..other.height as usize {
                 if (self_storage[i as usize] & other_storage[j as usize])
                     != <T as BitAnd>::Output::from(0 as u8)
                 {
-                    result.insert(i, j);
+                    Matrix::insert_in_words(result, result_width, i, j);
                 }
             }
         }
     }
 
-    fn is_heap(&self) -> bool {
-        let (_, size) = self.get_width_and_size();
-
-        size > size_of::<usize>() * 8
-    }
 }
 
-impl Drop for Matrix {
-    fn drop(&mut self) {
-        if self.is_heap() {
-            unsafe {
-                let (_, size) = self.get_width_and_size();
-                let ptr = self.data as *mut usize;
-                let len = (size / (size_of::<usize>() * 8)) + 1;
-                Vec::from_raw_parts(ptr, len, len);
+//  ____
+// |  _ \ _   _ _ __  ___
+// | |_) | | | | '_ \/ __|
+// |  _ <| |_| | | | \__ \
+// |_| \_\\__,_|_| |_|___/
+//
+
+impl Matrix {
+    /// Store the matrix as runs of identical rows if this at least halves
+    /// its storage, the matrix is given back unchanged otherwise.
+    pub fn compress(self, arena: &mut MatrixArena) -> Matrix {
+        if !self.is_heap() || self.runs > 0 {
+            return self;
+        }
+
+        let row_words = self.row_words();
+        let mut ends: Vec<u16> = Vec::new();
+        let mut rows: Vec<u64> = Vec::new();
+
+        self.for_each_run(arena, |range, row| {
+            if !ends.is_empty() && rows[rows.len() - row_words..] == *row {
+                *ends.last_mut().unwrap() = range.end as u16;
+            } else {
+                ends.push(range.end as u16);
+                rows.extend_from_slice(row);
             }
+        });
+
+        let num_words = Matrix::compressed_words(ends.len(), row_words);
+
+        if ends.is_empty() || 2 * num_words > self.num_words() {
+            return self;
         }
+
+        let compressed = Matrix {
+            height: self.height,
+            width: self.width,
+            usage_count: self.usage_count.clone(),
+            runs: ends.len() as u16,
+            data: arena.alloc(num_words),
+        };
+
+        let header = ends.len().div_ceil(4);
+        let words = arena.get_block_mut(compressed.data, num_words);
+        cast_mut::<u16>(&mut words[..header])[..ends.len()].copy_from_slice(&ends);
+        words[header..].copy_from_slice(&rows);
+
+        self.release(arena);
+        compressed
     }
-}
 
-impl Index<(usize, usize)> for Matrix {
-    type Output = bool;
+    /// Ends of the runs of a compressed matrix and the storage of their rows.
+    fn get_runs<'a>(&'a self, arena: &'a MatrixArena) -> (&'a [u16], &'a [u64]) {
+        let runs = self.runs as usize;
+        let (header, rows) = self.get_words(arena).split_at(runs.div_ceil(4));
+        (&cast::<u16>(header)[..runs], rows)
+    }
 
-    #[inline(always)]
-    fn index(&self, (row, col): (usize, usize)) -> &bool {
-        let (padded_width, _) = self.get_width_and_size();
+    /// Runs of rows of a compressed matrix, with the row shared by each run.
+    fn iter_runs<'a>(
+        &'a self,
+        arena: &'a MatrixArena,
+    ) -> impl Iterator<Item = (Range<usize>, &'a [u64])> + 'a {
+        let (ends, rows) = self.get_runs(arena);
+        let starts = iter::once(0).chain(ends.iter().map(|&end| end as usize));
+
+        starts
+            .zip(ends)
+            .zip(rows.chunks_exact(self.row_words()))
+            .map(|((start, &end), row)| (start..end as usize, row))
+    }
 
-        let result = match padded_width {
-            8 => {
-                let storage = self.get_storage::<u8>();
-                (storage[row] & (1 << col)) != 0
-            }
-            16 => {
-                let storage = self.get_storage::<u16>();
-                (storage[row] & (1 << col)) != 0
-            }
-            32 => {
-                let storage = self.get_storage::<u32>();
-                (storage[row] & (1 << col)) != 0
-            }
-            64 => {
-                let storage = self.get_storage::<u64>();
-                (storage[row] & (1 << col)) != 0
+    /// Call a function on the runs of rows of any matrix, with rows padded to
+    /// whole words as in compressed matrices. The rows of a matrix that is not
+    /// compressed are all given as runs of their own.
+    fn for_each_run<F>(&self, arena: &MatrixArena, mut f: F)
+    where
+        F: FnMut(Range<usize>, &[u64]),
+    {
+        if self.runs > 0 {
+            for (rows, row) in self.iter_runs(arena) {
+                f(rows, row);
             }
+
+            return;
+        }
+
+        let (padded_width, _) = self.get_width_and_size();
+
+        match padded_width {
+            8 => self.for_each_narrow_row::<u8, F>(arena, f),
+            16 => self.for_each_narrow_row::<u16, F>(arena, f),
+            32 => self.for_each_narrow_row::<u32, F>(arena, f),
             _ => {
-                let storage = self.get_storage::<u64>();
-                let i = col / 64;
-                let j = col % 64;
-                let effective_width = padded_width / 64;
+                let rows = self.get_words(arena).chunks_exact(self.row_words());
 
-                (storage[row * effective_width + i] & (1 << j)) != 0
+                for (i, row) in rows.take(self.height as usize).enumerate() {
+                    f(i..i + 1, row);
+                }
             }
-        };
+        }
+    }
 
-        if result {
-            &true
-        } else {
-            &false
+    fn for_each_narrow_row<T, F>(&self, arena: &MatrixArena, mut f: F)
+    where
+        T: Word + Into<u64>,
+        F: FnMut(Range<usize>, &[u64]),
+    {
+        let storage = self.get_storage::<T>(arena);
+
+        for (i, &row) in storage[..self.height as usize].iter().enumerate() {
+            f(i..i + 1, slice::from_ref(&row.into()));
         }
     }
+
+    /// Product of matrices of which at least one is compressed: each pair of
+    /// runs is compared once, and sets a whole block of the result.
+    fn mul_runs(
+        &self,
+        other: &Matrix,
+        arena: &MatrixArena,
+        result: &mut [u64],
+        result_width: usize,
+    ) {
+        let intersects = simd::select_intersects();
+
+        self.for_each_run(arena, |rows, self_row| {
+            other.for_each_run(arena, |cols, other_row| {
+                if unsafe { intersects(self_row, other_row) } {
+                    for i in rows.clone() {
+                        for j in cols.clone() {
+                            Matrix::insert_in_words(result, result_width, i, j);
+                        }
+                    }
+                }
+            })
+        })
+    }
 }
 
 //  ____              _
@@ -325,61 +637,354 @@ impl Index<(usize, usize)> for Matrix {
 // |_|  |_|\__,_|\__|_|  |_/_/\_\
 //
 
-/// Implements multiplication for matrices. The other matric is assumed to be transposed.
-impl Mul for &Matrix {
-    type Output = Matrix;
+impl Matrix {
+    /// Multiplication of matrices. The other matrix is assumed to be transposed.
+    pub fn mul(&self, other: &Matrix, arena: &mut MatrixArena) -> Matrix {
+        let result = Matrix::new(self.height as usize, other.height as usize, arena);
+        let (result_width, _) = result.get_width_and_size();
+        let (padded_width, _) = self.get_width_and_size();
 
-    fn mul(self, other: &Matrix) -> Matrix {
-        let mut result = Matrix::new(self.height as usize, other.height as usize);
+        result.fill(arena, |arena, words| {
+            if other.runs > 0 || (self.runs > 0 && padded_width <= 64) {
+                self.mul_runs(other, arena, words, result_width);
+            } else if padded_width <= 64 {
+                match padded_width {
+                    8 => self.mulx::<u8>(other, arena, words, result_width),
+                    16 => self.mulx::<u16>(other, arena, words, result_width),
+                    32 => self.mulx::<u32>(other, arena, words, result_width),
+                    64 => self.mulx::<u64>(other, arena, words, result_width),
+                    width => panic!("invalid matrix effective width {}", width),
+                }
+            } else if padded_width == 128 && self.runs == 0 {
+                for (i, self_row) in self.rows128(arena).enumerate() {
+                    for (j, other_row) in other.rows128(arena).enumerate() {
+                        if self_row & other_row != 0 {
+                            Matrix::insert_in_words(words, result_width, i, j);
+                        }
+                    }
+                }
+            } else {
+                self.mul_blocked(other, arena, words, result_width);
+            }
+        })
+    }
 
+    /// Product of wide matrices by tiles of 64 rows of the other matrix, which
+    /// stay in cache while all the rows of this matrix are compared to them.
+    ///
+    /// The entries of a row of the result that fall in a tile are found word
+    /// by word: a word of the row is only compared to the rows of the tile
+    /// whose entry is not found yet, and the row is left as soon as all of
+    /// them are found. The rows of a compressed matrix are compared once for
+    /// their whole run.
+    fn mul_blocked(
+        &self,
+        other: &Matrix,
+        arena: &MatrixArena,
+        result: &mut [u64],
+        result_width: usize,
+    ) {
+        let other_storage = other.get_storage::<u64>(arena);
         let (padded_width, _) = self.get_width_and_size();
-        if padded_width <= 64 {
-            match padded_width {
-                8 => self.mulx::<u8>(other, &mut result),
-                16 => self.mulx::<u16>(other, &mut result),
-                32 => self.mulx::<u32>(other, &mut result),
-                64 => self.mulx::<u64>(other, &mut result),
-                width => panic!("invalid matrix effective width {}", width),
-            }
+        let effective_width = padded_width / 64;
+        let other_height = other.height as usize;
+
+        // Rows of this matrix are only compared once per run if it is
+        // compressed.
+        let (self_storage, runs) = if self.runs > 0 {
+            let runs: Vec<_> = self.iter_runs(arena).map(|(rows, _)| rows).collect();
+            (self.get_runs(arena).1, runs)
         } else {
-            let self_storage = self.get_storage::<u64>();
-            let other_storage = other.get_storage::<u64>();
-            let effective_width = padded_width / 64;
+            (self.get_storage::<u64>(arena), Vec::new())
+        };
 
-            for i in 0..self.height as usize {
-                for j in 0..other.height as usize {
-                    for k in 0..effective_width {
-                        if (self_storage[i * effective_width + k]
-                            & other_storage[j * effective_width + k])
-                            != 0
-                        {
-                            result.insert(i, j);
-                            break;
+        for tile_start in (0..other_height).step_by(64) {
+            let tile_len = (other_height - tile_start).min(64);
+            let tile = &other_storage
+                [tile_start * effective_width..(tile_start + tile_len) * effective_width];
+
+            // Rows of the tile that have an entry set, the others can't
+            // intersect any row.
+            let tile_mask = tile
+                .chunks_exact(effective_width)
+                .enumerate()
+                .filter(|(_, row)| row.iter().any(|&word| word != 0))
+                .fold(0u64, |mask, (k, _)| mask | 1 << k);
+
+            if tile_mask == 0 {
+                continue;
+            }
+
+            for (i, row) in self_storage.chunks_exact(effective_width).enumerate() {
+                let mut left = tile_mask;
+                let mut found = 0u64;
+
+                for (w, &word) in row.iter().enumerate() {
+                    if word == 0 {
+                        continue;
+                    }
+
+                    let mut candidates = left;
+
+                    while candidates != 0 {
+                        let k = candidates.trailing_zeros() as usize;
+                        candidates &= candidates - 1;
+
+                        if word & tile[k * effective_width + w] != 0 {
+                            found |= 1 << k;
                         }
                     }
+
+                    left &= !found;
+
+                    if left == 0 {
+                        break;
+                    }
                 }
+
+                let rows = runs.get(i).cloned().unwrap_or(i..i + 1);
+
+                while found != 0 {
+                    let k = found.trailing_zeros() as usize;
+                    found &= found - 1;
+
+                    for row in rows.clone() {
+                        Matrix::insert_in_words(result, result_width, row, tile_start + k);
+                    }
+                }
+            }
+        }
+    }
+}
+
+//  ____ ___ __  __ ____
+// / ___|_ _|  \/  |  _ \
+// \___ \| || |\/| | | | |
+//  ___) | || |  | | |_| |
+// |____/___|_|  |_|____/
+//
+
+/// Kernels checking if two rows of a wide matrix share a bit, which is the
+/// inner loop of the products of wide matrices.
+pub mod simd {
+    /// Check if two slices of the same length have a common bit set. This is
+    /// unsafe as the kernel may require CPU features that must be checked first.
+    pub type Intersects = unsafe fn(&[u64], &[u64]) -> bool;
+
+    /// Pick the fastest kernel supported by the running CPU.
+    #[inline]
+    pub fn select_intersects() -> Intersects {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return intersects_avx2;
+            }
+        }
+
+        #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return intersects_neon;
+            }
+        }
+
+        intersects_scalar
+    }
+
+    /// Name of the kernel picked for the running CPU.
+    pub fn kernel_name() -> &'static str {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return "avx2";
+            }
+        }
+
+        #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return "neon";
+            }
+        }
+
+        "scalar"
+    }
+
+    unsafe fn intersects_scalar(a: &[u64], b: &[u64]) -> bool {
+        a.iter().zip(b).any(|(x, y)| x & y != 0)
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    #[target_feature(enable = "avx2")]
+    unsafe fn intersects_avx2(a: &[u64], b: &[u64]) -> bool {
+        use std::arch::x86_64::{__m256i, _mm256_loadu_si256, _mm256_testz_si256};
+
+        let len = a.len().min(b.len());
+        let mut k = 0;
+
+        while k + 4 <= len {
+            let x = _mm256_loadu_si256(a.as_ptr().add(k) as *const __m256i);
+            let y = _mm256_loadu_si256(b.as_ptr().add(k) as *const __m256i);
+
+            if _mm256_testz_si256(x, y) == 0 {
+                return true;
             }
+
+            k += 4;
         }
 
-        //		println!("Matrix multiplication:\n{:?}\n{:?}\n{:?}",self,other,result);
+        intersects_scalar(&a[k..len], &b[k..len])
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+    #[target_feature(enable = "neon")]
+    unsafe fn intersects_neon(a: &[u64], b: &[u64]) -> bool {
+        use std::arch::aarch64::{vandq_u64, vgetq_lane_u64, vld1q_u64};
+
+        let len = a.len().min(b.len());
+        let mut k = 0;
 
-        result
+        while k + 2 <= len {
+            let x = vld1q_u64(a.as_ptr().add(k));
+            let y = vld1q_u64(b.as_ptr().add(k));
+            let z = vandq_u64(x, y);
+
+            if vgetq_lane_u64::<0>(z) | vgetq_lane_u64::<1>(z) != 0 {
+                return true;
+            }
+
+            k += 2;
+        }
+
+        intersects_scalar(&a[k..len], &b[k..len])
     }
 }
 
-impl fmt::Debug for Matrix {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "")?;
-        for i in 0..self.height as usize {
-            for j in 0..self.width as usize {
-                let bit = match self[(i, j)] {
-                    false => ".",
-                    true => "x",
-                };
-                write!(f, "{}", bit)?;
+#[cfg(test)]
+mod tests {
+    use bit_set::BitSet;
+
+    use super::{simd, Matrix, MatrixArena};
+
+    const WIDTHS: [usize; 14] = [3, 8, 13, 16, 30, 32, 50, 64, 65, 100, 128, 129, 200, 300];
+    const HEIGHTS: [usize; 5] = [1, 7, 64, 70, 130];
+
+    /// Linear congruential generator, enough to get varied matrices.
+    fn next(seed: &mut u64) -> u64 {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
+    /// Sparse rows with a few entries each, most rows being repeated so that
+    /// the matrices built from them can be compressed.
+    fn random_rows(height: usize, width: usize, seed: &mut u64) -> Vec<Vec<bool>> {
+        let mut rows: Vec<Vec<bool>> = Vec::new();
+
+        for _ in 0..height {
+            if !rows.is_empty() && !next(seed).is_multiple_of(4) {
+                rows.push(rows.last().unwrap().clone());
+            } else {
+                rows.push((0..width).map(|_| next(seed) % (width as u64) < 3).collect());
+            }
+        }
+
+        rows
+    }
+
+    fn build(rows: &[Vec<bool>], width: usize, compress: bool, arena: &mut MatrixArena) -> Matrix {
+        let mut matrix = Matrix::new(rows.len(), width, arena);
+
+        for (i, row) in rows.iter().enumerate() {
+            for (j, &entry) in row.iter().enumerate() {
+                if entry {
+                    matrix.insert(arena, i, j);
+                }
+            }
+        }
+
+        if compress {
+            matrix.compress(arena)
+        } else {
+            matrix
+        }
+    }
+
+    fn intersects(a: &[bool], b: &[bool]) -> bool {
+        a.iter().zip(b).any(|(&x, &y)| x && y)
+    }
+
+    #[test]
+    fn products() {
+        let mut seed = 1;
+        let mut arena = MatrixArena::new();
+
+        for &width in &WIDTHS {
+            for &height in &HEIGHTS {
+                let rows = random_rows(height, width, &mut seed);
+                let other_rows = random_rows(HEIGHTS[(height + width) % 5], width, &mut seed);
+
+                for &(compress, compress_other) in
+                    &[(false, false), (true, false), (false, true), (true, true)]
+                {
+                    let matrix = build(&rows, width, compress, &mut arena);
+                    let other = build(&other_rows, width, compress_other, &mut arena);
+                    let product = matrix.mul(&other, &mut arena);
+
+                    for (i, row) in rows.iter().enumerate() {
+                        for (j, other_row) in other_rows.iter().enumerate() {
+                            let expected = intersects(row, other_row);
+                            assert_eq!(product.get(&arena, i, j), expected, "{}x{}", height, width);
+                        }
+                    }
+
+                    product.release(&mut arena);
+                    other.release(&mut arena);
+                    matrix.release(&mut arena);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn column_products() {
+        let mut seed = 2;
+        let mut arena = MatrixArena::new();
+
+        for &width in &WIDTHS {
+            for &height in &HEIGHTS {
+                let rows = random_rows(height, width, &mut seed);
+                let column = random_rows(1, width, &mut seed).pop().unwrap();
+
+                for &compress in &[false, true] {
+                    let matrix = build(&rows, width, compress, &mut arena);
+                    let mut result: BitSet = (0..width).filter(|&j| column[j]).collect();
+                    matrix.col_mul_inplace(&arena, &mut result);
+
+                    let expected: BitSet = (0..height)
+                        .filter(|&i| intersects(&rows[i], &column))
+                        .collect();
+                    assert_eq!(result, expected, "{}x{}", height, width);
+
+                    matrix.release(&mut arena);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simd_kernel() {
+        let mut seed = 3;
+        let intersects = simd::select_intersects();
+
+        for len in 0..20 {
+            for _ in 0..50 {
+                // Words with a single bit set, which rarely intersect.
+                let a: Vec<u64> = (0..len).map(|_| 1 << (next(&mut seed) % 64)).collect();
+                let b: Vec<u64> = (0..len).map(|_| 1 << (next(&mut seed) % 64)).collect();
+                let expected = a.iter().zip(&b).any(|(x, y)| x & y != 0);
+
+                assert_eq!(unsafe { intersects(&a, &b) }, expected);
             }
-            writeln!(f, "")?;
         }
-        writeln!(f, "")
     }
 }
@@ -0,0 +1,41 @@
+//  __  __
+// |  \/  | ___ _ __ __ _  ___
+// | |\/| |/ _ \ '__/ _` |/ _ \
+// | |  | |  __/ | | (_| |  __/
+// |_|  |_|\___|_|  \__, |\___|
+//                  |___/
+
+use std::ops::Range;
+
+/// Maximal region of the text covered by overlapping or adjacent matches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub span: Range<usize>,
+    /// Number of matches whose main span is inside of the region.
+    pub count: usize,
+}
+
+/// Coalesce the spans of matches into maximal regions, sorted by position.
+/// Two spans are merged when they overlap or when one ends where the other
+/// starts.
+pub fn merge<T>(spans: T) -> Vec<Region>
+where
+    T: Iterator<Item = Range<usize>>,
+{
+    let mut spans: Vec<_> = spans.collect();
+    spans.sort_by_key(|span| (span.start, span.end));
+
+    let mut regions: Vec<Region> = Vec::new();
+
+    for span in spans {
+        match regions.last_mut() {
+            Some(region) if span.start <= region.span.end => {
+                region.span.end = region.span.end.max(span.end);
+                region.count += 1;
+            }
+            _ => regions.push(Region { span, count: 1 }),
+        }
+    }
+
+    regions
+}
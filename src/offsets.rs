@@ -0,0 +1,106 @@
+//   ___   __  __          _
+//  / _ \ / _|/ _|___  ___| |_ ___
+// | | | | |_| |_/ __|/ _ \ __/ __|
+// | |_| |  _|  _\__ \  __/ |_\__ \
+//  \___/|_| |_| |___/\___|\__|___/
+//
+
+use std::fmt;
+
+/// Unit used to express positions in the text.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OffsetUnit {
+    /// Offsets in bytes of the UTF-8 encoding, as used internally.
+    Bytes,
+    /// Offsets in UTF-16 code units, as used by editors and JavaScript.
+    Utf16,
+    /// Offsets in Unicode scalar values, as used to index strings in Python.
+    Chars,
+}
+
+impl fmt::Display for OffsetUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OffsetUnit::Bytes => write!(f, "bytes"),
+            OffsetUnit::Utf16 => write!(f, "utf16"),
+            OffsetUnit::Chars => write!(f, "chars"),
+        }
+    }
+}
+
+/// Conversion table from byte offsets of a text to another offset unit.
+///
+/// The table is computed once for the whole text, which makes the conversion
+/// of any position a constant time lookup.
+pub struct OffsetTable {
+    unit: OffsetUnit,
+    /// Offset in the unit for each byte offset that lies on a char boundary.
+    offsets: Vec<usize>,
+}
+
+impl OffsetTable {
+    pub fn new(text: &str, unit: OffsetUnit) -> OffsetTable {
+        if unit == OffsetUnit::Bytes {
+            return OffsetTable {
+                unit,
+                offsets: Vec::new(),
+            };
+        }
+
+        let mut offsets = vec![0; text.len() + 1];
+        let mut curr_offset = 0;
+
+        for (pos, curr_char) in text.char_indices() {
+            offsets[pos] = curr_offset;
+            curr_offset += match unit {
+                OffsetUnit::Utf16 => curr_char.len_utf16(),
+                _ => 1,
+            };
+        }
+
+        offsets[text.len()] = curr_offset;
+        OffsetTable { unit, offsets }
+    }
+
+    pub fn get_unit(&self) -> OffsetUnit {
+        self.unit
+    }
+
+    /// Convert a byte offset, which must be on a char boundary, into the unit
+    /// of the table.
+    pub fn convert(&self, byte_offset: usize) -> usize {
+        match self.unit {
+            OffsetUnit::Bytes => byte_offset,
+            _ => self.offsets[byte_offset],
+        }
+    }
+}
+
+/// Index of the lines of a text, which converts byte offsets into 1-based line
+/// and column numbers. Columns are counted in characters.
+pub struct LineIndex<'t> {
+    text: &'t str,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl<'t> LineIndex<'t> {
+    pub fn new(text: &'t str) -> LineIndex<'t> {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+
+        LineIndex { text, line_starts }
+    }
+
+    /// Line and column of a byte offset, which must be on a char boundary.
+    /// The end of a line is given as the column following its last character.
+    pub fn position(&self, byte_offset: usize) -> (usize, usize) {
+        let line = self
+            .line_starts
+            .partition_point(|&start| start <= byte_offset)
+            - 1;
+        let column = self.text[self.line_starts[line]..byte_offset].chars().count();
+        (line + 1, column + 1)
+    }
+}
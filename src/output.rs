@@ -0,0 +1,100 @@
+//   ___        _               _
+//  / _ \ _   _| |_ _ __  _   _| |_
+// | | | | | | | __| '_ \| | | | __|
+// | |_| | |_| | |_| |_) | |_| | |_
+//  \___/ \__,_|\__| .__/ \__,_|\__|
+//                 |_|
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Size of the buffer of the output, writing matches one by one to a file or
+/// to a pipe is much slower.
+const BUFFER_SIZE: usize = 1 << 20;
+
+/// Destination of the matches, either STDOUT or a file.
+///
+/// Lines of the output are meant to be formatted in a reusable buffer with
+/// `push_int` and `push_quoted` and then written at once: going through the
+/// formatting machinery for each group dominates the running time when the
+/// matches are dense.
+///
+/// A file is written under a temporary name in the same directory and only
+/// renamed once complete, so that it is never seen partially written. STDOUT
+/// is only buffered if it is not a terminal, so that matches are still
+/// displayed as soon as they are found.
+pub struct Output {
+    writer: BufWriter<Box<dyn Write>>,
+    /// Temporary and final paths of the file.
+    paths: Option<(PathBuf, PathBuf)>,
+}
+
+impl Output {
+    pub fn create(path: Option<&str>) -> io::Result<Output> {
+        let path = match path {
+            None | Some("-") => {
+                let capacity = if atty::is(atty::Stream::Stdout) { 0 } else { BUFFER_SIZE };
+                let stdout: Box<dyn Write> = Box::new(io::stdout().lock());
+
+                return Ok(Output {
+                    writer: BufWriter::with_capacity(capacity, stdout),
+                    paths: None,
+                });
+            }
+            Some(path) => PathBuf::from(path),
+        };
+
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(format!(".tmp{}", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+        let file: Box<dyn Write> = Box::new(File::create(&tmp_path)?);
+
+        Ok(Output {
+            writer: BufWriter::with_capacity(BUFFER_SIZE, file),
+            paths: Some((tmp_path, path)),
+        })
+    }
+
+    /// Flush the output and give the file its final name.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        if let Some((tmp_path, path)) = self.paths.take() {
+            drop(self.writer);
+            fs::rename(tmp_path, path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Append an integer to a line of the output.
+pub fn push_int(line: &mut Vec<u8>, x: impl itoa::Integer) {
+    line.extend_from_slice(itoa::Buffer::new().format(x).as_bytes());
+}
+
+/// Append a text to a line of the output, quoted and escaped as with `{:?}`.
+/// Printable ASCII texts are copied as is.
+pub fn push_quoted(line: &mut Vec<u8>, text: &str) {
+    let plain = (text.bytes())
+        .all(|byte| (b' '..=b'~').contains(&byte) && byte != b'"' && byte != b'\\');
+
+    if plain {
+        line.push(b'"');
+        line.extend_from_slice(text.as_bytes());
+        line.push(b'"');
+    } else {
+        write!(line, "{:?}", text).expect("Writing to a vector can't fail");
+    }
+}
@@ -0,0 +1,564 @@
+//  ____                            _
+// |  _ \ __ _ _ __ __ _ _   _  ___| |_
+// | |_) / _` | '__/ _` | | | |/ _ \ __|
+// |  __/ (_| | | | (_| | |_| |  __/ |_
+// |_|   \__,_|_|  \__, |\__,_|\___|\__|
+//                    |_|
+
+use std::io::{self, Write};
+
+use super::mapping::Mapping;
+
+/// Maximal number of rows of a row group.
+const ROW_GROUP_SIZE: usize = 1 << 20;
+
+/// Size of the values of a column from which its row group is written, as
+/// pages can't exceed 2GB.
+const MAX_PAGE_SIZE: usize = 1 << 27;
+
+const MAGIC: &[u8] = b"PAR1";
+
+/// Physical types of Parquet.
+const INT64: i32 = 2;
+const BYTE_ARRAY: i32 = 6;
+
+/// Encodings of Parquet.
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+
+/// Write matches in a Parquet file with columns `file`, `span_start` and
+/// `span_end` for the main span, and an optional column holding the text of
+/// each group, which is null for the matches not binding it.
+///
+/// Each row group is written as a single uncompressed page per column with
+/// plain encoding.
+pub struct ParquetWriter<W: Write> {
+    output: W,
+    /// Number of bytes written so far, the offsets of pages are stored in the
+    /// footer of the file.
+    pos: usize,
+    file: String,
+    groups: Vec<String>,
+    columns: Vec<Column>,
+    /// Number of rows of the current row group and of the whole file.
+    num_rows: usize,
+    total_rows: usize,
+    row_groups: Vec<Thrift>,
+}
+
+impl<W: Write> ParquetWriter<W> {
+    /// Create a writer for matches read from the given file and which may
+    /// bind the given groups.
+    pub fn new(mut output: W, file: &str, groups: Vec<String>) -> io::Result<ParquetWriter<W>> {
+        output.write_all(MAGIC)?;
+
+        let mut columns = vec![
+            Column::new("file", BYTE_ARRAY, false),
+            Column::new("span_start", INT64, false),
+            Column::new("span_end", INT64, false),
+        ];
+        columns.extend(groups.iter().map(|group| Column::new(group, BYTE_ARRAY, true)));
+
+        Ok(ParquetWriter {
+            output,
+            pos: MAGIC.len(),
+            file: file.to_string(),
+            groups,
+            columns,
+            num_rows: 0,
+            total_rows: 0,
+            row_groups: Vec::new(),
+        })
+    }
+
+    pub fn write(&mut self, mapping: &Mapping) -> io::Result<()> {
+        // Mappings assigning no group, which are given by optional groups,
+        // have no span to write.
+        let span = match mapping.main_span() {
+            Some(span) => span,
+            None => return Ok(()),
+        };
+
+        self.columns[0].push_bytes(Some(self.file.as_bytes()));
+        self.columns[1].push_int(span.start as i64);
+        self.columns[2].push_int(span.end as i64);
+
+        for (group, column) in self.groups.iter().zip(&mut self.columns[3..]) {
+            column.push_bytes(mapping.get_text(group).map(str::as_bytes));
+        }
+
+        self.num_rows += 1;
+
+        if self.num_rows == ROW_GROUP_SIZE
+            || self.columns.iter().any(|column| column.values.len() >= MAX_PAGE_SIZE)
+        {
+            self.write_row_group()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the remaining matches and the footer of the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.num_rows > 0 {
+            self.write_row_group()?;
+        }
+
+        let mut schema = vec![Thrift::Struct(vec![
+            (4, Thrift::Binary(b"schema".to_vec())),
+            (5, Thrift::I32(self.columns.len() as i32)),
+        ])];
+        schema.extend(self.columns.iter().map(Column::schema));
+
+        let created_by = format!("enum-spanner-rs version {}", env!("CARGO_PKG_VERSION"));
+        let metadata = Thrift::Struct(vec![
+            (1, Thrift::I32(1)),
+            (2, Thrift::List(schema)),
+            (3, Thrift::I64(self.total_rows as i64)),
+            (4, Thrift::List(self.row_groups)),
+            (6, Thrift::Binary(created_by.into_bytes())),
+        ]);
+
+        let mut footer = Vec::new();
+        metadata.write(&mut footer);
+        footer.extend_from_slice(&(footer.len() as u32).to_le_bytes());
+        footer.extend_from_slice(MAGIC);
+        self.output.write_all(&footer)?;
+        self.output.flush()
+    }
+
+    fn write_row_group(&mut self) -> io::Result<()> {
+        let mut chunks = Vec::new();
+        let mut total_size = 0;
+
+        for column in &mut self.columns {
+            let offset = self.pos;
+            let page = column.take_page();
+            let header = Thrift::Struct(vec![
+                (1, Thrift::I32(0)),
+                (2, Thrift::I32(page.len() as i32)),
+                (3, Thrift::I32(page.len() as i32)),
+                (
+                    5,
+                    Thrift::Struct(vec![
+                        (1, Thrift::I32(self.num_rows as i32)),
+                        (2, Thrift::I32(PLAIN)),
+                        (3, Thrift::I32(RLE)),
+                        (4, Thrift::I32(RLE)),
+                    ]),
+                ),
+            ]);
+
+            let mut bytes = Vec::new();
+            header.write(&mut bytes);
+            bytes.extend_from_slice(&page);
+            self.output.write_all(&bytes)?;
+            self.pos += bytes.len();
+            total_size += bytes.len();
+
+            let metadata = Thrift::Struct(vec![
+                (1, Thrift::I32(column.physical_type)),
+                (2, Thrift::List(vec![Thrift::I32(PLAIN), Thrift::I32(RLE)])),
+                (3, Thrift::List(vec![Thrift::Binary(column.name.clone().into_bytes())])),
+                (4, Thrift::I32(0)),
+                (5, Thrift::I64(self.num_rows as i64)),
+                (6, Thrift::I64(bytes.len() as i64)),
+                (7, Thrift::I64(bytes.len() as i64)),
+                (9, Thrift::I64(offset as i64)),
+            ]);
+
+            chunks.push(Thrift::Struct(vec![
+                (2, Thrift::I64(offset as i64)),
+                (3, metadata),
+            ]));
+        }
+
+        self.row_groups.push(Thrift::Struct(vec![
+            (1, Thrift::List(chunks)),
+            (2, Thrift::I64(total_size as i64)),
+            (3, Thrift::I64(self.num_rows as i64)),
+        ]));
+
+        self.total_rows += self.num_rows;
+        self.num_rows = 0;
+        Ok(())
+    }
+}
+
+/// Values of a column in the current row group, with their definition levels
+/// if the column is optional.
+struct Column {
+    name: String,
+    physical_type: i32,
+    optional: bool,
+    values: Vec<u8>,
+    defined: Vec<bool>,
+}
+
+impl Column {
+    fn new(name: &str, physical_type: i32, optional: bool) -> Column {
+        Column {
+            name: name.to_string(),
+            physical_type,
+            optional,
+            values: Vec::new(),
+            defined: Vec::new(),
+        }
+    }
+
+    fn push_int(&mut self, value: i64) {
+        self.values.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_bytes(&mut self, value: Option<&[u8]>) {
+        if self.optional {
+            self.defined.push(value.is_some());
+        }
+
+        if let Some(value) = value {
+            self.values.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            self.values.extend_from_slice(value);
+        }
+    }
+
+    /// Element of the schema describing the column, strings are tagged as
+    /// UTF-8.
+    fn schema(&self) -> Thrift {
+        let mut fields = vec![
+            (1, Thrift::I32(self.physical_type)),
+            (3, Thrift::I32(self.optional as i32)),
+            (4, Thrift::Binary(self.name.clone().into_bytes())),
+        ];
+
+        if self.physical_type == BYTE_ARRAY {
+            fields.push((6, Thrift::I32(0)));
+            fields.push((10, Thrift::Struct(vec![(1, Thrift::Struct(Vec::new()))])));
+        }
+
+        Thrift::Struct(fields)
+    }
+
+    /// Build the content of a data page holding the values of the column and
+    /// start a new row group. Definition levels are stored as runs of the
+    /// RLE hybrid encoding, prefixed by their size.
+    fn take_page(&mut self) -> Vec<u8> {
+        let mut page = Vec::new();
+
+        if self.optional {
+            let mut levels = Vec::new();
+
+            for run in self.defined.chunk_by(|x, y| x == y) {
+                write_varint(&mut levels, (run.len() as u64) << 1);
+                levels.push(run[0] as u8);
+            }
+
+            page.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+            page.extend_from_slice(&levels);
+        }
+
+        page.append(&mut self.values);
+        self.defined.clear();
+        page
+    }
+}
+
+//  _____ _          _  __ _
+// |_   _| |__  _ __(_)/ _| |_
+//   | | | '_ \| '__| | |_| __|
+//   | | | | | | |  | |  _| |_
+//   |_| |_| |_|_|  |_|_|  \__|
+//
+
+/// Value encoded with Thrift's compact protocol, only covering what the
+/// metadata of Parquet files needs.
+enum Thrift {
+    I32(i32),
+    I64(i64),
+    Binary(Vec<u8>),
+    List(Vec<Thrift>),
+    /// Fields of a struct with their ids, in increasing order.
+    Struct(Vec<(i16, Thrift)>),
+}
+
+impl Thrift {
+    fn type_id(&self) -> u8 {
+        match self {
+            Thrift::I32(_) => 5,
+            Thrift::I64(_) => 6,
+            Thrift::Binary(_) => 8,
+            Thrift::List(_) => 9,
+            Thrift::Struct(_) => 12,
+        }
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            Thrift::I32(x) => write_varint(buf, ((x << 1) ^ (x >> 31)) as u32 as u64),
+            Thrift::I64(x) => write_varint(buf, ((x << 1) ^ (x >> 63)) as u64),
+            Thrift::Binary(bytes) => {
+                write_varint(buf, bytes.len() as u64);
+                buf.extend_from_slice(bytes);
+            }
+            Thrift::List(items) => {
+                // The type of the elements of an empty list doesn't matter.
+                let type_id = items.first().map_or(12, Thrift::type_id);
+
+                if items.len() < 15 {
+                    buf.push((items.len() as u8) << 4 | type_id);
+                } else {
+                    buf.push(0xf0 | type_id);
+                    write_varint(buf, items.len() as u64);
+                }
+
+                for item in items {
+                    item.write(buf);
+                }
+            }
+            Thrift::Struct(fields) => {
+                let mut last_id = 0;
+
+                for (id, value) in fields {
+                    buf.push(((id - last_id) as u8) << 4 | value.type_id());
+                    value.write(buf);
+                    last_id = *id;
+                }
+
+                buf.push(0);
+            }
+        }
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        buf.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+
+    buf.push(x as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::convert::TryInto;
+
+    use enum_spanner_rs::mapping::indexed_dag::IndexedDag;
+    use enum_spanner_rs::mapping::SpannerEnumerator;
+    use enum_spanner_rs::regex;
+
+    use super::{ParquetWriter, BYTE_ARRAY, INT64, MAGIC};
+
+    /// Value read with Thrift's compact protocol.
+    #[derive(Debug)]
+    enum Value {
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Value>),
+        Struct(BTreeMap<i16, Value>),
+    }
+
+    impl Value {
+        fn int(&self) -> i64 {
+            match self {
+                Value::Int(x) => *x,
+                value => panic!("not an integer: {:?}", value),
+            }
+        }
+
+        fn list(&self) -> &[Value] {
+            match self {
+                Value::List(items) => items,
+                value => panic!("not a list: {:?}", value),
+            }
+        }
+
+        fn field(&self, id: i16) -> &Value {
+            match self {
+                Value::Struct(fields) => &fields[&id],
+                value => panic!("not a struct: {:?}", value),
+            }
+        }
+
+        fn string(&self, id: i16) -> String {
+            match self.field(id) {
+                Value::Binary(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+                value => panic!("not a string: {:?}", value),
+            }
+        }
+    }
+
+    fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+        let mut x = 0;
+
+        for shift in (0..).step_by(7) {
+            let byte = buf[*pos];
+            *pos += 1;
+            x |= ((byte & 0x7f) as u64) << shift;
+
+            if byte < 0x80 {
+                break;
+            }
+        }
+
+        x
+    }
+
+    fn read_value(buf: &[u8], pos: &mut usize, type_id: u8) -> Value {
+        match type_id {
+            5 | 6 => {
+                let x = read_varint(buf, pos);
+                Value::Int((x >> 1) as i64 ^ -((x & 1) as i64))
+            }
+            8 => {
+                let len = read_varint(buf, pos) as usize;
+                *pos += len;
+                Value::Binary(buf[*pos - len..*pos].to_vec())
+            }
+            9 => {
+                let header = buf[*pos];
+                *pos += 1;
+                let len = match header >> 4 {
+                    15 => read_varint(buf, pos) as usize,
+                    len => len as usize,
+                };
+
+                Value::List((0..len).map(|_| read_value(buf, pos, header & 0xf)).collect())
+            }
+            12 => {
+                let mut fields = BTreeMap::new();
+                let mut id = 0;
+
+                loop {
+                    let header = buf[*pos];
+                    *pos += 1;
+
+                    if header == 0 {
+                        break;
+                    }
+
+                    id += (header >> 4) as i16;
+                    fields.insert(id, read_value(buf, pos, header & 0xf));
+                }
+
+                Value::Struct(fields)
+            }
+            type_id => panic!("unexpected type {}", type_id),
+        }
+    }
+
+    /// Values of a column, `None` for the rows where it is null.
+    type Column = Vec<Option<Vec<u8>>>;
+
+    /// Read a file of the schema written by `ParquetWriter`, which gives the
+    /// names of the columns and the values of each column.
+    fn read_file(file: &[u8]) -> (Vec<String>, Vec<Column>) {
+        let len = file.len();
+        assert_eq!(&file[..4], MAGIC);
+        assert_eq!(&file[len - 4..], MAGIC);
+
+        let footer_len = u32::from_le_bytes(file[len - 8..len - 4].try_into().unwrap()) as usize;
+        let mut pos = len - 8 - footer_len;
+        let metadata = read_value(file, &mut pos, 12);
+        assert_eq!(pos, len - 8);
+
+        let schema = &metadata.field(2).list()[1..];
+        let names = schema.iter().map(|element| element.string(4)).collect();
+        let mut columns = vec![Vec::new(); schema.len()];
+
+        for row_group in metadata.field(4).list() {
+            let num_rows = row_group.field(3).int() as usize;
+
+            for ((chunk, element), column) in
+                row_group.field(1).list().iter().zip(schema).zip(&mut columns)
+            {
+                let mut pos = chunk.field(3).field(9).int() as usize;
+                let header = read_value(file, &mut pos, 12);
+                let page = &file[pos..pos + header.field(2).int() as usize];
+                assert_eq!(header.field(5).field(1).int() as usize, num_rows);
+
+                // Optional columns start with runs of definition levels.
+                let mut defined = vec![true; num_rows];
+                let mut pos = 0;
+
+                if element.field(3).int() == 1 {
+                    let levels_len = u32::from_le_bytes(page[..4].try_into().unwrap()) as usize;
+                    pos = 4;
+                    defined.clear();
+
+                    while pos < 4 + levels_len {
+                        let run_len = read_varint(page, &mut pos) >> 1;
+                        defined.extend((0..run_len).map(|_| page[pos] == 1));
+                        pos += 1;
+                    }
+                }
+
+                for defined in defined {
+                    if !defined {
+                        column.push(None);
+                        continue;
+                    }
+
+                    let value_len = match element.field(1).int() as i32 {
+                        INT64 => 8,
+                        BYTE_ARRAY => {
+                            pos += 4;
+                            u32::from_le_bytes(page[pos - 4..pos].try_into().unwrap()) as usize
+                        }
+                        physical_type => panic!("unexpected type {}", physical_type),
+                    };
+
+                    column.push(Some(page[pos..pos + value_len].to_vec()));
+                    pos += value_len;
+                }
+
+                assert_eq!(pos, page.len());
+            }
+        }
+
+        assert_eq!(metadata.field(3).int() as usize, columns[0].len());
+        (names, columns)
+    }
+
+    #[test]
+    fn round_trip() {
+        let automaton = regex::compile(r"(?P<x>a)?(?P<y>b)é?");
+        let text = "ab bé ba";
+        let groups = vec!["x".to_string(), "y".to_string()];
+
+        let mut dag = IndexedDag::builder(automaton, text).build();
+        dag.preprocess();
+        let mappings: Vec<_> = dag.iter().collect();
+
+        let mut file = Vec::new();
+        let mut writer = ParquetWriter::new(&mut file, "file.txt", groups.clone()).unwrap();
+
+        for mapping in &mappings {
+            writer.write(mapping).unwrap();
+        }
+
+        writer.finish().unwrap();
+
+        let (names, columns) = read_file(&file);
+        assert_eq!(names, ["file", "span_start", "span_end", "x", "y"]);
+
+        let int = |x: usize| Some((x as i64).to_le_bytes().to_vec());
+        let spans = mappings.iter().map(|mapping| mapping.main_span().unwrap());
+        let text_of = |group| -> Vec<_> {
+            let texts = mappings.iter().map(|mapping| mapping.get_text(group));
+            texts.map(|text| text.map(|text| text.as_bytes().to_vec())).collect()
+        };
+
+        assert_eq!(columns[0], vec![Some(b"file.txt".to_vec()); mappings.len()]);
+        assert_eq!(columns[1], spans.clone().map(|span| int(span.start)).collect::<Vec<_>>());
+        assert_eq!(columns[2], spans.map(|span| int(span.end)).collect::<Vec<_>>());
+        assert_eq!(columns[3], text_of("x"));
+        assert_eq!(columns[4], text_of("y"));
+        assert!(columns[3].contains(&None));
+
+        // A file without matches only holds its schema.
+        let mut file = Vec::new();
+        ParquetWriter::new(&mut file, "file.txt", groups).unwrap().finish().unwrap();
+        assert!(read_file(&file).1.iter().all(Vec::is_empty));
+    }
+}
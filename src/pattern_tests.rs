@@ -0,0 +1,193 @@
+use super::mapping::{indexed_dag::IndexedDag, Mapping, SpannerEnumerator};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::regex;
+use super::regex::Construction;
+use super::yaml;
+
+/// Expected value of a variable, either as a span `[start, end]` or as the
+/// captured text.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Expectation {
+    Span([usize; 2]),
+    Text(String),
+}
+
+/// A pattern together with a text and the exhaustive list of mappings that
+/// are expected when evaluating the pattern over the text.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PatternTest {
+    name: Option<String>,
+    pattern: String,
+    text: String,
+    expected: Vec<HashMap<String, Expectation>>,
+}
+
+/// Outcome of a pattern test.
+pub struct PatternTestResult {
+    /// Expected mappings that were not produced.
+    pub missing: Vec<HashMap<String, Expectation>>,
+    /// Produced mappings that were not expected.
+    pub unexpected: Vec<String>,
+    /// Reason why the pattern could not be compiled.
+    pub error: Option<String>,
+}
+
+impl Expectation {
+    fn is_match(&self, text: &str, span: &std::ops::Range<usize>) -> bool {
+        match self {
+            Expectation::Span([start, end]) => span.start == *start && span.end == *end,
+            Expectation::Text(expected) => expected == &text[span.clone()],
+        }
+    }
+}
+
+impl PatternTest {
+    /// Read the tests of a JSON file, or of a YAML file if its extension is
+    /// `.yaml` or `.yml`.
+    pub fn read_from_file(filename: &Path) -> Result<Vec<PatternTest>, Box<dyn std::error::Error>> {
+        let mut input = String::new();
+        File::open(filename)?.read_to_string(&mut input)?;
+
+        match filename.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Ok(serde_json::from_value(yaml::parse(&input)?)?),
+            _ => Ok(serde_json::from_str(&input)?),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_ref().unwrap_or(&self.pattern)
+    }
+
+    pub fn run(&self) -> PatternTestResult {
+        let automaton = match regex::try_compile_with(&self.pattern, Construction::Glushkov) {
+            Ok(automaton) => automaton,
+            Err(err) => {
+                return PatternTestResult {
+                    missing: self.expected.clone(),
+                    unexpected: Vec::new(),
+                    error: Some(err),
+                }
+            }
+        };
+
+        let mut enumerator = IndexedDag::builder(automaton, &self.text).build();
+        enumerator.preprocess();
+        let mappings: Vec<Mapping> = enumerator.iter().collect();
+
+        let missing = self
+            .expected
+            .iter()
+            .filter(|expected| !mappings.iter().any(|m| self.is_match(expected, m)))
+            .cloned()
+            .collect();
+
+        let unexpected = mappings
+            .iter()
+            .filter(|m| !self.expected.iter().any(|expected| self.is_match(expected, m)))
+            .map(|m| m.to_string())
+            .collect();
+
+        PatternTestResult {
+            missing,
+            unexpected,
+            error: None,
+        }
+    }
+
+    /// Check if a mapping assigns exactly the expected variables to the expected
+    /// values.
+    fn is_match(&self, expected: &HashMap<String, Expectation>, mapping: &Mapping) -> bool {
+        let groups: Vec<_> = mapping.iter_groups().collect();
+
+        groups.len() == expected.len()
+            && groups.iter().all(|(name, span)| match expected.get(*name) {
+                None => false,
+                Some(expectation) => expectation.is_match(&self.text, span),
+            })
+    }
+}
+
+impl PatternTestResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+impl fmt::Display for PatternTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(error) = &self.error {
+            return writeln!(f, "    error: {}", error);
+        }
+
+        for expected in &self.missing {
+            writeln!(f, "    missing: {}", serde_json::to_string(expected).unwrap())?;
+        }
+
+        for mapping in &self.unexpected {
+            writeln!(f, "    unexpected: {}", mapping)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::PatternTest;
+
+    #[test]
+    fn read_formats() {
+        let dir = std::env::temp_dir().join(format!("pattern-tests-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let json = r#"[
+            {"name": "pairs", "pattern": "(?P<x>a)(?P<y>b)", "text": "abab",
+             "expected": [{"x": [0, 1], "y": [1, 2]}, {"x": "a", "y": [3, 4]}]},
+            {"pattern": "(?P<x>a", "text": "a", "expected": []}
+        ]"#;
+        let yaml = "
+- name: pairs
+  pattern: (?P<x>a)(?P<y>b)
+  text: abab
+  expected:
+    - {x: [0, 1], y: [1, 2]}
+    - x: a
+      y: [3, 4]
+- pattern: (?P<x>a
+  text: a
+  expected: []
+";
+
+        fs::write(dir.join("cases.json"), json).unwrap();
+        fs::write(dir.join("cases.yaml"), yaml).unwrap();
+        fs::write(dir.join("invalid.yml"), "- pattern: [a").unwrap();
+
+        for filename in &["cases.json", "cases.yaml"] {
+            let cases = PatternTest::read_from_file(&dir.join(filename)).unwrap();
+
+            assert_eq!(cases.len(), 2);
+            assert_eq!(cases[0].name(), "pairs");
+            assert!(cases[0].run().is_success());
+
+            // An invalid pattern fails its case only.
+            let result = cases[1].run();
+            assert!(!result.is_success());
+            assert!(result.error.is_some());
+        }
+
+        assert!(PatternTest::read_from_file(&dir.join("invalid.yml")).is_err());
+        assert!(PatternTest::read_from_file(&dir.join("missing.json")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+//  ____               _ _           _
+// |  _ \ _ __ ___  __| (_) ___ __ _| |_ ___
+// | |_) | '__/ _ \/ _` | |/ __/ _` | __/ _ \
+// |  __/| | |  __/ (_| | | (_| (_| | ||  __/
+// |_|   |_|  \___|\__,_|_|\___\__,_|\__\___|
+//
+
+use lib_regex::Regex;
+
+use super::mapping::{LengthBounds, Mapping};
+
+/// Conditions on the content of the groups of a mapping, which must all hold
+/// for the mapping to be kept.
+///
+/// Predicates are parsed from expressions such as
+/// `port:[0-9]+ && port<=65535 && len(host)<64`, where:
+///  - `x:REGEX` checks that the text of `x` is fully matched by a regex, which
+///    can't contain `&&`;
+///  - `x<N`, with any of `<`, `<=`, `>`, `>=`, `==` and `!=`, compares the
+///    text of `x` read as a number, a text which is not a number never
+///    satisfies the comparison;
+///  - `len(x)<N`, with the same operators, compares the length in bytes of the
+///    span of `x`.
+///
+/// A mapping which doesn't bind a group satisfies its conditions, as it does
+/// for `LengthBounds`, into which length conditions can be turned to prune
+/// the enumeration.
+#[derive(Clone, Debug)]
+pub struct Predicates {
+    conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug)]
+enum Condition {
+    Matches(String, Regex),
+    Value(String, Comparison, f64),
+    Len(String, Comparison, usize),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Predicates {
+    pub fn parse(expr: &str) -> Result<Predicates, String> {
+        let conditions = expr.split("&&").map(Condition::parse).collect::<Result<_, _>>()?;
+        Ok(Predicates { conditions })
+    }
+
+    /// Names of the groups the conditions are about.
+    pub fn get_groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = (self.conditions.iter())
+            .map(|condition| match condition {
+                Condition::Matches(group, _)
+                | Condition::Value(group, _, _)
+                | Condition::Len(group, _, _) => group.as_str(),
+            })
+            .collect();
+
+        groups.sort_unstable();
+        groups.dedup();
+        groups
+    }
+
+    /// Check if a mapping satisfies all the conditions.
+    pub fn accepts(&self, mapping: &Mapping) -> bool {
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Matches(group, regex) => {
+                mapping.get_text(group).is_none_or(|text| regex.is_match(text))
+            }
+            Condition::Value(group, op, value) => mapping.get_text(group).is_none_or(|text| {
+                text.trim().parse().is_ok_and(|x: f64| op.holds(x, *value))
+            }),
+            Condition::Len(group, op, len) => {
+                mapping.get(group).is_none_or(|span| op.holds(span.len(), *len))
+            }
+        })
+    }
+
+    /// Restrict length bounds with the conditions on lengths, such that the
+    /// enumeration skips the mappings that don't satisfy them.
+    pub fn restrict_lengths(&self, mut bounds: LengthBounds) -> LengthBounds {
+        for condition in &self.conditions {
+            if let Condition::Len(group, op, len) = condition {
+                let (min, max) = match op {
+                    Comparison::Lt => (0, len.saturating_sub(1)),
+                    Comparison::Le => (0, *len),
+                    Comparison::Gt => (len.saturating_add(1), usize::MAX),
+                    Comparison::Ge => (*len, usize::MAX),
+                    Comparison::Eq => (*len, *len),
+                    Comparison::Ne => (0, usize::MAX),
+                };
+
+                bounds = bounds.restrict(Some(group), min, max);
+            }
+        }
+
+        bounds
+    }
+}
+
+impl Condition {
+    fn parse(expr: &str) -> Result<Condition, String> {
+        let expr = expr.trim();
+
+        if let Some(rest) = expr.strip_prefix("len(") {
+            let (group, rest) = match rest.split_once(')') {
+                Some(split) => split,
+                None => return Err(format!("Unclosed parenthesis in predicate: {}", expr)),
+            };
+
+            let (op, value) = Comparison::parse(rest, expr)?;
+
+            return match value.parse() {
+                Ok(len) => Ok(Condition::Len(group.trim().to_string(), op, len)),
+                Err(_) => Err(format!("Not a length in predicate: {}", expr)),
+            };
+        }
+
+        let len = (expr.find(|c: char| !c.is_alphanumeric() && c != '_')).unwrap_or(expr.len());
+        let (group, rest) = expr.split_at(len);
+
+        if group.is_empty() {
+            return Err(format!("Expected a group name in predicate: {}", expr));
+        }
+
+        if let Some(regex) = rest.trim_start().strip_prefix(':') {
+            return match Regex::new(&format!("^(?:{})$", regex)) {
+                Ok(regex) => Ok(Condition::Matches(group.to_string(), regex)),
+                Err(err) => Err(format!("Invalid regex in predicate {}: {}", expr, err)),
+            };
+        }
+
+        let (op, value) = Comparison::parse(rest, expr)?;
+
+        match value.parse() {
+            Ok(value) => Ok(Condition::Value(group.to_string(), op, value)),
+            Err(_) => Err(format!("Not a number in predicate: {}", expr)),
+        }
+    }
+}
+
+impl Comparison {
+    /// Read an operator at the start of a string, followed by its operand.
+    fn parse<'a>(input: &'a str, expr: &str) -> Result<(Comparison, &'a str), String> {
+        let operators = [
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+
+        let input = input.trim_start();
+
+        for (symbol, op) in operators.iter() {
+            if let Some(operand) = input.strip_prefix(symbol) {
+                return Ok((*op, operand.trim()));
+            }
+        }
+
+        Err(format!("Expected a comparison in predicate: {}", expr))
+    }
+
+    fn holds<T: PartialOrd>(self, x: T, y: T) -> bool {
+        match self {
+            Comparison::Lt => x < y,
+            Comparison::Le => x <= y,
+            Comparison::Gt => x > y,
+            Comparison::Ge => x >= y,
+            Comparison::Eq => x == y,
+            Comparison::Ne => x != y,
+        }
+    }
+}
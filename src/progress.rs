@@ -3,7 +3,9 @@ use std::io;
 use std::io::Write;
 use std::iter;
 use std::str;
-use std::time;
+use std::time::Duration;
+
+use super::clock::Instant;
 
 //   ____                _              _
 //  / ___|___  _ __  ___| |_ __ _ _ __ | |_ ___
@@ -15,9 +17,69 @@ use std::time;
 static BAR_SIZE: usize = 40;
 static REFRESH_DELAY: u128 = 100;
 
-static PREFIXES: &[&str] = &["it", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei", "Zi", "Yi"];
+static PREFIXES: &[&str] = &["", "K", "M", "G", "T", "P", "E", "Z", "Y"];
 static SPINNER: &str = "⠁⠁⠉⠙⠚⠒⠂⠂⠒⠲⠴⠤⠄⠄⠤⠠⠠⠤⠦⠖⠒⠐⠐⠒⠓⠋⠉⠈⠈ ";
 
+//  ____  _
+// |  _ \| |__   __ _ ___  ___  ___
+// | |_) | '_ \ / _` / __|/ _ \/ __|
+// |  __/| | | | (_| \__ \  __/\__ \
+// |_|   |_| |_|\__,_|___/\___||___/
+//
+
+/// Passes over the text done by the preprocessing of an `IndexedDag`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+    /// Forward pass building the levels of the product DAG.
+    Build,
+    /// Backward pass removing the vertices from which no final state can be
+    /// reached.
+    Trim,
+    /// Forward pass computing the jump levels and the reach matrices.
+    Index,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Build => "build",
+            Phase::Trim => "trim",
+            Phase::Index => "index",
+        }
+    }
+}
+
+/// State of the preprocessing given to progress callbacks.
+#[derive(Clone, Debug)]
+pub struct PhaseProgress {
+    pub phase: Phase,
+    /// Position of the current phase among the phases that are run, from 0.
+    pub phase_no: usize,
+    pub num_phases: usize,
+    /// Number of characters of the text processed by the current phase, out
+    /// of `total`.
+    pub done: usize,
+    pub total: usize,
+    /// Time elapsed since the start of the first phase.
+    pub elapsed: Duration,
+}
+
+impl PhaseProgress {
+    /// Overall progress in percents, each phase counting as much as the others.
+    pub fn percentage(&self) -> f64 {
+        let phase = match self.total {
+            0 => 1.,
+            total => (self.done as f64 / total as f64).min(1.),
+        };
+
+        100. * (self.phase_no as f64 + phase) / cmp::max(1, self.num_phases) as f64
+    }
+}
+
+/// Function called with the progress of the preprocessing at the start and
+/// at the end of each phase, and every 100ms in between.
+pub type ProgressCallback<'a> = Box<dyn FnMut(&PhaseProgress) + Send + 'a>;
+
 //  ____
 // |  _ \ _ __ ___   __ _ _ __ ___  ___ ___
 // | |_) | '__/ _ \ / _` | '__/ _ \/ __/ __|
@@ -25,47 +87,123 @@ static SPINNER: &str = "⠁⠁⠉⠙⠚⠒⠂⠂⠒⠲⠴⠤⠄⠄⠤⠠⠠⠤
 // |_|   |_|  \___/ \__, |_|  \___||___/___/
 //                  |___/
 
-pub struct Progress<T, U>
-where
-    T: Iterator<Item = U>,
-{
-    /// Iterator that it actualy extracts from
-    iterator: T,
-    /// Hypotetical size of the iterator
-    max_iterations: usize,
-    /// Number of elements already extracted
-    count_iterations: usize,
+/// Progress of all the phases of the preprocessing, which is displayed as a
+/// single bar on STDERR and reported to an optional callback.
+pub struct Progress<'a> {
+    phases: Vec<Phase>,
+    state: PhaseProgress,
 
-    /// Creation instant of the progress bar
-    start_time: time::Instant,
+    /// Wether the bar is displayed
+    display: bool,
+    callback: Option<ProgressCallback<'a>>,
 
-    /// Wether the bar should automaticaly refresh while iterating it
-    auto_refresh: bool,
+    /// Start instants of the first phase and of the current phase
+    start_time: Instant,
+    phase_start_time: Instant,
 
     /// Last refresh instant
-    last_refresh: time::Instant,
-    /// Width of the bar during the previous refresh
+    last_refresh: Instant,
+    /// Width of the bar during the previous refresh, 0 if it was never
+    /// displayed
     last_width: usize,
 
-    /// Extra infos to display during loading
-    extra_msg: Option<String>,
-
     /// Purely estetic looping animation
     spinner: iter::Cycle<str::Chars<'static>>,
 }
 
-impl<T, U> Progress<T, U>
-where
-    T: Iterator<Item = U>,
-{
-    pub fn auto_refresh(mut self, toggle: bool) -> Progress<T, U> {
-        self.auto_refresh = toggle;
-        self
+impl<'a> Progress<'a> {
+    /// Create the progress of the given phases, which must then be run in
+    /// this order.
+    pub fn new(
+        phases: Vec<Phase>,
+        display: bool,
+        callback: Option<ProgressCallback<'a>>,
+    ) -> Progress<'a> {
+        let state = PhaseProgress {
+            phase: phases.first().copied().unwrap_or(Phase::Build),
+            phase_no: 0,
+            num_phases: phases.len(),
+            done: 0,
+            total: 0,
+            elapsed: Duration::from_secs(0),
+        };
+
+        Progress {
+            phases,
+            state,
+            display,
+            callback,
+            start_time: Instant::now(),
+            phase_start_time: Instant::now(),
+            last_refresh: Instant::now(),
+            last_width: 0,
+            spinner: SPINNER.chars().cycle(),
+        }
     }
 
-    pub fn refresh(&mut self) {
+    /// Run a phase over the items of an iterator, whose upper bound of its
+    /// size is taken as the length of the phase.
+    pub fn track<I: Iterator>(&mut self, phase: Phase, iterator: I) -> Tracked<'_, 'a, I> {
+        let total = iterator
+            .size_hint()
+            .1
+            .expect("Impossible to init progress bar for objects larger than a 64 bits integer");
+
+        self.state.phase = phase;
+        self.state.phase_no = self.phases.iter().position(|&p| p == phase).unwrap_or(0);
+        self.state.done = 0;
+        self.state.total = total;
+        self.phase_start_time = Instant::now();
+        self.refresh();
+
+        Tracked {
+            progress: self,
+            iterator,
+            finished: false,
+        }
+    }
+
+    /// End the display and give back the callback.
+    pub fn finish(self) -> Option<ProgressCallback<'a>> {
+        if self.last_width > 0 {
+            eprintln!();
+        }
+
+        self.callback
+    }
+
+    fn advance(&mut self) {
+        self.state.done += 1;
+
+        if (self.display || self.callback.is_some())
+            && self.last_refresh.elapsed().as_millis() > REFRESH_DELAY
+        {
+            self.refresh();
+        }
+    }
+
+    fn end_phase(&mut self) {
+        self.state.done = self.state.total;
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        self.state.elapsed = self.start_time.elapsed();
+        self.last_refresh = Instant::now();
+
+        if let Some(callback) = &mut self.callback {
+            callback(&self.state);
+        }
+
+        if self.display {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
         // Compute bar shape
-        let proportion = self.count_iterations as f64 / self.max_iterations as f64;
+        let percentage = self.state.percentage();
+        let proportion = percentage / 100.;
         let body_length = cmp::min(
             BAR_SIZE + 1,
             (proportion * (BAR_SIZE + 1) as f64).round() as usize,
@@ -82,97 +220,83 @@ where
         let void = " ".repeat(void_length);
         let head = ">".repeat(has_head.into());
 
-        // Compute speed
-        let mut speed = 1_000_000. * self.count_iterations as f64
-            / self.start_time.elapsed().as_micros() as f64;
+        // Compute speed of the current phase
+        let mut speed = 1_000_000. * self.state.done as f64
+            / self.phase_start_time.elapsed().as_micros().max(1) as f64;
         let mut prefix_index = 0;
 
-        while speed > 1_024. && prefix_index + 1 < PREFIXES.len() {
-            speed /= 1_024.;
+        while speed > 1_000. && prefix_index + 1 < PREFIXES.len() {
+            speed /= 1_000.;
             prefix_index += 1;
         }
 
-        // Display
-        let elapsed = self.start_time.elapsed().as_secs();
+        // Estimate remaining time from the average speed so far
+        let elapsed = self.state.elapsed.as_secs();
+        let eta = if percentage > 0. {
+            let remaining = self.state.elapsed.as_secs_f64() * (100. - percentage) / percentage;
+            let remaining = remaining.round() as u64;
+            format!("{:02}:{:02}", remaining / 60, remaining % 60)
+        } else {
+            String::from("--:--")
+        };
 
-        let mut display = format!(
-            "{} [{}{}{}]  {:02}:{:02}  {:.2} {}/s",
+        // Display
+        let display = format!(
+            "{:<5} {}/{} {} [{}{}{}] {:>3}%  {:02}:{:02}  ETA {}  {:.2}{} chars/s",
+            self.state.phase.name(),
+            self.state.phase_no + 1,
+            self.state.num_phases,
             self.spinner.next().unwrap(),
             body,
             head,
             void,
+            cmp::min(100, percentage.floor() as usize),
             elapsed / 60,
             elapsed % 60,
+            eta,
             speed,
             PREFIXES[prefix_index],
         );
 
-        if let Some(msg) = &self.extra_msg {
-            display = format!("{} -- {}", display, msg);
-        }
-
         eprint!("\r{}", display);
 
         if display.chars().count() < self.last_width {
             eprint!("{}", " ".repeat(self.last_width - display.chars().count()))
         }
 
-        io::stdout().flush().expect("Can't flush stdout");
-
-        // Update informations about last refresh
-        self.last_refresh = time::Instant::now();
+        io::stderr().flush().expect("Can't flush stderr");
         self.last_width = display.chars().count();
     }
 }
 
-impl<T, U> Progress<T, U>
-where
-    T: std::iter::Iterator<Item = U>,
-{
-    pub fn from_iter(iterator: T) -> Progress<T, U> {
-        let max_iterations = iterator
-            .size_hint()
-            .1
-            .expect("Impossible to init progress bar for objects larger than a 64 bits integer");
-
-        Progress {
-            iterator,
-            max_iterations,
-            count_iterations: 0,
-            start_time: time::Instant::now(),
-            auto_refresh: true,
-            last_refresh: time::Instant::now(),
-            last_width: 0,
-            extra_msg: None,
-            spinner: SPINNER.chars().cycle(),
-        }
-    }
+/// Iterator over the items of a phase, which updates the progress.
+pub struct Tracked<'p, 'a, I> {
+    progress: &'p mut Progress<'a>,
+    iterator: I,
+    finished: bool,
 }
 
-impl<T, U> Iterator for Progress<T, U>
-where
-    T: Iterator<Item = U>,
-{
-    type Item = U;
-
-    fn next(&mut self) -> Option<U> {
-        let ret = self.iterator.next();
+impl<'p, 'a, I: Iterator> Iterator for Tracked<'p, 'a, I> {
+    type Item = I::Item;
 
-        if self.auto_refresh {
-            match ret {
-                None => {
-                    self.refresh();
-                    print!("\n");
-                }
-                Some(_) => {
-                    if self.last_refresh.elapsed().as_millis() > REFRESH_DELAY {
-                        self.refresh();
-                    }
+    fn next(&mut self) -> Option<I::Item> {
+        match self.iterator.next() {
+            Some(item) => {
+                self.progress.advance();
+                Some(item)
+            }
+            None => {
+                if !self.finished {
+                    self.finished = true;
+                    self.progress.end_phase();
                 }
+
+                None
             }
         }
+    }
 
-        self.count_iterations += 1;
-        ret
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iterator.size_hint()
     }
 }
@@ -0,0 +1,124 @@
+//  ____           _
+// |  _ \ ___  ___(_)_ __   ___  ___
+// | |_) / _ \/ __| | '_ \ / _ \/ __|
+// |  _ <  __/ (__| | |_) |  __/\__ \
+// |_| \_\___|\___|_| .__/ \___||___/
+//                  |_|
+
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// A named pattern for a common extraction task.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Recipe {
+    name: String,
+    description: String,
+    pattern: String,
+}
+
+impl Recipe {
+    fn new(name: &str, description: &str, pattern: &str) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            description: description.to_string(),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// Recipes that are always available, their patterns only match whole
+    /// values: a match can't be extended by the characters around it.
+    pub fn builtin() -> Vec<Recipe> {
+        vec![
+            Recipe::new(
+                "extract-emails",
+                "Email addresses, split into login and server.",
+                r"(^|[^\w.])(?P<login>\w+(\.\w+)*)@(?P<server>\w+(\.\w+)+)($|[^\w.]|\.($|\W))",
+            ),
+            Recipe::new(
+                "extract-urls",
+                "HTTP and HTTPS URLs, split into host and path.",
+                concat!(
+                    r"(^|\W)https?://(?P<host>\w+(\.\w+)+)(?P<path>(/([\w.~%-]*[\w~%-])?)*)",
+                    r"($|[^\w.~%/-]|\.($|[^\w.~%/-]))",
+                ),
+            ),
+            Recipe::new(
+                "extract-ipv4",
+                "IPv4 addresses in dotted notation.",
+                r"(^|[^\d.])(?P<ip>\d{1,3}(\.\d{1,3}){3})($|[^\d.]|\.($|\D))",
+            ),
+            Recipe::new(
+                "extract-dates",
+                "ISO 8601 dates, split into year, month and day.",
+                r"(^|\D)(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})($|\D)",
+            ),
+        ]
+    }
+
+    /// Read user-defined recipes from a file in JSON syntax.
+    pub fn read_from_file(filename: &Path) -> Result<Vec<Recipe>, Box<dyn std::error::Error>> {
+        let mut input = String::new();
+        File::open(filename)?.read_to_string(&mut input)?;
+        Ok(serde_json::from_str(&input)?)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn get_pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// List the files given as input, directories are explored recursively and
+/// their files are listed in alphabetical order.
+pub fn collect_files(paths: &[&Path]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<_, _>>()?;
+            entries.sort();
+
+            let entries: Vec<&Path> = entries.iter().map(|entry| entry.as_path()).collect();
+            files.extend(collect_files(&entries)?);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::spanner::CompiledSpanner;
+    use super::Recipe;
+
+    fn count_matches(name: &str, text: &str) -> usize {
+        let recipes = Recipe::builtin();
+        let recipe = recipes.iter().find(|recipe| recipe.get_name() == name).unwrap();
+        CompiledSpanner::new(recipe.get_pattern()).find_all(text).len()
+    }
+
+    #[test]
+    fn builtin_whole_values() {
+        assert_eq!(count_matches("extract-emails", "contact john.doe@example.com now"), 1);
+        assert_eq!(count_matches("extract-emails", "a.b@c.d.org. or x@y.io"), 2);
+        assert_eq!(count_matches("extract-urls", "see https://example.com. or x.org"), 1);
+        assert_eq!(count_matches("extract-urls", "http://x.org/a/b.html, ok"), 1);
+        assert_eq!(count_matches("extract-ipv4", "ip 10.0.0.1. and 192.168.1.254"), 2);
+        assert_eq!(count_matches("extract-dates", "on 2024-01-15 and 12024-01-150"), 1);
+    }
+}
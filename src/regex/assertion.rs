@@ -0,0 +1,200 @@
+/// Removal of the assertions of an automaton, which are replaced by states
+/// keeping track of the context of the current position: the automaton is
+/// multiplied by the information on the characters around the position that
+/// the assertions need, and assertions become epsilon transitions that are
+/// only taken when their condition holds.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::super::automaton::{Assertion, Label};
+use super::reduce::Transition;
+
+/// What is known of the characters around the current position of a run.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Context {
+    /// No character was read yet.
+    at_start: bool,
+    /// The last character read is a newline, or no character was read yet.
+    after_newline: bool,
+    /// An end of line was asserted since the last character was read, thus
+    /// the next character must be a newline.
+    before_newline: bool,
+    /// The end of the text was asserted, thus no character can be read.
+    at_end: bool,
+}
+
+impl Context {
+    const INITIAL: Context = Context {
+        at_start: true,
+        after_newline: true,
+        before_newline: false,
+        at_end: false,
+    };
+
+    /// Context after asserting a condition, if it can hold.
+    fn assert(self, assertion: Assertion) -> Option<Context> {
+        match assertion {
+            Assertion::StartText if self.at_start => Some(self),
+            Assertion::StartLine if self.after_newline => Some(self),
+            Assertion::StartText | Assertion::StartLine => None,
+            Assertion::EndText => Some(Context {
+                at_end: true,
+                ..self
+            }),
+            Assertion::EndLine => Some(Context {
+                before_newline: true,
+                ..self
+            }),
+        }
+    }
+
+    /// Context after reading a character, which is a newline or not.
+    fn read(newline: bool) -> Context {
+        Context {
+            at_start: false,
+            after_newline: newline,
+            before_newline: false,
+            at_end: false,
+        }
+    }
+}
+
+/// Return an automaton without assertions recognizing the same language,
+/// the automaton is given unchanged if it has no assertion. As the label of
+/// the transitions towards a state only depends on the label of the
+/// transitions towards the original state and on its context, Glushkov
+/// automata remain Glushkov automata.
+pub fn eliminate(
+    nb_states: usize,
+    transitions: Vec<Transition>,
+    finals: Vec<usize>,
+) -> (usize, Vec<Transition>, Vec<usize>) {
+    let has_assertion = transitions
+        .iter()
+        .any(|(_, label, _)| matches!(**label, Label::Assertion(_)));
+
+    if !has_assertion {
+        return (nb_states, transitions, finals);
+    }
+
+    let mut is_final = vec![false; nb_states];
+    let mut adj = vec![Vec::new(); nb_states];
+
+    for &state in &finals {
+        is_final[state] = true;
+    }
+
+    // Atoms are split once into the newline and the other characters, which
+    // is only needed to check the anchors of lines.
+    let has_line_assertion = transitions.iter().any(|(_, label, _)| {
+        matches!(
+            **label,
+            Label::Assertion(Assertion::StartLine) | Label::Assertion(Assertion::EndLine)
+        )
+    });
+
+    for (source, label, target) in transitions {
+        let split = match &*label {
+            Label::Atom(_) if !has_line_assertion => (None, Some(label.clone())),
+            Label::Atom(atom) => {
+                let (newline, others) = atom.split_newline();
+                let wrap = |atom| Arc::new(Label::Atom(atom));
+                (newline.map(wrap), others.map(wrap))
+            }
+            _ => (None, None),
+        };
+
+        adj[source].push((label, split, target));
+    }
+
+    // Build the product of the automaton and the contexts reachable from the
+    // initial state.
+    let mut states = vec![(0, Context::INITIAL)];
+    let mut ids = HashMap::new();
+    ids.insert((0, Context::INITIAL), 0);
+
+    let mut product_transitions = Vec::new();
+    let mut epsilon_adj = Vec::new();
+
+    let mut get_id = |states: &mut Vec<_>, state: usize, context: Context| {
+        *ids.entry((state, context)).or_insert_with(|| {
+            states.push((state, context));
+            states.len() - 1
+        })
+    };
+
+    let mut curr = 0;
+
+    while curr < states.len() {
+        let (state, context) = states[curr];
+        epsilon_adj.push(Vec::new());
+
+        for (label, (newline, others), target) in &adj[state] {
+            match &**label {
+                Label::Atom(_) if context.at_end => {}
+                Label::Atom(_) => {
+                    if let Some(newline) = newline {
+                        let target = get_id(&mut states, *target, Context::read(true));
+                        product_transitions.push((curr, newline.clone(), target));
+                    }
+
+                    if let Some(others) = others.as_ref().filter(|_| !context.before_newline) {
+                        let target = get_id(&mut states, *target, Context::read(false));
+                        product_transitions.push((curr, others.clone(), target));
+                    }
+                }
+                Label::Assignation(_) => {
+                    let target = get_id(&mut states, *target, context);
+                    product_transitions.push((curr, label.clone(), target));
+                }
+                Label::Assertion(assertion) => {
+                    if let Some(next) = context.assert(*assertion) {
+                        let target = get_id(&mut states, *target, next);
+                        epsilon_adj[curr].push(target);
+                    }
+                }
+            }
+        }
+
+        curr += 1;
+    }
+
+    // Remove the epsilon transitions: a state gets the transitions and the
+    // finality of the states of its closure.
+    let nb_states = states.len();
+    let mut labeled_adj = vec![Vec::new(); nb_states];
+
+    for (source, label, target) in product_transitions {
+        labeled_adj[source].push((label, target));
+    }
+
+    let mut transitions = Vec::new();
+    let mut finals = Vec::new();
+    // State whose closure is being computed when each state was last seen.
+    let mut seen = vec![usize::MAX; nb_states];
+
+    for state in 0..nb_states {
+        let mut stack = vec![state];
+        let mut is_closure_final = false;
+
+        while let Some(middle) = stack.pop() {
+            if seen[middle] == state {
+                continue;
+            }
+
+            seen[middle] = state;
+            stack.extend(&epsilon_adj[middle]);
+            is_closure_final |= is_final[states[middle].0];
+
+            for (label, target) in &labeled_adj[middle] {
+                transitions.push((state, label.clone(), *target));
+            }
+        }
+
+        if is_closure_final {
+            finals.push(state);
+        }
+    }
+
+    (nb_states, transitions, finals)
+}
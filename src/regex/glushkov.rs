@@ -2,11 +2,13 @@
 /// linearized language out of a regexp's HIR, and finaly convert this
 /// expression to a variable NFA.
 use std::collections::LinkedList;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use super::super::automaton::Automaton;
 use super::super::automaton::Label;
+use super::assertion;
 use super::parse::Hir;
+use super::reduce;
 
 #[derive(Clone, Debug)]
 pub struct GlushkovFactors {
@@ -26,7 +28,7 @@ pub struct GlushkovFactors {
 #[derive(Clone, Debug)]
 pub struct GlushkovTerm {
     id: usize,
-    label: Rc<Label>,
+    label: Arc<Label>,
 }
 
 #[derive(Clone, Debug)]
@@ -41,6 +43,16 @@ pub struct LocalLang {
 impl LocalLang {
     /// Create an automaton that recognise the same langage.
     pub fn into_automaton(self) -> Automaton {
+        self.build_automaton(true)
+    }
+
+    /// Build the automaton without merging equivalent positions, such that
+    /// each way of matching the regex is a distinct run.
+    pub fn into_unmerged_automaton(self) -> Automaton {
+        self.build_automaton(false)
+    }
+
+    fn build_automaton(self, merge: bool) -> Automaton {
         let iner_transitions = self
             .factors
             .f
@@ -52,14 +64,26 @@ impl LocalLang {
             .into_iter()
             .map(|target| (0, target.label, target.id + 1));
 
-        let transitions = iner_transitions.chain(pref_transitions);
+        let transitions: Vec<_> = iner_transitions.chain(pref_transitions).collect();
         let mut finals: Vec<usize> = self.factors.d.into_iter().map(|x| x.id + 1).collect();
 
         if self.factors.g {
             finals.push(0);
         }
 
-        Automaton::new(self.nb_terms + 1, transitions, finals.into_iter())
+        let (nb_states, transitions, finals) =
+            assertion::eliminate(self.nb_terms + 1, transitions, finals);
+
+        // Remove useless states and merge equivalent positions.
+        let useful = reduce::useful_states(nb_states, &transitions, &finals);
+        let (class, nb_classes) = match merge {
+            true => reduce::merge_positions(nb_states, &transitions, &finals, &useful),
+            false => reduce::keep_states(&useful),
+        };
+        let (nb_states, transitions, finals) =
+            reduce::quotient(transitions, finals, &class, nb_classes);
+
+        Automaton::new(nb_states, transitions.into_iter(), finals.into_iter())
     }
 
     /// Return a language representing the input Hir.
@@ -84,7 +108,7 @@ impl LocalLang {
 
     /// Register a new atom in the local language and return the associated
     /// term.
-    fn register_label(&mut self, label: Rc<Label>, id_offset: usize) -> GlushkovTerm {
+    fn register_label(&mut self, label: Arc<Label>, id_offset: usize) -> GlushkovTerm {
         self.nb_terms += 1;
         GlushkovTerm {
             id: self.nb_terms + id_offset - 1,
@@ -94,7 +118,7 @@ impl LocalLang {
 
     /// Return a local language representing an expression containing a single
     /// term.
-    fn label(label: Rc<Label>, id_offset: usize) -> LocalLang {
+    fn label(label: Arc<Label>, id_offset: usize) -> LocalLang {
         let mut lang = LocalLang::empty();
         let term = lang.register_label(label, id_offset);
         lang.factors.p.push_back(term.clone());
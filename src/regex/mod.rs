@@ -1,12 +1,92 @@
+mod assertion;
 mod glushkov;
 mod parse;
+mod reduce;
+mod thompson;
+
+use serde::{Deserialize, Serialize};
 
 use super::automaton::Automaton;
+use super::mapping::Mapping;
+use super::spanner::CompiledSpanner;
+
+/// Algorithm used to translate a regex into an automaton.
+#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum Construction {
+    Glushkov,
+    Thompson,
+}
+
+/// Constructions of the regex syntax that can be compiled, each given with a
+/// pattern using it.
+pub const SUPPORTED_SYNTAX: [(&str, &str); 12] = [
+    ("literals", "abc"),
+    ("classes", r"[a-z]\d\W."),
+    ("concatenation", "ab"),
+    ("alternation", "a|b"),
+    ("repetitions", "a*b+c?"),
+    ("bounded repetitions", "a{2,3}b{2}"),
+    ("named groups", "(?P<x>a)"),
+    ("non-capturing groups", "(?:ab)+"),
+    ("text anchors", r"(\A|,)a($|,)"),
+    ("line anchors", "a(?m:^b$)"),
+    ("inline flags", "(?i)a(?-i:b)(?s:.)"),
+    ("classes of ASCII bytes", r"(?-u)[^a]"),
+];
+
+/// Constructions of the regex syntax that are rejected, each given with a
+/// pattern using it.
+pub const UNSUPPORTED_SYNTAX: [(&str, &str); 3] = [
+    ("word boundaries", r"\ba\b"),
+    ("groups assigned several times", r"(?P<x>a)*"),
+    ("non-ASCII bytes", r"(?-u)\xff"),
+];
 
 pub fn compile(regex: &str) -> Automaton {
+    compile_with(regex, Construction::Glushkov)
+}
+
+pub fn compile_with(regex: &str, construction: Construction) -> Automaton {
     let hir = parse::Hir::from_regex(&regex, false);
+    construct(hir, construction)
+}
 
-    glushkov::LocalLang::from_hir(hir, 0).into_automaton()
+/// Compile a regex, or explain why it can't be, such as a syntax error or a
+/// group assigned several times.
+pub fn try_compile_with(regex: &str, construction: Construction) -> Result<Automaton, String> {
+    check_groups(regex)?;
+    let hir = parse::Hir::try_from_regex(regex, false)?;
+    Ok(construct(hir, construction))
+}
+
+/// Compile a regex without merging the equivalent positions of its Glushkov
+/// automaton, such that runs matching different parts of the regex stay
+/// distinct, as `Mapping::count_runs` expects. Thompson automata are never
+/// merged.
+pub fn compile_unmerged_with(regex: &str, construction: Construction) -> Automaton {
+    let hir = parse::Hir::from_regex(&regex, false);
+
+    match construction {
+        Construction::Glushkov => glushkov::LocalLang::from_hir(hir, 0).into_unmerged_automaton(),
+        Construction::Thompson => thompson::EpsilonNfa::from_hir(hir).into_automaton(),
+    }
+}
+
+fn construct(hir: parse::Hir, construction: Construction) -> Automaton {
+    match construction {
+        Construction::Glushkov => glushkov::LocalLang::from_hir(hir, 0).into_automaton(),
+        Construction::Thompson => thompson::EpsilonNfa::from_hir(hir).into_automaton(),
+    }
+}
+
+/// Check that no match of a regex assigns a group several times, as a mapping
+/// only gives a single span to each group, which is not the case of a group
+/// under a repetition. The automaton of such a regex is not functional.
+pub fn check_groups(regex: &str) -> Result<(), String> {
+    match parse::Hir::try_from_regex(regex, true)?.opened_variables() {
+        Ok(_) => Ok(()),
+        Err(name) => Err(format!("The group {} can be assigned several times in a match.", name)),
+    }
 }
 
 pub fn compile_raw(regex: &str) -> Automaton {
@@ -15,13 +95,16 @@ pub fn compile_raw(regex: &str) -> Automaton {
     glushkov::LocalLang::from_hir(hir, 0).into_automaton()
 }
 
-#[cfg(test)]
+/// Check if a regex matches somewhere in a text, without indexing the text.
 pub fn is_match(regex: &str, text: &str) -> bool {
-    let automaton = compile(&regex);
-    let matches = compile_matches(automaton, text, 1);
+    CompiledSpanner::new(regex).is_match(text)
+}
 
-    let ret = matches.iter().next().is_some();
-    ret
+/// Find one match of a regex in a text. The preprocessing stops after the
+/// forward pass if there is no match and the enumeration stops after the
+/// first mapping.
+pub fn find_first<'t>(regex: &str, text: &'t str) -> Option<Mapping<'t>> {
+    CompiledSpanner::new(regex).find_first(text)
 }
 
 #[cfg(test)]
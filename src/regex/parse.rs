@@ -1,14 +1,18 @@
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use regex_syntax;
+use regex_syntax::hir::Anchor as LibAnchor;
+use regex_syntax::hir::Class as LibClass;
 use regex_syntax::hir::GroupKind as LibGroup;
 use regex_syntax::hir::HirKind as LibHir;
+use regex_syntax::hir::Literal as LibLiteral;
 use regex_syntax::hir::RepetitionKind as LibRepKind;
 use regex_syntax::hir::RepetitionRange as LibRepRange;
+use regex_syntax::hir::{ClassUnicode, ClassUnicodeRange};
 
 use super::super::automaton::atom::Atom;
-use super::super::automaton::Label;
+use super::super::automaton::{Assertion, Label};
 use super::super::mapping::{Marker, Variable};
 
 /// A simple Hir, with branchements of arity at most 2 and at little redundancy
@@ -18,7 +22,7 @@ pub enum Hir {
     /// Empty langage
     Empty,
     /// Langage of words of length 1
-    Label(Rc<Label>), // embeded into an Rc to avoid duplicating heavy complex literals
+    Label(Arc<Label>), // embeded into an Arc to avoid duplicating heavy complex literals
     /// Concatenation of two langages
     Concat(Box<Hir>, Box<Hir>),
     /// Union of two langages
@@ -31,28 +35,32 @@ pub enum Hir {
 
 impl Hir {
     pub fn from_regex(regex: &str, raw: bool) -> Hir {
-        let (anchor_begin, anchor_end, regex) = if raw {
-            (true, true, regex.to_string())
-        } else {
-            Hir::reformat(regex)
-        };
+        match Hir::try_from_regex(regex, raw) {
+            Ok(hir) => hir,
+            Err(err) => panic!("{}", err),
+        }
+    }
 
+    /// Parse a regex, or explain why it is invalid or not supported.
+    pub fn try_from_regex(regex: &str, raw: bool) -> Result<Hir, String> {
         let mut variables = HashMap::new();
 
+        // Classes of bytes that may match non-ASCII bytes, such as `(?-u).`,
+        // are accepted and clipped to ASCII by `unicode_class`.
         let lib_hir = regex_syntax::ParserBuilder::new()
-            .dot_matches_new_line(true)
+            .allow_invalid_utf8(true)
             .build()
-            .parse(&regex)
-            .expect("Invalid regexp syntax");
-        let hir = Hir::from_lib_hir(lib_hir, &mut variables);
+            .parse(regex)
+            .map_err(|err| format!("Invalid regexp syntax: {}", err))?;
+        let hir = Hir::from_lib_hir(lib_hir, &mut variables)?;
 
         if raw {
-            return hir;
+            return Ok(hir);
         }
 
         let hir = match variables.len() {
             0 => {
-                let var = Rc::new(Variable::new("match".to_string(), 0));
+                let var = Arc::new(Variable::new("match".to_string(), 0));
                 let marker_open = Label::Assignation(Marker::Open(var.clone()));
                 let marker_close = Label::Assignation(Marker::Close(var));
 
@@ -69,23 +77,10 @@ impl Hir {
             _ => panic!("LibHir broken!"),
         };
 
-        let hir = match anchor_begin {
-            true => hir,
-            false => Hir::concat(
-                Hir::option(Hir::closure(Hir::label(Label::Atom(Atom::Class(
-                    any.clone(),
-                ))))),
-                hir,
-            ),
-        };
-
-        match anchor_end {
-            true => hir,
-            false => Hir::concat(
-                hir,
-                Hir::option(Hir::closure(Hir::label(Label::Atom(Atom::Class(any))))),
-            ),
-        }
+        // Matches can be anywhere in the text, anchors are assertions checked
+        // by the automaton.
+        let any = Hir::option(Hir::closure(Hir::label(Label::Atom(Atom::Class(any)))));
+        Ok(Hir::concat(Hir::concat(any.clone(), hir), any))
     }
 
     /// Construct an Hir from regex_syntax's Hir format.
@@ -95,17 +90,28 @@ impl Hir {
     /// Hir.
     fn from_lib_hir(
         hir: regex_syntax::hir::Hir,
-        variables: &mut HashMap<String, Rc<Variable>>,
-    ) -> Hir {
-        match hir.into_kind() {
+        variables: &mut HashMap<String, Arc<Variable>>,
+    ) -> Result<Hir, String> {
+        Ok(match hir.into_kind() {
             LibHir::Empty => Hir::epsilon(),
 
-            LibHir::Literal(lit) => Hir::label(Label::Atom(Atom::Literal(lit))),
+            LibHir::Literal(lit) => {
+                Hir::label(Label::Atom(Atom::Literal(Hir::unicode_literal(lit)?)))
+            }
+
+            LibHir::Class(class) => {
+                Hir::label(Label::Atom(Atom::Class(Hir::unicode_class(class)?)))
+            }
 
-            LibHir::Class(class) => Hir::label(Label::Atom(Atom::Class(class))),
+            LibHir::Anchor(anchor) => Hir::label(Label::Assertion(match anchor {
+                LibAnchor::StartLine => Assertion::StartLine,
+                LibAnchor::EndLine => Assertion::EndLine,
+                LibAnchor::StartText => Assertion::StartText,
+                LibAnchor::EndText => Assertion::EndText,
+            })),
 
             LibHir::Repetition(rep) => {
-                let hir = Hir::from_lib_hir(*rep.hir, variables);
+                let hir = Hir::from_lib_hir(*rep.hir, variables)?;
                 let new_hir = match rep.kind {
                     LibRepKind::ZeroOrOne => Hir::option(hir),
                     LibRepKind::ZeroOrMore => Hir::option(Hir::closure(hir)),
@@ -116,7 +122,7 @@ impl Hir {
             }
 
             LibHir::Group(group) => {
-                let subtree = Hir::from_lib_hir(*group.hir, variables);
+                let subtree = Hir::from_lib_hir(*group.hir, variables)?;
                 let new_hir = match group.kind {
                     LibGroup::NonCapturing | LibGroup::CaptureIndex(_) => subtree,
                     LibGroup::CaptureName { name, index: _ } => {
@@ -131,7 +137,7 @@ impl Hir {
                                 .map(|v| v.clone())
                                 .unwrap_or_else(|| {
                                     let x =
-                                        Rc::new(Variable::new(real_name.clone(), variables.len()));
+                                        Arc::new(Variable::new(real_name.clone(), variables.len()));
                                     variables.insert(real_name, x.clone());
 
                                     x
@@ -150,17 +156,95 @@ impl Hir {
                 new_hir
             }
 
-            LibHir::Concat(sub) => sub.into_iter().fold(Hir::epsilon(), |acc, branch| {
-                let add_hir = Hir::from_lib_hir(branch, variables);
-                Hir::concat(acc, add_hir)
-            }),
+            LibHir::Concat(sub) => sub.into_iter().try_fold(Hir::epsilon(), |acc, branch| {
+                let add_hir = Hir::from_lib_hir(branch, variables)?;
+                Ok::<_, String>(Hir::concat(acc, add_hir))
+            })?,
+
+            LibHir::Alternation(sub) => sub.into_iter().try_fold(Hir::Empty, |acc, branch| {
+                let add_hir = Hir::from_lib_hir(branch, variables)?;
+                Ok::<_, String>(Hir::alternation(acc, add_hir))
+            })?,
+
+            other => return Err(format!("Not implemented: {:?}", other)),
+        })
+    }
+
+    /// Literals of patterns without Unicode support are bytes, which are
+    /// necessarily ASCII characters as the text is valid UTF-8.
+    fn unicode_literal(lit: LibLiteral) -> Result<LibLiteral, String> {
+        match lit {
+            LibLiteral::Byte(byte) if byte.is_ascii() => Ok(LibLiteral::Unicode(byte as char)),
+            LibLiteral::Byte(byte) => {
+                Err(format!("Non-ASCII bytes are not supported: {:#x}", byte))
+            }
+            lit => Ok(lit),
+        }
+    }
+
+    /// Classes of patterns without Unicode support are classes of bytes,
+    /// which are turned into the classes of the same ASCII characters. A
+    /// non-ASCII byte is only part of a character of the text, thus can't be
+    /// matched on its own and is left out of the class.
+    fn unicode_class(class: LibClass) -> Result<LibClass, String> {
+        match class {
+            LibClass::Bytes(class) => {
+                let ranges: Vec<_> = (class.iter())
+                    .filter(|range| range.start().is_ascii())
+                    .map(|range| (range.start() as char, range.end().min(0x7f) as char))
+                    .map(|(start, end)| ClassUnicodeRange::new(start, end))
+                    .collect();
+
+                if ranges.is_empty() {
+                    let bytes: Vec<_> = (class.iter())
+                        .map(|range| format!("{:#x}-{:#x}", range.start(), range.end()))
+                        .collect();
+                    return Err(format!(
+                        "Classes of non-ASCII bytes are not supported: [{}]",
+                        bytes.join(", ")
+                    ));
+                }
+
+                Ok(LibClass::Unicode(ClassUnicode::new(ranges)))
+            }
+            class => Ok(class),
+        }
+    }
+
+    /// Names of the variables opened by the words of the langage. A mapping
+    /// assigns a single span to each variable, thus this gives the name of a
+    /// variable that a word can open twice, such as a group under a
+    /// repetition, as an error.
+    pub fn opened_variables(&self) -> Result<HashSet<&str>, &str> {
+        match self {
+            Hir::Empty => Ok(HashSet::new()),
+            Hir::Label(label) => match label.as_ref() {
+                Label::Assignation(Marker::Open(var)) => {
+                    Ok(Some(var.get_name()).into_iter().collect())
+                }
+                _ => Ok(HashSet::new()),
+            },
+            Hir::Option(hir) => hir.opened_variables(),
+            Hir::Alternation(hir1, hir2) => {
+                let mut names = hir1.opened_variables()?;
+                names.extend(hir2.opened_variables()?);
+                Ok(names)
+            }
+            Hir::Concat(hir1, hir2) => {
+                let mut names = hir1.opened_variables()?;
 
-            LibHir::Alternation(sub) => sub.into_iter().fold(Hir::Empty, |acc, branch| {
-                let add_hir = Hir::from_lib_hir(branch, variables);
-                Hir::alternation(acc, add_hir)
-            }),
+                for name in hir2.opened_variables()? {
+                    if !names.insert(name) {
+                        return Err(name);
+                    }
+                }
 
-            other => panic!("Not implemented: {:?}", other),
+                Ok(names)
+            }
+            Hir::Closure(hir) => match hir.opened_variables()?.into_iter().min() {
+                Some(name) => Err(name),
+                None => Ok(HashSet::new()),
+            },
         }
     }
 
@@ -169,7 +253,7 @@ impl Hir {
     }
 
     fn label(label: Label) -> Hir {
-        Hir::Label(Rc::new(label))
+        Hir::Label(Arc::new(label))
     }
 
     fn option(hir: Hir) -> Hir {
@@ -220,22 +304,4 @@ impl Hir {
 
         result
     }
-
-    fn reformat(regex: &str) -> (bool, bool, String) {
-        let mut regex = String::from(regex);
-
-        let anchor_begin = Some(&b'^') == regex.as_bytes().first();
-        let anchor_end = Some(&b'$') == regex.as_bytes().last();
-
-        // Remove anchor characters
-        if anchor_begin {
-            regex.remove(0);
-        }
-
-        if anchor_end {
-            regex.remove(regex.len() - 1);
-        }
-
-        (anchor_begin, anchor_end, regex)
-    }
 }
@@ -0,0 +1,235 @@
+/// Reduction of the automata built by the different constructions before they
+/// are handed to the enumeration: useless states are removed, equivalent
+/// states can be merged, and states are renumbered such that the initial
+/// state is 0 and assignations go from smaller to larger ids.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use super::super::automaton::Label;
+
+pub type Transition = (usize, Arc<Label>, usize);
+
+/// Return the states that are reachable from the initial state and from which
+/// a final state can be reached. The initial state is always kept.
+pub fn useful_states(nb_states: usize, transitions: &[Transition], finals: &[usize]) -> Vec<bool> {
+    let mut adj = vec![Vec::new(); nb_states];
+    let mut rev_adj = vec![Vec::new(); nb_states];
+
+    for (source, _, target) in transitions {
+        adj[*source].push(*target);
+        rev_adj[*target].push(*source);
+    }
+
+    let reachable = explore(&adj, vec![0]);
+    let coreachable = explore(&rev_adj, finals.to_vec());
+
+    (0..nb_states)
+        .map(|state| state == 0 || (reachable[state] && coreachable[state]))
+        .collect()
+}
+
+/// Put each useful state in its own class.
+pub fn keep_states(useful: &[bool]) -> (Vec<usize>, usize) {
+    let mut nb_classes = 0;
+    let class = useful
+        .iter()
+        .map(|&is_useful| {
+            if is_useful {
+                nb_classes += 1;
+                nb_classes - 1
+            } else {
+                usize::MAX
+            }
+        })
+        .collect();
+
+    (class, nb_classes)
+}
+
+/// Partition useful states such that states of a same class share the same
+/// label on their incoming transitions, the same finality and successors in
+/// the same classes.
+///
+/// This is only correct if all the transitions towards a state share the same
+/// label, which is the case of Glushkov automata.
+pub fn merge_positions(
+    nb_states: usize,
+    transitions: &[Transition],
+    finals: &[usize],
+    useful: &[bool],
+) -> (Vec<usize>, usize) {
+    let mut is_final = vec![false; nb_states];
+    let mut in_label = vec![None; nb_states];
+    let mut adj = vec![Vec::new(); nb_states];
+
+    for &state in finals {
+        is_final[state] = true;
+    }
+
+    for (source, label, target) in transitions {
+        if useful[*source] && useful[*target] {
+            in_label[*target] = Some(label.clone());
+            adj[*source].push(*target);
+        }
+    }
+
+    // Initial partition of the states, by label and finality. The initial
+    // state is kept alone in its class.
+    let mut labels: Vec<Arc<Label>> = Vec::new();
+    let mut class = vec![usize::MAX; nb_states];
+    let mut signatures = HashMap::new();
+
+    for state in (0..nb_states).filter(|&state| useful[state]) {
+        let label_id = in_label[state].as_ref().map(|label| {
+            labels
+                .iter()
+                .position(|other| **other == **label)
+                .unwrap_or_else(|| {
+                    labels.push(label.clone());
+                    labels.len() - 1
+                })
+        });
+
+        let nb_classes = signatures.len();
+        class[state] = *signatures
+            .entry((state == 0, label_id, is_final[state]))
+            .or_insert(nb_classes);
+    }
+
+    // Refine the partition until states of a same class have successors in
+    // the same classes.
+    let mut nb_classes = signatures.len();
+
+    loop {
+        let mut signatures = HashMap::new();
+        let mut new_class = vec![usize::MAX; nb_states];
+
+        for state in (0..nb_states).filter(|&state| useful[state]) {
+            let mut successors: Vec<usize> = adj[state].iter().map(|&x| class[x]).collect();
+            successors.sort();
+            successors.dedup();
+
+            let nb_new_classes = signatures.len();
+            new_class[state] = *signatures
+                .entry((class[state], successors))
+                .or_insert(nb_new_classes);
+        }
+
+        let stable = signatures.len() == nb_classes;
+        class = new_class;
+        nb_classes = signatures.len();
+
+        if stable {
+            return (class, nb_classes);
+        }
+    }
+}
+
+/// Build the automaton whose states are the classes of a partition of the
+/// input states, states of class `usize::MAX` are removed.
+///
+/// The classes are numbered in a topological order of assignations, ties are
+/// broken by keeping the original order of states.
+pub fn quotient(
+    transitions: Vec<Transition>,
+    finals: Vec<usize>,
+    class: &[usize],
+    nb_classes: usize,
+) -> (usize, Vec<Transition>, Vec<usize>) {
+    let mut repr = vec![usize::MAX; nb_classes];
+
+    for state in (0..class.len()).rev().filter(|&state| class[state] != usize::MAX) {
+        repr[class[state]] = state;
+    }
+
+    // Remove transitions that became duplicated.
+    let mut seen: HashMap<_, Vec<Arc<Label>>> = HashMap::new();
+    let class_transitions: Vec<_> = transitions
+        .into_iter()
+        .filter(|(source, _, target)| class[*source] != usize::MAX && class[*target] != usize::MAX)
+        .map(|(source, label, target)| (class[source], label, class[target]))
+        .filter(|(source, label, target)| {
+            let labels = seen.entry((*source, *target)).or_default();
+            let is_new = labels.iter().all(|other| **other != **label);
+
+            if is_new {
+                labels.push(label.clone());
+            }
+
+            is_new
+        })
+        .collect();
+
+    let mut in_degree = vec![0; nb_classes];
+    let mut assignations = vec![Vec::new(); nb_classes];
+
+    for (source, label, target) in &class_transitions {
+        if let Label::Assignation(_) = **label {
+            assignations[*source].push(*target);
+            in_degree[*target] += 1;
+        }
+    }
+
+    let mut heap: BinaryHeap<_> = (0..nb_classes)
+        .filter(|&x| in_degree[x] == 0)
+        .map(|x| Reverse((repr[x], x)))
+        .collect();
+    let mut new_id = vec![usize::MAX; nb_classes];
+    let mut nb_numbered = 0;
+
+    while let Some(Reverse((_, x))) = heap.pop() {
+        new_id[x] = nb_numbered;
+        nb_numbered += 1;
+
+        for &target in &assignations[x] {
+            in_degree[target] -= 1;
+
+            if in_degree[target] == 0 {
+                heap.push(Reverse((repr[target], target)));
+            }
+        }
+    }
+
+    // If the input contains a cycle of assignations, keep the remaining states
+    // in their original order and let the automaton report it.
+    let mut remaining: Vec<_> = (0..nb_classes)
+        .filter(|&x| new_id[x] == usize::MAX)
+        .collect();
+    remaining.sort_by_key(|&x| repr[x]);
+
+    for x in remaining {
+        new_id[x] = nb_numbered;
+        nb_numbered += 1;
+    }
+
+    let transitions = class_transitions
+        .into_iter()
+        .map(|(source, label, target)| (new_id[source], label, new_id[target]))
+        .collect();
+
+    let mut finals: Vec<usize> = finals
+        .into_iter()
+        .filter(|&state| class[state] != usize::MAX)
+        .map(|state| new_id[class[state]])
+        .collect();
+    finals.sort();
+    finals.dedup();
+
+    (nb_classes, transitions, finals)
+}
+
+/// Return the set of states that can be reached from a list of states.
+fn explore(adj: &[Vec<usize>], start: Vec<usize>) -> Vec<bool> {
+    let mut seen = vec![false; adj.len()];
+    let mut stack = start;
+
+    while let Some(state) = stack.pop() {
+        if !seen[state] {
+            seen[state] = true;
+            stack.extend(adj[state].iter().filter(|&&x| !seen[x]));
+        }
+    }
+
+    seen
+}
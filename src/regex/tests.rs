@@ -1,4 +1,16 @@
-use super::is_match;
+use std::collections::HashMap;
+use std::iter;
+use std::panic;
+
+use super::super::algebra::Plan;
+use super::super::automaton::Automaton;
+use super::super::gazetteer::Gazetteer;
+use super::super::mapping::{IndexedDag, Mapping};
+use super::super::spanner::CompiledSpanner;
+use super::{
+    check_groups, compile, find_first, is_match, try_compile_with, Construction, SUPPORTED_SYNTAX,
+    UNSUPPORTED_SYNTAX,
+};
 
 #[test]
 fn wildcard() {
@@ -74,7 +86,23 @@ fn concatenation() {
 #[test]
 fn union() {
     assert!(is_match(r"^foo|bar$", "bar"));
-    assert!(!is_match(r"^foo|bar$", "foobar"));
+    assert!(is_match(r"^foo|bar$", "foobar"));
+    assert!(!is_match(r"^foo|bar$", "barfoo"));
+    assert!(is_match(r"^(foo|bar)$", "bar"));
+    assert!(!is_match(r"^(foo|bar)$", "foobar"));
+}
+
+#[test]
+fn inner_anchors() {
+    assert!(is_match(r"foo$|^bar", "bar foo"));
+    assert!(!is_match(r"foo$|^bar", "foo bar"));
+    assert!(is_match(r"(^|@)a", "a"));
+    assert!(is_match(r"(^|@)a", "b@a"));
+    assert!(!is_match(r"(^|@)a", "ba"));
+    assert!(!is_match(r"a^b", "ab"));
+
+    let spanner = CompiledSpanner::new(r"(?P<x>\w+)(@|$)");
+    assert_eq!(spanner.find_all("ab@c").len(), 3);
 }
 
 #[test]
@@ -110,3 +138,176 @@ fn end_token() {
     assert!(is_match(r"foo", "foobar"));
     assert!(!is_match(r"foo$", "foobar"));
 }
+
+#[test]
+fn inline_flags() {
+    assert!(is_match(r"(?i)^ab$", "AB"));
+    assert!(is_match(r"(?i:a)b", "Ab"));
+    assert!(!is_match(r"(?i:a)b", "AB"));
+    assert!(!is_match(r"(?-s)a.b", "a\nb"));
+    assert!(!is_match(r"a.b", "a\nb"));
+    assert!(is_match(r"(?s)a.b", "a\nb"));
+    assert!(is_match(r"(?s:.)\n", "\n\n"));
+    assert!(is_match(r"(?-u)\w", "a"));
+
+    // Classes of bytes only match ASCII characters, bytes of other characters
+    // can't be matched on their own.
+    assert!(is_match(r"^(?-u).$", "a"));
+    assert!(!is_match(r"(?-u).", "é\n"));
+    assert!(is_match(r"^(?-u)(?s)[^a]$", "\n"));
+
+    let error = |regex| try_compile_with(regex, Construction::Glushkov).err().unwrap();
+    assert!(error(r"(?-u)[\x80-\xff]").starts_with("Classes of non-ASCII bytes"));
+    assert!(error(r"(?-u)\xff").starts_with("Non-ASCII bytes"));
+
+    let text = "ab\nAb\naB";
+    assert_eq!(CompiledSpanner::new(r"(?m)^a").find_all(text).len(), 2);
+    assert_eq!(CompiledSpanner::new(r"(?m:b$)").find_all(text).len(), 2);
+    assert_eq!(CompiledSpanner::new(r"(?m)^(?P<x>[^\n]*)$").find_all(text).len(), 3);
+}
+
+#[test]
+fn state_reduction() {
+    // The two 'b' positions are equivalent and should be merged.
+    assert_eq!(compile(r"^(ab|cb)$").get_nb_states(), 6);
+    assert!(is_match(r"^(ab|cb)$", "ab"));
+    assert!(is_match(r"^(ab|cb)$", "cb"));
+    assert!(!is_match(r"^(ab|cb)$", "ac"));
+}
+
+#[test]
+fn first_match() {
+    let mapping = find_first(r"(?P<x>a+)@(?P<y>b+)", "cc a@bb").unwrap();
+    assert_eq!(mapping.main_span(), Some(3..7));
+    assert!(find_first(r"(?P<x>a+)@(?P<y>b+)", "cc a@cc").is_none());
+}
+
+#[test]
+fn compiled_spanner() {
+    let spanner = CompiledSpanner::new(r"(?P<x>a+)@(?P<y>b+)");
+
+    assert_eq!(spanner.find_all("a@b aa@bb").len(), 5);
+    assert!(spanner.is_match("cc a@bb"));
+    assert!(!spanner.is_match("cc a@cc"));
+    assert_eq!(spanner.find_first("cc a@b").unwrap().main_span(), Some(3..6));
+}
+
+#[test]
+fn functional() {
+    assert!(compile(r"(?P<x>a+)@(?P<y>b+)").is_functional());
+    assert!(compile(r"a|b").is_functional());
+    assert!(compile(r"(?P<x>a|bc)(?P<y>c)").is_functional());
+    assert!(!compile(r"(?P<x>a)?b").is_functional());
+    assert!(!compile(r"(?P<x>a)|(?P<y>b)").is_functional());
+    assert!(!compile(r"(?P<x>a)*").is_functional());
+}
+
+#[test]
+fn thread_safety() {
+    fn is_sync<T: Send + Sync>() {}
+    fn is_send<T: Send>() {}
+
+    is_sync::<Automaton>();
+    is_sync::<CompiledSpanner>();
+    is_sync::<Mapping>();
+    is_send::<IndexedDag>();
+
+    let spanner = CompiledSpanner::new(r"(?P<x>a+)@(?P<y>b+)");
+    let spanner = &spanner;
+    let counts: Vec<_> = std::thread::scope(|scope| {
+        let workers: Vec<_> = ["a@b", "a@b aa@bb"]
+            .iter()
+            .map(|text| scope.spawn(move || spanner.find_all(text).len()))
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+
+    assert_eq!(counts, vec![1, 5]);
+}
+
+#[test]
+fn algebra() {
+    let definitions: HashMap<_, _> = [
+        ("p1", r"(?P<user>\w+)@(?P<host>\w+)"),
+        ("p2", r"(?P<host>\w+)\.(?P<tld>com|org)"),
+    ]
+    .iter()
+    .map(|(name, regex)| (name.to_string(), regex.to_string()))
+    .collect();
+
+    let text = "john@mail.com bob@x.org";
+    let plan = Plan::parse("join(p1, p2) | project(user, tld)", &definitions).unwrap();
+    assert_eq!(plan.get_schema(), vec!["user", "tld"]);
+
+    let mappings = plan.eval(text);
+    assert_eq!(mappings.len(), 7);
+    assert!(mappings.iter().any(|mapping| {
+        mapping.get_text("user") == Some("john") && mapping.get_text("tld") == Some("com")
+    }));
+
+    let plan = Plan::parse("union('(?P<x>a)', \"(?P<x>b)\")", &definitions).unwrap();
+    assert_eq!(plan.eval("bab").len(), 3);
+
+    assert!(Plan::parse("join(p1, p3)", &definitions).is_err());
+    assert!(Plan::parse("project(p1, tld)", &definitions).is_err());
+    assert!(Plan::parse("p1 | sort(user)", &definitions).is_err());
+}
+
+#[test]
+fn algebra_dictionaries() {
+    let definitions: HashMap<_, _> = [("date", r"(?P<date>\d{4})")]
+        .iter()
+        .map(|(name, regex)| (name.to_string(), regex.to_string()))
+        .collect();
+
+    let city = Gazetteer::new(vec!["Paris", "Lyon", "New York"]).with_group("city");
+    let dictionaries: HashMap<_, _> = iter::once(("city".to_string(), city)).collect();
+    let text = "Paris 2020, New York, then a long trip to Lyon. 2021";
+
+    let plan = Plan::parse_with("near(city, date, 3)", &definitions, &dictionaries).unwrap();
+    assert_eq!(plan.get_schema(), vec!["city", "date"]);
+    assert_eq!(plan.eval(text).len(), 3);
+
+    let plan = Plan::parse_with("near(date, city, 1)", &definitions, &dictionaries).unwrap();
+    let mappings = plan.eval(text);
+    assert_eq!(mappings.len(), 1);
+    assert_eq!(mappings[0].get_text("city"), Some("Paris"));
+
+    let plan = "concat(city, '(?P<sep>, )', '(?P<w>[a-zA-Z]+)')";
+    let mappings = Plan::parse_with(plan, &definitions, &dictionaries).unwrap().eval(text);
+    assert_eq!(mappings.len(), 4);
+    assert!(mappings.iter().all(|mapping| mapping.get_text("city") == Some("New York")));
+
+    let plan = "'(?P<s>[^,.]+)' | contains(city) | project(s)";
+    let mappings = Plan::parse_with(plan, &definitions, &dictionaries).unwrap().eval(text);
+    let has_city = |s: &str| ["Paris", "Lyon", "New York"].iter().any(|city| s.contains(city));
+    assert!(mappings.iter().all(|mapping| has_city(mapping.get_text("s").unwrap())));
+    let trip = Some(" then a long trip to Lyon");
+    assert!(mappings.iter().any(|mapping| mapping.get_text("s") == trip));
+
+    assert!(Plan::parse_with("near(city, date)", &definitions, &dictionaries).is_err());
+    assert!(Plan::parse_with("near(city, date, x)", &definitions, &dictionaries).is_err());
+    assert!(Plan::parse("contains(city, date)", &definitions).is_err());
+}
+
+#[test]
+fn syntax_tables() {
+    for (_, pattern) in SUPPORTED_SYNTAX.iter() {
+        CompiledSpanner::new(pattern);
+    }
+
+    for (name, pattern) in UNSUPPORTED_SYNTAX.iter() {
+        let compiled = panic::catch_unwind(|| CompiledSpanner::new(pattern));
+        assert!(compiled.is_err(), "{} compiled", name);
+    }
+}
+
+#[test]
+fn repeated_groups() {
+    assert!(check_groups(r"(?P<x__1>a)|(?P<x__2>b)").is_ok());
+    assert!(check_groups(r"(?P<x>a){1}b*").is_ok());
+    assert!(check_groups(r"(?P<x>a|b)*").is_err());
+    assert!(check_groups(r"((?P<x>a)b)+").is_err());
+    assert!(check_groups(r"(?P<x>a){2}").is_err());
+    assert!(check_groups(r"(?P<x__1>a)(?P<x__2>b)").is_err());
+}
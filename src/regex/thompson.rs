@@ -0,0 +1,183 @@
+/// Implementation of Thompson's construction, which builds an automaton with
+/// epsilon transitions out of a regexp's HIR by gluing small fragments
+/// together, epsilon transitions are then removed to get a variable NFA.
+use std::sync::Arc;
+
+use super::super::automaton::Automaton;
+use super::super::automaton::Label;
+use super::assertion;
+use super::parse::Hir;
+use super::reduce;
+
+/// A part of the automaton with a single entry state and a single exit state.
+#[derive(Clone, Copy, Debug)]
+struct Fragment {
+    start: usize,
+    end: usize,
+}
+
+/// An automaton whose transitions may be epsilon transitions, represented by
+/// the absence of label.
+#[derive(Clone, Debug)]
+pub struct EpsilonNfa {
+    nb_states: usize,
+    transitions: Vec<(usize, Option<Arc<Label>>, usize)>,
+    initial: usize,
+    last: usize,
+}
+
+impl EpsilonNfa {
+    /// Return an automaton recognizing the input Hir.
+    pub fn from_hir(hir: Hir) -> EpsilonNfa {
+        let mut nfa = EpsilonNfa {
+            nb_states: 0,
+            transitions: Vec::new(),
+            initial: 0,
+            last: 0,
+        };
+
+        let fragment = nfa.fragment(hir);
+        nfa.initial = fragment.start;
+        nfa.last = fragment.end;
+        nfa
+    }
+
+    /// Create an automaton that recognise the same langage, without epsilon
+    /// transitions.
+    pub fn into_automaton(self) -> Automaton {
+        // Compute the epsilon closure of each state.
+        let mut epsilon_adj = vec![Vec::new(); self.nb_states];
+
+        for (source, label, target) in &self.transitions {
+            if label.is_none() {
+                epsilon_adj[*source].push(*target);
+            }
+        }
+
+        let closures: Vec<Vec<usize>> = (0..self.nb_states)
+            .map(|state| {
+                let mut seen = vec![false; self.nb_states];
+                let mut stack = vec![state];
+                let mut closure = Vec::new();
+
+                while let Some(source) = stack.pop() {
+                    if !seen[source] {
+                        seen[source] = true;
+                        closure.push(source);
+                        stack.extend(&epsilon_adj[source]);
+                    }
+                }
+
+                closure
+            })
+            .collect();
+
+        // A labeled transition can be taken from any state of which the source
+        // is in the closure.
+        let mut labeled_adj = vec![Vec::new(); self.nb_states];
+
+        for (source, label, target) in self.transitions {
+            if let Some(label) = label {
+                labeled_adj[source].push((label, target));
+            }
+        }
+
+        // The initial state is swapped with state 0 to be the initial state of
+        // the output automaton.
+        let initial = self.initial;
+        let rename = |state: usize| match state {
+            x if x == initial => 0,
+            0 => initial,
+            x => x,
+        };
+
+        let mut transitions = Vec::new();
+        let mut finals = Vec::new();
+
+        for (state, closure) in closures.iter().enumerate() {
+            for &middle in closure {
+                for (label, target) in &labeled_adj[middle] {
+                    transitions.push((rename(state), label.clone(), rename(*target)));
+                }
+
+                if middle == self.last {
+                    finals.push(rename(state));
+                }
+            }
+        }
+
+        let (nb_states, transitions, finals) =
+            assertion::eliminate(closures.len(), transitions, finals);
+
+        let useful = reduce::useful_states(nb_states, &transitions, &finals);
+        let (class, nb_classes) = reduce::keep_states(&useful);
+        let (nb_states, transitions, finals) =
+            reduce::quotient(transitions, finals, &class, nb_classes);
+
+        Automaton::new(nb_states, transitions.into_iter(), finals.into_iter())
+    }
+
+    /// Build the fragment of automaton representing the input Hir.
+    fn fragment(&mut self, hir: Hir) -> Fragment {
+        match hir {
+            Hir::Empty => self.new_fragment(),
+            Hir::Label(label) => {
+                let fragment = self.new_fragment();
+                self.add(fragment.start, Some(label), fragment.end);
+                fragment
+            }
+            Hir::Concat(hir1, hir2) => {
+                let frag1 = self.fragment(*hir1);
+                let frag2 = self.fragment(*hir2);
+                self.add(frag1.end, None, frag2.start);
+
+                Fragment {
+                    start: frag1.start,
+                    end: frag2.end,
+                }
+            }
+            Hir::Alternation(hir1, hir2) => {
+                let fragment = self.new_fragment();
+                let frag1 = self.fragment(*hir1);
+                let frag2 = self.fragment(*hir2);
+
+                for branch in &[frag1, frag2] {
+                    self.add(fragment.start, None, branch.start);
+                    self.add(branch.end, None, fragment.end);
+                }
+
+                fragment
+            }
+            Hir::Option(hir) => {
+                let fragment = self.new_fragment();
+                let inner = self.fragment(*hir);
+                self.add(fragment.start, None, inner.start);
+                self.add(inner.end, None, fragment.end);
+                self.add(fragment.start, None, fragment.end);
+                fragment
+            }
+            Hir::Closure(hir) => {
+                let fragment = self.new_fragment();
+                let inner = self.fragment(*hir);
+                self.add(fragment.start, None, inner.start);
+                self.add(inner.end, None, inner.start);
+                self.add(inner.end, None, fragment.end);
+                fragment
+            }
+        }
+    }
+
+    /// Return a fragment made of two fresh states.
+    fn new_fragment(&mut self) -> Fragment {
+        self.nb_states += 2;
+
+        Fragment {
+            start: self.nb_states - 2,
+            end: self.nb_states - 1,
+        }
+    }
+
+    fn add(&mut self, source: usize, label: Option<Arc<Label>>, target: usize) {
+        self.transitions.push((source, label, target));
+    }
+}
@@ -0,0 +1,122 @@
+//  ____            _
+// |  _ \ ___ _ __ | | __ _  ___ ___
+// | |_) / _ \ '_ \| |/ _` |/ __/ _ \
+// |  _ <  __/ |_) | | (_| | (_|  __/
+// |_| \_\___| .__/|_|\__,_|\___\___|
+//           |_|
+
+use std::cmp::Reverse;
+
+use super::mapping::Mapping;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+    Literal(String),
+    Group(String),
+}
+
+/// Replacement text for a match, which can reference named groups of the
+/// match with `${group}`. A literal dollar sign is written `$$`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                literal.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('$') => literal.push('$'),
+                Some('{') => {
+                    let mut name = String::new();
+
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("Unclosed group in template: {}", template)),
+                        }
+                    }
+
+                    if name.is_empty() {
+                        return Err(format!("Empty group name in template: {}", template));
+                    }
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+
+                    segments.push(Segment::Group(name));
+                }
+                _ => return Err(format!("Expected `${{` or `$$` in template: {}", template)),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Template { segments })
+    }
+
+    /// Instantiate the template for a mapping, a group that is not assigned
+    /// by the mapping is replaced by an empty string.
+    pub fn expand(&self, mapping: &Mapping) -> String {
+        let mut result = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => result += literal,
+                Segment::Group(name) => {
+                    if let Some(text) = mapping.get_text(name) {
+                        result += text;
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Rewrite a text by substituting matches with a template.
+///
+/// As matches may overlap, they are selected from left to right, a longest
+/// match being preferred among matches starting at the same position. Empty
+/// matches are never replaced.
+pub fn replace<'t, T>(text: &str, mappings: T, template: &Template) -> String
+where
+    T: Iterator<Item = Mapping<'t>>,
+{
+    let mut matches: Vec<_> = mappings
+        .filter_map(|mapping| mapping.main_span().map(|span| (span, mapping)))
+        .filter(|(span, _)| !span.is_empty())
+        .collect();
+
+    matches.sort_by_key(|(span, _)| (span.start, Reverse(span.end)));
+
+    let mut result = String::new();
+    let mut last_end = 0;
+
+    for (span, mapping) in matches {
+        if span.start < last_end {
+            continue;
+        }
+
+        result += &text[last_end..span.start];
+        result += &template.expand(&mapping);
+        last_end = span.end;
+    }
+
+    result += &text[last_end..];
+    result
+}
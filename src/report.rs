@@ -0,0 +1,226 @@
+//  ____                       _
+// |  _ \ ___ _ __   ___  _ __| |_
+// | |_) / _ \ '_ \ / _ \| '__| __|
+// |  _ <  __/ |_) | (_) | |  | |_
+// |_| \_\___| .__/ \___/|_|   \__|
+//           |_|
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::ops::Range;
+
+use super::mapping::Mapping;
+
+/// Background colors given to groups, in their order of appearance.
+static COLORS: &[&str] = &[
+    "#f8a5a5", "#a5e0a5", "#f5e08a", "#a5c8f8", "#dba5f0", "#a5ecec",
+];
+
+static STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+pre { white-space: pre-wrap; border: 1px solid #ccc; padding: 1em; }
+mark:hover { outline: 2px solid #333; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ccc; padding: 0.2em 0.5em; text-align: left; }
+td code { white-space: pre-wrap; }
+.offsets { color: #777; font-size: smaller; }";
+
+/// Standalone HTML page showing the text with the spans of all groups
+/// highlighted, followed by a table of the matches.
+///
+/// Where spans of several groups overlap, the text is painted with the color
+/// of the innermost one, as in the terminal highlighting, and hovering it
+/// lists all the groups covering it.
+pub struct Report<'t> {
+    text: &'t str,
+    groups: Vec<String>,
+    /// Spans assigned to groups, with the number of matches assigning them.
+    spans: HashMap<(usize, Range<usize>), usize>,
+    rows: Vec<Vec<Option<Range<usize>>>>,
+}
+
+impl<'t> Report<'t> {
+    pub fn new(text: &'t str) -> Report<'t> {
+        Report {
+            text,
+            groups: Vec::new(),
+            spans: HashMap::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, mapping: &Mapping) {
+        let mut row = vec![None; self.groups.len()];
+
+        for (name, range) in mapping.iter_groups() {
+            let group = match self.groups.iter().position(|group| group == name) {
+                Some(group) => group,
+                None => {
+                    self.groups.push(name.to_string());
+                    row.push(None);
+                    self.groups.len() - 1
+                }
+            };
+
+            *self.spans.entry((group, range.clone())).or_insert(0) += 1;
+            row[group] = Some(range);
+        }
+
+        self.rows.push(row);
+    }
+
+    pub fn render(&self) -> String {
+        let mut html = String::new();
+
+        writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">").unwrap();
+        writeln!(html, "<title>Matches</title>\n<style>\n{}", STYLE).unwrap();
+
+        for group in 0..self.groups.len() {
+            writeln!(html, ".g{} {{ background: {}; }}", group, self.color(group)).unwrap();
+        }
+
+        writeln!(html, "</style>\n</head>\n<body>").unwrap();
+        writeln!(html, "<h1>{} matches</h1>", self.rows.len()).unwrap();
+
+        let legend: Vec<String> = self
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(group, name)| format!("<mark class=\"g{}\">{}</mark>", group, escape(name)))
+            .collect();
+        writeln!(html, "<p>Groups: {}</p>", legend.join(" ")).unwrap();
+
+        writeln!(html, "<pre>{}</pre>", self.render_text()).unwrap();
+        writeln!(html, "{}</body>\n</html>", self.render_table()).unwrap();
+        html
+    }
+
+    fn color(&self, group: usize) -> &'static str {
+        COLORS[group % COLORS.len()]
+    }
+
+    /// Text cut at the bounds of all spans, each piece being painted with the
+    /// innermost group covering it, that is the group starting last.
+    fn render_text(&self) -> String {
+        let mut events: Vec<(usize, bool, usize, Range<usize>)> = Vec::new();
+
+        for (group, range) in self.spans.keys() {
+            events.push((range.start, true, *group, range.clone()));
+            events.push((range.end, false, *group, range.clone()));
+        }
+
+        // Closing spans before opening others at the same position keeps
+        // empty pieces out of the output.
+        events.sort_by_key(|(pos, opens, _, _)| (*pos, *opens));
+
+        let mut active: BTreeMap<(usize, Reverse<usize>, usize), usize> = BTreeMap::new();
+        let mut covering = vec![0; self.groups.len()];
+        let mut output = String::new();
+        let mut piece_start = 0;
+
+        for (pos, opens, group, range) in events {
+            if pos > piece_start {
+                let innermost = active.keys().next_back().map(|(_, _, group)| *group);
+                output += &self.render_piece(piece_start..pos, innermost, &covering);
+                piece_start = pos;
+            }
+
+            let key = (range.start, Reverse(range.end), group);
+
+            if opens {
+                *active.entry(key).or_insert(0) += 1;
+                covering[group] += 1;
+            } else if let Some(count) = active.get_mut(&key) {
+                *count -= 1;
+                covering[group] -= 1;
+
+                if *count == 0 {
+                    active.remove(&key);
+                }
+            }
+        }
+
+        output += &escape(&self.text[piece_start..]);
+        output
+    }
+
+    /// Render a piece of text with the color of its innermost group, along
+    /// with the number of spans of each group covering it.
+    fn render_piece(
+        &self,
+        piece: Range<usize>,
+        innermost: Option<usize>,
+        covering: &[usize],
+    ) -> String {
+        let text = escape(&self.text[piece]);
+
+        let innermost = match innermost {
+            None => return text,
+            Some(group) => group,
+        };
+
+        let title: Vec<String> = covering
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(group, count)| format!("{}: {} span(s)", self.groups[group], count))
+            .collect();
+
+        format!(
+            "<mark class=\"g{}\" title=\"{}\">{}</mark>",
+            innermost,
+            escape(&title.join(", ")),
+            text
+        )
+    }
+
+    fn render_table(&self) -> String {
+        let mut table = String::from("<table>\n<tr><th>#</th>");
+
+        for (group, name) in self.groups.iter().enumerate() {
+            write!(table, "<th class=\"g{}\">{}</th>", group, escape(name)).unwrap();
+        }
+
+        table += "</tr>\n";
+
+        for (count, row) in self.rows.iter().enumerate() {
+            write!(table, "<tr><td>{}</td>", count + 1).unwrap();
+
+            for group in 0..self.groups.len() {
+                match row.get(group).cloned().flatten() {
+                    None => table += "<td></td>",
+                    Some(range) => write!(
+                        table,
+                        "<td><code>{}</code> <span class=\"offsets\">{}..{}</span></td>",
+                        escape(&self.text[range.clone()]),
+                        range.start,
+                        range.end
+                    )
+                    .unwrap(),
+                }
+            }
+
+            table += "</tr>\n";
+        }
+
+        table + "</table>\n"
+    }
+}
+
+/// Escape the characters of a text that have a meaning in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&#39;",
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
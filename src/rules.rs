@@ -0,0 +1,167 @@
+//  ____        _
+// |  _ \ _   _| | ___  ___
+// | |_) | | | | |/ _ \/ __|
+// |  _ <| |_| | |  __/\__ \
+// |_| \_\\__,_|_|\___||___/
+//
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::iter;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use enum_spanner_rs::automaton::{Automaton, Label};
+use enum_spanner_rs::mapping::{Mapping, Marker, Variable};
+use enum_spanner_rs::regex::{self, Construction};
+
+/// A named extractor of a rules file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rule {
+    name: String,
+    pattern: String,
+    #[serde(default)]
+    options: RuleOptions,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RuleOptions {
+    /// Skip the matches whose texts were already extracted by the rule.
+    #[serde(default)]
+    pub unique: bool,
+    /// Construction of the automaton of the pattern, Glushkov by default.
+    pub construction: Option<Construction>,
+}
+
+impl Rule {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_options(&self) -> &RuleOptions {
+        &self.options
+    }
+}
+
+/// Rules compiled into a single automaton, so that all of them are evaluated
+/// in one pass over each document.
+///
+/// The automaton is the union of the automata of the rules, where the groups
+/// of each rule are renamed `RULE.GROUP` and each rule assigns an additional
+/// variable named after the rule, which tells the rule of a match apart even if
+/// it binds no group.
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    automaton: Automaton,
+}
+
+impl RuleSet {
+    /// Read the rules from a file in JSON syntax, the names of the rules must
+    /// be unique and can't contain a dot.
+    pub fn read_from_file(filename: &Path) -> Result<RuleSet, Box<dyn std::error::Error>> {
+        let mut input = String::new();
+        File::open(filename)?.read_to_string(&mut input)?;
+        let rules: Vec<Rule> = serde_json::from_str(&input)?;
+
+        for (i, rule) in rules.iter().enumerate() {
+            if rule.name.is_empty() || rule.name.contains('.') {
+                return Err(format!("Invalid rule name: {:?}", rule.name).into());
+            }
+
+            if rules[..i].iter().any(|other| other.name == rule.name) {
+                return Err(format!("Duplicated rule name: {}", rule.name).into());
+            }
+        }
+
+        Ok(RuleSet::new(rules))
+    }
+
+    pub fn new(rules: Vec<Rule>) -> RuleSet {
+        assert!(!rules.is_empty(), "At least one rule is needed");
+
+        // The union starts from a new initial state and ends in a single final
+        // state, the states of the rules come in between.
+        let mut transitions = Vec::new();
+        let mut nb_states = 1;
+        let mut nb_vars = 0;
+        let mut ends = Vec::new();
+
+        for rule in &rules {
+            let construction = rule.options.construction.unwrap_or(Construction::Glushkov);
+            let automaton = regex::compile_with(&rule.pattern, construction);
+            let offset = nb_states;
+
+            // The variables of the rule are numbered after the variable of
+            // its match.
+            let rule_var = Arc::new(Variable::new(rule.name.clone(), nb_vars));
+            let mut variables = HashMap::new();
+
+            for var in automaton.get_variables() {
+                let name = format!("{}.{}", rule.name, var.get_name());
+                let renamed = Arc::new(Variable::new(name, nb_vars + 1 + var.get_id()));
+                variables.insert(var.get_id(), renamed);
+            }
+
+            nb_vars += 1 + variables.keys().map(|id| id + 1).max().unwrap_or(0);
+
+            let rename = |label: &Arc<Label>| match &**label {
+                Label::Assignation(Marker::Open(var)) => Arc::new(Label::Assignation(
+                    Marker::Open(variables[&var.get_id()].clone()),
+                )),
+                Label::Assignation(Marker::Close(var)) => Arc::new(Label::Assignation(
+                    Marker::Close(variables[&var.get_id()].clone()),
+                )),
+                _ => label.clone(),
+            };
+
+            transitions.extend(automaton.transitions.iter().map(|(source, label, target)| {
+                (source + offset, rename(label), target + offset)
+            }));
+
+            let open = Arc::new(Label::Assignation(Marker::Open(rule_var.clone())));
+            transitions.push((0, open, automaton.get_initial() + offset));
+
+            let close = Arc::new(Label::Assignation(Marker::Close(rule_var)));
+            ends.extend(automaton.finals.iter().map(|state| (state + offset, close.clone())));
+
+            nb_states += automaton.get_nb_states();
+        }
+
+        let last = nb_states;
+        transitions.extend(ends.into_iter().map(|(state, close)| (state, close, last)));
+        let automaton = Automaton::new(nb_states + 1, transitions.into_iter(), iter::once(last));
+
+        RuleSet { rules, automaton }
+    }
+
+    pub fn get_automaton(&self) -> &Automaton {
+        &self.automaton
+    }
+
+    /// Rule which produced a match of the automaton, with a mapping over the
+    /// groups of the rule.
+    pub fn extract<'t>(&self, text: &'t str, mapping: &Mapping<'t>) -> (&Rule, Mapping<'t>) {
+        let rule = (self.rules.iter())
+            .find(|rule| mapping.get(&rule.name).is_some())
+            .expect("A match should be assigned to a rule");
+
+        let prefix = format!("{}.", rule.name);
+        let groups: Vec<(&str, Range<usize>)> = (mapping.iter_groups())
+            .filter_map(|(name, range)| name.strip_prefix(&prefix).map(|name| (name, range)))
+            .collect();
+
+        let mut schema: Vec<String> = Vec::new();
+
+        for (name, _) in &groups {
+            if !schema.iter().any(|group| group == name) {
+                schema.push(name.to_string());
+            }
+        }
+
+        (rule, Mapping::from_groups(text, &schema, groups))
+    }
+}
@@ -0,0 +1,267 @@
+//  ____                _      _
+// / ___|  ___ _ __ ___ (_)_ __(_)_ __   __ _
+// \___ \ / _ \ '_ ` _ \| | '__| | '_ \ / _` |
+//  ___) |  __/ | | | | | | |  | | | | | (_| |
+// |____/ \___|_| |_| |_|_|_|  |_|_| |_|\__, |
+//                                      |___/
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::mapping::Mapping;
+
+/// Semiring over which the scores of mappings are computed.
+///
+/// The score of a mapping is the product of the scores of its groups, which
+/// are the product of the score of binding the group and of the score of each
+/// character of its span. The sum combines the scores of several mappings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Semiring {
+    /// Probabilities with the usual sum and product.
+    Probability,
+    /// Probabilities of the most likely mapping, the sum is the maximum.
+    Viterbi,
+    /// Costs summed along a mapping, the sum is the minimum.
+    Tropical,
+}
+
+impl Semiring {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Semiring::Probability => "probability",
+            Semiring::Viterbi => "viterbi",
+            Semiring::Tropical => "tropical",
+        }
+    }
+
+    /// Find a semiring from the name given by `get_name`.
+    pub fn from_name(name: &str) -> Option<Semiring> {
+        [Semiring::Probability, Semiring::Viterbi, Semiring::Tropical]
+            .iter()
+            .copied()
+            .find(|semiring| semiring.get_name() == name)
+    }
+
+    pub fn zero(&self) -> f64 {
+        match self {
+            Semiring::Probability | Semiring::Viterbi => 0.,
+            Semiring::Tropical => f64::INFINITY,
+        }
+    }
+
+    pub fn one(&self) -> f64 {
+        match self {
+            Semiring::Probability | Semiring::Viterbi => 1.,
+            Semiring::Tropical => 0.,
+        }
+    }
+
+    pub fn add(&self, x: f64, y: f64) -> f64 {
+        match self {
+            Semiring::Probability => x + y,
+            Semiring::Viterbi => x.max(y),
+            Semiring::Tropical => x.min(y),
+        }
+    }
+
+    pub fn mul(&self, x: f64, y: f64) -> f64 {
+        match self {
+            Semiring::Probability | Semiring::Viterbi => x * y,
+            Semiring::Tropical => x + y,
+        }
+    }
+
+    /// Product of `n` copies of `x`.
+    pub fn pow(&self, x: f64, n: usize) -> f64 {
+        match self {
+            Semiring::Probability | Semiring::Viterbi => x.powf(n as f64),
+            Semiring::Tropical => x * n as f64,
+        }
+    }
+}
+
+/// Scores of the groups of a pattern, the groups without a score have score
+/// one, which doesn't change the score of mappings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    semiring: Semiring,
+    groups: HashMap<String, f64>,
+    chars: HashMap<String, f64>,
+}
+
+impl Annotation {
+    pub fn new(semiring: Semiring) -> Annotation {
+        Annotation {
+            semiring,
+            groups: HashMap::new(),
+            chars: HashMap::new(),
+        }
+    }
+
+    /// Score of binding a group.
+    pub fn with_group_score(mut self, group: &str, score: f64) -> Annotation {
+        self.groups.insert(group.to_string(), score);
+        self
+    }
+
+    /// Score of each character of the span of a group.
+    pub fn with_char_score(mut self, group: &str, score: f64) -> Annotation {
+        self.chars.insert(group.to_string(), score);
+        self
+    }
+
+    pub fn get_semiring(&self) -> Semiring {
+        self.semiring
+    }
+
+    /// Score of a mapping.
+    pub fn score(&self, mapping: &Mapping) -> f64 {
+        let semiring = self.semiring;
+
+        mapping
+            .iter_groups_text()
+            .fold(semiring.one(), |score, (group, text)| {
+                let bind = self.groups.get(group).copied().unwrap_or(semiring.one());
+                let chars = match self.chars.get(group) {
+                    Some(&x) => semiring.pow(x, text.chars().count()),
+                    None => semiring.one(),
+                };
+
+                semiring.mul(score, semiring.mul(bind, chars))
+            })
+    }
+
+    /// Sum of the scores of several mappings, e.g. the expected number of
+    /// mappings extracted with probabilities or the cost of the cheapest one
+    /// in the tropical semiring.
+    pub fn total<'t, I>(&self, mappings: I) -> f64
+    where
+        I: IntoIterator<Item = Mapping<'t>>,
+    {
+        mappings.into_iter().fold(self.semiring.zero(), |total, mapping| {
+            self.semiring.add(total, self.score(&mapping))
+        })
+    }
+}
+
+//  ____
+// |  _ \ _ __ _____   _____ _ __   __ _ _ __   ___ ___
+// | |_) | '__/ _ \ \ / / _ \ '_ \ / _` | '_ \ / __/ _ \
+// |  __/| | | (_) \ V /  __/ | | | (_| | | | | (_|  __/
+// |_|   |_|  \___/ \_/ \___|_| |_|\__,_|_| |_|\___\___|
+//
+
+/// Provenance of a mapping in the semiring of polynomials with natural
+/// coefficients, whose variables are tokens.
+///
+/// Tokens are groups of the pattern which are projected out of the output,
+/// e.g. groups around the branches of an alternation. A mapping binding some
+/// tokens contributes the product of these tokens to the provenance of its
+/// projection, and the provenance of a projected mapping is the sum of these
+/// contributions.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Provenance {
+    /// Coefficient of each monomial, given as the sorted list of its tokens.
+    monomials: BTreeMap<Vec<String>, u64>,
+}
+
+impl Provenance {
+    pub fn zero() -> Provenance {
+        Provenance::default()
+    }
+
+    pub fn one() -> Provenance {
+        Provenance::token_product(Vec::new())
+    }
+
+    /// Provenance of a single token.
+    pub fn token(name: &str) -> Provenance {
+        Provenance::token_product(vec![name.to_string()])
+    }
+
+    fn token_product(mut tokens: Vec<String>) -> Provenance {
+        tokens.sort();
+        Provenance {
+            monomials: BTreeMap::from([(tokens, 1)]),
+        }
+    }
+
+    pub fn add(&self, other: &Provenance) -> Provenance {
+        let mut monomials = self.monomials.clone();
+
+        for (monomial, coef) in &other.monomials {
+            *monomials.entry(monomial.clone()).or_insert(0) += coef;
+        }
+
+        Provenance { monomials }
+    }
+
+    pub fn mul(&self, other: &Provenance) -> Provenance {
+        let mut monomials = BTreeMap::new();
+
+        for (monomial1, coef1) in &self.monomials {
+            for (monomial2, coef2) in &other.monomials {
+                let mut monomial: Vec<_> = monomial1.iter().chain(monomial2).cloned().collect();
+                monomial.sort();
+                *monomials.entry(monomial).or_insert(0) += coef1 * coef2;
+            }
+        }
+
+        Provenance { monomials }
+    }
+
+    /// Project out the groups used as tokens from the mappings, each distinct
+    /// projection is given once along with its provenance, in the order of
+    /// their first occurrence.
+    pub fn project<'t, I>(mappings: I, tokens: &[String]) -> Vec<(Mapping<'t>, Provenance)>
+    where
+        I: IntoIterator<Item = Mapping<'t>>,
+    {
+        let mut projections: Vec<(Mapping<'t>, Provenance)> = Vec::new();
+        let mut index: HashMap<Mapping<'t>, usize> = HashMap::new();
+
+        for mapping in mappings {
+            let bound: Vec<String> = (tokens.iter())
+                .filter(|token| mapping.get(token).is_some())
+                .cloned()
+                .collect();
+            let provenance = Provenance::token_product(bound);
+            let projection = mapping.without_groups(tokens);
+
+            match index.get(&projection) {
+                Some(&i) => {
+                    let (_, total) = &mut projections[i];
+                    *total = total.add(&provenance);
+                }
+                None => {
+                    index.insert(projection.clone(), projections.len());
+                    projections.push((projection, provenance));
+                }
+            }
+        }
+
+        projections
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.monomials.is_empty() {
+            return write!(f, "0");
+        }
+
+        for (i, (monomial, coef)) in self.monomials.iter().enumerate() {
+            if i > 0 {
+                write!(f, " + ")?;
+            }
+
+            match (coef, monomial.is_empty()) {
+                (_, true) => write!(f, "{}", coef)?,
+                (1, false) => write!(f, "{}", monomial.join("·"))?,
+                (_, false) => write!(f, "{}·{}", coef, monomial.join("·"))?,
+            }
+        }
+
+        Ok(())
+    }
+}
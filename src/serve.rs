@@ -0,0 +1,185 @@
+//  ____
+// / ___|  ___ _ ____   _____
+// \___ \ / _ \ '__\ \ / / _ \
+//  ___) |  __/ |   \ V /  __/
+// |____/ \___|_|    \_/ \___|
+//
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::prelude::*;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use enum_spanner_rs::mapping::{DeterministicEva, SpannerEnumerator};
+use enum_spanner_rs::offsets::{OffsetTable, OffsetUnit};
+use enum_spanner_rs::spanner::CompiledSpanner;
+use serde::Deserialize;
+
+use super::{mapping_to_json, trim_trailing_newlines};
+
+/// Number of compiled patterns kept between requests, the oldest ones are
+/// dropped first.
+const CACHE_SIZE: usize = 64;
+
+/// A query received by the server, the document is either given inline as
+/// `text` or read from `file`.
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: serde_json::Value,
+    pattern: String,
+    text: Option<String>,
+    file: Option<PathBuf>,
+    #[serde(default)]
+    options: RequestOptions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RequestOptions {
+    /// Only return the number of matches.
+    count: bool,
+    /// Skip this number of matches, which serves pages of matches along with
+    /// `limit`.
+    offset: usize,
+    /// Stop the enumeration after this number of matches.
+    limit: Option<usize>,
+    /// Also give the offsets in this unit: bytes, chars or utf16.
+    offset_unit: Option<String>,
+}
+
+/// Evaluate requests with the patterns compiled by previous requests.
+pub struct Server {
+    spanners: HashMap<String, CompiledSpanner>,
+    /// Patterns of the cache, from the oldest to the most recent.
+    order: VecDeque<String>,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server {
+            spanners: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Answer each line of the input, which holds a request in JSON syntax,
+    /// with a line holding the result in JSON syntax. A request that fails
+    /// is answered with an object holding an `error` message.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => {
+                    let id = request.id.clone();
+                    let mut response = match self.handle(&request) {
+                        Ok(response) => response,
+                        Err(err) => serde_json::json!({ "error": err }),
+                    };
+                    response["id"] = id;
+                    response
+                }
+                Err(err) => serde_json::json!({
+                    "id": null,
+                    "error": format!("Invalid request: {}", err),
+                }),
+            };
+
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &Request) -> Result<serde_json::Value, String> {
+        let text = match (&request.text, &request.file) {
+            (Some(text), None) => text.clone(),
+            (None, Some(path)) => {
+                let mut text = String::new();
+                File::open(path)
+                    .and_then(|mut file| file.read_to_string(&mut text))
+                    .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+                trim_trailing_newlines(&mut text);
+                text
+            }
+            _ => return Err("Exactly one of text and file must be given".to_string()),
+        };
+
+        let offset_unit = match request.options.offset_unit.as_deref() {
+            None | Some("bytes") => None,
+            Some("utf16") => Some(OffsetUnit::Utf16),
+            Some("chars") => Some(OffsetUnit::Chars),
+            Some(s) => return Err(format!("Invalid option for offset unit: {}", s)),
+        };
+
+        let spanner = self.get_spanner(&request.pattern)?;
+
+        // Without limit nor offset, matches are counted without being
+        // enumerated.
+        if request.options.count && request.options.limit.is_none() && request.options.offset == 0 {
+            let count = DeterministicEva::new(spanner.get_automaton().clone(), &text).count_runs();
+            let count = u64::try_from(count).map_err(|_| format!("Too many matches: {}", count))?;
+            return Ok(serde_json::json!({ "count": count }));
+        }
+
+        let index = spanner.index(&text);
+        let offset = request.options.offset;
+        let limit = request.options.limit.unwrap_or(usize::MAX);
+
+        if request.options.count {
+            let count = index.iter_range(offset, limit).count();
+            return Ok(serde_json::json!({ "count": count }));
+        }
+
+        let offset_table = offset_unit.map(|unit| OffsetTable::new(&text, unit));
+        let matches = index
+            .iter_range(offset, limit)
+            .map(|mapping| mapping_to_json(&mapping, &text, offset_table.as_ref()).into())
+            .collect::<Vec<serde_json::Value>>();
+
+        Ok(serde_json::json!({
+            "count": matches.len(),
+            "matches": matches,
+        }))
+    }
+
+    /// Compiled spanner for a pattern, which is only compiled if it is not in
+    /// the cache yet.
+    fn get_spanner(&mut self, pattern: &str) -> Result<&CompiledSpanner, String> {
+        if !self.spanners.contains_key(pattern) {
+            // Invalid patterns make the compilation panic, which must not
+            // stop the server nor be reported on STDERR.
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(|_| {}));
+            let result = panic::catch_unwind(AssertUnwindSafe(|| CompiledSpanner::new(pattern)));
+            panic::set_hook(hook);
+
+            let spanner = result.map_err(|err| {
+                let message = err
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown error".to_string());
+                format!("Invalid pattern: {}", message)
+            })?;
+
+            if self.order.len() == CACHE_SIZE {
+                let oldest = self.order.pop_front().unwrap();
+                self.spanners.remove(&oldest);
+            }
+
+            self.order.push_back(pattern.to_string());
+            self.spanners.insert(pattern.to_string(), spanner);
+        }
+
+        Ok(&self.spanners[pattern])
+    }
+}
@@ -0,0 +1,95 @@
+//   ____                      _ _          _ ____
+//  / ___|___  _ __ ___  _ __ (_) | ___  __| / ___| _ __   __ _ _ __  _ __   ___ _ __
+// | |   / _ \| '_ ` _ \| '_ \| | |/ _ \/ _` \___ \| '_ \ / _` | '_ \| '_ \ / _ \ '__|
+// | |__| (_) | | | | | | |_) | | |  __/ (_| |___) | |_) | (_| | | | | | | |  __/ |
+//  \____\___/|_| |_| |_| .__/|_|_|\___|\__,_|____/| .__/ \__,_|_| |_|_| |_|\___|_|
+//                      |_|                        |_|
+
+use super::automaton::Automaton;
+use super::mapping::indexed_dag::{IndexedDag, IndexedDagBuilder, TrimmingStrategy};
+use super::mapping::{Mapping, SpannerEnumerator};
+use super::regex;
+
+/// A pattern compiled once into an automaton, which can then be evaluated over
+/// any number of documents.
+///
+/// Each document is indexed by its own `IndexedDag` working on a copy of the
+/// automaton, thus the spanner itself is never modified and can be shared
+/// between its users.
+#[derive(Clone)]
+pub struct CompiledSpanner {
+    automaton: Automaton,
+    jump_distance: usize,
+    trimming_strategy: TrimmingStrategy,
+}
+
+impl CompiledSpanner {
+    pub fn new(regex: &str) -> CompiledSpanner {
+        match regex::try_compile_with(regex, regex::Construction::Glushkov) {
+            Ok(automaton) => CompiledSpanner::from_automaton(automaton),
+            Err(message) => panic!("{}", message),
+        }
+    }
+
+    pub fn from_automaton(automaton: Automaton) -> CompiledSpanner {
+        CompiledSpanner {
+            automaton,
+            jump_distance: 1,
+            trimming_strategy: TrimmingStrategy::FullTrimming,
+        }
+    }
+
+    pub fn with_index_options(
+        mut self,
+        jump_distance: usize,
+        trimming_strategy: TrimmingStrategy,
+    ) -> CompiledSpanner {
+        self.jump_distance = jump_distance;
+        self.trimming_strategy = trimming_strategy;
+        self
+    }
+
+    pub fn get_automaton(&self) -> &Automaton {
+        &self.automaton
+    }
+
+    /// Options of the index of a document, which can then be completed and
+    /// built.
+    pub fn builder<'t>(&self, text: &'t str) -> IndexedDagBuilder<'t> {
+        IndexedDag::builder(self.automaton.clone(), text)
+            .with_jump_distance(self.jump_distance)
+            .with_trimming(self.trimming_strategy)
+    }
+
+    /// Enumerator over a document, which still has to be preprocessed. This
+    /// allows to set other options on the index.
+    pub fn enumerator<'t>(&self, text: &'t str) -> IndexedDag<'t> {
+        self.builder(text).build()
+    }
+
+    /// Index a document, the matches can then be enumerated with constant
+    /// delay from the returned index.
+    pub fn index<'t>(&self, text: &'t str) -> IndexedDag<'t> {
+        let mut index = self.enumerator(text);
+        index.preprocess();
+        index
+    }
+
+    /// Check if there is a match in a document, without indexing it.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.enumerator(text).has_match()
+    }
+
+    /// Find one match in a document, the enumeration stops after the first
+    /// mapping.
+    pub fn find_first<'t>(&self, text: &'t str) -> Option<Mapping<'t>> {
+        self.index(text).first()
+    }
+
+    /// Collect all the matches in a document.
+    pub fn find_all<'t>(&self, text: &'t str) -> Vec<Mapping<'t>> {
+        let index = self.index(text);
+        let matches = index.iter().collect();
+        matches
+    }
+}
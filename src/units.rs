@@ -0,0 +1,168 @@
+//  _   _       _ _
+// | | | |_ __ (_) |_ ___
+// | | | | '_ \| | __/ __|
+// | |_| | | | | | |_\__ \
+//  \___/|_| |_|_|\__|___/
+//
+
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+use std::iter;
+
+use super::csv::CsvReader;
+
+/// Part of the input which is matched on its own, such that no match spans
+/// several units: a record of a text, a cell of a CSV column or a field of a
+/// JSON record.
+///
+/// The offsets of matches are relative to the text of their unit, as cells
+/// and fields are unescaped thus don't appear as such in the input.
+pub struct Unit<'a> {
+    /// Kind of the unit, which labels its matches with its number.
+    pub kind: &'static str,
+    /// Position of the unit in the input, starting from 1.
+    pub number: usize,
+    pub text: Cow<'a, str>,
+    /// JSON record holding the text, which is output with its matches.
+    pub record: Option<JsonRecord>,
+}
+
+/// Records of a text separated by a string.
+pub fn records<'a>(text: &'a str, separator: &'a str) -> impl Iterator<Item = Unit<'a>> {
+    text.split(separator).enumerate().map(|(index, record)| Unit {
+        kind: "record",
+        number: index + 1,
+        text: Cow::Borrowed(record),
+        record: None,
+    })
+}
+
+/// Cells of a column of CSV rows, given by its number starting from 1 or by
+/// its name in the header row, which is not matched against. Rows without
+/// the column are skipped.
+pub fn csv_cells<R: BufRead>(
+    input: R,
+    delimiter: char,
+    column: &str,
+) -> impl Iterator<Item = Unit<'static>> {
+    let mut rows = CsvReader::new(input, delimiter)
+        .map(|row| row.expect("Could not read the CSV input."))
+        .enumerate()
+        .map(|(index, row)| (index + 1, row));
+
+    let column = match column.parse::<usize>() {
+        Ok(column) if column > 0 => column - 1,
+        Ok(_) => panic!("Columns are numbered from 1."),
+        Err(_) => {
+            let header = rows.next().map(|(_, header)| header).unwrap_or_default();
+
+            match header.iter().position(|name| name == column) {
+                Some(column) => column,
+                None => panic!("No column named {} in the header row.", column),
+            }
+        }
+    };
+
+    rows.filter(move |(_, row)| column < row.len()).map(move |(number, mut row)| Unit {
+        kind: "row",
+        number,
+        text: Cow::Owned(row.swap_remove(column)),
+        record: None,
+    })
+}
+
+/// String fields of JSON records, one record per line. Records without the
+/// field are skipped, and lines which are not JSON objects are kept track of.
+pub struct JsonFields<R: BufRead> {
+    lines: iter::Enumerate<io::Lines<R>>,
+    pointer: String,
+    invalid_lines: Vec<usize>,
+}
+
+impl<R: BufRead> JsonFields<R> {
+    /// Fields given by their name or by a JSON pointer such as
+    /// /payload/message.
+    pub fn new(input: R, field: &str) -> JsonFields<R> {
+        let pointer = if field.starts_with('/') {
+            field.to_string()
+        } else {
+            format!("/{}", field.replace('~', "~0").replace('/', "~1"))
+        };
+
+        JsonFields {
+            lines: input.lines().enumerate(),
+            pointer,
+            invalid_lines: Vec::new(),
+        }
+    }
+
+    /// Numbers of the lines read so far which are not JSON objects.
+    pub fn get_invalid_lines(&self) -> &[usize] {
+        &self.invalid_lines
+    }
+}
+
+impl<R: BufRead> Iterator for JsonFields<R> {
+    type Item = Unit<'static>;
+
+    fn next(&mut self) -> Option<Unit<'static>> {
+        for (index, line) in &mut self.lines {
+            let line = line.expect("Could not read the input.");
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let value: serde_json::Value = match serde_json::from_str(&line) {
+                Ok(value @ serde_json::Value::Object(_)) => value,
+                _ => {
+                    self.invalid_lines.push(index + 1);
+                    continue;
+                }
+            };
+
+            let text = match value.pointer(&self.pointer) {
+                Some(serde_json::Value::String(text)) => text.clone(),
+                _ => continue,
+            };
+
+            return Some(Unit {
+                kind: "line",
+                number: index + 1,
+                text: Cow::Owned(text),
+                record: Some(JsonRecord { line, value }),
+            });
+        }
+
+        None
+    }
+}
+
+/// A JSON record, as it was read and parsed.
+pub struct JsonRecord {
+    line: String,
+    value: serde_json::Value,
+}
+
+impl JsonRecord {
+    /// The record with an additional field `matches` listing the given JSON
+    /// objects. It is appended to the record as it was read, which keeps the
+    /// order of its keys, unless it has a field with the same name which is
+    /// replaced.
+    pub fn with_matches(&self, found: &[String]) -> String {
+        let found = format!("[{}]", found.join(","));
+
+        match self.line.trim_end().strip_suffix('}') {
+            Some(body) if self.value.get("matches").is_none() => {
+                let empty = self.value.as_object().is_some_and(|object| object.is_empty());
+                let separator = if empty { "" } else { "," };
+                format!(r#"{}{}"matches":{}}}"#, body, separator, found)
+            }
+            _ => {
+                let mut value = self.value.clone();
+                value["matches"] = serde_json::from_str(&found).unwrap();
+                value.to_string()
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// __     __        _  __
+// \ \   / /__ _ __(_)/ _|_   _
+//  \ \ / / _ \ '__| | |_| | | |
+//   \ V /  __/ |  | |  _| |_| |
+//    \_/ \___|_|  |_|_|  \__, |
+//                        |___/
+
+use std::collections::HashSet;
+
+use super::mapping::Mapping;
+
+/// Differences between the mappings produced by an enumerator and the
+/// mappings produced by a reference enumerator over the same text.
+pub struct Verification<'t> {
+    num_expected: usize,
+    num_actual: usize,
+    /// Mappings produced by the reference but not by the enumerator.
+    missing: Vec<Mapping<'t>>,
+    /// Mappings produced by the enumerator but not by the reference.
+    unexpected: Vec<Mapping<'t>>,
+    /// Mappings produced several times by the enumerator.
+    duplicates: Vec<Mapping<'t>>,
+}
+
+impl<'t> Verification<'t> {
+    pub fn new<E, A>(expected: E, actual: A) -> Verification<'t>
+    where
+        E: Iterator<Item = Mapping<'t>>,
+        A: Iterator<Item = Mapping<'t>>,
+    {
+        let expected: HashSet<_> = expected.collect();
+        let mut actual_set = HashSet::new();
+        let mut num_actual = 0;
+        let mut duplicates = Vec::new();
+
+        for mapping in actual {
+            num_actual += 1;
+
+            if actual_set.contains(&mapping) {
+                duplicates.push(mapping);
+            } else {
+                actual_set.insert(mapping);
+            }
+        }
+
+        // Samples are displayed in the order of the text.
+        let by_position = |mappings: &mut Vec<Mapping>| {
+            mappings.sort_by_key(|mapping| mapping.main_span().map(|span| (span.start, span.end)))
+        };
+
+        let mut missing: Vec<_> = expected.difference(&actual_set).cloned().collect();
+        let mut unexpected: Vec<_> = actual_set.difference(&expected).cloned().collect();
+        by_position(&mut missing);
+        by_position(&mut unexpected);
+        by_position(&mut duplicates);
+
+        Verification {
+            num_expected: expected.len(),
+            num_actual,
+            missing,
+            unexpected,
+            duplicates,
+        }
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty() && self.duplicates.is_empty()
+    }
+
+    /// Display a summary of the differences, with at most `sample_size`
+    /// mappings of each kind.
+    pub fn print(&self, sample_size: usize) {
+        println!(
+            "{} mappings expected, {} mappings enumerated.",
+            self.num_expected, self.num_actual
+        );
+
+        let kinds = [
+            ("missing", &self.missing),
+            ("unexpected", &self.unexpected),
+            ("duplicated", &self.duplicates),
+        ];
+
+        for (kind, mappings) in kinds.iter() {
+            if mappings.is_empty() {
+                continue;
+            }
+
+            println!("{} {} mappings, for instance:", mappings.len(), kind);
+
+            for mapping in mappings.iter().take(sample_size) {
+                println!(" - {}", mapping);
+            }
+        }
+
+        if self.is_consistent() {
+            println!("OK");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// __        __
+// \ \      / /_ _ ___ _ __ ___
+//  \ \ /\ / / _` / __| '_ ` _ \
+//   \ V  V / (_| \__ \ | | | | |
+//    \_/\_/ \__,_|___/_| |_| |_|
+//
+
+//! Raw bindings exported to a WebAssembly host, they are wrapped into a
+//! JavaScript API by `wasm/enum_spanner.js`.
+//!
+//! Strings are passed as a pointer and a length in bytes to a buffer
+//! allocated with `alloc`, results are written as JSON in an output buffer
+//! that stays valid until the next call.
+
+use std::cell::RefCell;
+
+use super::offsets::{OffsetTable, OffsetUnit};
+use super::spanner::CompiledSpanner;
+
+thread_local! {
+    static AUTOMATA: RefCell<Vec<Option<CompiledSpanner>>> = const { RefCell::new(Vec::new()) };
+    static OUTPUT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Allocate a buffer of `len` bytes in the memory of the module.
+#[no_mangle]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// Release a buffer allocated with `alloc`.
+///
+/// # Safety
+///
+/// The buffer must have been returned by `alloc` with the same length.
+#[no_mangle]
+pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+unsafe fn read_str<'a>(ptr: *const u8, len: usize) -> &'a str {
+    std::str::from_utf8(std::slice::from_raw_parts(ptr, len)).expect("Input is not valid UTF-8")
+}
+
+/// Compile a pattern into an automaton and return a handle to it.
+///
+/// # Safety
+///
+/// The pattern must be a valid UTF-8 buffer of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn compile(ptr: *const u8, len: usize) -> usize {
+    let spanner = CompiledSpanner::new(read_str(ptr, len));
+
+    AUTOMATA.with(|automata| {
+        let mut automata = automata.borrow_mut();
+        automata.push(Some(spanner));
+        automata.len() - 1
+    })
+}
+
+/// Release an automaton returned by `compile`.
+#[no_mangle]
+pub extern "C" fn free_automaton(handle: usize) {
+    AUTOMATA.with(|automata| automata.borrow_mut()[handle] = None);
+}
+
+/// Index a text with an automaton and enumerate all its matches into the
+/// output buffer, as a JSON list of objects mapping each group to its span in
+/// bytes and in UTF-16 code units, and its text. Returns the length of the
+/// output.
+///
+/// # Safety
+///
+/// The text must be a valid UTF-8 buffer of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn find_all(handle: usize, ptr: *const u8, len: usize) -> usize {
+    let text = read_str(ptr, len);
+    let mappings = AUTOMATA.with(|automata| {
+        automata.borrow()[handle]
+            .as_ref()
+            .expect("Automaton was released")
+            .find_all(text)
+    });
+
+    let utf16_offsets = OffsetTable::new(text, OffsetUnit::Utf16);
+
+    let matches: Vec<serde_json::Value> = mappings
+        .iter()
+        .map(|mapping| {
+            let groups: serde_json::Map<String, serde_json::Value> = mapping
+                .iter_groups()
+                .map(|(name, range)| {
+                    let group = serde_json::json!({
+                        "start": range.start,
+                        "end": range.end,
+                        "start_utf16": utf16_offsets.convert(range.start),
+                        "end_utf16": utf16_offsets.convert(range.end),
+                        "text": &text[range],
+                    });
+
+                    (name.to_string(), group)
+                })
+                .collect();
+
+            groups.into()
+        })
+        .collect();
+
+    OUTPUT.with(|output| {
+        let mut output = output.borrow_mut();
+        *output = serde_json::Value::from(matches).to_string();
+        output.len()
+    })
+}
+
+/// Pointer to the output buffer filled by the last call to `find_all`.
+#[no_mangle]
+pub extern "C" fn output_ptr() -> *const u8 {
+    OUTPUT.with(|output| output.borrow().as_ptr())
+}
@@ -0,0 +1,505 @@
+// __   __ _    __  __ _
+// \ \ / // \  |  \/  | |
+//  \ V // _ \ | |\/| | |
+//   | |/ ___ \| |  | | |___
+//   |_/_/   \_\_|  |_|_____|
+//
+
+use serde_json::{Map, Number, Value};
+
+/// Parse a YAML document into the equivalent JSON value.
+///
+/// Only the subset of YAML needed by hand-written input files is supported:
+/// block mappings and sequences, flow collections `[...]` and `{...}`, plain,
+/// single-quoted and double-quoted scalars, literal block scalars `|` and
+/// comments. Anchors, tags and multiple documents are not.
+pub fn parse(input: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        lines: input.lines().map(|line| line.trim_end_matches('\r')).collect(),
+        pos: 0,
+        item: None,
+    };
+
+    parser.skip_blank();
+
+    let value = match parser.peek() {
+        None => Value::Null,
+        Some((indent, _)) => parser.parse_node(indent)?,
+    };
+
+    parser.skip_blank();
+
+    match parser.peek() {
+        None => Ok(value),
+        Some(_) => Err(parser.error("unexpected indentation")),
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    /// Index of the current line.
+    pos: usize,
+    /// Content of a sequence item starting on the line of its dash, which
+    /// replaces the current line, with its indentation.
+    item: Option<(usize, &'a str)>,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at line {}", message, self.pos + 1)
+    }
+
+    /// Skip the lines that hold nothing but spaces and comments.
+    fn skip_blank(&mut self) {
+        while self.item.is_none() && self.pos < self.lines.len() {
+            if !strip_comment(self.lines[self.pos]).trim().is_empty() {
+                break;
+            }
+
+            self.pos += 1;
+        }
+    }
+
+    /// Indentation and content of the current line, without its comment.
+    fn peek(&self) -> Option<(usize, &'a str)> {
+        if let Some(item) = self.item {
+            return Some(item);
+        }
+
+        let line = self.lines.get(self.pos)?;
+        let content = strip_comment(line.trim_start()).trim_end();
+        Some((line.len() - line.trim_start().len(), content))
+    }
+
+    fn next_line(&mut self) {
+        self.item = None;
+        self.pos += 1;
+        self.skip_blank();
+    }
+
+    fn parse_node(&mut self, indent: usize) -> Result<Value, String> {
+        let (_, content) = self.peek().unwrap();
+
+        if is_sequence_item(content) {
+            self.parse_sequence(indent)
+        } else if split_key(content).is_some() {
+            self.parse_mapping(indent)
+        } else {
+            let value = parse_flow(content).map_err(|message| self.error(&message))?;
+            self.next_line();
+            Ok(value)
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+
+        while let Some((line_indent, content)) = self.peek() {
+            if line_indent != indent || !is_sequence_item(content) {
+                break;
+            }
+
+            let rest = content[1..].trim_start();
+
+            if rest.is_empty() {
+                self.next_line();
+
+                match self.peek() {
+                    Some((item_indent, _)) if item_indent > indent => {
+                        items.push(self.parse_node(item_indent)?)
+                    }
+                    _ => items.push(Value::Null),
+                }
+            } else {
+                let item_indent = indent + content.len() - rest.len();
+                self.item = Some((item_indent, rest));
+                items.push(self.parse_node(item_indent)?);
+            }
+        }
+
+        self.check_dedent(indent)?;
+        Ok(Value::Array(items))
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+
+        while let Some((line_indent, content)) = self.peek() {
+            if line_indent != indent || is_sequence_item(content) {
+                break;
+            }
+
+            let (key, rest) = match split_key(content) {
+                Some(entry) => entry,
+                None => return Err(self.error("expected a key")),
+            };
+
+            let key = match parse_flow(key).map_err(|message| self.error(&message))? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+
+            let value = if rest.starts_with('|') {
+                self.parse_literal(indent, rest)?
+            } else if !rest.is_empty() {
+                let value = parse_flow(rest).map_err(|message| self.error(&message))?;
+                self.next_line();
+                value
+            } else {
+                self.next_line();
+
+                match self.peek() {
+                    Some((value_indent, _)) if value_indent > indent => {
+                        self.parse_node(value_indent)?
+                    }
+                    // The items of a sequence may be as indented as its key.
+                    Some((value_indent, content))
+                        if value_indent == indent && is_sequence_item(content) =>
+                    {
+                        self.parse_sequence(indent)?
+                    }
+                    _ => Value::Null,
+                }
+            };
+
+            map.insert(key, value);
+        }
+
+        self.check_dedent(indent)?;
+        Ok(Value::Object(map))
+    }
+
+    /// Parse a literal block scalar, given the indentation of its key and its
+    /// header `|`, `|-` or `|+`, whose lines keep their line breaks.
+    fn parse_literal(&mut self, indent: usize, header: &str) -> Result<Value, String> {
+        let chomping = match header {
+            "|" => "clip",
+            "|-" => "strip",
+            "|+" => "keep",
+            _ => return Err(self.error("unsupported block scalar header")),
+        };
+
+        self.item = None;
+        self.pos += 1;
+
+        let mut lines = Vec::new();
+        let mut block_indent = None;
+
+        while let Some(line) = self.lines.get(self.pos) {
+            let line_indent = line.len() - line.trim_start().len();
+
+            if line.trim().is_empty() {
+                lines.push("");
+            } else if line_indent > indent && line_indent >= block_indent.unwrap_or(line_indent) {
+                let block_indent = *block_indent.get_or_insert(line_indent);
+                lines.push(&line[block_indent..]);
+            } else {
+                break;
+            }
+
+            self.pos += 1;
+        }
+
+        let mut text = lines.join("\n");
+
+        match chomping {
+            "keep" => text.push('\n'),
+            "clip" => {
+                text.truncate(text.trim_end_matches('\n').len());
+                text.push('\n');
+            }
+            _ => text.truncate(text.trim_end_matches('\n').len()),
+        }
+
+        self.skip_blank();
+        Ok(Value::String(text))
+    }
+
+    /// Check that a collection is not followed by a more indented line.
+    fn check_dedent(&self, indent: usize) -> Result<(), String> {
+        match self.peek() {
+            Some((line_indent, _)) if line_indent > indent => {
+                Err(self.error("unexpected indentation"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+/// Remove the comment at the end of a line, which starts with a `#` preceded
+/// by a space outside of quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return &line[..i],
+            _ => (),
+        }
+
+        previous = c;
+    }
+
+    line
+}
+
+/// Split a mapping entry into its key and its value, at the first colon
+/// followed by a space or ending the line, outside of quotes and brackets.
+fn split_key(content: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    let mut depth = 0;
+    let mut previous = ' ';
+
+    for (i, c) in content.char_indices() {
+        match quote {
+            Some(q) if c == q && !(q == '"' && previous == '\\') => quote = None,
+            Some(_) => (),
+            None => match c {
+                '"' | '\'' if i == 0 => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                ':' if depth == 0 => {
+                    let rest = &content[i + 1..];
+
+                    if rest.is_empty() || rest.starts_with(' ') {
+                        return Some((content[..i].trim_end(), rest.trim_start()));
+                    }
+                }
+                _ => (),
+            },
+        }
+
+        previous = c;
+    }
+
+    None
+}
+
+/// Parse a value written on a single line, which is a scalar or a flow
+/// collection.
+fn parse_flow(content: &str) -> Result<Value, String> {
+    let mut flow = Flow { input: content, pos: 0 };
+    let value = flow.parse_value(&[])?;
+    flow.skip_spaces();
+
+    if flow.pos < content.len() {
+        return Err(format!("unexpected characters after a value: {:?}", &content[flow.pos..]));
+    }
+
+    Ok(value)
+}
+
+struct Flow<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Flow<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) {
+        self.pos = self.input.len() - self.rest().trim_start().len();
+    }
+
+    /// Parse a value, a plain scalar ending at the first of the given
+    /// characters, such as a comma or a closing bracket inside a collection.
+    fn parse_value(&mut self, stops: &[char]) -> Result<Value, String> {
+        self.skip_spaces();
+
+        match self.rest().chars().next() {
+            Some('[') => self.parse_collection(']'),
+            Some('{') => self.parse_collection('}'),
+            Some('"') => self.parse_double_quoted(),
+            Some('\'') => self.parse_single_quoted(),
+            _ => {
+                let rest = self.rest();
+                let end = rest.find(|c| stops.contains(&c)).unwrap_or(rest.len());
+
+                self.pos += end;
+                Ok(plain_scalar(rest[..end].trim_end()))
+            }
+        }
+    }
+
+    fn parse_collection(&mut self, closing: char) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        let mut map = Map::new();
+
+        loop {
+            self.skip_spaces();
+
+            if self.rest().starts_with(closing) {
+                self.pos += 1;
+                break;
+            }
+
+            if closing == '}' {
+                let key = match self.parse_value(&[':', ',', '}'])? {
+                    Value::String(key) => key,
+                    key => key.to_string(),
+                };
+
+                self.skip_spaces();
+
+                let value = match self.rest().starts_with(':') {
+                    true => {
+                        self.pos += 1;
+                        self.parse_value(&[',', '}'])?
+                    }
+                    false => Value::Null,
+                };
+
+                map.insert(key, value);
+            } else {
+                items.push(self.parse_value(&[',', ']'])?);
+            }
+
+            self.skip_spaces();
+
+            match self.rest().chars().next() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == closing => (),
+                _ => return Err(format!("unclosed collection, expected {:?}", closing)),
+            }
+        }
+
+        match closing {
+            '}' => Ok(Value::Object(map)),
+            _ => Ok(Value::Array(items)),
+        }
+    }
+
+    /// Double-quoted scalars have the same escape sequences as JSON strings,
+    /// except for a few that are rarely used.
+    fn parse_double_quoted(&mut self) -> Result<Value, String> {
+        let rest = self.rest();
+        let mut escaped = false;
+
+        for (i, c) in rest.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    self.pos += i + 1;
+                    return serde_json::from_str(&rest[..=i]).map_err(|err| err.to_string());
+                }
+                _ => (),
+            }
+        }
+
+        Err("unclosed double-quoted string".to_string())
+    }
+
+    /// Single-quoted scalars have no escape sequences, but a doubled quote.
+    fn parse_single_quoted(&mut self) -> Result<Value, String> {
+        let rest = &self.rest()[1..];
+        let mut text = String::new();
+        let mut chars = rest.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            if c != '\'' {
+                text.push(c);
+            } else if let Some((_, '\'')) = chars.peek() {
+                text.push('\'');
+                chars.next();
+            } else {
+                self.pos += i + 2;
+                return Ok(Value::String(text));
+            }
+        }
+
+        Err("unclosed single-quoted string".to_string())
+    }
+}
+
+/// Unquoted scalars are null, booleans or numbers if they look like one, and
+/// strings otherwise.
+fn plain_scalar(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" => return Value::Null,
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => (),
+    }
+
+    if let Ok(n) = text.parse::<i64>() {
+        return Value::Number(n.into());
+    }
+
+    if let Ok(Some(n)) = text.parse::<f64>().map(Number::from_f64) {
+        return Value::Number(n);
+    }
+
+    Value::String(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::parse;
+
+    #[test]
+    fn subset() {
+        let input = r#"
+# Cases of a pattern test file
+- name: dates   # inline comment
+  pattern: '(?P<d>\d+)-(?P<m>\d+)'
+  text: "on 2024-01, it's #1\n"
+  expected:
+    - d: [3, 7]
+      m: "01"
+    - {d: '2024', m: [8, 10]}
+- pattern: a
+  text: |
+    first line
+      indented # not a comment
+
+  expected:
+  - match: a
+  extra: [true, null, 1.5, -2, plain text]
+"#;
+
+        assert_eq!(
+            parse(input).unwrap(),
+            json!([
+                {
+                    "name": "dates",
+                    "pattern": r"(?P<d>\d+)-(?P<m>\d+)",
+                    "text": "on 2024-01, it's #1\n",
+                    "expected": [
+                        {"d": [3, 7], "m": "01"},
+                        {"d": "2024", "m": [8, 10]},
+                    ],
+                },
+                {
+                    "pattern": "a",
+                    "text": "first line\n  indented # not a comment\n",
+                    "expected": [{"match": "a"}],
+                    "extra": [true, null, 1.5, -2, "plain text"],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("a: [1, 2").is_err());
+        assert!(parse("a: 'b").is_err());
+        assert!(parse("a: 1\n    b: 2").is_err());
+        assert!(parse("- a\n  - b").is_err());
+    }
+}
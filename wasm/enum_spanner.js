@@ -0,0 +1,60 @@
+// JavaScript wrapper around the WebAssembly build of enum-spanner-rs, see
+// the "WebAssembly" section of the README.
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+export class EnumSpanner {
+  constructor(instance) {
+    this.exports = instance.exports;
+  }
+
+  // Instantiate the module from the URL of `enum_spanner_rs.wasm`.
+  static async load(url) {
+    const { instance } = await WebAssembly.instantiateStreaming(fetch(url));
+    return new EnumSpanner(instance);
+  }
+
+  // Run a function over a string copied into the memory of the module.
+  withString(string, callback) {
+    const bytes = encoder.encode(string);
+    const ptr = this.exports.alloc(bytes.length);
+    new Uint8Array(this.exports.memory.buffer, ptr, bytes.length).set(bytes);
+
+    try {
+      return callback(ptr, bytes.length);
+    } finally {
+      this.exports.dealloc(ptr, bytes.length);
+    }
+  }
+
+  // Compile a pattern, the result must be released with `free()`.
+  compile(pattern) {
+    const handle = this.withString(pattern, (ptr, len) => this.exports.compile(ptr, len));
+    return new Pattern(this, handle);
+  }
+}
+
+export class Pattern {
+  constructor(spanner, handle) {
+    this.spanner = spanner;
+    this.handle = handle;
+  }
+
+  // Iterate over all the matches of the pattern in a text. Each match maps
+  // group names to their `start` and `end` offsets in bytes, their
+  // `start_utf16` and `end_utf16` offsets in the JavaScript string and their
+  // `text`.
+  *matches(text) {
+    const exports = this.spanner.exports;
+    const len = this.spanner.withString(text, (ptr, len) =>
+      exports.find_all(this.handle, ptr, len)
+    );
+    const output = new Uint8Array(exports.memory.buffer, exports.output_ptr(), len);
+    yield* JSON.parse(decoder.decode(output));
+  }
+
+  free() {
+    this.spanner.exports.free_automaton(this.handle);
+  }
+}